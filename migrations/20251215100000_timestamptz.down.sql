@@ -0,0 +1,12 @@
+DROP TRIGGER IF EXISTS user_infos_set_updated ON user_infos;
+DROP TRIGGER IF EXISTS users_set_updated ON users;
+DROP FUNCTION IF EXISTS set_updated_timestamp();
+
+ALTER TABLE user_infos
+  DROP COLUMN IF EXISTS timezone,
+  ALTER COLUMN created TYPE TIMESTAMP USING created AT TIME ZONE 'UTC',
+  ALTER COLUMN updated TYPE TIMESTAMP USING updated AT TIME ZONE 'UTC';
+
+ALTER TABLE users
+  ALTER COLUMN created TYPE TIMESTAMP USING created AT TIME ZONE 'UTC',
+  ALTER COLUMN updated TYPE TIMESTAMP USING updated AT TIME ZONE 'UTC';
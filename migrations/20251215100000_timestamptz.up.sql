@@ -0,0 +1,23 @@
+ALTER TABLE users
+  ALTER COLUMN created TYPE TIMESTAMPTZ USING created AT TIME ZONE 'UTC',
+  ALTER COLUMN updated TYPE TIMESTAMPTZ USING updated AT TIME ZONE 'UTC';
+
+ALTER TABLE user_infos
+  ALTER COLUMN created TYPE TIMESTAMPTZ USING created AT TIME ZONE 'UTC',
+  ALTER COLUMN updated TYPE TIMESTAMPTZ USING updated AT TIME ZONE 'UTC',
+  ADD COLUMN IF NOT EXISTS timezone VARCHAR(64);
+
+CREATE OR REPLACE FUNCTION set_updated_timestamp() RETURNS TRIGGER AS $$
+BEGIN
+  NEW.updated = NOW();
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER users_set_updated
+  BEFORE UPDATE ON users
+  FOR EACH ROW EXECUTE FUNCTION set_updated_timestamp();
+
+CREATE TRIGGER user_infos_set_updated
+  BEFORE UPDATE ON user_infos
+  FOR EACH ROW EXECUTE FUNCTION set_updated_timestamp();
@@ -0,0 +1,7 @@
+DROP TABLE IF EXISTS audit_log;
+
+DROP INDEX IF EXISTS idx_users_deletion_scheduled_at;
+
+ALTER TABLE users
+  DROP COLUMN IF EXISTS anonymized_at,
+  DROP COLUMN IF EXISTS deletion_scheduled_at;
@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS api_tokens;
+
+ALTER TABLE users
+  DROP COLUMN IF EXISTS service_account;
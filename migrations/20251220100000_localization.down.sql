@@ -0,0 +1,10 @@
+UPDATE users SET role = CASE role
+  WHEN 'owner' THEN 'Владелец'
+  WHEN 'admin' THEN 'Администратор'
+  WHEN 'employee' THEN 'Сотрудник'
+  WHEN 'guest' THEN 'Гость'
+  ELSE role
+END;
+
+ALTER TABLE user_infos
+  DROP COLUMN IF EXISTS locale;
@@ -0,0 +1,10 @@
+ALTER TABLE user_infos
+  ADD COLUMN IF NOT EXISTS locale VARCHAR(16);
+
+UPDATE users SET role = CASE role
+  WHEN 'Владелец' THEN 'owner'
+  WHEN 'Администратор' THEN 'admin'
+  WHEN 'Сотрудник' THEN 'employee'
+  WHEN 'Гость' THEN 'guest'
+  ELSE role
+END;
@@ -0,0 +1,444 @@
+//! Интерфейс командной строки
+//!
+//! Помимо запуска сервера бинарный файл `alfred` выполняет служебные операции:
+//! управление миграциями, создание владельца и администрирование пользователей,
+//! проверку настроек. Команды работают напрямую с `PgStorage` и `UsersService`,
+//! поэтому не требуют ни запущенного сервера, ни ручных SQL-запросов.
+//!
+//! Пароль не передается аргументом, чтобы он не попал в список процессов
+//! и историю оболочки. В терминале он запрашивается без отображения ввода,
+//! а в скриптах передается первой строкой стандартного ввода:
+//!
+//! ```sh
+//! alfred users create --email owner@example.com --role owner < owner-password
+//! ```
+
+use std::{
+    io::{BufRead, IsTerminal},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use clap::{Parser, Subcommand};
+use tracing_subscriber::EnvFilter;
+
+use crate::{
+    AppError, AppResult,
+    crypto::PasswordHashing,
+    models::{PasswordPolicy, User, UserRole},
+    services::{UsersListResponse, UsersService},
+    settings::{self, DatabaseSettings, SETTINGS_FILE, Settings},
+    storage::{CachedUsersRepository, DEFAULT_NOTIFY_CHANNEL, PgStorage, UsersRepository},
+};
+
+/// Фильтр журнала служебных команд, если `RUST_LOG` не задан
+const ADMIN_LOG_FILTER: &str = "warn";
+
+/// Переменная окружения с паролем для команд управления пользователями
+const PASSWORD_ENV: &str = "ALF_USER_PASSWORD";
+
+/// Сервер аутентификации Alfred
+#[derive(Debug, Parser)]
+#[command(name = "alfred", version)]
+pub struct Cli {
+    /// Путь к файлу настроек
+    #[arg(short, long, global = true, env = "ALF_CONFIG", default_value = SETTINGS_FILE)]
+    pub config: String,
+
+    /// Команда; без нее запускается сервер
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Команды верхнего уровня
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Запускает сервер
+    Serve,
+    /// Управляет миграциями схемы базы данных
+    #[command(subcommand)]
+    Migrate(MigrateCommand),
+    /// Управляет пользователями
+    #[command(subcommand)]
+    Users(UsersCommand),
+    /// Работает с настройками
+    #[command(subcommand)]
+    Config(ConfigCommand),
+}
+
+/// Команды управления миграциями
+#[derive(Debug, Subcommand)]
+pub enum MigrateCommand {
+    /// Применяет все непримененные миграции
+    Up,
+    /// Откатывает последнюю миграцию или все миграции новее указанной версии
+    Down {
+        /// Версия, которая останется последней примененной
+        #[arg(long)]
+        target: Option<i64>,
+    },
+    /// Показывает примененные и ожидающие миграции
+    Status,
+}
+
+/// Команды управления пользователями
+#[derive(Debug, Subcommand)]
+pub enum UsersCommand {
+    /// Создает пользователя
+    Create {
+        /// Email пользователя
+        #[arg(long)]
+        email: String,
+        /// Роль пользователя; пароль читается из стандартного ввода
+        #[arg(long, default_value = "guest", value_parser = UserRole::from_str)]
+        role: UserRole,
+    },
+    /// Меняет роль пользователя
+    SetRole {
+        /// UUID или email пользователя
+        user: String,
+        /// Новая роль
+        #[arg(value_parser = UserRole::from_str)]
+        role: UserRole,
+    },
+    /// Устанавливает пользователю новый пароль, прочитанный из стандартного ввода
+    ResetPassword {
+        /// UUID или email пользователя
+        user: String,
+    },
+    /// Выводит список пользователей
+    List {
+        /// Показывать только пользователей с этой ролью
+        #[arg(long, value_parser = UserRole::from_str)]
+        role: Option<UserRole>,
+        /// Строка поиска по email, имени пользователя, имени и фамилии
+        #[arg(long)]
+        search: Option<String>,
+        /// Номер страницы
+        #[arg(long)]
+        page: Option<u32>,
+        /// Количество пользователей на странице
+        #[arg(long)]
+        per_page: Option<u32>,
+    },
+}
+
+/// Команды работы с настройками
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Выводит действующие настройки со скрытыми секретами и проверяет их
+    Check,
+}
+
+/// Выполняет служебную команду
+///
+/// Запуск сервера (`serve`) выполняется бинарным файлом и сюда не передается.
+///
+/// # Аргументы
+///
+/// * `config` - Путь к файлу настроек
+/// * `command` - Команда для выполнения
+///
+/// # Возвращает
+///
+/// * `AppResult<()>` - Результат выполнения команды
+pub async fn run(config: &str, command: Command) -> AppResult<()> {
+    if let Command::Config(ConfigCommand::Check) = command {
+        return check_config(config);
+    }
+    let settings = settings::init(config)?;
+    init_admin_logger();
+    let pool = connect(&settings.database_settings).await?;
+    let storage = PgStorage::connect(pool).await?;
+    let result = match command {
+        Command::Migrate(command) => migrate(&storage, command).await,
+        Command::Users(command) => users(&settings, &storage, command).await,
+        Command::Serve | Command::Config(_) => {
+            Err(AppError::Custom("command is not handled here".to_string()))
+        }
+    };
+    storage.close().await;
+    result
+}
+
+/// Создает пул соединений с базой данных по настройкам
+///
+/// # Аргументы
+///
+/// * `settings` - Настройки подключения к базе данных
+///
+/// # Возвращает
+///
+/// * `AppResult<sqlx::PgPool>` - Пул соединений или ошибку подключения
+pub async fn connect(settings: &DatabaseSettings) -> AppResult<sqlx::PgPool> {
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .max_connections(settings.max_connections.unwrap_or(8))
+        .idle_timeout(Duration::from_secs(settings.idle_timeout.unwrap_or(30)))
+        .connect(settings.db_url().as_ref())
+        .await?;
+    Ok(pool)
+}
+
+/// Выводит действующие настройки со скрытыми секретами и проверяет их
+fn check_config(config: &str) -> AppResult<()> {
+    let settings = settings::load(config)?;
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&settings.redacted()).unwrap_or_default()
+    );
+    settings.validate()?;
+    eprintln!("configuration is valid");
+    Ok(())
+}
+
+/// Включает журнал служебных команд в stderr, чтобы он не смешивался с выводом
+fn init_admin_logger() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| ADMIN_LOG_FILTER.into());
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .try_init();
+}
+
+async fn migrate(storage: &PgStorage, command: MigrateCommand) -> AppResult<()> {
+    match command {
+        MigrateCommand::Up => {
+            let applied = storage.migrate().await?;
+            if applied.is_empty() {
+                println!("schema is up to date");
+            }
+            for version in applied {
+                println!("applied {version}");
+            }
+        }
+        MigrateCommand::Down { target } => {
+            let reverted = storage.revert_migrations(target).await?;
+            if reverted.is_empty() {
+                println!("nothing to revert");
+            }
+            for version in reverted {
+                println!("reverted {version}");
+            }
+        }
+        MigrateCommand::Status => {
+            for migration in storage.migration_status().await? {
+                let state = if migration.applied {
+                    "applied"
+                } else {
+                    "pending"
+                };
+                println!(
+                    "{}  {state:<7}  {}",
+                    migration.version, migration.description
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn users(settings: &Settings, storage: &PgStorage, command: UsersCommand) -> AppResult<()> {
+    PasswordPolicy::from_settings(&settings.password_policy)?.install()?;
+    PasswordHashing::from_settings(&settings.password_hashing)?.install()?;
+    let service = users_service(settings, storage);
+    match command {
+        UsersCommand::Create { email, role } => {
+            let password = read_password()?;
+            let user = service
+                .signup(&email, &password, Some(role.as_ref()))
+                .await?;
+            println!("created {} {} ({})", user.user_id, user.email, user.role);
+        }
+        UsersCommand::SetRole { user, role } => {
+            let user = find_user(&service, &user).await?;
+            let user = service.set_role(None, user.user_id, role).await?;
+            println!("{} {} is now {}", user.user_id, user.email, user.role);
+        }
+        UsersCommand::ResetPassword { user } => {
+            let user = find_user(&service, &user).await?;
+            let password = read_password()?;
+            let user = service.reset_password(user.user_id, &password).await?;
+            println!("password reset for {} {}", user.user_id, user.email);
+        }
+        UsersCommand::List {
+            role,
+            search,
+            page,
+            per_page,
+        } => {
+            let list = service
+                .list(
+                    page.map(|p| p.to_string()),
+                    per_page.map(|p| p.to_string()),
+                    role.map(|r| r.to_string()),
+                    search,
+                )
+                .await?;
+            print!("{}", format_users(&list));
+        }
+    }
+    Ok(())
+}
+
+/// Собирает сервис пользователей для служебных команд
+///
+/// Если у сервера включена межсерверная инвалидация кэша, изменения
+/// рассылаются через `NOTIFY`, чтобы запущенные экземпляры не отдавали
+/// устаревшие данные.
+fn users_service(settings: &Settings, storage: &PgStorage) -> UsersService {
+    let repository: Arc<dyn UsersRepository> = match &settings.cache_settings {
+        Some(cache) if cache.notify => {
+            let channel = cache
+                .notify_channel
+                .as_deref()
+                .unwrap_or(DEFAULT_NOTIFY_CHANNEL);
+            Arc::new(
+                CachedUsersRepository::new(
+                    storage.clone(),
+                    Duration::from_secs(cache.ttl),
+                    cache.max_entries,
+                )
+                .with_pg_notify(storage.pool().clone(), channel),
+            )
+        }
+        _ => Arc::new(storage.clone()),
+    };
+    UsersService::new(repository)
+        .with_audit(Arc::new(storage.clone()))
+        .with_deletion_grace(chrono::Duration::days(
+            settings.account_settings.deletion_cooling_off_days,
+        ))
+}
+
+/// Находит пользователя по UUID или email
+async fn find_user(service: &UsersService, user: &str) -> AppResult<User> {
+    match uuid::Uuid::parse_str(user) {
+        Ok(id) => service.storage.get(id).await,
+        Err(_) => service.get_user_info(user).await,
+    }
+}
+
+/// Читает пароль для команды управления пользователями
+///
+/// Пароль берется из `ALF_USER_PASSWORD`, если переменная задана. Иначе
+/// в терминале он запрашивается без отображения ввода, а из канала
+/// или файла читается первая строка.
+fn read_password() -> AppResult<String> {
+    let password = match std::env::var(PASSWORD_ENV) {
+        Ok(password) => password,
+        Err(_) if std::io::stdin().is_terminal() => rpassword::prompt_password("Password: ")?,
+        Err(_) => {
+            let mut line = String::new();
+            std::io::stdin().lock().read_line(&mut line)?;
+            line.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+    if password.is_empty() {
+        return Err(AppError::Custom("password is required".to_string()));
+    }
+    Ok(password)
+}
+
+/// Форматирует страницу списка пользователей в виде таблицы
+fn format_users(list: &UsersListResponse) -> String {
+    let mut out = String::new();
+    for user in &list.users {
+        out.push_str(&format!(
+            "{}  {:<8}  {}\n",
+            user.user_id,
+            user.role.as_ref(),
+            user.email
+        ));
+    }
+    out.push_str(&format!(
+        "page {} of {}, {} users total\n",
+        list.current_filter.page(),
+        list.total_pages(),
+        list.total
+    ));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        let cli = Cli::try_parse_from(["alfred"]).unwrap();
+        assert!(cli.command.is_none());
+        assert_eq!(cli.config, SETTINGS_FILE);
+
+        let cli = Cli::try_parse_from([
+            "alfred",
+            "users",
+            "create",
+            "--email",
+            "owner@example.com",
+            "--role",
+            "Owner",
+            "--config",
+            "prod.toml",
+        ])
+        .unwrap();
+        assert_eq!(cli.config, "prod.toml");
+        assert!(matches!(
+            cli.command,
+            Some(Command::Users(UsersCommand::Create {
+                role: UserRole::Owner,
+                ..
+            }))
+        ));
+
+        let cli = Cli::try_parse_from(["alfred", "migrate", "down", "--target", "20251215100000"])
+            .unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Migrate(MigrateCommand::Down {
+                target: Some(20251215100000)
+            }))
+        ));
+
+        let result = Cli::try_parse_from(["alfred", "users", "set-role", "a@b.c", "superuser"]);
+        assert!(result.is_err());
+        // Пароль не принимается аргументом, чтобы не попасть в список процессов
+        let result = Cli::try_parse_from([
+            "alfred",
+            "users",
+            "reset-password",
+            "a@b.c",
+            "--password",
+            "secret",
+        ]);
+        assert!(result.is_err());
+    }
+
+    #[sqlx::test]
+    async fn test_users_commands(pool: sqlx::PgPool) -> AppResult<()> {
+        let storage = PgStorage::with_pool(pool);
+        let service = UsersService::new(Arc::new(storage.clone()));
+        let created = service
+            .signup("Owner@Example.com", "str0nGp@ssw0rD", Some("owner"))
+            .await?;
+
+        let by_email = find_user(&service, "owner@example.com").await?;
+        let by_id = find_user(&service, &created.user_id.to_string()).await?;
+        assert_eq!(by_email.user_id, created.user_id);
+        assert_eq!(by_id.user_id, created.user_id);
+        let result = find_user(&service, &uuid::Uuid::new_v4().to_string()).await;
+        assert!(matches!(result, Err(AppError::EntryNotFound)));
+
+        let list = service.list(None, None, None, None).await?;
+        let table = format_users(&list);
+        assert!(table.contains(&format!("{}  owner     owner@example.com", created.user_id)));
+        assert!(table.ends_with("page 1 of 1, 1 users total\n"));
+        Ok(())
+    }
+}
@@ -1,26 +1,596 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, OnceLock},
+    time::Instant,
+};
+
 use argon2::{
-    Argon2,
-    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString, rand_core::OsRng},
+    Algorithm, Argon2, KeyId, Params, ParamsBuilder, Version,
+    password_hash::{
+        PasswordHash, PasswordHasher as _, PasswordVerifier, SaltString,
+        rand_core::{OsRng, RngCore},
+    },
 };
+use sha2::{Digest, Sha256};
+use tokio::sync::Semaphore;
+
+use crate::{AppError, AppResult, metrics, settings::PasswordHashSettings};
+
+/// Значение хэша, с которым вход по паролю невозможен
+///
+/// Используется для анонимизированных аккаунтов и учетных записей
+/// без пароля.
+pub const UNUSABLE_PASSWORD_HASH: &str = "!";
+
+/// Префикс, по которому API-ключи отличаются от JWT
+pub const API_TOKEN_PREFIX: &str = "alf_";
+
+/// Количество случайных байт в API-ключе
+const API_TOKEN_BYTES: usize = 32;
+
+/// Префикс хэшей PBKDF2-SHA256 в форматах PHC и passlib
+const PBKDF2_SHA256_PREFIX: &str = "$pbkdf2-sha256$";
+
+/// Наибольшая стоимость bcrypt, принимаемая при импорте
+const MAX_BCRYPT_COST: u32 = 16;
+/// Наибольшее число итераций PBKDF2, принимаемое при импорте
+const MAX_PBKDF2_ROUNDS: u32 = 2_000_000;
+/// Наибольшая длина результата PBKDF2 в байтах, принимаемая при импорте
+const MAX_PBKDF2_OUTPUT_LEN: usize = 64;
+/// Наибольший объем памяти Argon2 в КиБ, принимаемый при импорте
+const MAX_ARGON2_M_COST: u32 = 256 * 1024;
+/// Наибольшее число проходов Argon2, принимаемое при импорте
+const MAX_ARGON2_T_COST: u32 = 16;
+/// Наибольшее число потоков Argon2, принимаемое при импорте
+const MAX_ARGON2_P_COST: u32 = 16;
+
+/// Настройки хэширования, установленные при старте приложения
+static HASHING: OnceLock<PasswordHashing> = OnceLock::new();
+
+/// Алгоритм проверки паролей, перенесенных из других систем
+///
+/// Реализация распознает свой формат хэша (PHC или modular crypt)
+/// и проверяет по нему пароль. Новые хэши всегда вычисляются Argon2id,
+/// а такие хэши пересчитываются при первом успешном входе.
+pub trait PasswordHasher: Send + Sync {
+    /// Название алгоритма для журналов
+    fn name(&self) -> &'static str;
+    /// Проверяет, что хэш записан в формате этого алгоритма
+    fn recognizes(&self, hash: &str) -> bool;
+    /// Проверяет, что хэш разбирается и его стоимость не превышает
+    /// допустимой, не вычисляя хэш
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(())` - Хэш можно сохранить
+    /// * `Err(AppError::CryptoError)` - Хэш поврежден или слишком дорог для проверки
+    fn check_params(&self, hash: &str) -> AppResult<()>;
+    /// Проверяет пароль по хэшу
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(bool)` - Совпадает ли пароль с хэшем
+    /// * `Err(AppError::CryptoError)` - Хэш поврежден
+    fn verify(&self, hash: &str, password: &str) -> AppResult<bool>;
+}
+
+/// Хэши bcrypt в формате modular crypt (`$2a$`, `$2b$`, `$2y$`)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BcryptHasher;
+
+impl PasswordHasher for BcryptHasher {
+    fn name(&self) -> &'static str {
+        "bcrypt"
+    }
+
+    fn recognizes(&self, hash: &str) -> bool {
+        ["$2a$", "$2b$", "$2y$"]
+            .iter()
+            .any(|prefix| hash.starts_with(prefix))
+    }
+
+    fn check_params(&self, hash: &str) -> AppResult<()> {
+        let parts = hash
+            .parse::<bcrypt::HashParts>()
+            .map_err(|e| AppError::CryptoError(e.to_string()))?;
+        if !(4..=MAX_BCRYPT_COST).contains(&parts.get_cost()) {
+            return Err(AppError::CryptoError(format!(
+                "bcrypt cost must be between 4 and {MAX_BCRYPT_COST}"
+            )));
+        }
+        let encoded = hash.rsplit('$').next().unwrap_or_default();
+        if !encoded
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'.' || b == b'/')
+        {
+            return Err(AppError::CryptoError("malformed bcrypt hash".into()));
+        }
+        Ok(())
+    }
+
+    fn verify(&self, hash: &str, password: &str) -> AppResult<bool> {
+        bcrypt::verify(password, hash).map_err(|e| AppError::CryptoError(e.to_string()))
+    }
+}
+
+/// Хэши PBKDF2-SHA256
+///
+/// Поддерживаются формат PHC (`$pbkdf2-sha256$i=29000,l=32$<соль>$<хэш>`)
+/// и modular crypt библиотеки passlib (`$pbkdf2-sha256$29000$<соль>$<хэш>`),
+/// где соль и хэш записаны в base64 с `.` вместо `+` и без выравнивания.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Pbkdf2Sha256Hasher;
+
+impl PasswordHasher for Pbkdf2Sha256Hasher {
+    fn name(&self) -> &'static str {
+        "pbkdf2-sha256"
+    }
+
+    fn recognizes(&self, hash: &str) -> bool {
+        hash.starts_with(PBKDF2_SHA256_PREFIX)
+    }
+
+    fn check_params(&self, hash: &str) -> AppResult<()> {
+        let (rounds, output_len) = if is_pbkdf2_phc(hash) {
+            let parsed_hash =
+                PasswordHash::new(hash).map_err(|e| AppError::CryptoError(e.to_string()))?;
+            let params = pbkdf2::Params::try_from(&parsed_hash)
+                .map_err(|e| AppError::CryptoError(e.to_string()))?;
+            if parsed_hash.salt.is_none() || parsed_hash.hash.is_none() {
+                return Err(AppError::CryptoError("malformed pbkdf2-sha256 hash".into()));
+            }
+            (params.rounds, params.output_length)
+        } else {
+            let (rounds, _, expected) = parse_passlib_pbkdf2(hash)?;
+            (rounds, expected.len())
+        };
+        if rounds > MAX_PBKDF2_ROUNDS || output_len > MAX_PBKDF2_OUTPUT_LEN {
+            return Err(AppError::CryptoError(format!(
+                "pbkdf2-sha256 hash must use at most {MAX_PBKDF2_ROUNDS} rounds \
+                 and {MAX_PBKDF2_OUTPUT_LEN} bytes of output"
+            )));
+        }
+        Ok(())
+    }
+
+    fn verify(&self, hash: &str, password: &str) -> AppResult<bool> {
+        if is_pbkdf2_phc(hash) {
+            let parsed_hash =
+                PasswordHash::new(hash).map_err(|e| AppError::CryptoError(e.to_string()))?;
+            let res = pbkdf2::Pbkdf2
+                .verify_password(password.as_bytes(), &parsed_hash)
+                .is_ok();
+            return Ok(res);
+        }
+        let (rounds, salt, expected) = parse_passlib_pbkdf2(hash)?;
+        let mut derived = vec![0u8; expected.len()];
+        pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), &salt, rounds, &mut derived);
+        Ok(constant_time_eq(&derived, &expected))
+    }
+}
+
+/// Хэш PBKDF2-SHA256 записан в формате PHC, а не passlib
+fn is_pbkdf2_phc(hash: &str) -> bool {
+    hash.strip_prefix(PBKDF2_SHA256_PREFIX)
+        .is_some_and(|rest| rest.starts_with("i="))
+}
 
-use crate::{AppError, AppResult};
+/// Разбирает хэш passlib на число итераций, соль и ожидаемый результат
+fn parse_passlib_pbkdf2(hash: &str) -> AppResult<(u32, Vec<u8>, Vec<u8>)> {
+    let malformed = || AppError::CryptoError("malformed pbkdf2-sha256 hash".into());
+    let rest = hash
+        .strip_prefix(PBKDF2_SHA256_PREFIX)
+        .ok_or_else(malformed)?;
+    let mut parts = rest.split('$');
+    let (Some(rounds), Some(salt), Some(expected), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(malformed());
+    };
+    let rounds = rounds
+        .parse::<u32>()
+        .ok()
+        .filter(|rounds| *rounds > 0)
+        .ok_or_else(malformed)?;
+    let salt = decode_ab64(salt).ok_or_else(malformed)?;
+    let expected = decode_ab64(expected)
+        .filter(|expected| !expected.is_empty())
+        .ok_or_else(malformed)?;
+    Ok((rounds, salt, expected))
+}
+
+/// Декодирует base64 passlib: `.` вместо `+`, без выравнивания
+fn decode_ab64(value: &str) -> Option<Vec<u8>> {
+    use base64::Engine;
+    base64::engine::general_purpose::STANDARD_NO_PAD
+        .decode(value.replace('.', "+"))
+        .ok()
+}
+
+/// Сравнивает байты за время, не зависящее от места первого различия
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Алгоритмы, хэши которых принимаются по умолчанию помимо Argon2
+fn legacy_hashers() -> Vec<Box<dyn PasswordHasher>> {
+    vec![Box::new(BcryptHasher), Box::new(Pbkdf2Sha256Hasher)]
+}
+
+/// Параметры хэширования паролей Argon2id
+///
+/// Перец (pepper) передается в Argon2 как секретный ключ и в базе
+/// не хранится. Его идентификатор записывается в хэш параметром `keyid`,
+/// поэтому перец можно сменить: хэши со старым перцем проверяются,
+/// пока он перечислен в `retired_peppers`, и пересчитываются при входе.
+///
+/// Хэши других алгоритмов проверяются подключенными `PasswordHasher`
+/// (по умолчанию bcrypt и PBKDF2-SHA256) и всегда требуют пересчета.
+pub struct PasswordHashing {
+    params: Params,
+    pepper: Option<(Vec<u8>, Vec<u8>)>,
+    retired_peppers: HashMap<Vec<u8>, Vec<u8>>,
+    legacy: Vec<Box<dyn PasswordHasher>>,
+    permits: Arc<Semaphore>,
+    /// Хэш, по которому проверяется пароль, когда сверять его не с чем
+    dummy_hash: OnceLock<String>,
+}
+
+impl Default for PasswordHashing {
+    fn default() -> Self {
+        Self {
+            params: Params::default(),
+            pepper: None,
+            retired_peppers: HashMap::new(),
+            legacy: legacy_hashers(),
+            permits: Arc::new(Semaphore::new(default_hashing_concurrency())),
+            dummy_hash: OnceLock::new(),
+        }
+    }
+}
+
+impl PasswordHashing {
+    /// Создает настройки хэширования
+    ///
+    /// # Аргументы
+    ///
+    /// * `settings` - Параметры Argon2, перец и размер пула хэширования
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(PasswordHashing)` - Настройки хэширования
+    /// * `Err(AppError::CryptoError)` - Параметры Argon2 недопустимы
+    ///   или идентификатор перца длиннее 8 байт
+    pub fn from_settings(settings: &PasswordHashSettings) -> AppResult<Self> {
+        let crypto_error = |e: argon2::Error| AppError::CryptoError(e.to_string());
+        let mut builder = ParamsBuilder::new();
+        builder
+            .m_cost(settings.memory_kib)
+            .t_cost(settings.iterations)
+            .p_cost(settings.parallelism);
+        if settings.pepper.is_some() {
+            builder.keyid(KeyId::new(settings.pepper_id.as_bytes()).map_err(crypto_error)?);
+        }
+        let params = builder.build().map_err(crypto_error)?;
+        let retired_peppers = settings
+            .retired_peppers
+            .iter()
+            .map(|(id, pepper)| (id.as_bytes().to_vec(), pepper.as_bytes().to_vec()))
+            .collect();
+        let concurrency = settings
+            .max_concurrent
+            .unwrap_or_else(default_hashing_concurrency)
+            .max(1);
+        Ok(Self {
+            params,
+            pepper: settings.pepper.as_ref().map(|pepper| {
+                (
+                    settings.pepper_id.as_bytes().to_vec(),
+                    pepper.as_bytes().to_vec(),
+                )
+            }),
+            retired_peppers,
+            legacy: legacy_hashers(),
+            permits: Arc::new(Semaphore::new(concurrency)),
+            dummy_hash: OnceLock::new(),
+        })
+    }
+
+    /// Подключает алгоритм для проверки хэшей из другой системы
+    ///
+    /// # Аргументы
+    ///
+    /// * `hasher` - Алгоритм, проверяемый после Argon2 и ранее подключенных
+    pub fn with_hasher(mut self, hasher: impl PasswordHasher + 'static) -> Self {
+        self.legacy.push(Box::new(hasher));
+        self
+    }
+
+    /// Устанавливает настройки для всего приложения
+    ///
+    /// Вызывается один раз при старте, до первого хэширования.
+    pub fn install(self) -> AppResult<()> {
+        HASHING
+            .set(self)
+            .map_err(|_| AppError::Custom("password hashing is already configured".into()))
+    }
+
+    /// Возвращает установленные настройки или настройки по умолчанию
+    pub fn current() -> &'static PasswordHashing {
+        HASHING.get_or_init(PasswordHashing::default)
+    }
+
+    /// Хэширует пароль с текущими параметрами и перцем
+    pub fn hash(&self, password: &str) -> AppResult<String> {
+        let started = Instant::now();
+        let salt = SaltString::generate(&mut OsRng);
+        let argon2 = self.argon2(self.pepper.as_ref().map(|(_, pepper)| pepper.as_slice()))?;
+        let res = argon2
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| AppError::CryptoError(e.to_string()))?
+            .to_string();
+        metrics::record_password_hash("hash", started);
+        Ok(res)
+    }
+
+    /// Проверяет пароль по хэшу
+    ///
+    /// Перец выбирается по `keyid` из хэша; хэш с неизвестным
+    /// идентификатором перца не совпадает ни с одним паролем.
+    /// Хэши других алгоритмов проверяет распознавший их `PasswordHasher`.
+    /// С `UNUSABLE_PASSWORD_HASH` пароль не совпадает, но проверка занимает
+    /// столько же времени, сколько с настоящим хэшем.
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(bool)` - Совпадает ли пароль с хэшем
+    /// * `Err(AppError::CryptoError)` - Хэш поврежден или его формат неизвестен
+    pub fn verify(&self, hash: &str, password: &str) -> AppResult<bool> {
+        if hash == UNUSABLE_PASSWORD_HASH {
+            self.verify_argon2(self.dummy_hash()?, password)?;
+            return Ok(false);
+        }
+        if is_argon2(hash) {
+            return self.verify_argon2(hash, password);
+        }
+        let hasher = self
+            .legacy_hasher(hash)
+            .ok_or_else(|| AppError::CryptoError("unsupported password hash format".into()))?;
+        tracing::debug!("verifying {} password hash", hasher.name());
+        hasher.verify(hash, password)
+    }
+
+    /// Проверяет, что хэш можно сохранить как пароль пользователя
+    ///
+    /// Формат должен распознаваться, а хэш - разбираться. Хэш при этом
+    /// не вычисляется, но его стоимость ограничена: иначе импортированный
+    /// хэш занимал бы поток при каждом входе сколь угодно долго.
+    pub fn accepts(&self, hash: &str) -> bool {
+        if is_argon2(hash) {
+            return check_argon2_params(hash).is_ok();
+        }
+        self.legacy_hasher(hash)
+            .is_some_and(|hasher| hasher.check_params(hash).is_ok())
+    }
+
+    fn legacy_hasher(&self, hash: &str) -> Option<&dyn PasswordHasher> {
+        self.legacy
+            .iter()
+            .find(|hasher| hasher.recognizes(hash))
+            .map(Box::as_ref)
+    }
+
+    fn verify_argon2(&self, hash: &str, password: &str) -> AppResult<bool> {
+        let parsed_hash =
+            PasswordHash::new(hash).map_err(|e| AppError::CryptoError(e.to_string()))?;
+        let keyid = Params::try_from(&parsed_hash)
+            .map(|params| params.keyid().to_vec())
+            .unwrap_or_default();
+        let pepper = if keyid.is_empty() {
+            None
+        } else {
+            match self.pepper_by_id(&keyid) {
+                Some(pepper) => Some(pepper),
+                None => return Ok(false),
+            }
+        };
+        let started = Instant::now();
+        let res = self
+            .argon2(pepper)?
+            .verify_password(password.as_bytes(), &parsed_hash)
+            .is_ok();
+        metrics::record_password_hash("verify", started);
+        Ok(res)
+    }
+
+    /// Проверяет, что хэш получен с устаревшими параметрами или перцем
+    /// и его следует пересчитать
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        if hash == UNUSABLE_PASSWORD_HASH {
+            return false;
+        }
+        let Ok(parsed_hash) = PasswordHash::new(hash) else {
+            return true;
+        };
+        if parsed_hash.algorithm != Algorithm::Argon2id.ident()
+            || parsed_hash.version != Some(Version::V0x13.into())
+        {
+            return true;
+        }
+        let Ok(params) = Params::try_from(&parsed_hash) else {
+            return true;
+        };
+        params.m_cost() != self.params.m_cost()
+            || params.t_cost() != self.params.t_cost()
+            || params.p_cost() != self.params.p_cost()
+            || params.keyid() != self.params.keyid()
+    }
+
+    /// Хэширует пароль в пуле блокирующих задач
+    ///
+    /// Число одновременных вычислений ограничено, чтобы всплеск входов
+    /// не занял все потоки пула и память.
+    pub async fn hash_blocking(&'static self, password: String) -> AppResult<String> {
+        let _permit = self.acquire().await?;
+        tokio::task::spawn_blocking(move || self.hash(&password))
+            .await
+            .map_err(|e| AppError::CryptoError(e.to_string()))?
+    }
+
+    /// Проверяет пароль в пуле блокирующих задач
+    pub async fn verify_blocking(&'static self, hash: String, password: String) -> AppResult<bool> {
+        let _permit = self.acquire().await?;
+        tokio::task::spawn_blocking(move || self.verify(&hash, &password))
+            .await
+            .map_err(|e| AppError::CryptoError(e.to_string()))?
+    }
+
+    /// Хэш-заглушка с текущими параметрами, вычисляемый при первом обращении
+    fn dummy_hash(&self) -> AppResult<&str> {
+        if let Some(hash) = self.dummy_hash.get() {
+            return Ok(hash);
+        }
+        let hash = self.hash("alfred-dummy-password")?;
+        Ok(self.dummy_hash.get_or_init(|| hash))
+    }
+
+    async fn acquire(&self) -> AppResult<tokio::sync::OwnedSemaphorePermit> {
+        self.permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|e| AppError::CryptoError(e.to_string()))
+    }
+
+    fn pepper_by_id(&self, keyid: &[u8]) -> Option<&[u8]> {
+        match &self.pepper {
+            Some((id, pepper)) if id == keyid => Some(pepper),
+            _ => self.retired_peppers.get(keyid).map(Vec::as_slice),
+        }
+    }
+
+    fn argon2<'a>(&'a self, pepper: Option<&'a [u8]>) -> AppResult<Argon2<'a>> {
+        match pepper {
+            Some(pepper) => Argon2::new_with_secret(
+                pepper,
+                Algorithm::Argon2id,
+                Version::V0x13,
+                self.params.clone(),
+            )
+            .map_err(|e| AppError::CryptoError(e.to_string())),
+            None => Ok(Argon2::new(
+                Algorithm::Argon2id,
+                Version::V0x13,
+                self.params.clone(),
+            )),
+        }
+    }
+}
+
+fn is_argon2(hash: &str) -> bool {
+    hash.starts_with("$argon2")
+}
+
+/// Проверяет, что хэш Argon2 разбирается и его параметры не превышают допустимых
+fn check_argon2_params(hash: &str) -> AppResult<()> {
+    let parsed_hash = PasswordHash::new(hash).map_err(|e| AppError::CryptoError(e.to_string()))?;
+    Algorithm::try_from(parsed_hash.algorithm).map_err(|e| AppError::CryptoError(e.to_string()))?;
+    let params =
+        Params::try_from(&parsed_hash).map_err(|e| AppError::CryptoError(e.to_string()))?;
+    if parsed_hash.salt.is_none() || parsed_hash.hash.is_none() {
+        return Err(AppError::CryptoError("malformed argon2 hash".into()));
+    }
+    if params.m_cost() > MAX_ARGON2_M_COST
+        || params.t_cost() > MAX_ARGON2_T_COST
+        || params.p_cost() > MAX_ARGON2_P_COST
+    {
+        return Err(AppError::CryptoError(format!(
+            "argon2 hash must use at most m={MAX_ARGON2_M_COST}, \
+             t={MAX_ARGON2_T_COST} and p={MAX_ARGON2_P_COST}"
+        )));
+    }
+    Ok(())
+}
+
+/// Число одновременных вычислений хэша по умолчанию - по числу ядер
+fn default_hashing_concurrency() -> usize {
+    std::thread::available_parallelism().map_or(4, |n| n.get())
+}
 
 pub fn hash_password(password: &str) -> AppResult<String> {
-    let password = password.as_bytes();
-    let salt = SaltString::generate(&mut OsRng);
-    let res = Argon2::default()
-        .hash_password(password, &salt)
-        .map_err(|e| AppError::CryptoError(e.to_string()))?
-        .to_string();
-    Ok(res)
+    PasswordHashing::current().hash(password)
 }
 
 pub fn verify_password(hash: &str, password: &str) -> AppResult<bool> {
-    let parsed_hash = PasswordHash::new(hash).map_err(|e| AppError::CryptoError(e.to_string()))?;
-    let res = Argon2::default()
-        .verify_password(password.as_bytes(), &parsed_hash)
-        .is_ok();
-    Ok(res)
+    PasswordHashing::current().verify(hash, password)
+}
+
+/// Хэширует пароль, не блокируя поток асинхронного рантайма
+pub async fn hash_password_blocking(password: &str) -> AppResult<String> {
+    PasswordHashing::current()
+        .hash_blocking(password.to_string())
+        .await
+}
+
+/// Проверяет пароль, не блокируя поток асинхронного рантайма
+pub async fn verify_password_blocking(hash: &str, password: &str) -> AppResult<bool> {
+    PasswordHashing::current()
+        .verify_blocking(hash.to_string(), password.to_string())
+        .await
+}
+
+/// Проверяет, что хэш нужно пересчитать с текущими параметрами
+pub fn needs_rehash(hash: &str) -> bool {
+    PasswordHashing::current().needs_rehash(hash)
+}
+
+/// Проверяет, что готовый хэш из другой системы можно импортировать
+pub fn is_supported_password_hash(hash: &str) -> bool {
+    PasswordHashing::current().accepts(hash)
+}
+
+/// Генерирует новый API-ключ вида `alf_<64 hex-символа>`
+pub fn generate_api_token() -> String {
+    let mut bytes = [0u8; API_TOKEN_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+    format!("{API_TOKEN_PREFIX}{}", to_hex(&bytes))
+}
+
+/// Вычисляет хэш API-ключа для хранения и поиска
+///
+/// Ключ содержит 256 бит случайных данных, поэтому медленная функция
+/// хэширования не нужна: достаточно SHA-256, что позволяет искать
+/// ключ по хэшу без перебора.
+pub fn hash_api_token(token: &str) -> String {
+    to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+/// Генерирует случайную строку, пригодную для URL
+///
+/// # Аргументы
+///
+/// * `bytes` - Количество случайных байт; длина строки примерно в 4/3 больше
+pub fn random_urlsafe(bytes: usize) -> String {
+    use base64::Engine;
+    let mut buf = vec![0u8; bytes];
+    OsRng.fill_bytes(&mut buf);
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(buf)
+}
+
+/// Вычисляет `code_challenge` PKCE по методу `S256` (RFC 7636)
+pub fn pkce_challenge(code_verifier: &str) -> String {
+    use base64::Engine;
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    use std::fmt::Write;
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut acc, b| {
+            let _ = write!(acc, "{b:02x}");
+            acc
+        })
 }
 
 #[cfg(test)]
@@ -42,4 +612,185 @@ mod tests {
         let result = verify_password(&hashed, "wrongPass").unwrap();
         assert!(!result);
     }
+    #[test]
+    fn test_unusable_hash() {
+        let result = verify_password(UNUSABLE_PASSWORD_HASH, "").unwrap();
+        assert!(!result);
+        let hashing = hashing(None, &[]);
+        assert!(
+            !hashing
+                .verify(UNUSABLE_PASSWORD_HASH, "alfred-dummy-password")
+                .unwrap()
+        );
+        assert!(hashing.dummy_hash.get().is_some());
+    }
+    fn hashing(pepper: Option<(&str, &str)>, retired: &[(&str, &str)]) -> PasswordHashing {
+        PasswordHashing::from_settings(&PasswordHashSettings {
+            memory_kib: 1024,
+            iterations: 1,
+            parallelism: 1,
+            pepper: pepper.map(|(_, pepper)| pepper.to_string()),
+            pepper_id: pepper.map_or("1", |(id, _)| id).to_string(),
+            retired_peppers: retired
+                .iter()
+                .map(|(id, pepper)| (id.to_string(), pepper.to_string()))
+                .collect(),
+            max_concurrent: Some(1),
+        })
+        .unwrap()
+    }
+    #[test]
+    fn test_needs_rehash_on_changed_params() {
+        let weak = hashing(None, &[]);
+        let hash = weak.hash("somePassword").unwrap();
+        assert!(hash.contains("m=1024,t=1,p=1"));
+        assert!(!weak.needs_rehash(&hash));
+        assert!(PasswordHashing::default().needs_rehash(&hash));
+        // Хэш с текущими параметрами пересчитывать не нужно
+        assert!(!needs_rehash(&hash_password("somePassword").unwrap()));
+        assert!(!needs_rehash(UNUSABLE_PASSWORD_HASH));
+        // Argon2i и старая версия алгоритма считаются устаревшими
+        let argon2i = Argon2::new(Algorithm::Argon2i, Version::V0x13, Params::default())
+            .hash_password(b"somePassword", &SaltString::generate(&mut OsRng))
+            .unwrap()
+            .to_string();
+        assert!(needs_rehash(&argon2i));
+    }
+    #[test]
+    fn test_pepper_rotation() {
+        let peppered = hashing(Some(("p1", "first pepper")), &[]);
+        let hash = peppered.hash("somePassword").unwrap();
+        assert!(hash.contains("keyid="));
+        assert!(peppered.verify(&hash, "somePassword").unwrap());
+        // Без перца хэш не проверяется, а старый хэш без перца требует пересчета
+        let plain = hashing(None, &[]);
+        assert!(!plain.verify(&hash, "somePassword").unwrap());
+        assert!(peppered.needs_rehash(&plain.hash("somePassword").unwrap()));
+        assert!(
+            peppered
+                .verify(&plain.hash("somePassword").unwrap(), "somePassword")
+                .unwrap()
+        );
+
+        // После смены перца старые хэши проверяются и помечаются к пересчету
+        let rotated = hashing(Some(("p2", "second pepper")), &[("p1", "first pepper")]);
+        assert!(rotated.verify(&hash, "somePassword").unwrap());
+        assert!(rotated.needs_rehash(&hash));
+        let wrong_pepper = hashing(Some(("p1", "other pepper")), &[]);
+        assert!(!wrong_pepper.verify(&hash, "somePassword").unwrap());
+    }
+    // Тестовый вектор OpenBSD bcrypt для пароля "U*U"
+    const BCRYPT_HASH: &str = "$2a$05$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOeW";
+    const PBKDF2_PHC_HASH: &str = "$pbkdf2-sha256$i=10000,l=32$c2FsdHNhbHRzYWx0c2FsdA$/HBtt7Z/7p0CzRvSN1B+KXrKNskvRts1UWw61zKTFUo";
+    const PBKDF2_PASSLIB_HASH: &str =
+        "$pbkdf2-sha256$29000$N2YMIWQsBWBMae09x1jrPQ$lEjTD4tvq5SOB5ctjdSxvnbzU5PQEfMComvCIxNEqq8";
+    #[test]
+    fn test_legacy_hashes() {
+        assert!(verify_password(BCRYPT_HASH, "U*U").unwrap());
+        assert!(!verify_password(BCRYPT_HASH, "U*V").unwrap());
+        let bcrypt_2b = bcrypt::hash("somePassword", 4).unwrap();
+        assert!(verify_password(&bcrypt_2b, "somePassword").unwrap());
+
+        for hash in [PBKDF2_PHC_HASH, PBKDF2_PASSLIB_HASH] {
+            assert!(verify_password(hash, "password").unwrap(), "{hash}");
+            assert!(!verify_password(hash, "Password").unwrap(), "{hash}");
+        }
+
+        // Хэши других алгоритмов всегда пересчитываются
+        for hash in [BCRYPT_HASH, PBKDF2_PHC_HASH, PBKDF2_PASSLIB_HASH] {
+            assert!(needs_rehash(hash), "{hash}");
+            assert!(is_supported_password_hash(hash), "{hash}");
+        }
+        assert!(is_supported_password_hash(
+            &hash_password("somePassword").unwrap()
+        ));
+    }
+    #[test]
+    fn test_unsupported_hashes() {
+        for hash in [
+            "5f4dcc3b5aa765d61d8327deb882cf99",
+            "$1$saltsalt$qjXMvbEw8oaL.CzflDugX/",
+            "$pbkdf2-sha256$0$N2YMIWQsBWBMae09x1jrPQ$lEjT",
+            "$pbkdf2-sha256$29000$N2YMIWQsBWBMae09x1jrPQ",
+            "$2b$05$short",
+            UNUSABLE_PASSWORD_HASH,
+        ] {
+            assert!(!is_supported_password_hash(hash), "{hash}");
+        }
+        // Хэши, проверка которых заняла бы поток надолго, не принимаются
+        for hash in [
+            BCRYPT_HASH.replacen("$05$", "$31$", 1),
+            PBKDF2_PASSLIB_HASH.replacen("$29000$", "$4000000000$", 1),
+            PBKDF2_PHC_HASH.replacen("i=10000", "i=4000000000", 1),
+            hash_password("somePassword")
+                .unwrap()
+                .replacen("m=19456", "m=4194304", 1),
+        ] {
+            assert!(!is_supported_password_hash(&hash), "{hash}");
+        }
+        assert!(verify_password("5f4dcc3b5aa765d61d8327deb882cf99", "password").is_err());
+    }
+    #[test]
+    fn test_custom_hasher() {
+        struct PlainHasher;
+        impl PasswordHasher for PlainHasher {
+            fn name(&self) -> &'static str {
+                "plain"
+            }
+            fn recognizes(&self, hash: &str) -> bool {
+                hash.starts_with("$plain$")
+            }
+            fn check_params(&self, _hash: &str) -> AppResult<()> {
+                Ok(())
+            }
+            fn verify(&self, hash: &str, password: &str) -> AppResult<bool> {
+                Ok(hash.strip_prefix("$plain$") == Some(password))
+            }
+        }
+        let hashing = hashing(None, &[]).with_hasher(PlainHasher);
+        assert!(hashing.verify("$plain$secret", "secret").unwrap());
+        assert!(hashing.accepts("$plain$secret"));
+        assert!(hashing.needs_rehash("$plain$secret"));
+        assert!(hashing.verify(BCRYPT_HASH, "U*U").unwrap());
+        assert!(
+            PasswordHashing::default()
+                .verify("$plain$secret", "secret")
+                .is_err()
+        );
+    }
+    #[tokio::test]
+    async fn test_blocking_pool() {
+        let hash = hash_password_blocking("somePassword").await.unwrap();
+        let mut checks = tokio::task::JoinSet::new();
+        for _ in 0..4 {
+            let hash = hash.clone();
+            checks.spawn(async move { verify_password_blocking(&hash, "somePassword").await });
+        }
+        while let Some(result) = checks.join_next().await {
+            assert!(result.unwrap().unwrap());
+        }
+    }
+    #[test]
+    fn test_api_token_generation() {
+        let token = generate_api_token();
+        assert!(token.starts_with(API_TOKEN_PREFIX));
+        assert_eq!(token.len(), API_TOKEN_PREFIX.len() + API_TOKEN_BYTES * 2);
+        assert_ne!(token, generate_api_token());
+
+        let hashed = hash_api_token(&token);
+        assert_eq!(hashed.len(), 64);
+        assert_eq!(hashed, hash_api_token(&token));
+        assert_ne!(hashed, hash_api_token(&generate_api_token()));
+    }
+    #[test]
+    fn test_pkce_challenge() {
+        // Пример из приложения B RFC 7636
+        let verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+        assert_eq!(
+            pkce_challenge(verifier),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+        assert_eq!(random_urlsafe(32).len(), 43);
+        assert_ne!(random_urlsafe(32), random_urlsafe(32));
+    }
 }
@@ -1,9 +1,16 @@
+use std::collections::BTreeMap;
+
 use axum::{http::StatusCode, response::IntoResponse};
 use serde::Serialize;
 use thiserror::Error;
-use validator::{ValidationError, ValidationErrors};
+use utoipa::ToSchema;
+use validator::{ValidationError, ValidationErrors, ValidationErrorsKind};
 
-use crate::storage::UsersFilterBuilderError;
+use crate::{
+    i18n::{self, tr, tr_args},
+    models::{PasswordReport, PasswordRule},
+    storage::UsersFilterBuilderError,
+};
 
 #[derive(Debug, Error)]
 pub enum AppError {
@@ -19,8 +26,18 @@ pub enum AppError {
     EntryAlreadyExists,
     #[error("Invalid input")]
     InvalidInput,
+    #[error("Malformed request: {0}")]
+    MalformedRequest(String),
+    #[error("Invalid request body: {0}")]
+    InvalidBody(String),
+    #[error("No route for {0}")]
+    RouteNotFound(String),
+    #[error("{}", .0.canonical_reason().unwrap_or("Request failed"))]
+    Http(StatusCode),
     #[error("Invalid credentials")]
     InvalidCredentials,
+    #[error("{0}")]
+    Unauthenticated(&'static str),
     #[error("Invalid user role")]
     InvalidUserRole(String),
     #[error("Validation error")]
@@ -37,37 +54,437 @@ pub enum AppError {
     IOError(#[from] std::io::Error),
     #[error("Access denied")]
     AccessDenied,
+    #[error("{0}")]
+    CsrfRejected(&'static str),
+    #[error("Too many requests")]
+    TooManyRequests,
+    #[error("Identity provider error: {0}")]
+    IdentityProvider(String),
+    #[error("Password does not meet the policy")]
+    WeakPassword(PasswordReport),
 }
 
 pub type AppResult<T> = Result<T, AppError>;
 
-#[derive(Serialize)]
-struct ApiError {
+/// Стабильный код ошибки для клиентов
+///
+/// В отличие от текста сообщения, коды не меняются между версиями,
+/// поэтому клиенты должны ветвиться по ним.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// Внутренняя ошибка сервера; подробности есть только в журнале
+    InternalError,
+    /// Запись не найдена
+    NotFound,
+    /// Запись уже существует
+    AlreadyExists,
+    /// Неверный формат запроса или параметра
+    InvalidInput,
+    /// Запрос не удалось разобрать: синтаксис JSON, строка запроса, путь
+    MalformedRequest,
+    /// JSON не соответствует ожидаемой структуре
+    InvalidBody,
+    /// Маршрут не существует
+    RouteNotFound,
+    /// Метод не поддерживается маршрутом
+    MethodNotAllowed,
+    /// Запрос не уложился в отведенное время
+    RequestTimeout,
+    /// Тело запроса слишком большое
+    PayloadTooLarge,
+    /// Тип содержимого не поддерживается
+    UnsupportedMediaType,
+    /// Запрос без действующего токена
+    Unauthenticated,
+    /// Неверная почта, пароль или ключ
+    InvalidCredentials,
+    /// Недостаточно прав
+    AccessDenied,
+    /// Запрос с cookie не прошел проверку CSRF
+    CsrfRejected,
+    /// Данные не прошли проверку; подробности по полям в `details`
+    ValidationFailed,
+    /// Пароль не соответствует парольной политике
+    WeakPassword,
+    /// Неизвестная роль пользователя
+    InvalidRole,
+    /// Превышен лимит запросов
+    RateLimited,
+    /// Ошибка внешнего провайдера учетных записей
+    IdentityProviderError,
+}
+
+/// Ошибка проверки одного поля
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub(crate) struct FieldError {
+    /// Код нарушенного правила
+    code: String,
+    /// Описание для пользователя
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    /// Параметры правила, например минимальная длина
+    #[serde(skip_serializing_if = "serde_json::Map::is_empty")]
+    #[schema(value_type = Object)]
+    params: serde_json::Map<String, serde_json::Value>,
+}
+
+impl From<&ValidationError> for FieldError {
+    /// Переводит сообщение проверки на язык запроса
+    ///
+    /// Сообщения правил - шаблоны каталога с местами для параметров
+    /// правила; для встроенных правил без сообщения берется общее.
+    fn from(error: &ValidationError) -> Self {
+        // validator добавляет проверяемое значение - это может быть пароль
+        let params: serde_json::Map<String, serde_json::Value> = error
+            .params
+            .iter()
+            .filter(|(name, _)| *name != "value")
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect();
+        let message = match (&error.message, error.code.as_ref()) {
+            (Some(message), _) => Some(message.as_ref()),
+            (None, "email") => Some("Invalid email address"),
+            (None, _) => None,
+        };
+        let message = message.map(|message| {
+            i18n::format(tr(message), |name| {
+                params.get(name).map(|value| match value {
+                    serde_json::Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                })
+            })
+        });
+        Self {
+            code: error.code.to_string(),
+            message,
+            params,
+        }
+    }
+}
+
+impl From<&PasswordRule> for FieldError {
+    fn from(rule: &PasswordRule) -> Self {
+        let mut params = match serde_json::to_value(rule) {
+            Ok(serde_json::Value::Object(params)) => params,
+            _ => serde_json::Map::new(),
+        };
+        let code = match params.remove("rule") {
+            Some(serde_json::Value::String(code)) => code,
+            _ => "password".to_string(),
+        };
+        Self {
+            code,
+            message: Some(rule.message()),
+            params,
+        }
+    }
+}
+
+/// Тело ответа с ошибкой
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub(crate) struct ApiError {
+    /// `fail` для ошибок клиента, `error` для ошибок сервера
     status: &'static str,
+    /// Стабильный код ошибки
+    code: ErrorCode,
+    /// Описание ошибки
     message: String,
+    /// Ошибки по полям запроса
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    details: BTreeMap<String, Vec<FieldError>>,
+    /// Идентификатор запроса из заголовка `alfred-request-id`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+}
+
+impl ApiError {
+    /// Дополняет ответ идентификатором запроса
+    pub(crate) fn with_request_id(mut self, request_id: impl Into<String>) -> Self {
+        self.request_id = Some(request_id.into());
+        self
+    }
 }
+
 impl From<AppError> for ApiError {
     fn from(value: AppError) -> Self {
+        let status = value.status();
+        let mut details = BTreeMap::new();
+        let message = match &value {
+            _ if status.is_server_error() => {
+                tr(status.canonical_reason().unwrap_or("Internal Server Error")).to_string()
+            }
+            AppError::ValidationErrors(errors) => {
+                collect_field_errors(errors, "", &mut details);
+                tr("Validation errors").to_string()
+            }
+            AppError::ValidationError(error) => FieldError::from(error)
+                .message
+                .unwrap_or_else(|| tr("Validation error").to_string()),
+            AppError::WeakPassword(report) => {
+                details.insert(
+                    "password".to_string(),
+                    report.failed_rules.iter().map(FieldError::from).collect(),
+                );
+                report.message()
+            }
+            AppError::MalformedRequest(reason) => {
+                tr_args("Malformed request: {reason}", &[("reason", reason)])
+            }
+            AppError::InvalidBody(reason) => {
+                tr_args("Invalid request body: {reason}", &[("reason", reason)])
+            }
+            AppError::RouteNotFound(uri) => tr_args("No route for {uri}", &[("uri", uri)]),
+            AppError::Http(status) => {
+                tr(status.canonical_reason().unwrap_or("Request failed")).to_string()
+            }
+            AppError::Unauthenticated(reason) | AppError::CsrfRejected(reason) => {
+                tr(reason).to_string()
+            }
+            _ => tr(&value.to_string()).to_string(),
+        };
         Self {
-            status: "error",
-            message: value.to_string(),
+            status: if status.is_server_error() {
+                "error"
+            } else {
+                "fail"
+            },
+            code: value.code(),
+            message,
+            details,
+            request_id: None,
         }
     }
 }
 
-impl IntoResponse for AppError {
-    fn into_response(self) -> axum::response::Response {
-        let status = match self {
+/// Раскладывает вложенные ошибки проверки по путям полей:
+/// `info.timezone`, `items[0].name`
+fn collect_field_errors(
+    errors: &ValidationErrors,
+    prefix: &str,
+    details: &mut BTreeMap<String, Vec<FieldError>>,
+) {
+    for (field, kind) in errors.errors() {
+        let path = if prefix.is_empty() {
+            field.to_string()
+        } else {
+            format!("{prefix}.{field}")
+        };
+        match kind {
+            ValidationErrorsKind::Field(errors) => {
+                details
+                    .entry(path)
+                    .or_default()
+                    .extend(errors.iter().map(FieldError::from));
+            }
+            ValidationErrorsKind::Struct(errors) => collect_field_errors(errors, &path, details),
+            ValidationErrorsKind::List(items) => {
+                for (index, errors) in items {
+                    collect_field_errors(errors, &format!("{path}[{index}]"), details);
+                }
+            }
+        }
+    }
+}
+
+impl AppError {
+    /// HTTP-статус ответа
+    pub fn status(&self) -> StatusCode {
+        match self {
             AppError::EntryNotFound => StatusCode::NOT_FOUND,
-            AppError::AccessDenied
-            | AppError::EntryAlreadyExists
-            | AppError::InvalidInput
-            | AppError::InvalidCredentials
-            | AppError::InvalidUserRole(_)
+            AppError::EntryAlreadyExists => StatusCode::CONFLICT,
+            AppError::InvalidInput
+            | AppError::MalformedRequest(_)
+            | AppError::UuidError(_)
+            | AppError::BuilderError(_) => StatusCode::BAD_REQUEST,
+            AppError::RouteNotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Http(status) => *status,
+            AppError::Unauthenticated(_) | AppError::InvalidCredentials => StatusCode::UNAUTHORIZED,
+            AppError::AccessDenied | AppError::CsrfRejected(_) => StatusCode::FORBIDDEN,
+            AppError::InvalidUserRole(_)
+            | AppError::InvalidBody(_)
             | AppError::ValidationError(_)
-            | AppError::ValidationErrors(_) => StatusCode::BAD_REQUEST,
-            _ => StatusCode::INTERNAL_SERVER_ERROR,
+            | AppError::ValidationErrors(_)
+            | AppError::WeakPassword(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
+            AppError::IdentityProvider(_) => StatusCode::BAD_GATEWAY,
+            AppError::Custom(_)
+            | AppError::DatabaseInternalError(_)
+            | AppError::DatabaseMigrationError(_)
+            | AppError::CryptoError(_)
+            | AppError::IOError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Стабильный код ошибки
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            AppError::EntryNotFound => ErrorCode::NotFound,
+            AppError::EntryAlreadyExists => ErrorCode::AlreadyExists,
+            AppError::InvalidInput | AppError::UuidError(_) | AppError::BuilderError(_) => {
+                ErrorCode::InvalidInput
+            }
+            AppError::MalformedRequest(_) => ErrorCode::MalformedRequest,
+            AppError::InvalidBody(_) => ErrorCode::InvalidBody,
+            AppError::RouteNotFound(_) => ErrorCode::RouteNotFound,
+            AppError::Http(status) => match *status {
+                StatusCode::NOT_FOUND => ErrorCode::RouteNotFound,
+                StatusCode::METHOD_NOT_ALLOWED => ErrorCode::MethodNotAllowed,
+                StatusCode::REQUEST_TIMEOUT => ErrorCode::RequestTimeout,
+                StatusCode::PAYLOAD_TOO_LARGE => ErrorCode::PayloadTooLarge,
+                StatusCode::UNSUPPORTED_MEDIA_TYPE => ErrorCode::UnsupportedMediaType,
+                StatusCode::UNAUTHORIZED => ErrorCode::Unauthenticated,
+                StatusCode::FORBIDDEN => ErrorCode::AccessDenied,
+                StatusCode::TOO_MANY_REQUESTS => ErrorCode::RateLimited,
+                status if status.is_client_error() => ErrorCode::MalformedRequest,
+                _ => ErrorCode::InternalError,
+            },
+            AppError::Unauthenticated(_) => ErrorCode::Unauthenticated,
+            AppError::InvalidCredentials => ErrorCode::InvalidCredentials,
+            AppError::AccessDenied => ErrorCode::AccessDenied,
+            AppError::CsrfRejected(_) => ErrorCode::CsrfRejected,
+            AppError::ValidationError(_) | AppError::ValidationErrors(_) => {
+                ErrorCode::ValidationFailed
+            }
+            AppError::WeakPassword(_) => ErrorCode::WeakPassword,
+            AppError::InvalidUserRole(_) => ErrorCode::InvalidRole,
+            AppError::TooManyRequests => ErrorCode::RateLimited,
+            AppError::IdentityProvider(_) => ErrorCode::IdentityProviderError,
+            AppError::Custom(_)
+            | AppError::DatabaseInternalError(_)
+            | AppError::DatabaseMigrationError(_)
+            | AppError::CryptoError(_)
+            | AppError::IOError(_) => ErrorCode::InternalError,
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    /// Формирует JSON-ответ с ошибкой
+    ///
+    /// Текст ошибок сервера может содержать SQL или пути к файлам,
+    /// поэтому клиенту отдается только код, а подробности пишутся в журнал.
+    /// Тело ответа также кладется в расширения, чтобы middleware
+    /// дополнило его идентификатором запроса.
+    fn into_response(self) -> axum::response::Response {
+        let status = self.status();
+        if status.is_server_error() {
+            tracing::error!(error = %self, "request failed");
+        }
+        let body = ApiError::from(self);
+        let mut response = (status, axum::Json(&body)).into_response();
+        response.extensions_mut().insert(body);
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use validator::Validate;
+
+    use super::*;
+    use crate::models::UserToUpdate;
+
+    async fn body(error: AppError) -> (StatusCode, serde_json::Value) {
+        let response = error.into_response();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_error_statuses() {
+        let cases = [
+            (AppError::InvalidCredentials, 401, "invalid_credentials"),
+            (AppError::AccessDenied, 403, "access_denied"),
+            (AppError::EntryAlreadyExists, 409, "already_exists"),
+            (
+                AppError::InvalidUserRole("root".into()),
+                422,
+                "invalid_role",
+            ),
+            (AppError::EntryNotFound, 404, "not_found"),
+            (AppError::TooManyRequests, 429, "rate_limited"),
+        ];
+        for (error, status, code) in cases {
+            let (actual, body) = body(error).await;
+            assert_eq!(actual.as_u16(), status);
+            assert_eq!(body["code"], code);
+            assert_eq!(body["status"], "fail");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_internal_error_is_redacted() {
+        let (status, body) = body(AppError::DatabaseInternalError(sqlx::Error::Protocol(
+            "relation \"users\" does not exist".into(),
+        )))
+        .await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["status"], "error");
+        assert_eq!(body["code"], "internal_error");
+        assert_eq!(body["message"], "Внутренняя ошибка сервера");
+        assert!(!body.to_string().contains("relation"));
+    }
+
+    #[tokio::test]
+    async fn test_validation_details() {
+        let update = UserToUpdate {
+            email: "not-an-email".into(),
+            info: crate::models::UserInfo {
+                timezone: Some("Mars/Olympus".into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let errors = update.validate().unwrap_err();
+        let (status, body) = body(errors.into()).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "validation_failed");
+        assert_eq!(body["details"]["email"][0]["code"], "email");
+        assert_eq!(
+            body["details"]["info"][0]["message"],
+            "Неизвестный часовой пояс: Mars/Olympus"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_messages_follow_locale() {
+        let (_, en) = crate::i18n::Locale::En
+            .scope(body(AppError::Unauthenticated("No token provided")))
+            .await;
+        assert_eq!(en["message"], "No token provided");
+        let (_, ru) = body(AppError::Unauthenticated("No token provided")).await;
+        assert_eq!(ru["message"], "Токен не передан");
+
+        let update = UserToUpdate {
+            email: "user@example.com".into(),
+            info: crate::models::UserInfo {
+                timezone: Some("Mars/Olympus".into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let error = AppError::from(update.validate().unwrap_err());
+        let (_, en) = crate::i18n::Locale::En.scope(body(error)).await;
+        assert_eq!(en["message"], "Validation errors");
+        assert_eq!(
+            en["details"]["info"][0]["message"],
+            "Unknown time zone: Mars/Olympus"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_weak_password_details() {
+        let report = crate::models::PasswordReport {
+            score: 0,
+            failed_rules: vec![PasswordRule::TooShort { min: 8 }],
         };
-        (status, axum::Json(ApiError::from(self))).into_response()
+        let (status, body) = body(AppError::WeakPassword(report)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["details"]["password"][0]["code"], "too_short");
+        assert_eq!(body["details"]["password"][0]["params"]["min"], 8);
     }
 }
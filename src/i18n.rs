@@ -0,0 +1,357 @@
+//! Модуль локализации сообщений API
+//!
+//! Сообщения в коде пишутся по-английски и служат ключами каталога,
+//! как в gettext; переводы на остальные языки хранятся в таблицах ниже.
+//! Язык выбирается для каждого запроса: по настройке пользователя,
+//! а если она не задана - по заголовку `Accept-Language`. Выбранный язык
+//! действует до конца обработки запроса, поэтому функции перевода
+//! не требуют передавать его явно.
+
+use std::{collections::HashMap, fmt::Display, str::FromStr, sync::LazyLock};
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{AppError, AppResult};
+
+tokio::task_local! {
+    /// Язык обрабатываемого запроса
+    static CURRENT: Locale;
+}
+
+/// Поддерживаемый язык сообщений
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    /// Русский - язык по умолчанию
+    #[default]
+    Ru,
+    /// Английский
+    En,
+}
+
+impl Locale {
+    /// Все поддерживаемые языки
+    pub const ALL: [Locale; 2] = [Locale::Ru, Locale::En];
+
+    /// Языковой тег для заголовка `Content-Language`
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Locale::Ru => "ru",
+            Locale::En => "en",
+        }
+    }
+
+    /// Находит язык по тегу BCP 47; учитывается только основной подтег,
+    /// поэтому `en-GB` и `EN` соответствуют английскому
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let primary = tag.trim().split(['-', '_']).next()?;
+        Self::ALL
+            .into_iter()
+            .find(|locale| locale.tag().eq_ignore_ascii_case(primary))
+    }
+
+    /// Выбирает язык по заголовку `Accept-Language`
+    ///
+    /// Языки перебираются по убыванию веса `q`; при равном весе важнее
+    /// тот, что указан раньше. Языки с `q=0` и `*` пропускаются.
+    ///
+    /// # Аргументы
+    ///
+    /// * `header` - Значение заголовка, например `en-US,en;q=0.9,ru;q=0.8`
+    ///
+    /// # Возвращает
+    ///
+    /// * `Some(Locale)` - Первый поддерживаемый язык
+    /// * `None` - Ни один из запрошенных языков не поддерживается
+    pub fn negotiate(header: &str) -> Option<Self> {
+        let mut ranges: Vec<(&str, f32)> = header
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let tag = parts.next()?.trim();
+                let q = parts
+                    .filter_map(|param| param.trim().strip_prefix("q="))
+                    .find_map(|q| q.trim().parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                (!tag.is_empty() && tag != "*" && q > 0.0).then_some((tag, q))
+            })
+            .collect();
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranges.into_iter().find_map(|(tag, _)| Self::from_tag(tag))
+    }
+
+    /// Язык текущего запроса или язык по умолчанию вне запроса
+    pub fn current() -> Self {
+        CURRENT.try_with(|locale| *locale).unwrap_or_default()
+    }
+
+    /// Выполняет `future` с этим языком в качестве текущего
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        CURRENT.scope(self, future).await
+    }
+
+    /// Переводит сообщение на этот язык
+    ///
+    /// Сообщение без перевода возвращается как есть.
+    pub fn translate<'a>(&self, msgid: &'a str) -> &'a str {
+        let catalog = match self {
+            Locale::En => return msgid,
+            Locale::Ru => &RU,
+        };
+        catalog.get(msgid).copied().unwrap_or(msgid)
+    }
+}
+
+impl Display for Locale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.tag())
+    }
+}
+
+impl AsRef<str> for Locale {
+    fn as_ref(&self) -> &str {
+        self.tag()
+    }
+}
+
+impl FromStr for Locale {
+    type Err = AppError;
+
+    /// Парсит языковой тег в `Locale`
+    fn from_str(s: &str) -> AppResult<Self> {
+        Self::from_tag(s).ok_or(AppError::InvalidInput)
+    }
+}
+
+/// Переводит сообщение на язык текущего запроса
+pub fn tr(msgid: &str) -> &str {
+    Locale::current().translate(msgid)
+}
+
+/// Переводит сообщение и подставляет аргументы вместо `{имя}`
+///
+/// # Аргументы
+///
+/// * `msgid` - Сообщение на английском с местами для аргументов
+/// * `args` - Значения аргументов по именам
+pub fn tr_args(msgid: &str, args: &[(&str, &dyn Display)]) -> String {
+    format(tr(msgid), |name| {
+        args.iter()
+            .find(|(arg, _)| *arg == name)
+            .map(|(_, value)| value.to_string())
+    })
+}
+
+/// Подставляет значения в места `{имя}` шаблона
+///
+/// Места, для которых значения нет, остаются без изменений.
+pub fn format(template: &str, arg: impl Fn(&str) -> Option<String>) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        match tail
+            .find('}')
+            .and_then(|end| Some((end, arg(&tail[1..end])?)))
+        {
+            Some((end, value)) => {
+                result.push_str(&value);
+                rest = &tail[end + 1..];
+            }
+            None => {
+                result.push('{');
+                rest = &tail[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Перевод сообщений на русский язык
+static RU: LazyLock<HashMap<&'static str, &'static str>> = LazyLock::new(|| {
+    HashMap::from([
+        // Ошибки
+        ("Entry not found", "Запись не найдена"),
+        ("Entry already exists", "Запись уже существует"),
+        ("Invalid input", "Неверные входные данные"),
+        (
+            "Malformed request: {reason}",
+            "Некорректный запрос: {reason}",
+        ),
+        (
+            "Invalid request body: {reason}",
+            "Неверное тело запроса: {reason}",
+        ),
+        ("No route for {uri}", "Маршрут {uri} не найден"),
+        ("Invalid credentials", "Неверные учетные данные"),
+        ("Invalid user role", "Неизвестная роль пользователя"),
+        ("Validation error", "Ошибка проверки данных"),
+        ("Validation errors", "Данные не прошли проверку"),
+        ("Access denied", "Доступ запрещен"),
+        ("Too many requests", "Слишком много запросов"),
+        ("No token provided", "Токен не передан"),
+        ("Invalid token", "Недействительный токен"),
+        ("Invalid API token", "Недействительный API-ключ"),
+        (
+            "Sign in again to confirm",
+            "Войдите заново, чтобы подтвердить действие",
+        ),
+        (
+            "Unknown client certificate",
+            "Клиентский сертификат не принадлежит сервисному аккаунту",
+        ),
+        (
+            "Cross-origin request rejected",
+            "Запрос с другого источника отклонен",
+        ),
+        ("Missing CSRF token", "Не передан CSRF-токен"),
+        ("Invalid CSRF token", "Недействительный CSRF-токен"),
+        ("Request failed", "Не удалось выполнить запрос"),
+        // Стандартные описания HTTP-статусов
+        ("Bad Request", "Неверный запрос"),
+        ("Unauthorized", "Требуется аутентификация"),
+        ("Forbidden", "Доступ запрещен"),
+        ("Not Found", "Не найдено"),
+        ("Method Not Allowed", "Метод не поддерживается"),
+        ("Request Timeout", "Истекло время ожидания запроса"),
+        ("Payload Too Large", "Слишком большое тело запроса"),
+        ("Unsupported Media Type", "Неподдерживаемый тип содержимого"),
+        ("Too Many Requests", "Слишком много запросов"),
+        ("Internal Server Error", "Внутренняя ошибка сервера"),
+        ("Bad Gateway", "Ошибка внешнего сервиса"),
+        ("Service Unavailable", "Сервис недоступен"),
+        ("Gateway Timeout", "Внешний сервис не ответил вовремя"),
+        // Роли
+        ("Owner", "Владелец"),
+        ("Administrator", "Администратор"),
+        ("Employee", "Сотрудник"),
+        ("Guest", "Гость"),
+        // Парольная политика
+        (
+            "Password must be at least {min} characters long",
+            "Пароль должен содержать не менее {min} символов",
+        ),
+        (
+            "Password must be at most {max} characters long",
+            "Пароль должен содержать не более {max} символов",
+        ),
+        (
+            "Password must not contain whitespace",
+            "Пароль не должен содержать пробелы",
+        ),
+        (
+            "Password must contain at least one digit",
+            "Пароль должен содержать хотя бы одну цифру",
+        ),
+        (
+            "Password must contain at least one uppercase letter",
+            "Пароль должен содержать хотя бы одну заглавную букву",
+        ),
+        (
+            "Password must contain at least one lowercase letter",
+            "Пароль должен содержать хотя бы одну строчную букву",
+        ),
+        (
+            "Password must contain at least one special character",
+            "Пароль должен содержать хотя бы один специальный символ",
+        ),
+        ("Password is too common", "Пароль слишком распространён"),
+        (
+            "Password has appeared in a data breach",
+            "Пароль встречается в утечках данных",
+        ),
+        (
+            "Password is too easy to guess",
+            "Пароль слишком легко подобрать",
+        ),
+        (
+            "Password requirements: {rules}",
+            "Требования к паролю: {rules}",
+        ),
+        // Проверка полей
+        ("Invalid value", "Недопустимое значение"),
+        ("Invalid email address", "Неверный адрес электронной почты"),
+        ("Passwords do not match", "Пароли не совпадают"),
+        (
+            "Password must be between {min} and {max} characters long",
+            "Пароль должен содержать от {min} до {max} символов",
+        ),
+        (
+            "Name must be between {min} and {max} characters long",
+            "Имя должно содержать от {min} до {max} символов",
+        ),
+        (
+            "Title must be between {min} and {max} characters long",
+            "Название должно содержать от {min} до {max} символов",
+        ),
+        (
+            "Name may contain only lowercase Latin letters, digits and hyphens",
+            "Имя может содержать только строчные латинские буквы, цифры и дефис",
+        ),
+        ("Unknown time zone: {tz}", "Неизвестный часовой пояс: {tz}"),
+        (
+            "Unsupported password hash format",
+            "Неподдерживаемый формат хэша пароля",
+        ),
+    ])
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placeholders(message: &str) -> Vec<&str> {
+        let mut names: Vec<&str> = message
+            .split('{')
+            .skip(1)
+            .filter_map(|part| part.split_once('}').map(|(name, _)| name))
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(Locale::negotiate("en-US,en;q=0.9"), Some(Locale::En));
+        assert_eq!(
+            Locale::negotiate("de, ru;q=0.5, en;q=0.4"),
+            Some(Locale::Ru)
+        );
+        assert_eq!(Locale::negotiate("ru;q=0.3, EN-gb;q=0.8"), Some(Locale::En));
+        assert_eq!(Locale::negotiate("en;q=0, ru;q=0.1"), Some(Locale::Ru));
+        assert_eq!(Locale::negotiate("*, de"), None);
+        assert_eq!(Locale::negotiate(""), None);
+        assert_eq!("en".parse::<Locale>().unwrap(), Locale::En);
+        assert!("fr".parse::<Locale>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_translate_in_scope() {
+        assert_eq!(tr("Access denied"), "Доступ запрещен");
+        let message = Locale::En
+            .scope(async { tr_args("Unknown time zone: {tz}", &[("tz", &"Mars")]) })
+            .await;
+        assert_eq!(message, "Unknown time zone: Mars");
+        let message = Locale::Ru
+            .scope(async { tr_args("Unknown time zone: {tz}", &[("tz", &"Mars")]) })
+            .await;
+        assert_eq!(message, "Неизвестный часовой пояс: Mars");
+        // Сообщение без перевода и места без значений не теряются
+        assert_eq!(tr("Something else"), "Something else");
+        assert_eq!(format("{a} {b} {", |_| None), "{a} {b} {");
+    }
+
+    #[test]
+    fn test_catalog_placeholders() {
+        for (msgid, translation) in RU.iter() {
+            assert_eq!(
+                placeholders(msgid),
+                placeholders(translation),
+                "placeholders differ for {msgid:?}"
+            );
+        }
+    }
+}
@@ -1,10 +1,14 @@
 mod error;
-pub use error::{AppError, AppResult};
+pub use error::{AppError, AppResult, ErrorCode};
+pub mod cli;
 pub mod crypto;
+pub mod i18n;
 pub mod logger;
+pub mod metrics;
 pub mod models;
 mod server;
-pub use server::{AppState, Server};
+pub use server::{AppState, JwtKeys, RateLimits, Server};
 pub mod services;
 pub mod settings;
 pub mod storage;
+pub mod telemetry;
@@ -1,29 +1,463 @@
-/// Инициализирует глобальный журнал с заданным уровнем.
+//! Модуль журнала приложения
+//!
+//! Записи выводятся в консоль и, если настроено, в файлы с ротацией,
+//! текстом или в JSON. Какие записи попадают в журнал, определяет фильтр
+//! в формате `RUST_LOG`; его можно заменить без перезапуска через служебный
+//! порт. Значения полей с паролями, токенами и заголовками авторизации
+//! в журнал не попадают.
+
+use std::fmt::{self, Debug};
+
+use serde_json::{Map, Value};
+use tracing::{
+    Event, Subscriber,
+    field::{Field, Visit},
+};
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{
+    EnvFilter, Layer, Registry,
+    field::{MakeExt, RecordFields},
+    fmt::{
+        FmtContext, FormatEvent, FormatFields, FormattedFields, MakeWriter,
+        format::{Writer, debug_fn},
+    },
+    layer::SubscriberExt,
+    registry::LookupSpan,
+    reload,
+};
+
+use crate::{
+    AppError, AppResult,
+    settings::{LogFileSettings, LogFormat, LogRotation, LoggingSettings},
+    telemetry::Telemetry,
+};
+
+/// Значение, которое выводится вместо секрета
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Поля, значения которых скрываются целиком
+const SENSITIVE_FIELDS: [&str; 4] = ["authorization", "cookie", "set-cookie", "code_verifier"];
+
+/// Окончания имен полей с секретами: `password`, `new_password`,
+/// `access_token`, `client_secret` и т.п.
+const SENSITIVE_SUFFIXES: [&str; 4] = ["password", "token", "secret", "api_key"];
+
+type Output = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Журнал приложения
 ///
-/// Настраивает журнал с помощью `tracing_subscriber::fmt`, который будет:
-/// - отображать только сообщения с уровнем не ниже указанного `level`
-/// - включать информацию о файле и номере строки
-/// - исключать отображение цели (target)
+/// Пока значение живо, записи в файлы дописываются в фоне,
+/// поэтому его нужно хранить до завершения приложения.
+pub struct Logger {
+    filter: LogFilter,
+    _file_guard: Option<WorkerGuard>,
+}
+
+impl Logger {
+    /// Фильтр журнала для изменения во время работы
+    pub fn filter(&self) -> LogFilter {
+        self.filter.clone()
+    }
+}
+
+/// Фильтр записей журнала, который можно заменить во время работы
+#[derive(Clone)]
+pub struct LogFilter {
+    handle: reload::Handle<EnvFilter, Registry>,
+}
+
+impl LogFilter {
+    /// Текущие директивы фильтра
+    pub fn current(&self) -> AppResult<String> {
+        self.handle
+            .with_current(ToString::to_string)
+            .map_err(|e| AppError::Custom(format!("log filter: {e}")))
+    }
+
+    /// Заменяет фильтр
+    ///
+    /// # Аргументы
+    ///
+    /// * `directives` - Директивы в формате `RUST_LOG`, например `debug,sqlx=warn`
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(String)` - Новые директивы фильтра
+    /// * `Err(AppError::MalformedRequest)` - Директивы не разобраны
+    pub fn set(&self, directives: &str) -> AppResult<String> {
+        let filter = parse_filter(directives)?;
+        self.handle
+            .reload(filter)
+            .map_err(|e| AppError::Custom(format!("log filter: {e}")))?;
+        tracing::info!("log filter changed to '{directives}'");
+        self.current()
+    }
+}
+
+/// Инициализирует глобальный журнал
+///
+/// Фильтр берется из переменной окружения `RUST_LOG`, а если она
+/// не задана - из настроек. Если передан `telemetry`, спаны дополнительно
+/// экспортируются в OpenTelemetry со своим фильтром.
 ///
 /// # Аргументы
 ///
-/// * `level` - максимальный уровень логирования (от `DEBUG` до `ERROR`)
-///
-/// # Паника
-///
-/// Функция паникует, если не удается установить глобальный подписчик.
-pub fn init(level: tracing::Level) {
-    let subscriber = tracing_subscriber::fmt()
-        .with_max_level(level)
-        .with_file(true)
-        .with_line_number(true)
-        .with_target(false)
-        .finish();
-
-    tracing::subscriber::set_global_default(subscriber).expect("Failed to initialize logger");
-    tracing::debug!("DEBUG messages allowed");
-    tracing::info!("INFO messages allowed");
-    tracing::warn!("WARN messages allowed");
-    tracing::error!("ERROR messages allowed");
-    tracing::info!("logger initialized with level: '{level}");
+/// * `settings` - Настройки журнала
+/// * `telemetry` - Экспорт трассировок, если он включен
+///
+/// # Возвращает
+///
+/// * `Ok(Logger)` - Журнал установлен
+/// * `Err(AppError)` - Неверный фильтр, недоступен каталог файлов
+///   или глобальный журнал уже установлен
+pub fn init(settings: &LoggingSettings, telemetry: Option<&Telemetry>) -> AppResult<Logger> {
+    let directives = std::env::var(EnvFilter::DEFAULT_ENV)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| settings.filter.clone());
+    let filter = parse_filter(&directives)
+        .map_err(|e| AppError::Custom(format!("log filter '{directives}': {e}")))?;
+    let (filter, handle) = reload::Layer::new(filter);
+
+    let mut outputs = vec![output(settings.format, std::io::stdout, true)];
+    let file_guard = match &settings.file {
+        Some(file) => {
+            let (writer, guard) = tracing_appender::non_blocking(appender(file)?);
+            outputs.push(output(file.format, writer, false));
+            Some(guard)
+        }
+        None => None,
+    };
+    let registry = tracing_subscriber::registry().with(outputs.with_filter(filter));
+
+    // `Option<Layer>` не передает слою диспетчер, поэтому ветки раздельные
+    let result = match telemetry {
+        Some(telemetry) => {
+            tracing::subscriber::set_global_default(registry.with(telemetry.layer()))
+        }
+        None => tracing::subscriber::set_global_default(registry),
+    };
+    result.map_err(|e| AppError::Custom(format!("logger: {e}")))?;
+    tracing::info!("logger initialized with filter '{directives}'");
+    Ok(Logger {
+        filter: LogFilter { handle },
+        _file_guard: file_guard,
+    })
+}
+
+/// Разбирает директивы фильтра; пустая строка не пропускает ничего, кроме ошибок
+fn parse_filter(directives: &str) -> AppResult<EnvFilter> {
+    EnvFilter::builder()
+        .parse(directives)
+        .map_err(|e| AppError::MalformedRequest(e.to_string()))
+}
+
+/// Создает вывод журнала в заданном формате
+fn output<W>(format: LogFormat, writer: W, ansi: bool) -> Output
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi);
+    match format {
+        LogFormat::Text => Box::new(
+            layer
+                .with_file(true)
+                .with_line_number(true)
+                .with_target(false)
+                .fmt_fields(debug_fn(redacted_field).delimited(" ")),
+        ),
+        LogFormat::Json => Box::new(layer.fmt_fields(JsonFields).event_format(JsonFormat)),
+    }
+}
+
+/// Создает файл журнала с ротацией
+fn appender(settings: &LogFileSettings) -> AppResult<RollingFileAppender> {
+    let rotation = match settings.rotation {
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+        LogRotation::Never => Rotation::NEVER,
+    };
+    let mut builder = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(&settings.prefix);
+    if let Some(max_files) = settings.max_files {
+        builder = builder.max_log_files(max_files);
+    }
+    builder
+        .build(&settings.directory)
+        .map_err(|e| AppError::Custom(format!("log file: {e}")))
+}
+
+/// Значение поля скрывается
+///
+/// Имена сравниваются без учета регистра, а `-` приравнивается к `_`,
+/// так что `Authorization`, `x-api-key` и `new_password` тоже скрываются.
+pub fn is_sensitive(field: &str) -> bool {
+    let name = field.to_ascii_lowercase().replace('-', "_");
+    SENSITIVE_FIELDS
+        .iter()
+        .any(|sensitive| name == sensitive.replace('-', "_"))
+        || SENSITIVE_SUFFIXES
+            .iter()
+            .any(|suffix| name.ends_with(suffix))
+}
+
+/// Выводит поле текстовой записи, скрывая секреты
+fn redacted_field(writer: &mut Writer<'_>, field: &Field, value: &dyn Debug) -> fmt::Result {
+    match field.name() {
+        "message" => write!(writer, "{value:?}"),
+        name if is_sensitive(name) => write!(writer, "{name}={REDACTED}"),
+        name => write!(writer, "{name}={value:?}"),
+    }
+}
+
+/// Собирает поля записи в объект JSON, скрывая секреты
+#[derive(Default)]
+struct JsonVisitor(Map<String, Value>);
+
+impl JsonVisitor {
+    fn insert(&mut self, field: &Field, value: Value) {
+        let value = if is_sensitive(field.name()) {
+            Value::from(REDACTED)
+        } else {
+            value
+        };
+        self.0.insert(field.name().to_string(), value);
+    }
+}
+
+impl Visit for JsonVisitor {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, Value::from(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.insert(field, Value::from(format!("{value:?}")));
+    }
+}
+
+/// Поля спанов в JSON для вывода вместе с записью
+struct JsonFields;
+
+impl<'w> FormatFields<'w> for JsonFields {
+    fn format_fields<R: RecordFields>(&self, mut writer: Writer<'w>, fields: R) -> fmt::Result {
+        let mut visitor = JsonVisitor::default();
+        fields.record(&mut visitor);
+        write!(writer, "{}", Value::Object(visitor.0))
+    }
+
+    fn add_fields(
+        &self,
+        current: &mut FormattedFields<Self>,
+        fields: &tracing::span::Record<'_>,
+    ) -> fmt::Result {
+        let mut visitor = JsonVisitor(serde_json::from_str(&current.fields).unwrap_or_default());
+        fields.record(&mut visitor);
+        current.fields = Value::Object(visitor.0).to_string();
+        Ok(())
+    }
+}
+
+/// Запись журнала одной строкой JSON
+///
+/// Содержит время, уровень, цель, место в коде, поля записи
+/// и цепочку спанов от корневого с их полями.
+struct JsonFormat;
+
+impl<S> FormatEvent<S, JsonFields> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, JsonFields>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let metadata = event.metadata();
+        let mut fields = JsonVisitor::default();
+        event.record(&mut fields);
+
+        let mut record = Map::new();
+        record.insert(
+            "timestamp".into(),
+            chrono::Utc::now()
+                .to_rfc3339_opts(chrono::SecondsFormat::Micros, true)
+                .into(),
+        );
+        record.insert("level".into(), metadata.level().as_str().into());
+        record.insert("target".into(), metadata.target().into());
+        if let Some(file) = metadata.file() {
+            record.insert("file".into(), file.into());
+        }
+        if let Some(line) = metadata.line() {
+            record.insert("line".into(), line.into());
+        }
+        record.insert("fields".into(), Value::Object(fields.0));
+        if let Some(scope) = ctx.event_scope() {
+            let spans: Vec<Value> = scope
+                .from_root()
+                .map(|span| {
+                    let mut object = span
+                        .extensions()
+                        .get::<FormattedFields<JsonFields>>()
+                        .and_then(|fields| serde_json::from_str(&fields.fields).ok())
+                        .unwrap_or_else(Map::new);
+                    object.insert("name".into(), span.name().into());
+                    Value::Object(object)
+                })
+                .collect();
+            record.insert("spans".into(), Value::Array(spans));
+        }
+        writeln!(writer, "{}", Value::Object(record))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    /// Буфер, в который пишет журнал в тестах
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'w> MakeWriter<'w> for Buffer {
+        type Writer = Self;
+
+        fn make_writer(&'w self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    impl Buffer {
+        fn lines(&self) -> Vec<String> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .map(str::to_string)
+                .collect()
+        }
+    }
+
+    fn log(format: LogFormat, directives: &str) -> Buffer {
+        let buffer = Buffer::default();
+        let filter = parse_filter(directives).unwrap();
+        let subscriber = tracing_subscriber::registry()
+            .with(vec![output(format, buffer.clone(), false)].with_filter(filter));
+        tracing::subscriber::with_default(subscriber, || {
+            let span =
+                tracing::info_span!("signin", email = "user@example.com", password = "hunter2");
+            let _guard = span.enter();
+            tracing::info!(authorization = "Bearer abc.def", user_id = 7, "signed in");
+            tracing::debug!("details");
+            tracing::warn!(target: "sqlx::query", "slow statement");
+        });
+        buffer
+    }
+
+    #[test]
+    fn test_is_sensitive() {
+        for field in [
+            "password",
+            "new_password",
+            "Authorization",
+            "x-api-key",
+            "access_token",
+        ] {
+            assert!(is_sensitive(field), "{field}");
+        }
+        for field in ["email", "token_id", "user_id", "message"] {
+            assert!(!is_sensitive(field), "{field}");
+        }
+    }
+
+    #[test]
+    fn test_text_output() {
+        let buffer = log(LogFormat::Text, "info,sqlx=error");
+        let lines = buffer.lines();
+        assert_eq!(lines.len(), 1, "{lines:?}");
+        let line = &lines[0];
+        assert!(line.contains("signed in"));
+        assert!(line.contains(r#"email="user@example.com""#));
+        assert!(line.contains("password=[REDACTED]"));
+        assert!(line.contains("authorization=[REDACTED] user_id=7"));
+        assert!(!line.contains("hunter2") && !line.contains("abc.def"));
+    }
+
+    #[test]
+    fn test_json_output() {
+        let buffer = log(LogFormat::Json, "debug");
+        let lines = buffer.lines();
+        assert_eq!(lines.len(), 3, "{lines:?}");
+        let record: Value = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(record["level"], "INFO");
+        assert_eq!(record["fields"]["message"], "signed in");
+        assert_eq!(record["fields"]["authorization"], REDACTED);
+        assert_eq!(record["fields"]["user_id"], 7);
+        assert_eq!(record["spans"][0]["name"], "signin");
+        assert_eq!(record["spans"][0]["email"], "user@example.com");
+        assert_eq!(record["spans"][0]["password"], REDACTED);
+        let record: Value = serde_json::from_str(&lines[2]).unwrap();
+        assert_eq!(record["target"], "sqlx::query");
+    }
+
+    #[test]
+    fn test_change_filter() {
+        let buffer = Buffer::default();
+        let (filter, handle) = reload::Layer::new(parse_filter("info").unwrap());
+        let log_filter = LogFilter { handle };
+        let subscriber = tracing_subscriber::registry()
+            .with(vec![output(LogFormat::Text, buffer.clone(), false)].with_filter(filter));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!("hidden");
+            assert_eq!(log_filter.current().unwrap(), "info");
+            assert_eq!(
+                log_filter.set("warn,alfred=debug").unwrap(),
+                "alfred=debug,warn"
+            );
+            tracing::debug!("visible");
+            assert!(matches!(
+                log_filter.set("alfred=loud"),
+                Err(AppError::MalformedRequest(_))
+            ));
+            assert_eq!(log_filter.current().unwrap(), "alfred=debug,warn");
+        });
+        let lines = buffer.lines();
+        assert_eq!(lines.len(), 2, "{lines:?}");
+        assert!(lines[0].contains("log filter changed to 'warn,alfred=debug'"));
+        assert!(lines[1].contains("visible"));
+    }
 }
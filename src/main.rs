@@ -2,32 +2,155 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use alfred::AppResult;
-use alfred::storage::PgStorage;
+use alfred::cli::{Cli, Command};
+use alfred::services::{
+    BootstrapOutcome, BootstrapService, DatabaseCheck, HealthService, MigrationsCheck, TcpCheck,
+};
+use alfred::settings::RateLimitStoreKind;
+use alfred::storage::{
+    CachedUsersRepository, DEFAULT_NOTIFY_CHANNEL, MemoryRateLimitStore, PgStorage, RateLimitStore,
+    UsersRepository,
+};
+use clap::Parser;
 
 #[tokio::main]
 async fn main() -> AppResult<()> {
-    if std::env::var("ALF_PRODUCTION").is_ok() {
-        alfred::logger::init(tracing::Level::INFO);
-    } else {
-        alfred::logger::init(tracing::Level::DEBUG);
+    let cli = Cli::parse();
+    match cli.command {
+        None | Some(Command::Serve) => serve(alfred::settings::init(&cli.config)?).await,
+        Some(command) => alfred::cli::run(&cli.config, command).await,
     }
+}
+
+/// Запускает сервер со всеми фоновыми задачами
+async fn serve(settings: alfred::settings::Settings) -> AppResult<()> {
+    let telemetry = if settings.telemetry.enabled {
+        Some(alfred::telemetry::Telemetry::init(&settings.telemetry)?)
+    } else {
+        None
+    };
+    let logger = alfred::logger::init(&settings.logging, telemetry.as_ref())?;
     tracing::info!("Hello from Alfred!");
-    let settings = alfred::settings::init("settings.toml");
-    let db_url = settings.database_settings.db_url();
-    let pool = sqlx::postgres::PgPoolOptions::new()
-        .max_connections(settings.database_settings.max_connections.unwrap_or(8))
-        .idle_timeout(Duration::from_secs(
-            settings.database_settings.idle_timeout.unwrap_or(30),
-        ))
-        .connect(db_url.as_ref())
-        .await?;
+    alfred::models::PasswordPolicy::from_settings(&settings.password_policy)?.install()?;
+    alfred::crypto::PasswordHashing::from_settings(&settings.password_hashing)?.install()?;
+    let pool = alfred::cli::connect(&settings.database_settings).await?;
     let pg_storage = Arc::new(PgStorage::init(pool).await?);
-    let users_service = Arc::new(alfred::services::UsersService::new(pg_storage.clone()));
+    let users_repository: Arc<dyn UsersRepository> = match &settings.cache_settings {
+        Some(cache) => {
+            let mut cached = CachedUsersRepository::new(
+                pg_storage.as_ref().clone(),
+                Duration::from_secs(cache.ttl),
+                cache.max_entries,
+            );
+            if cache.notify {
+                let channel = cache
+                    .notify_channel
+                    .as_deref()
+                    .unwrap_or(DEFAULT_NOTIFY_CHANNEL);
+                cached = cached.with_pg_notify(pg_storage.pool().clone(), channel);
+            }
+            let cached = Arc::new(cached);
+            cached.spawn_listener().await?;
+            cached
+        }
+        None => pg_storage.clone(),
+    };
+    let users_service = Arc::new(
+        alfred::services::UsersService::new(users_repository)
+            .with_audit(pg_storage.clone())
+            .with_deletion_grace(chrono::Duration::days(
+                settings.account_settings.deletion_cooling_off_days,
+            )),
+    );
+    let bootstrap = Arc::new(BootstrapService::new(users_service.clone()));
+    match bootstrap.run(&settings.bootstrap).await? {
+        BootstrapOutcome::OwnerExists => {}
+        BootstrapOutcome::OwnerCreated(owner) => {
+            tracing::info!(user_id = %owner.user_id, "Created owner account from settings");
+        }
+        BootstrapOutcome::SetupToken(token) => {
+            tracing::warn!("No owner account exists, setup token printed to stderr");
+            eprintln!(
+                "No owner account exists. Sign up with \"setup_token\": \"{token}\" \
+                 to become the owner; the token is valid until this process exits."
+            );
+        }
+        BootstrapOutcome::Disabled => {
+            tracing::warn!("No owner account exists; create one with `alfred users create`");
+        }
+    }
+    let purge_service = users_service.clone();
+    let purge_interval = Duration::from_secs(settings.account_settings.purge_interval_secs);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(purge_interval);
+        loop {
+            interval.tick().await;
+            match purge_service.purge_due_deletions().await {
+                Ok(0) => {}
+                Ok(n) => tracing::info!("Anonymized {n} accounts after cooling-off period"),
+                Err(e) => tracing::error!("Failed to purge deleted accounts: {e}"),
+            }
+        }
+    });
+    let api_tokens_service = Arc::new(
+        alfred::services::ApiTokensService::new(pg_storage.clone(), users_service.storage.clone())
+            .with_audit(pg_storage.clone()),
+    );
     let jwt_settings = settings.jwt();
-    let state = Arc::new(alfred::AppState::new(users_service, jwt_settings));
+    let jwt_keys = Arc::new(alfred::JwtKeys::from_settings(&jwt_settings)?);
+    let mut state =
+        alfred::AppState::new(users_service, api_tokens_service, jwt_settings, jwt_keys);
+    if let Some(oidc_settings) = settings.oidc_settings.clone() {
+        let oidc_service = alfred::services::OidcService::new(oidc_settings)?;
+        state = state.with_oidc(Arc::new(oidc_service));
+    }
+    if settings.rate_limits.enabled {
+        let store: Arc<dyn RateLimitStore> = match settings.rate_limits.store {
+            RateLimitStoreKind::Memory => Arc::new(MemoryRateLimitStore::default()),
+            RateLimitStoreKind::Postgres => pg_storage.clone(),
+        };
+        let rate_limits = Arc::new(alfred::RateLimits::new(&settings.rate_limits, store));
+        let purge_limits = rate_limits.clone();
+        let purge_interval = Duration::from_secs(settings.rate_limits.purge_interval_secs);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(purge_interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = purge_limits
+                    .store()
+                    .purge_idle_buckets(purge_limits.idle_timeout())
+                    .await
+                {
+                    tracing::error!("Failed to purge rate limit buckets: {e}");
+                }
+            }
+        });
+        state = state.with_rate_limits(rate_limits);
+    }
+    let mut health = HealthService::new(Duration::from_millis(settings.health.check_timeout_ms))
+        .with_check(DatabaseCheck(pg_storage.as_ref().clone()))
+        .with_check(MigrationsCheck(pg_storage.as_ref().clone()));
+    if let Some(mail_addr) = settings
+        .email_settings
+        .as_ref()
+        .and_then(|email| email.server_address())
+    {
+        health = health.with_check(TcpCheck::new("mail", mail_addr));
+    }
+    state = state.with_health(Arc::new(health));
+    if settings.server_settings.admin_port.is_some() {
+        let metrics = alfred::metrics::Metrics::install()?.with_pool(pg_storage.pool().clone());
+        state = state.with_metrics(Arc::new(metrics));
+    }
+    state = state
+        .with_log_filter(logger.filter())
+        .with_bootstrap(bootstrap);
+    let state = Arc::new(state);
     let server = alfred::Server::new(settings.server_settings, state);
     if server.start().await.is_err() {
         pg_storage.close().await;
     }
+    drop(logger);
+    drop(telemetry);
     Ok(())
 }
@@ -0,0 +1,211 @@
+//! Модуль метрик в формате Prometheus
+//!
+//! Метрики пишутся через фасад `metrics` из любого места приложения,
+//! а экспортер собирает их в памяти и отдает текстом на `/metrics`
+//! служебного порта. Пока экспортер не установлен, запись метрик
+//! ничего не делает, поэтому тесты и утилиты могут его не устанавливать.
+
+use std::{
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use sqlx::PgPool;
+
+use crate::{AppError, AppResult, services::UsersService};
+
+/// Число HTTP-запросов
+pub const HTTP_REQUESTS_TOTAL: &str = "alfred_http_requests_total";
+/// Длительность обработки HTTP-запросов
+pub const HTTP_REQUEST_DURATION: &str = "alfred_http_request_duration_seconds";
+/// Число соединений в пуле базы данных
+pub const DB_POOL_SIZE: &str = "alfred_db_pool_connections";
+/// Число свободных соединений в пуле базы данных
+pub const DB_POOL_IDLE: &str = "alfred_db_pool_idle_connections";
+/// Время ожидания соединения из пула в момент сбора метрик
+pub const DB_POOL_WAIT: &str = "alfred_db_pool_acquire_wait_seconds";
+/// Длительность вычисления и проверки хэшей Argon2
+pub const PASSWORD_HASH_DURATION: &str = "alfred_password_hash_duration_seconds";
+/// Число попыток входа по паролю
+pub const SIGNIN_TOTAL: &str = "alfred_signin_total";
+/// Число пользователей по ролям
+pub const USERS_TOTAL: &str = "alfred_users";
+/// Число обращений к кэшу пользователей
+pub const USERS_CACHE_REQUESTS_TOTAL: &str = "alfred_users_cache_requests_total";
+
+/// Метка маршрута для запросов, не попавших ни в один маршрут
+///
+/// Путь таких запросов в метку не попадает: иначе перебор адресов
+/// создал бы неограниченное число временных рядов.
+pub const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Границы корзин гистограммы времени ответа, секунды
+const HTTP_DURATION_BUCKETS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// Границы корзин гистограммы хэширования паролей, секунды
+const HASH_DURATION_BUCKETS: [f64; 10] = [0.01, 0.025, 0.05, 0.1, 0.2, 0.3, 0.5, 1.0, 2.0, 5.0];
+
+/// Сколько ждать соединение при сборе метрик пула
+const POOL_WAIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Экспортер, установленный при старте приложения
+static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+
+/// Сборщик метрик для служебного порта
+///
+/// Счетчики и гистограммы записываются по мере обработки запросов;
+/// показатели пула и число пользователей снимаются при каждом сборе.
+#[derive(Clone)]
+pub struct Metrics {
+    handle: PrometheusHandle,
+    pool: Option<PgPool>,
+}
+
+impl Metrics {
+    /// Устанавливает глобальный экспортер Prometheus
+    ///
+    /// Повторный вызов возвращает уже установленный экспортер.
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(Metrics)` - Сборщик метрик
+    /// * `Err(AppError::Custom)` - Уже установлен другой глобальный сборщик
+    pub fn install() -> AppResult<Self> {
+        let handle = match HANDLE.get() {
+            Some(handle) => handle.clone(),
+            None => {
+                let handle = PrometheusBuilder::new()
+                    .set_buckets_for_metric(
+                        Matcher::Full(HTTP_REQUEST_DURATION.to_string()),
+                        &HTTP_DURATION_BUCKETS,
+                    )
+                    .and_then(|builder| {
+                        builder.set_buckets_for_metric(
+                            Matcher::Full(PASSWORD_HASH_DURATION.to_string()),
+                            &HASH_DURATION_BUCKETS,
+                        )
+                    })
+                    .and_then(PrometheusBuilder::install_recorder)
+                    .map_err(|e| AppError::Custom(format!("metrics recorder: {e}")))?;
+                HANDLE.get_or_init(|| handle).clone()
+            }
+        };
+        Ok(Self { handle, pool: None })
+    }
+
+    /// Включает показатели пула соединений базы данных
+    pub fn with_pool(mut self, pool: PgPool) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Собирает метрики в текстовом формате Prometheus
+    ///
+    /// # Аргументы
+    ///
+    /// * `users` - Сервис пользователей для подсчета по ролям
+    ///
+    /// # Возвращает
+    ///
+    /// Текст для ответа `/metrics`
+    pub async fn render(&self, users: &UsersService) -> String {
+        if let Some(pool) = &self.pool {
+            record_pool(pool).await;
+        }
+        match users.count_by_role().await {
+            Ok(counts) => {
+                for (role, count) in counts {
+                    gauge!(USERS_TOTAL, "role" => role.to_string()).set(count);
+                }
+            }
+            Err(e) => tracing::error!("failed to count users for metrics: {e}"),
+        }
+        self.handle.run_upkeep();
+        self.handle.render()
+    }
+}
+
+/// Снимает показатели пула соединений
+async fn record_pool(pool: &PgPool) {
+    gauge!(DB_POOL_SIZE).set(pool.size());
+    gauge!(DB_POOL_IDLE).set(pool.num_idle() as f64);
+    let started = Instant::now();
+    match tokio::time::timeout(POOL_WAIT_TIMEOUT, pool.acquire()).await {
+        Ok(Ok(_connection)) => gauge!(DB_POOL_WAIT).set(started.elapsed().as_secs_f64()),
+        Ok(Err(e)) => tracing::error!("failed to acquire connection for metrics: {e}"),
+        Err(_) => gauge!(DB_POOL_WAIT).set(POOL_WAIT_TIMEOUT.as_secs_f64()),
+    }
+}
+
+/// Записывает обработанный HTTP-запрос
+///
+/// # Аргументы
+///
+/// * `method` - Метод запроса
+/// * `route` - Шаблон маршрута, например `/api/v1/users/{id}`
+/// * `status` - Код ответа
+/// * `duration` - Время обработки
+pub fn record_http_request(method: &str, route: &str, status: u16, duration: Duration) {
+    let labels = [
+        ("method", method.to_string()),
+        ("route", route.to_string()),
+        ("status", status.to_string()),
+    ];
+    counter!(HTTP_REQUESTS_TOTAL, &labels).increment(1);
+    histogram!(HTTP_REQUEST_DURATION, &labels).record(duration);
+}
+
+/// Записывает длительность операции Argon2
+///
+/// # Аргументы
+///
+/// * `operation` - `hash` или `verify`
+/// * `started` - Момент начала операции
+pub fn record_password_hash(operation: &'static str, started: Instant) {
+    histogram!(PASSWORD_HASH_DURATION, "operation" => operation).record(started.elapsed());
+}
+
+/// Записывает попытку входа по паролю
+pub fn record_signin(success: bool) {
+    let result = if success { "success" } else { "failure" };
+    counter!(SIGNIN_TOTAL, "result" => result).increment(1);
+}
+
+/// Записывает обращение к кэшу пользователей
+///
+/// # Аргументы
+///
+/// * `hit` - Пользователь найден в кэше
+pub fn record_users_cache(hit: bool) {
+    let result = if hit { "hit" } else { "miss" };
+    counter!(USERS_CACHE_REQUESTS_TOTAL, "result" => result).increment(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_recorded_metrics() {
+        let metrics = Metrics::install().unwrap();
+        // Повторная установка возвращает тот же экспортер
+        Metrics::install().unwrap();
+        record_http_request("GET", "/metrics-test/{id}", 200, Duration::from_millis(30));
+        record_signin(false);
+        record_users_cache(true);
+
+        let text = metrics.handle.render();
+        assert!(text.contains(
+            r#"alfred_http_requests_total{method="GET",route="/metrics-test/{id}",status="200"} 1"#
+        ));
+        assert!(text.contains(
+            r#"alfred_http_request_duration_seconds_bucket{method="GET",route="/metrics-test/{id}",status="200",le="0.05"} 1"#
+        ));
+        assert!(text.contains(r#"alfred_signin_total{result="failure"}"#));
+        assert!(text.contains(r#"alfred_users_cache_requests_total{result="hit"}"#));
+    }
+}
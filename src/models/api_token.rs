@@ -0,0 +1,202 @@
+//! Модуль для работы с API-ключами
+//!
+//! Этот модуль содержит структуры долгоживущих API-ключей, которыми
+//! скрипты и интеграции аутентифицируются вместо пароля пользователя.
+
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::AppError;
+
+/// Область действия API-ключа
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, ToSchema)]
+pub enum ApiScope {
+    /// Чтение собственного профиля
+    #[serde(rename = "profile:read")]
+    ProfileRead,
+    /// Изменение собственного профиля
+    #[serde(rename = "profile:write")]
+    ProfileWrite,
+    /// Чтение данных других пользователей (только для администраторов)
+    #[serde(rename = "users:read")]
+    UsersRead,
+    /// Изменение и удаление других пользователей (только для администраторов)
+    #[serde(rename = "users:write")]
+    UsersWrite,
+}
+
+impl ApiScope {
+    /// Возвращает `true`, если область действия доступна только администраторам
+    pub fn is_admin(&self) -> bool {
+        matches!(self, ApiScope::UsersRead | ApiScope::UsersWrite)
+    }
+}
+
+impl AsRef<str> for ApiScope {
+    fn as_ref(&self) -> &str {
+        match self {
+            ApiScope::ProfileRead => "profile:read",
+            ApiScope::ProfileWrite => "profile:write",
+            ApiScope::UsersRead => "users:read",
+            ApiScope::UsersWrite => "users:write",
+        }
+    }
+}
+
+impl Display for ApiScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_ref())
+    }
+}
+
+impl FromStr for ApiScope {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "profile:read" => Ok(ApiScope::ProfileRead),
+            "profile:write" => Ok(ApiScope::ProfileWrite),
+            "users:read" => Ok(ApiScope::UsersRead),
+            "users:write" => Ok(ApiScope::UsersWrite),
+            _ => Err(AppError::Custom(format!("Unknown API scope: {s}"))),
+        }
+    }
+}
+
+/// Сведения об API-ключе
+///
+/// Сам ключ не хранится: в базе остается только его хэш
+/// и короткий префикс для отображения в списке.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+pub struct ApiToken {
+    /// Уникальный идентификатор ключа
+    pub token_id: uuid::Uuid,
+
+    /// Владелец ключа
+    pub user_id: uuid::Uuid,
+
+    /// Название ключа, заданное пользователем
+    pub name: String,
+
+    /// Начало ключа для распознавания в списке
+    pub prefix: String,
+
+    /// Разрешенные области действия
+    pub scopes: Vec<ApiScope>,
+
+    /// Дата и время создания
+    pub created: chrono::DateTime<chrono::Utc>,
+
+    /// Момент, после которого ключ перестает действовать
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Время последнего использования
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Время отзыва ключа
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl ApiToken {
+    /// Проверяет, разрешена ли ключу указанная область действия
+    pub fn allows(&self, scope: ApiScope) -> bool {
+        self.scopes.contains(&scope)
+    }
+
+    /// Проверяет, действует ли ключ в указанный момент
+    pub fn is_active(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        self.revoked_at.is_none() && self.expires_at.is_none_or(|at| at > now)
+    }
+}
+
+/// Запрос на выпуск нового API-ключа
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Validate, ToSchema)]
+pub struct NewApiToken {
+    /// Название ключа
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Title must be between {min} and {max} characters long"
+    ))]
+    pub name: String,
+
+    /// Запрашиваемые области действия
+    #[validate(length(min = 1, message = "Нужно указать хотя бы одну область действия"))]
+    pub scopes: Vec<ApiScope>,
+
+    /// Момент истечения срока действия; `None` - бессрочный ключ
+    #[serde(default)]
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Только что выпущенный API-ключ
+///
+/// Единственное место, где ключ возвращается в открытом виде.
+/// Повторно получить его нельзя.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+pub struct IssuedApiToken {
+    /// Ключ в открытом виде
+    pub token: String,
+
+    /// Сведения о ключе
+    #[serde(flatten)]
+    pub details: ApiToken,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(scopes: Vec<ApiScope>) -> ApiToken {
+        ApiToken {
+            token_id: uuid::Uuid::new_v4(),
+            user_id: uuid::Uuid::new_v4(),
+            name: "ci".to_string(),
+            prefix: "alf_0123".to_string(),
+            scopes,
+            created: chrono::Utc::now(),
+            expires_at: None,
+            last_used_at: None,
+            revoked_at: None,
+        }
+    }
+
+    #[test]
+    fn test_api_scope_round_trip() {
+        for scope in [
+            ApiScope::ProfileRead,
+            ApiScope::ProfileWrite,
+            ApiScope::UsersRead,
+            ApiScope::UsersWrite,
+        ] {
+            assert_eq!(scope.as_ref().parse::<ApiScope>().unwrap(), scope);
+            assert_eq!(
+                serde_json::to_string(&scope).unwrap(),
+                format!("\"{}\"", scope.as_ref())
+            );
+        }
+        assert!("admin".parse::<ApiScope>().is_err());
+    }
+
+    #[test]
+    fn test_api_token_activity() {
+        let now = chrono::Utc::now();
+        let mut t = token(vec![ApiScope::ProfileRead]);
+        assert!(t.is_active(now));
+        assert!(t.allows(ApiScope::ProfileRead));
+        assert!(!t.allows(ApiScope::ProfileWrite));
+
+        t.expires_at = Some(now - chrono::Duration::seconds(1));
+        assert!(!t.is_active(now));
+
+        t.expires_at = Some(now + chrono::Duration::hours(1));
+        t.revoked_at = Some(now);
+        assert!(!t.is_active(now));
+    }
+}
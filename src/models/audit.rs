@@ -0,0 +1,202 @@
+//! Модуль для работы с журналом аудита
+//!
+//! Этот модуль содержит структуры событий, которые фиксируются
+//! при действиях пользователей и администраторов.
+
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::AppError;
+
+/// Действие, зафиксированное в журнале аудита
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    /// Регистрация пользователя
+    Signup,
+    /// Успешный вход
+    Signin,
+    /// Неудачная попытка входа
+    SigninFailed,
+    /// Изменение профиля
+    ProfileUpdated,
+    /// Запрос на удаление собственного аккаунта
+    DeletionRequested,
+    /// Отмена запроса на удаление
+    DeletionCancelled,
+    /// Окончательная анонимизация аккаунта
+    Anonymized,
+    /// Выгрузка персональных данных
+    DataExported,
+    /// Выпуск API-ключа
+    ApiTokenIssued,
+    /// Отзыв API-ключа
+    ApiTokenRevoked,
+    /// Создание сервисного аккаунта
+    ServiceAccountCreated,
+    /// Импорт пользователя с готовым хэшем пароля
+    UserImported,
+    /// Смена роли пользователя
+    RoleChanged,
+    /// Сброс пароля администратором
+    PasswordReset,
+}
+
+impl AuditAction {
+    /// Возвращает `true` для событий, относящихся к истории входов
+    pub fn is_login(&self) -> bool {
+        matches!(self, AuditAction::Signin | AuditAction::SigninFailed)
+    }
+}
+
+impl AsRef<str> for AuditAction {
+    fn as_ref(&self) -> &str {
+        match self {
+            AuditAction::Signup => "signup",
+            AuditAction::Signin => "signin",
+            AuditAction::SigninFailed => "signin_failed",
+            AuditAction::ProfileUpdated => "profile_updated",
+            AuditAction::DeletionRequested => "deletion_requested",
+            AuditAction::DeletionCancelled => "deletion_cancelled",
+            AuditAction::Anonymized => "anonymized",
+            AuditAction::DataExported => "data_exported",
+            AuditAction::ApiTokenIssued => "api_token_issued",
+            AuditAction::ApiTokenRevoked => "api_token_revoked",
+            AuditAction::ServiceAccountCreated => "service_account_created",
+            AuditAction::UserImported => "user_imported",
+            AuditAction::RoleChanged => "role_changed",
+            AuditAction::PasswordReset => "password_reset",
+        }
+    }
+}
+
+impl Display for AuditAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_ref())
+    }
+}
+
+impl FromStr for AuditAction {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "signup" => Ok(AuditAction::Signup),
+            "signin" => Ok(AuditAction::Signin),
+            "signin_failed" => Ok(AuditAction::SigninFailed),
+            "profile_updated" => Ok(AuditAction::ProfileUpdated),
+            "deletion_requested" => Ok(AuditAction::DeletionRequested),
+            "deletion_cancelled" => Ok(AuditAction::DeletionCancelled),
+            "anonymized" => Ok(AuditAction::Anonymized),
+            "data_exported" => Ok(AuditAction::DataExported),
+            "api_token_issued" => Ok(AuditAction::ApiTokenIssued),
+            "api_token_revoked" => Ok(AuditAction::ApiTokenRevoked),
+            "service_account_created" => Ok(AuditAction::ServiceAccountCreated),
+            "user_imported" => Ok(AuditAction::UserImported),
+            "role_changed" => Ok(AuditAction::RoleChanged),
+            "password_reset" => Ok(AuditAction::PasswordReset),
+            _ => Err(AppError::Custom(format!("Unknown audit action: {s}"))),
+        }
+    }
+}
+
+/// Событие журнала аудита
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+pub struct AuditEvent {
+    /// Уникальный идентификатор события
+    pub event_id: uuid::Uuid,
+
+    /// Пользователь, которого касается событие
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<uuid::Uuid>,
+
+    /// Пользователь, совершивший действие
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actor_id: Option<uuid::Uuid>,
+
+    /// Совершенное действие
+    pub action: AuditAction,
+
+    /// Дополнительные сведения о событии
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+
+    /// Дата и время события
+    pub created: chrono::DateTime<chrono::Utc>,
+}
+
+/// Данные для записи нового события в журнал аудита
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewAuditEvent {
+    /// Пользователь, которого касается событие
+    pub user_id: Option<uuid::Uuid>,
+    /// Пользователь, совершивший действие
+    pub actor_id: Option<uuid::Uuid>,
+    /// Совершенное действие
+    pub action: AuditAction,
+    /// Дополнительные сведения о событии
+    pub details: Option<String>,
+}
+
+impl NewAuditEvent {
+    /// Создает событие о действии пользователя над собственным аккаунтом
+    ///
+    /// # Аргументы
+    ///
+    /// * `user_id` - Идентификатор пользователя
+    /// * `action` - Совершенное действие
+    pub fn own(user_id: uuid::Uuid, action: AuditAction) -> Self {
+        Self {
+            user_id: Some(user_id),
+            actor_id: Some(user_id),
+            action,
+            details: None,
+        }
+    }
+    /// Добавляет к событию дополнительные сведения
+    pub fn with_details(mut self, details: impl Into<String>) -> Self {
+        self.details = Some(details.into());
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_action_round_trip() {
+        let actions = [
+            AuditAction::Signup,
+            AuditAction::Signin,
+            AuditAction::SigninFailed,
+            AuditAction::ProfileUpdated,
+            AuditAction::DeletionRequested,
+            AuditAction::DeletionCancelled,
+            AuditAction::Anonymized,
+            AuditAction::DataExported,
+            AuditAction::ApiTokenIssued,
+            AuditAction::ApiTokenRevoked,
+            AuditAction::ServiceAccountCreated,
+            AuditAction::UserImported,
+        ];
+        for action in actions {
+            assert_eq!(action.as_ref().parse::<AuditAction>().unwrap(), action);
+            assert_eq!(
+                serde_json::to_string(&action).unwrap(),
+                format!("\"{}\"", action.as_ref())
+            );
+        }
+        assert!("unknown".parse::<AuditAction>().is_err());
+    }
+
+    #[test]
+    fn test_audit_action_is_login() {
+        assert!(AuditAction::Signin.is_login());
+        assert!(AuditAction::SigninFailed.is_login());
+        assert!(!AuditAction::Signup.is_login());
+        assert!(!AuditAction::DataExported.is_login());
+    }
+}
@@ -0,0 +1,33 @@
+//! Модуль для работы с внешними учетными записями
+//!
+//! Этот модуль содержит сведения о пользователе, полученные
+//! от внешнего провайдера идентификации (OpenID Connect).
+
+use serde::{Deserialize, Serialize};
+
+/// Пользователь, подтвержденный внешним провайдером
+///
+/// Заполняется из проверенного ID-токена.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct ExternalIdentity {
+    /// Идентификатор провайдера (`iss`)
+    pub issuer: String,
+
+    /// Идентификатор пользователя у провайдера (`sub`)
+    pub subject: String,
+
+    /// Email пользователя
+    pub email: String,
+
+    /// Подтвержден ли email провайдером
+    pub email_verified: bool,
+
+    /// Имя пользователя
+    pub given_name: Option<String>,
+
+    /// Фамилия пользователя
+    pub family_name: Option<String>,
+
+    /// Группы пользователя у провайдера
+    pub groups: Vec<String>,
+}
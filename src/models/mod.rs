@@ -2,5 +2,19 @@
 //!
 //! Этот модуль содержит структуры и методы для работы с данными
 
+mod api_token;
+pub use api_token::{ApiScope, ApiToken, IssuedApiToken, NewApiToken};
+mod audit;
+pub use audit::{AuditAction, AuditEvent, NewAuditEvent};
+mod identity;
+pub use identity::ExternalIdentity;
+mod password_policy;
+pub use password_policy::{
+    BreachedPasswords, PASSWORD_MAX_LENGTH_CEILING, PASSWORD_MIN_LENGTH_FLOOR, PasswordPolicy,
+    PasswordReport, PasswordRule, estimate_score,
+};
 mod user;
-pub use user::{SigninData, SignupData, User, UserInfo, UserRole, UserToUpdate};
+pub use user::{
+    ImportedUser, LocalizedUser, SERVICE_ACCOUNT_EMAIL_DOMAIN, ServiceAccountData, SigninData,
+    SignupData, User, UserInfo, UserRole, UserToUpdate,
+};
@@ -0,0 +1,686 @@
+//! Модуль для работы с парольной политикой
+//!
+//! Этот модуль содержит настраиваемые требования к паролям, проверку
+//! по списку утекших паролей и оценку стойкости пароля по шкале 0-4.
+
+use std::{
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use utoipa::ToSchema;
+
+use crate::{
+    AppError, AppResult,
+    i18n::{tr, tr_args},
+    models::user::is_special_char,
+    settings::PasswordPolicySettings,
+};
+
+/// Минимально допустимая в настройках длина пароля
+pub const PASSWORD_MIN_LENGTH_FLOOR: usize = 8;
+/// Максимально допустимая в настройках длина пароля
+pub const PASSWORD_MAX_LENGTH_CEILING: usize = 256;
+
+/// Встроенный список самых распространенных паролей
+///
+/// Проверяется всегда, даже если список утечек не подключен.
+const COMMON_PASSWORDS: [&str; 17] = [
+    "password",
+    "12345678",
+    "qwerty",
+    "admin123",
+    "letmein",
+    "welcome",
+    "monkey",
+    "sunshine",
+    "password1",
+    "123123",
+    "11111111",
+    "abcd1234",
+    "trustno1",
+    "dragon",
+    "baseball",
+    "пароль",
+    "йцукен",
+];
+
+/// Ряды клавиатуры для поиска «дорожек» вроде `qwerty` или `йцукен`
+const KEYBOARD_ROWS: [&str; 7] = [
+    "1234567890",
+    "qwertyuiop",
+    "asdfghjkl",
+    "zxcvbnm",
+    "йцукенгшщзхъ",
+    "фывапролджэ",
+    "ячсмитьбю",
+];
+
+/// Политика, установленная при старте приложения
+static POLICY: OnceLock<PasswordPolicy> = OnceLock::new();
+
+/// Невыполненное требование к паролю
+#[derive(Debug, Clone, Serialize, PartialEq, Eq, ToSchema)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum PasswordRule {
+    /// Пароль короче минимальной длины
+    TooShort { min: usize },
+    /// Пароль длиннее максимальной длины
+    TooLong { max: usize },
+    /// Пароль содержит пробельные символы
+    Whitespace,
+    /// Нет ни одной цифры
+    MissingDigit,
+    /// Нет ни одной заглавной буквы
+    MissingUppercase,
+    /// Нет ни одной строчной буквы
+    MissingLowercase,
+    /// Нет ни одного специального символа
+    MissingSpecial,
+    /// Пароль входит во встроенный список распространенных
+    Common,
+    /// Пароль встречается в списке утечек
+    Breached,
+    /// Оценка стойкости ниже требуемой
+    TooWeak { score: u8, min_score: u8 },
+}
+
+impl PasswordRule {
+    /// Описание требования для пользователя на языке запроса
+    pub fn message(&self) -> String {
+        match self {
+            PasswordRule::TooShort { min } => tr_args(
+                "Password must be at least {min} characters long",
+                &[("min", min)],
+            ),
+            PasswordRule::TooLong { max } => tr_args(
+                "Password must be at most {max} characters long",
+                &[("max", max)],
+            ),
+            PasswordRule::Whitespace => tr("Password must not contain whitespace").into(),
+            PasswordRule::MissingDigit => tr("Password must contain at least one digit").into(),
+            PasswordRule::MissingUppercase => {
+                tr("Password must contain at least one uppercase letter").into()
+            }
+            PasswordRule::MissingLowercase => {
+                tr("Password must contain at least one lowercase letter").into()
+            }
+            PasswordRule::MissingSpecial => {
+                tr("Password must contain at least one special character").into()
+            }
+            PasswordRule::Common => tr("Password is too common").into(),
+            PasswordRule::Breached => tr("Password has appeared in a data breach").into(),
+            PasswordRule::TooWeak { .. } => tr("Password is too easy to guess").into(),
+        }
+    }
+}
+
+/// Результат проверки пароля
+#[derive(Debug, Clone, Serialize, PartialEq, Eq, ToSchema)]
+pub struct PasswordReport {
+    /// Оценка стойкости от 0 (подбирается мгновенно) до 4 (очень стойкий)
+    pub score: u8,
+    /// Невыполненные требования
+    pub failed_rules: Vec<PasswordRule>,
+}
+
+impl PasswordReport {
+    /// Пароль удовлетворяет всем требованиям
+    pub fn is_ok(&self) -> bool {
+        self.failed_rules.is_empty()
+    }
+
+    /// Описание всех невыполненных требований одной строкой
+    pub fn message(&self) -> String {
+        let rules: Vec<String> = self.failed_rules.iter().map(|r| r.message()).collect();
+        tr_args(
+            "Password requirements: {rules}",
+            &[("rules", &rules.join(", "))],
+        )
+    }
+}
+
+/// Список утекших паролей в виде SHA-1
+///
+/// Поддерживаются два формата, используемые наборами Pwned Passwords:
+/// * файл со строками `<SHA-1>[:<количество>]` - загружается в память
+///   и проверяется двоичным поиском;
+/// * каталог файлов диапазонов с именами из первых пяти символов хэша
+///   (`21BD1`, `21BD1.txt`) и строками `<остальные 35 символов>:<количество>`.
+///   Файлы читаются по требованию, поэтому полный набор не занимает память.
+#[derive(Debug, Clone)]
+pub enum BreachedPasswords {
+    /// Отсортированные хэши из одного файла
+    Sorted(Vec<[u8; 20]>),
+    /// Каталог файлов диапазонов
+    Ranges {
+        dir: PathBuf,
+        /// Минимальное число утечек, при котором пароль отклоняется
+        min_count: u64,
+    },
+}
+
+impl BreachedPasswords {
+    /// Загружает список утечек
+    ///
+    /// # Аргументы
+    ///
+    /// * `path` - Путь к файлу или каталогу диапазонов
+    /// * `min_count` - Минимальное число утечек, при котором пароль отклоняется
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<BreachedPasswords>` - Список или ошибку чтения
+    pub fn load(path: &Path, min_count: u64) -> AppResult<Self> {
+        if path.is_dir() {
+            return Ok(Self::Ranges {
+                dir: path.to_path_buf(),
+                min_count,
+            });
+        }
+        let reader = BufReader::new(std::fs::File::open(path)?);
+        let mut hashes = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            let (hash, count) = split_entry(&line);
+            if count < min_count {
+                continue;
+            }
+            if let Some(hash) = parse_sha1(hash) {
+                hashes.push(hash);
+            }
+        }
+        hashes.sort_unstable();
+        hashes.dedup();
+        Ok(Self::Sorted(hashes))
+    }
+
+    /// Проверяет, встречается ли пароль в утечках
+    ///
+    /// Для каталога диапазонов читает файл с диска, поэтому из асинхронного
+    /// кода вызывается через `PasswordPolicy::check_blocking`.
+    pub fn contains(&self, password: &str) -> bool {
+        let digest: [u8; 20] = Sha1::digest(password.as_bytes()).into();
+        match self {
+            Self::Sorted(hashes) => hashes.binary_search(&digest).is_ok(),
+            Self::Ranges { dir, min_count } => {
+                let hex = to_upper_hex(&digest);
+                let (prefix, suffix) = hex.split_at(5);
+                let file = [dir.join(prefix), dir.join(format!("{prefix}.txt"))]
+                    .into_iter()
+                    .find_map(|path| std::fs::File::open(path).ok());
+                let Some(file) = file else {
+                    return false;
+                };
+                BufReader::new(file)
+                    .lines()
+                    .map_while(Result::ok)
+                    .any(|line| {
+                        let (hash, count) = split_entry(&line);
+                        hash.eq_ignore_ascii_case(suffix) && count >= *min_count
+                    })
+            }
+        }
+    }
+}
+
+/// Разбирает строку `<хэш>[:<количество>]`
+fn split_entry(line: &str) -> (&str, u64) {
+    match line.trim().split_once(':') {
+        Some((hash, count)) => (hash, count.trim().parse().unwrap_or(1)),
+        None => (line.trim(), 1),
+    }
+}
+
+fn parse_sha1(hex: &str) -> Option<[u8; 20]> {
+    if hex.len() != 40 {
+        return None;
+    }
+    let mut res = [0u8; 20];
+    for (i, byte) in res.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(res)
+}
+
+fn to_upper_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02X}")).collect()
+}
+
+/// Требования к паролям
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub require_digit: bool,
+    pub require_uppercase: bool,
+    pub require_lowercase: bool,
+    pub require_special: bool,
+    pub min_score: u8,
+    pub breached: Option<BreachedPasswords>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: PASSWORD_MIN_LENGTH_FLOOR,
+            max_length: 64,
+            require_digit: true,
+            require_uppercase: true,
+            require_lowercase: true,
+            require_special: true,
+            min_score: 0,
+            breached: None,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// Создает политику из настроек
+    ///
+    /// # Аргументы
+    ///
+    /// * `settings` - Настройки парольной политики
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(PasswordPolicy)` - Политика с загруженным списком утечек
+    /// * `Err(AppError::InvalidInput)` - Границы длины или оценки вне допустимых
+    /// * `Err(AppError::IOError)` - Не удалось прочитать список утечек
+    pub fn from_settings(settings: &PasswordPolicySettings) -> AppResult<Self> {
+        if settings.min_length < PASSWORD_MIN_LENGTH_FLOOR
+            || settings.max_length > PASSWORD_MAX_LENGTH_CEILING
+            || settings.min_length > settings.max_length
+            || settings.min_score > 4
+        {
+            return Err(AppError::InvalidInput);
+        }
+        let breached = settings
+            .breached_passwords_path
+            .as_deref()
+            .map(|path| BreachedPasswords::load(Path::new(path), settings.breached_min_count))
+            .transpose()?;
+        Ok(Self {
+            min_length: settings.min_length,
+            max_length: settings.max_length,
+            require_digit: settings.require_digit,
+            require_uppercase: settings.require_uppercase,
+            require_lowercase: settings.require_lowercase,
+            require_special: settings.require_special,
+            min_score: settings.min_score,
+            breached,
+        })
+    }
+
+    /// Устанавливает политику для всего приложения
+    ///
+    /// Вызывается один раз при старте, до первой проверки пароля.
+    pub fn install(self) -> AppResult<()> {
+        POLICY
+            .set(self)
+            .map_err(|_| AppError::Custom("password policy is already installed".into()))
+    }
+
+    /// Возвращает установленную политику или политику по умолчанию
+    pub fn current() -> &'static PasswordPolicy {
+        POLICY.get_or_init(PasswordPolicy::default)
+    }
+
+    /// Проверяет пароль без обращения к диску
+    ///
+    /// Каталог диапазонов утечек здесь не проверяется: поиск в нем читает
+    /// файл и заблокировал бы поток асинхронного рантайма. Полную проверку
+    /// выполняет `check_blocking`.
+    ///
+    /// # Аргументы
+    ///
+    /// * `password` - Пароль для проверки
+    /// * `user_inputs` - Данные пользователя (email, имя), которые
+    ///   не должны облегчать подбор пароля
+    ///
+    /// # Возвращает
+    ///
+    /// Оценку стойкости и список невыполненных требований
+    pub fn check(&self, password: &str, user_inputs: &[&str]) -> PasswordReport {
+        let mut failed_rules = Vec::new();
+        let length = password.chars().count();
+        if length < self.min_length {
+            failed_rules.push(PasswordRule::TooShort {
+                min: self.min_length,
+            });
+        }
+        if length > self.max_length {
+            failed_rules.push(PasswordRule::TooLong {
+                max: self.max_length,
+            });
+        }
+        if password.chars().any(char::is_whitespace) {
+            failed_rules.push(PasswordRule::Whitespace);
+        }
+        let lowered = password.to_lowercase();
+        if COMMON_PASSWORDS.contains(&lowered.as_str()) {
+            failed_rules.push(PasswordRule::Common);
+        }
+        if self.require_digit && !password.chars().any(char::is_numeric) {
+            failed_rules.push(PasswordRule::MissingDigit);
+        }
+        if self.require_uppercase && !password.chars().any(char::is_uppercase) {
+            failed_rules.push(PasswordRule::MissingUppercase);
+        }
+        if self.require_lowercase && !password.chars().any(char::is_lowercase) {
+            failed_rules.push(PasswordRule::MissingLowercase);
+        }
+        if self.require_special && !password.chars().any(is_special) {
+            failed_rules.push(PasswordRule::MissingSpecial);
+        }
+        if let Some(breached @ BreachedPasswords::Sorted(_)) = &self.breached
+            && breached.contains(password)
+        {
+            failed_rules.push(PasswordRule::Breached);
+        }
+        let score = estimate_score(password, user_inputs);
+        if score < self.min_score {
+            failed_rules.push(PasswordRule::TooWeak {
+                score,
+                min_score: self.min_score,
+            });
+        }
+        PasswordReport {
+            score,
+            failed_rules,
+        }
+    }
+
+    /// Проверяет пароль, включая поиск в каталоге диапазонов утечек
+    ///
+    /// Файл диапазона читается в пуле блокирующих задач.
+    ///
+    /// # Аргументы
+    ///
+    /// * `password` - Пароль для проверки
+    /// * `user_inputs` - Данные пользователя (email, имя), которые
+    ///   не должны облегчать подбор пароля
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(PasswordReport)` - Оценка стойкости и список невыполненных требований
+    /// * `Err(AppError::Custom)` - Задача поиска завершилась аварийно
+    pub async fn check_blocking(
+        &self,
+        password: &str,
+        user_inputs: &[&str],
+    ) -> AppResult<PasswordReport> {
+        let mut report = self.check(password, user_inputs);
+        let Some(ranges @ BreachedPasswords::Ranges { .. }) = self.breached.clone() else {
+            return Ok(report);
+        };
+        let password = password.to_string();
+        let breached = tokio::task::spawn_blocking(move || ranges.contains(&password))
+            .await
+            .map_err(|e| AppError::Custom(format!("breached passwords lookup: {e}")))?;
+        if breached {
+            // Сохраняет порядок правил, как в `check`
+            let position = report
+                .failed_rules
+                .iter()
+                .position(|rule| matches!(rule, PasswordRule::TooWeak { .. }))
+                .unwrap_or(report.failed_rules.len());
+            report.failed_rules.insert(position, PasswordRule::Breached);
+        }
+        Ok(report)
+    }
+}
+
+/// Специальный символ: знак из ASCII-набора или любой не-ASCII символ,
+/// не являющийся буквой, цифрой или пробелом
+fn is_special(c: char) -> bool {
+    is_special_char(c) || (!c.is_ascii() && !c.is_alphanumeric() && !c.is_whitespace())
+}
+
+/// Оценивает стойкость пароля по шкале 0-4
+///
+/// Упрощенный вариант подхода zxcvbn: оценивается десятичный логарифм
+/// числа попыток подбора. Повторы, последовательности (`abc`, `321`),
+/// соседние клавиши и известные пароли или данные пользователя
+/// внутри пароля почти не добавляют стойкости.
+///
+/// Пороги шкалы совпадают с zxcvbn: 10^3, 10^6, 10^8 и 10^10 попыток.
+pub fn estimate_score(password: &str, user_inputs: &[&str]) -> u8 {
+    let guesses = estimate_guesses_log10(password, user_inputs);
+    match guesses {
+        g if g < 3.0 => 0,
+        g if g < 6.0 => 1,
+        g if g < 8.0 => 2,
+        g if g < 10.0 => 3,
+        _ => 4,
+    }
+}
+
+fn estimate_guesses_log10(password: &str, user_inputs: &[&str]) -> f64 {
+    let chars: Vec<char> = password.to_lowercase().chars().collect();
+    if chars.is_empty() {
+        return 0.0;
+    }
+    let mut cardinality = 0u32;
+    if password.chars().any(|c| c.is_ascii_lowercase()) {
+        cardinality += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_uppercase()) {
+        cardinality += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_digit()) {
+        cardinality += 10;
+    }
+    if password
+        .chars()
+        .any(|c| c.is_ascii() && !c.is_ascii_alphanumeric())
+    {
+        cardinality += 33;
+    }
+    if password.chars().any(|c| !c.is_ascii() && c.is_lowercase()) {
+        cardinality += 33;
+    }
+    if password.chars().any(|c| !c.is_ascii() && c.is_uppercase()) {
+        cardinality += 33;
+    }
+    if password
+        .chars()
+        .any(|c| !c.is_ascii() && !c.is_alphabetic())
+    {
+        cardinality += 50;
+    }
+    let per_char = f64::from(cardinality).log10();
+
+    let mut weights = vec![1.0; chars.len()];
+    for i in 1..chars.len() {
+        let (prev, cur) = (chars[i - 1], chars[i]);
+        weights[i] = if prev == cur {
+            0.1
+        } else if (prev as u32).abs_diff(cur as u32) == 1 || keyboard_adjacent(prev, cur) {
+            0.2
+        } else {
+            1.0
+        };
+    }
+
+    // Совпадения со словарем заменяют оценку символов на размер словаря
+    let unleeted: String = chars.iter().map(|&c| unleet(c)).collect();
+    let dictionary_guesses = (COMMON_PASSWORDS.len() as f64).log10();
+    let words = COMMON_PASSWORDS
+        .iter()
+        .copied()
+        .chain(user_inputs.iter().flat_map(|input| {
+            input
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|word| word.chars().count() >= 4)
+        }));
+    let mut covered = vec![false; chars.len()];
+    let mut dictionary_total = 0.0;
+    for word in words {
+        let word = word.to_lowercase();
+        let word_len = word.chars().count();
+        for (byte_idx, _) in unleeted.match_indices(&word) {
+            let start = unleeted[..byte_idx].chars().count();
+            if covered[start..start + word_len].iter().any(|c| *c) {
+                continue;
+            }
+            covered[start..start + word_len].fill(true);
+            dictionary_total += dictionary_guesses.max(1.0);
+        }
+    }
+
+    let chars_total: f64 = weights
+        .iter()
+        .zip(&covered)
+        .filter(|(_, covered)| !**covered)
+        .map(|(weight, _)| weight * per_char)
+        .sum();
+    chars_total + dictionary_total
+}
+
+/// Заменяет распространенные «leet»-подстановки буквами
+fn unleet(c: char) -> char {
+    match c {
+        '0' => 'o',
+        '1' | '!' => 'i',
+        '3' => 'e',
+        '4' | '@' => 'a',
+        '5' | '$' => 's',
+        '7' => 't',
+        other => other,
+    }
+}
+
+fn keyboard_adjacent(a: char, b: char) -> bool {
+    KEYBOARD_ROWS.iter().any(|row| {
+        let row: Vec<char> = row.chars().collect();
+        row.windows(2)
+            .any(|pair| (pair[0] == a && pair[1] == b) || (pair[0] == b && pair[1] == a))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(policy: &PasswordPolicy, password: &str) -> Vec<PasswordRule> {
+        policy.check(password, &[]).failed_rules
+    }
+
+    #[test]
+    fn test_policy_is_unicode_aware() {
+        let policy = PasswordPolicy::default();
+        assert!(policy.check("Жёлтый-Кот-42", &[]).is_ok());
+        assert_eq!(
+            rules(&policy, "жёлтый-кот-42"),
+            vec![PasswordRule::MissingUppercase]
+        );
+        // Длина считается в символах, а не в байтах
+        assert_eq!(
+            rules(&policy, "Ёж-42ёж"),
+            vec![PasswordRule::TooShort { min: 8 }]
+        );
+        // Неразрывный пробел тоже пробел
+        assert!(rules(&policy, "Жёлтый\u{a0}Кот-42").contains(&PasswordRule::Whitespace));
+        // Символы вне ASCII считаются специальными
+        assert!(policy.check("Жёлтый№Кот42", &[]).is_ok());
+    }
+
+    #[test]
+    fn test_policy_is_configurable() {
+        let policy = PasswordPolicy {
+            min_length: 12,
+            require_special: false,
+            require_uppercase: false,
+            min_score: 3,
+            ..Default::default()
+        };
+        assert_eq!(
+            rules(&policy, "abcdefgh1"),
+            vec![
+                PasswordRule::TooShort { min: 12 },
+                PasswordRule::TooWeak {
+                    score: 1,
+                    min_score: 3
+                }
+            ]
+        );
+        assert_eq!(
+            rules(&policy, "plum river 9 lantern"),
+            vec![PasswordRule::Whitespace]
+        );
+        assert!(policy.check("plum-river-9-lantern", &[]).is_ok());
+
+        let report = PasswordPolicy::default().check("short", &[]);
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["failed_rules"][0]["rule"], "too_short");
+        assert_eq!(json["failed_rules"][0]["min"], 8);
+    }
+
+    #[test]
+    fn test_policy_settings_bounds() {
+        let mut settings = PasswordPolicySettings::default();
+        assert!(PasswordPolicy::from_settings(&settings).is_ok());
+        settings.min_length = 4;
+        assert!(PasswordPolicy::from_settings(&settings).is_err());
+        settings.min_length = 8;
+        settings.max_length = 1024;
+        assert!(PasswordPolicy::from_settings(&settings).is_err());
+        settings.max_length = 64;
+        settings.min_score = 5;
+        assert!(PasswordPolicy::from_settings(&settings).is_err());
+    }
+
+    #[test]
+    fn test_estimate_score() {
+        assert_eq!(estimate_score("aaaaaaaa", &[]), 0);
+        assert_eq!(estimate_score("password", &[]), 0);
+        assert_eq!(estimate_score("qwertyuiop", &[]), 0);
+        assert!(estimate_score("P@ssw0rd", &[]) <= 1);
+        assert_eq!(estimate_score("xK9#mQ2$vL7!", &[]), 4);
+        assert_eq!(estimate_score("Жёлтый-Кот-42-Бежит!", &[]), 4);
+        // Данные пользователя внутри пароля снижают оценку
+        assert!(
+            estimate_score("ivanov1985!", &["ivanov@example.com"])
+                < estimate_score("ivanov1985!", &[])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_breached_passwords_file_and_ranges() {
+        let dir = tempfile::tempdir().unwrap();
+        // SHA-1("Tr0ub4dour&3") и SHA-1("correcthorse")
+        let troubadour = to_upper_hex(&Sha1::digest(b"Tr0ub4dour&3"));
+        let horse = to_upper_hex(&Sha1::digest(b"correcthorse"));
+
+        let file = dir.path().join("pwned.txt");
+        std::fs::write(&file, format!("{troubadour}:12\n{horse}:1\n")).unwrap();
+        let list = BreachedPasswords::load(&file, 2).unwrap();
+        assert!(list.contains("Tr0ub4dour&3"));
+        assert!(!list.contains("correcthorse"));
+        assert!(!list.contains("Tr0ub4dour&4"));
+
+        let ranges = dir.path().join("ranges");
+        std::fs::create_dir_all(&ranges).unwrap();
+        std::fs::write(
+            ranges.join(&troubadour[..5]),
+            format!("{}:12\r\n", &troubadour[5..]),
+        )
+        .unwrap();
+        let list = BreachedPasswords::load(&ranges, 1).unwrap();
+        assert!(list.contains("Tr0ub4dour&3"));
+        assert!(!list.contains("correcthorse"));
+
+        // Каталог диапазонов проверяется только вне потока рантайма
+        let policy = PasswordPolicy {
+            breached: Some(list),
+            ..Default::default()
+        };
+        assert!(rules(&policy, "Tr0ub4dour&3").is_empty());
+        let report = policy.check_blocking("Tr0ub4dour&3", &[]).await.unwrap();
+        assert_eq!(report.failed_rules, vec![PasswordRule::Breached]);
+    }
+}
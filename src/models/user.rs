@@ -7,15 +7,20 @@ use std::{fmt::Display, str::FromStr};
 
 use serde::{Deserialize, Serialize};
 use tracing::instrument;
+use utoipa::ToSchema;
 use validator::{Validate, ValidationError};
 
-use crate::{AppError, AppResult};
+use crate::{
+    AppError, AppResult,
+    i18n::{Locale, tr},
+    models::PasswordPolicy,
+};
 
 /// Представляет пользователя системы
 ///
 /// Содержит основную информацию о пользователе, включая учетные данные,
 /// роль, личную информацию и временные метки создания/обновления.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default, Hash, ToSchema)]
 pub struct User {
     /// Уникальный идентификатор пользователя
     pub user_id: uuid::Uuid,
@@ -35,18 +40,96 @@ pub struct User {
     /// Дополнительная информация о пользователе
     pub info: UserInfo,
 
+    /// Дата и время создания пользователя (UTC)
+    pub created: chrono::DateTime<chrono::Utc>,
+
+    /// Дата и время последнего обновления пользователя (UTC)
+    pub updated: chrono::DateTime<chrono::Utc>,
+
+    /// Момент, после которого аккаунт будет анонимизирован
+    ///
+    /// Устанавливается, когда пользователь запросил удаление аккаунта.
+    /// До наступления этого момента удаление можно отменить.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deletion_scheduled_at: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Признак сервисного аккаунта
+    ///
+    /// Сервисные аккаунты используются интеграциями, не имеют пароля
+    /// и аутентифицируются только по API-ключам.
+    #[serde(default)]
+    pub service_account: bool,
+}
+
+impl User {
+    /// Возвращает представление пользователя с временными метками
+    /// в указанном часовом поясе
+    ///
+    /// # Аргументы
+    ///
+    /// * `tz` - Часовой пояс, в котором отображаются `created` и `updated`
+    ///
+    /// # Возвращает
+    ///
+    /// `LocalizedUser` с метками времени в формате RFC 3339 со смещением пояса
+    pub fn localized(&self, tz: chrono_tz::Tz) -> LocalizedUser {
+        LocalizedUser {
+            user_id: self.user_id,
+            email: self.email.clone(),
+            role: self.role.clone(),
+            role_label: self.role.label().to_string(),
+            info: self.info.clone(),
+            created: self.created.with_timezone(&tz).fixed_offset(),
+            updated: self.updated.with_timezone(&tz).fixed_offset(),
+            deletion_scheduled_at: self
+                .deletion_scheduled_at
+                .map(|at| at.with_timezone(&tz).fixed_offset()),
+            service_account: self.service_account,
+        }
+    }
+}
+
+/// Пользователь с временными метками в предпочитаемом часовом поясе
+///
+/// Используется в ответах API, где время отображается в поясе
+/// запрашивающего пользователя.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+pub struct LocalizedUser {
+    /// Уникальный идентификатор пользователя
+    pub user_id: uuid::Uuid,
+
+    /// Email пользователя
+    pub email: String,
+
+    /// Роль пользователя в системе
+    pub role: UserRole,
+
+    /// Название роли на языке запроса
+    pub role_label: String,
+
+    /// Дополнительная информация о пользователе
+    pub info: UserInfo,
+
     /// Дата и время создания пользователя
-    pub created: chrono::NaiveDateTime,
+    pub created: chrono::DateTime<chrono::FixedOffset>,
 
     /// Дата и время последнего обновления пользователя
-    pub updated: chrono::NaiveDateTime,
+    pub updated: chrono::DateTime<chrono::FixedOffset>,
+
+    /// Момент запланированной анонимизации аккаунта
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deletion_scheduled_at: Option<chrono::DateTime<chrono::FixedOffset>>,
+
+    /// Признак сервисного аккаунта
+    #[serde(default)]
+    pub service_account: bool,
 }
 
 /// Дополнительная информация о пользователе
 ///
 /// Содержит опциональные поля с личной информацией пользователя.
 /// Все поля пропускаются при сериализации, если имеют значение `None`.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default, Hash, ToSchema)]
 pub struct UserInfo {
     /// Имя пользователя
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -71,6 +154,14 @@ pub struct UserInfo {
     /// Биография или описание пользователя
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bio: Option<String>,
+
+    /// Предпочитаемый часовой пояс в формате IANA (например, `Europe/Moscow`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+
+    /// Предпочитаемый язык сообщений; важнее заголовка `Accept-Language`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<Locale>,
 }
 
 impl UserInfo {
@@ -105,27 +196,44 @@ impl UserInfo {
     pub fn has_profile_data(&self) -> bool {
         self.first_name.is_some() || self.last_name.is_some() || self.username.is_some()
     }
+
+    /// Возвращает предпочитаемый часовой пояс пользователя
+    ///
+    /// # Возвращает
+    ///
+    /// Часовой пояс из профиля или `UTC`, если пояс не указан или не распознан
+    pub fn tz(&self) -> chrono_tz::Tz {
+        self.timezone
+            .as_deref()
+            .and_then(|tz| tz.parse().ok())
+            .unwrap_or(chrono_tz::UTC)
+    }
 }
 
 /// Роль пользователя в системе
 ///
 /// Определяет уровень доступа и привилегии пользователя.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default, Hash)]
+/// В API и базе данных роль передается стабильным идентификатором
+/// (`owner`, `admin`, `employee`, `guest`); прежние русские названия
+/// принимаются при разборе для совместимости. Название для показа
+/// пользователю возвращает `label`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default, Hash, ToSchema)]
+#[serde(rename_all = "lowercase")]
 pub enum UserRole {
     /// Владелец системы - полный доступ ко всем функциям
-    #[serde(rename = "Владелец")]
+    #[serde(alias = "Владелец")]
     Owner,
 
     /// Администратор - доступ к управлению пользователями и настройками
-    #[serde(rename = "Администратор")]
+    #[serde(alias = "Администратор")]
     Admin,
 
     /// Сотрудник - базовый доступ к рабочим функциям
-    #[serde(rename = "Сотрудник")]
+    #[serde(alias = "Сотрудник")]
     Employee,
 
     /// Гость - минимальный доступ, только просмотр
-    #[serde(rename = "Гость")]
+    #[serde(alias = "Гость")]
     #[default]
     Guest,
 }
@@ -184,17 +292,26 @@ impl UserRole {
             UserRole::Guest,
         ]
     }
+
+    /// Возвращает название роли на языке текущего запроса
+    ///
+    /// # Возвращает
+    ///
+    /// Название для показа пользователю, например «Администратор» или `Administrator`.
+    pub fn label(&self) -> &'static str {
+        tr(match self {
+            UserRole::Owner => "Owner",
+            UserRole::Admin => "Administrator",
+            UserRole::Employee => "Employee",
+            UserRole::Guest => "Guest",
+        })
+    }
 }
 
 impl Display for UserRole {
+    /// Выводит стабильный идентификатор роли
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let string = match self {
-            UserRole::Owner => "Владелец",
-            UserRole::Admin => "Администратор",
-            UserRole::Employee => "Сотрудник",
-            UserRole::Guest => "Гость",
-        };
-        write!(f, "{string}")
+        f.write_str(self.as_ref())
     }
 }
 
@@ -239,13 +356,13 @@ impl TryFrom<String> for UserRole {
     }
 }
 impl AsRef<str> for UserRole {
-    /// Возвращает строковое представление роли на русском языке
+    /// Возвращает стабильный идентификатор роли
     fn as_ref(&self) -> &str {
         match self {
-            UserRole::Owner => "Владелец",
-            UserRole::Admin => "Администратор",
-            UserRole::Employee => "Сотрудник",
-            UserRole::Guest => "Гость",
+            UserRole::Owner => "owner",
+            UserRole::Admin => "admin",
+            UserRole::Employee => "employee",
+            UserRole::Guest => "guest",
         }
     }
 }
@@ -264,19 +381,13 @@ pub struct SignupData {
 
     /// Пароль пользователя
     ///
-    /// Должен соответствовать требованиям безопасности:
+    /// Должен соответствовать парольной политике (`PasswordPolicy::current`);
+    /// по умолчанию:
     /// * 8-64 символа
     /// * Содержать цифры, буквы в разных регистрах и специальные символы
     /// * Не содержать пробелов
     /// * Не быть распространённым паролем
-    #[validate(
-        length(
-            min = 8,
-            max = 64,
-            message = "Пароль должен содержать от 8 до 64 символов"
-        ),
-        custom(function = "validate_password")
-    )]
+    #[validate(custom(function = "validate_password"))]
     pub password: String,
 
     /// Роль нового пользователя
@@ -346,19 +457,13 @@ pub struct SigninData {
 
     /// Пароль пользователя
     ///
-    /// Должен соответствовать требованиям безопасности:
-    /// * 8-64 символа
-    /// * Содержать цифры, буквы в разных регистрах и специальные символы
-    /// * Не содержать пробелов
-    /// * Не быть распространённым паролем
-    #[validate(
-        length(
-            min = 8,
-            max = 64,
-            message = "Пароль должен содержать от 8 до 64 символов"
-        ),
-        custom(function = "validate_password")
-    )]
+    /// Проверяется только длина: пароль, заданный по прежней
+    /// парольной политике, должен оставаться пригодным для входа.
+    #[validate(length(
+        min = 8,
+        max = 256,
+        message = "Password must be between {min} and {max} characters long"
+    ))]
     pub password: String,
 }
 
@@ -405,7 +510,7 @@ impl TryFrom<(&str, &str)> for SigninData {
     }
 }
 
-/// Проверяет пароль на соответствие требованиям безопасности
+/// Проверяет пароль на соответствие парольной политике
 ///
 /// # Аргументы
 ///
@@ -418,61 +523,10 @@ impl TryFrom<(&str, &str)> for SigninData {
 ///   с описанием всех найденных проблем
 #[instrument(name = "validate password", skip(password))]
 fn validate_password(password: &str) -> Result<(), ValidationError> {
-    let mut errors = Vec::new();
-
-    // Проверка на пробелы
-    if password.contains(' ') {
-        errors.push("Пароль не должен содержать пробелы");
-    }
-
-    // Проверка на распространённые пароли
-    let common_passwords = [
-        "password",
-        "12345678",
-        "qwerty",
-        "admin123",
-        "letmein",
-        "welcome",
-        "monkey",
-        "sunshine",
-        "password1",
-        "123123",
-        "11111111",
-        "abcd1234",
-        "trustno1",
-        "dragon",
-        "baseball",
-    ];
-    if common_passwords
-        .iter()
-        .any(|&p| password.to_lowercase() == p)
-    {
-        errors.push("Пароль слишком распространён");
-    }
-
-    // Проверка наличия цифр
-    if !password.chars().any(|c| c.is_ascii_digit()) {
-        errors.push("Пароль должен содержать хотя бы одну цифру");
-    }
-
-    // Проверка наличия букв в верхнем регистре
-    if !password.chars().any(|c| c.is_ascii_uppercase()) {
-        errors.push("Пароль должен содержать хотя бы одну заглавную букву");
-    }
-
-    // Проверка наличия букв в нижнем регистре
-    if !password.chars().any(|c| c.is_ascii_lowercase()) {
-        errors.push("Пароль должен содержать хотя бы одну строчную букву");
-    }
-
-    // Проверка наличия специальных символов
-    if !password.chars().any(is_special_char) {
-        errors.push("Пароль должен содержать хотя бы один специальный символ");
-    }
-
-    if !errors.is_empty() {
+    let report = PasswordPolicy::current().check(password, &[]);
+    if !report.is_ok() {
         let mut error = validator::ValidationError::new("password");
-        error.message = Some(format!("Требования к паролю: {}", errors.join(", ")).into());
+        error.message = Some(report.message().into());
         return Err(error);
     }
 
@@ -490,7 +544,7 @@ fn validate_password(password: &str) -> Result<(), ValidationError> {
 /// # Возвращает
 ///
 /// `true` если символ является специальным, иначе `false`
-const fn is_special_char(c: char) -> bool {
+pub(super) const fn is_special_char(c: char) -> bool {
     matches!(
         c,
         '!' | '@'
@@ -523,12 +577,38 @@ const fn is_special_char(c: char) -> bool {
     )
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default, Hash)]
+#[derive(
+    Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default, Hash, Validate, ToSchema,
+)]
 pub struct UserToUpdate {
+    #[validate(email)]
     pub email: String,
     pub role: UserRole,
+    #[validate(custom(function = "validate_timezone"))]
     pub info: UserInfo,
 }
+
+/// Проверяет, что часовой пояс в профиле является валидным именем IANA
+///
+/// # Аргументы
+///
+/// * `info` - Информация о пользователе
+///
+/// # Возвращает
+///
+/// * `Ok(())` - если пояс не указан или распознан
+/// * `Err(ValidationError)` - если имя пояса неизвестно
+fn validate_timezone(info: &UserInfo) -> Result<(), ValidationError> {
+    match info.timezone.as_deref() {
+        Some(tz) if tz.parse::<chrono_tz::Tz>().is_err() => {
+            let mut error = ValidationError::new("timezone");
+            error.message = Some("Unknown time zone: {tz}".into());
+            error.add_param("tz".into(), &tz);
+            Err(error)
+        }
+        _ => Ok(()),
+    }
+}
 impl From<User> for UserToUpdate {
     fn from(value: User) -> Self {
         Self {
@@ -539,6 +619,86 @@ impl From<User> for UserToUpdate {
     }
 }
 
+/// Домен служебных адресов сервисных аккаунтов
+pub const SERVICE_ACCOUNT_EMAIL_DOMAIN: &str = "service.alfred.invalid";
+
+/// Данные для создания сервисного аккаунта
+///
+/// Сервисный аккаунт не принадлежит человеку: у него нет пароля,
+/// а email формируется из имени и служебного домена.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Validate, ToSchema)]
+pub struct ServiceAccountData {
+    /// Имя аккаунта: строчные латинские буквы, цифры и дефис
+    #[validate(
+        length(
+            min = 3,
+            max = 64,
+            message = "Name must be between {min} and {max} characters long"
+        ),
+        custom(function = "validate_service_account_name")
+    )]
+    pub name: String,
+
+    /// Роль сервисного аккаунта
+    #[serde(default)]
+    pub role: UserRole,
+}
+
+impl ServiceAccountData {
+    /// Возвращает служебный email сервисного аккаунта
+    pub fn email(&self) -> String {
+        format!("{}@{SERVICE_ACCOUNT_EMAIL_DOMAIN}", self.name)
+    }
+}
+
+/// Данные пользователя, переносимого из другой системы
+///
+/// Пароль передается готовым хэшем: Argon2, bcrypt или PBKDF2-SHA256
+/// в формате PHC или modular crypt. Хэш другого алгоритма
+/// пересчитывается в Argon2 при первом успешном входе.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Validate, ToSchema)]
+pub struct ImportedUser {
+    /// Email пользователя
+    #[validate(email)]
+    pub email: String,
+
+    /// Хэш пароля из прежней системы
+    #[validate(custom(function = "validate_password_hash"))]
+    pub password_hash: String,
+
+    /// Роль пользователя
+    #[serde(default)]
+    pub role: UserRole,
+}
+
+/// Проверяет, что формат хэша пароля поддерживается
+fn validate_password_hash(hash: &str) -> Result<(), ValidationError> {
+    if crate::crypto::is_supported_password_hash(hash) {
+        Ok(())
+    } else {
+        let mut error = ValidationError::new("password_hash");
+        error.message = Some("Unsupported password hash format".into());
+        Err(error)
+    }
+}
+
+/// Проверяет, что имя сервисного аккаунта годится для служебного email
+fn validate_service_account_name(name: &str) -> Result<(), ValidationError> {
+    let valid = name
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !name.starts_with('-')
+        && !name.ends_with('-');
+    if valid {
+        Ok(())
+    } else {
+        let mut error = ValidationError::new("service_account_name");
+        error.message =
+            Some("Name may contain only lowercase Latin letters, digits and hyphens".into());
+        Err(error)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -575,10 +735,20 @@ mod tests {
 
     #[test]
     fn test_user_role_display() {
-        assert_eq!(UserRole::Owner.to_string(), "Владелец");
-        assert_eq!(UserRole::Admin.to_string(), "Администратор");
-        assert_eq!(UserRole::Employee.to_string(), "Сотрудник");
-        assert_eq!(UserRole::Guest.to_string(), "Гость");
+        assert_eq!(UserRole::Owner.to_string(), "owner");
+        assert_eq!(UserRole::Admin.to_string(), "admin");
+        assert_eq!(UserRole::Employee.to_string(), "employee");
+        assert_eq!(UserRole::Guest.to_string(), "guest");
+    }
+
+    #[tokio::test]
+    async fn test_user_role_label() {
+        assert_eq!(UserRole::Owner.label(), "Владелец");
+        assert_eq!(UserRole::Admin.label(), "Администратор");
+        let labels = crate::i18n::Locale::En
+            .scope(async { UserRole::iter().map(UserRole::label).collect::<Vec<_>>() })
+            .await;
+        assert_eq!(labels, ["Owner", "Administrator", "Employee", "Guest"]);
     }
 
     #[test]
@@ -622,10 +792,10 @@ mod tests {
 
     #[test]
     fn test_user_role_as_ref() {
-        assert_eq!(UserRole::Owner.as_ref(), "Владелец");
-        assert_eq!(UserRole::Admin.as_ref(), "Администратор");
-        assert_eq!(UserRole::Employee.as_ref(), "Сотрудник");
-        assert_eq!(UserRole::Guest.as_ref(), "Гость");
+        assert_eq!(UserRole::Owner.as_ref(), "owner");
+        assert_eq!(UserRole::Admin.as_ref(), "admin");
+        assert_eq!(UserRole::Employee.as_ref(), "employee");
+        assert_eq!(UserRole::Guest.as_ref(), "guest");
     }
 
     #[test]
@@ -855,8 +1025,7 @@ mod tests {
 
     #[test]
     fn test_user_serialization() {
-        // Создаем NaiveDateTime без deprecated метода
-        let datetime = chrono::DateTime::from_timestamp(0, 0).unwrap().naive_utc();
+        let datetime = chrono::DateTime::from_timestamp(0, 0).unwrap();
 
         // Проверяем, что password_hash пропускается при сериализации
         let user = User {
@@ -867,12 +1036,58 @@ mod tests {
             info: UserInfo::default(),
             created: datetime,
             updated: datetime,
+            deletion_scheduled_at: None,
+            service_account: false,
         };
 
         let json = serde_json::to_string(&user).unwrap();
         assert!(!json.contains("password_hash"));
         assert!(json.contains("test@example.com"));
-        assert!(json.contains("Администратор"));
+        assert!(json.contains("\"role\":\"admin\""));
+        assert!(json.contains("1970-01-01T00:00:00Z"));
+    }
+
+    #[test]
+    fn test_user_localized() {
+        let datetime = chrono::DateTime::from_timestamp(0, 0).unwrap();
+        let user = User {
+            email: "test@example.com".to_string(),
+            info: UserInfo {
+                timezone: Some("Europe/Moscow".to_string()),
+                ..Default::default()
+            },
+            created: datetime,
+            updated: datetime,
+            ..Default::default()
+        };
+
+        let localized = user.localized(user.info.tz());
+        let json = serde_json::to_string(&localized).unwrap();
+        assert!(json.contains("1970-01-01T03:00:00+03:00"));
+        assert!(!json.contains("password_hash"));
+        assert_eq!(localized.created, user.created);
+
+        // Неизвестный пояс заменяется на UTC
+        let info = UserInfo {
+            timezone: Some("Mars/Olympus".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(info.tz(), chrono_tz::UTC);
+    }
+
+    #[test]
+    fn test_user_to_update_timezone_validation() {
+        let mut user = UserToUpdate {
+            email: "test@example.com".to_string(),
+            ..Default::default()
+        };
+        assert!(user.validate().is_ok());
+
+        user.info.timezone = Some("Asia/Yekaterinburg".to_string());
+        assert!(user.validate().is_ok());
+
+        user.info.timezone = Some("Mars/Olympus".to_string());
+        assert!(user.validate().is_err());
     }
 
     #[test]
@@ -902,15 +1117,18 @@ mod tests {
         let employee = UserRole::Employee;
         let guest = UserRole::Guest;
 
-        assert_eq!(serde_json::to_string(&owner).unwrap(), "\"Владелец\"");
-        assert_eq!(serde_json::to_string(&admin).unwrap(), "\"Администратор\"");
-        assert_eq!(serde_json::to_string(&employee).unwrap(), "\"Сотрудник\"");
-        assert_eq!(serde_json::to_string(&guest).unwrap(), "\"Гость\"");
+        assert_eq!(serde_json::to_string(&owner).unwrap(), "\"owner\"");
+        assert_eq!(serde_json::to_string(&admin).unwrap(), "\"admin\"");
+        assert_eq!(serde_json::to_string(&employee).unwrap(), "\"employee\"");
+        assert_eq!(serde_json::to_string(&guest).unwrap(), "\"guest\"");
 
         // Проверяем десериализацию
-        let owner_deserialized: UserRole = serde_json::from_str("\"Владелец\"").unwrap();
+        let owner_deserialized: UserRole = serde_json::from_str("\"owner\"").unwrap();
         assert_eq!(owner_deserialized, UserRole::Owner);
 
+        // Прежние русские названия принимаются для совместимости
+        let owner_deserialized: UserRole = serde_json::from_str("\"Владелец\"").unwrap();
+        assert_eq!(owner_deserialized, UserRole::Owner);
         let guest_deserialized: UserRole = serde_json::from_str("\"Гость\"").unwrap();
         assert_eq!(guest_deserialized, UserRole::Guest);
     }
@@ -1012,8 +1230,7 @@ mod tests {
 
     #[test]
     fn test_equality_and_hash() {
-        // Создаем NaiveDateTime без deprecated метода
-        let datetime = chrono::DateTime::from_timestamp(0, 0).unwrap().naive_utc();
+        let datetime = chrono::DateTime::from_timestamp(0, 0).unwrap();
 
         let user1 = User {
             user_id: uuid::Uuid::new_v4(),
@@ -1023,6 +1240,8 @@ mod tests {
             info: UserInfo::default(),
             created: datetime,
             updated: datetime,
+            deletion_scheduled_at: None,
+            service_account: false,
         };
 
         let user2 = User {
@@ -1033,6 +1252,8 @@ mod tests {
             info: UserInfo::default(),
             created: datetime,
             updated: datetime,
+            deletion_scheduled_at: None,
+            service_account: false,
         };
 
         // Два пользователя НЕ равны, потому что password_hash разный!
@@ -1056,6 +1277,8 @@ mod tests {
             info: user1.info.clone(),
             created: user1.created,
             updated: user1.updated,
+            deletion_scheduled_at: user1.deletion_scheduled_at,
+            service_account: user1.service_account,
         };
 
         assert_eq!(user1, user3); // Теперь они равны
@@ -1066,4 +1289,44 @@ mod tests {
         set2.insert(user3);
         assert_eq!(set2.len(), 1); // Дубликат не добавляется
     }
+
+    #[test]
+    fn test_service_account_data_validation() {
+        let data = ServiceAccountData {
+            name: "ci-bot".to_string(),
+            role: UserRole::Employee,
+        };
+        assert!(data.validate().is_ok());
+        assert_eq!(data.email(), "ci-bot@service.alfred.invalid");
+
+        for name in ["CI", "ci bot", "-ci", "ci-", "бот-ci"] {
+            let data = ServiceAccountData {
+                name: name.to_string(),
+                role: UserRole::Guest,
+            };
+            assert!(data.validate().is_err(), "{name} should be rejected");
+        }
+    }
+
+    #[test]
+    fn test_imported_user_validation() {
+        let data = ImportedUser {
+            email: "legacy@example.com".to_string(),
+            password_hash: "$2a$05$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOeW"
+                .to_string(),
+            role: UserRole::Employee,
+        };
+        assert!(data.validate().is_ok());
+
+        let plain = ImportedUser {
+            password_hash: "str0nGp@ssw0rD".to_string(),
+            ..data.clone()
+        };
+        assert!(plain.validate().is_err());
+        let bad_email = ImportedUser {
+            email: "legacy".to_string(),
+            ..data
+        };
+        assert!(bad_email.validate().is_err());
+    }
 }
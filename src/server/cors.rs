@@ -0,0 +1,48 @@
+//! Правила CORS из настроек сервера
+//!
+//! Сессия передается в cookie, поэтому ответы всегда разрешают передачу
+//! учетных данных и `*` в качестве источника не допускается.
+
+use std::time::Duration;
+
+use axum::http::{HeaderName, HeaderValue, Method};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::{
+    server::{csrf::CSRF_HEADER, origins::AllowedOrigins},
+    settings::CorsSettings,
+};
+
+/// Собирает слой CORS
+///
+/// Некорректные имена заголовков пропускаются: настройки проверяются
+/// при запуске, и сюда они попадают только в тестах.
+///
+/// # Аргументы
+///
+/// * `settings` - Настройки CORS
+/// * `origins` - Источники, которым разрешены запросы
+pub(crate) fn layer(settings: &CorsSettings, origins: AllowedOrigins) -> CorsLayer {
+    let headers = settings
+        .allowed_headers
+        .iter()
+        .filter_map(|name| match HeaderName::from_bytes(name.as_bytes()) {
+            Ok(name) => Some(name),
+            Err(_) => {
+                tracing::warn!("Skipping invalid CORS header name '{name}'");
+                None
+            }
+        })
+        .chain([HeaderName::from_static(CSRF_HEADER)])
+        .collect::<Vec<_>>();
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::predicate(
+            move |origin: &HeaderValue, _parts| {
+                origin.to_str().is_ok_and(|origin| origins.matches(origin))
+            },
+        ))
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
+        .allow_headers(headers)
+        .max_age(Duration::from_secs(settings.max_age_secs))
+        .allow_credentials(true)
+}
@@ -0,0 +1,270 @@
+//! Защита от CSRF для запросов, аутентифицированных cookie
+//!
+//! При входе вместе с сессионной cookie выдается CSRF-токен: он кладется
+//! в cookie `alfred-csrf`, доступную скрипту, и возвращается в теле ответа.
+//! Изменяющие запросы с сессионной cookie должны повторить его в заголовке
+//! `X-CSRF-Token`. Токен подписан секретом JWT и привязан к сессии, поэтому
+//! подброшенная на поддомене cookie не пройдет проверку.
+
+use axum::http::{HeaderMap, Method, header};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::{crypto::random_urlsafe, server::origins::AllowedOrigins, settings::JWTSettings};
+
+/// Cookie с CSRF-токеном
+pub const CSRF_COOKIE: &str = "alfred-csrf";
+/// Заголовок, в котором клиент повторяет CSRF-токен
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// Количество случайных байт в CSRF-токене
+const CSRF_NONCE_BYTES: usize = 16;
+
+/// Сессионный токен, полученный из cookie
+///
+/// Добавляется в расширения запроса middleware `auth`, только если
+/// запрос аутентифицирован cookie, а не заголовком `Authorization`.
+#[derive(Clone, Debug)]
+pub struct SessionCookie(pub String);
+
+/// Выпускает CSRF-токен для сессии
+///
+/// # Аргументы
+///
+/// * `secret` - Секрет подписи
+/// * `session_token` - Сессионный JWT
+///
+/// # Возвращает
+///
+/// Токен вида `<nonce>.<подпись>`
+pub fn issue_token(secret: &str, session_token: &str) -> String {
+    let nonce = random_urlsafe(CSRF_NONCE_BYTES);
+    let signature =
+        URL_SAFE_NO_PAD.encode(mac(secret, &nonce, session_token).finalize().into_bytes());
+    format!("{nonce}.{signature}")
+}
+
+/// Проверяет, что CSRF-токен выпущен для этой сессии
+fn verify_token(secret: &str, token: &str, session_token: &str) -> bool {
+    let Some((nonce, signature)) = token.split_once('.') else {
+        return false;
+    };
+    let Ok(signature) = URL_SAFE_NO_PAD.decode(signature) else {
+        return false;
+    };
+    mac(secret, nonce, session_token)
+        .verify_slice(&signature)
+        .is_ok()
+}
+
+fn mac(secret: &str, nonce: &str, session_token: &str) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(nonce.as_bytes());
+    mac.update(b".");
+    mac.update(session_token.as_bytes());
+    mac
+}
+
+/// Создает cookie с CSRF-токеном
+///
+/// В отличие от сессионной cookie она доступна скрипту страницы,
+/// чтобы тот мог передать токен в заголовке.
+pub fn create_cookie(csrf_token: &str, jwt: &JWTSettings) -> String {
+    Cookie::build((CSRF_COOKIE, csrf_token.to_owned()))
+        .path("/")
+        .max_age(time::Duration::hours(jwt.maxage))
+        .same_site(SameSite::Lax)
+        .to_string()
+}
+
+/// Проверка CSRF для запросов с сессионной cookie
+#[derive(Clone, Debug)]
+pub struct CsrfGuard {
+    origins: AllowedOrigins,
+    secret: String,
+}
+
+impl CsrfGuard {
+    /// Создает проверку для указанных источников
+    ///
+    /// # Аргументы
+    ///
+    /// * `origins` - Разрешенные источники, те же, что и для CORS
+    /// * `secret` - Секрет, которым подписываются CSRF-токены
+    pub fn new(origins: AllowedOrigins, secret: &str) -> Self {
+        Self {
+            origins,
+            secret: secret.to_string(),
+        }
+    }
+
+    /// Проверяет запрос, аутентифицированный сессионной cookie
+    ///
+    /// Безопасные методы пропускаются. Для остальных источник из `Origin`
+    /// (или `Referer`, если `Origin` нет) должен быть среди разрешенных,
+    /// а заголовок `X-CSRF-Token` - с cookie `alfred-csrf` и сессией.
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(())` - Запрос можно выполнять
+    /// * `Err(&str)` - Причина отказа для ответа клиенту
+    pub fn check(
+        &self,
+        method: &Method,
+        headers: &HeaderMap,
+        jar: &CookieJar,
+        session_token: &str,
+    ) -> Result<(), &'static str> {
+        if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+            return Ok(());
+        }
+        if !self.origin_allowed(headers) {
+            return Err("Cross-origin request rejected");
+        }
+        let header_token = headers
+            .get(CSRF_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .ok_or("Missing CSRF token")?;
+        let cookie_token = jar.get(CSRF_COOKIE).map(|cookie| cookie.value());
+        if cookie_token != Some(header_token)
+            || !verify_token(&self.secret, header_token, session_token)
+        {
+            return Err("Invalid CSRF token");
+        }
+        Ok(())
+    }
+
+    fn origin_allowed(&self, headers: &HeaderMap) -> bool {
+        if let Some(origin) = headers.get(header::ORIGIN) {
+            return origin
+                .to_str()
+                .is_ok_and(|origin| self.origins.matches(origin));
+        }
+        match headers.get(header::REFERER).map(|referer| referer.to_str()) {
+            Some(Ok(referer)) => self.origins.matches_referer(referer),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    const SECRET: &str = "secret";
+    const SESSION: &str = "session-jwt";
+
+    fn request(
+        origin: Option<&str>,
+        referer: Option<&str>,
+        csrf: Option<&str>,
+    ) -> (HeaderMap, CookieJar) {
+        let mut headers = HeaderMap::new();
+        if let Some(origin) = origin {
+            headers.insert(header::ORIGIN, HeaderValue::from_str(origin).unwrap());
+        }
+        if let Some(referer) = referer {
+            headers.insert(header::REFERER, HeaderValue::from_str(referer).unwrap());
+        }
+        let mut jar = CookieJar::new();
+        if let Some(csrf) = csrf {
+            headers.insert(CSRF_HEADER, HeaderValue::from_str(csrf).unwrap());
+            jar = jar.add(Cookie::new(CSRF_COOKIE, csrf.to_string()));
+        }
+        (headers, jar)
+    }
+
+    #[test]
+    fn test_csrf_token_is_bound_to_session() {
+        let token = issue_token(SECRET, SESSION);
+        assert!(verify_token(SECRET, &token, SESSION));
+        assert!(!verify_token(SECRET, &token, "other-session"));
+        assert!(!verify_token("other-secret", &token, SESSION));
+        assert!(!verify_token(SECRET, "garbage", SESSION));
+        assert_ne!(token, issue_token(SECRET, SESSION));
+    }
+
+    #[test]
+    fn test_csrf_guard() {
+        let guard = CsrfGuard::new(
+            AllowedOrigins::new(["https://alfred.example.com/", "https://*.app.example.com"]),
+            SECRET,
+        );
+        let token = issue_token(SECRET, SESSION);
+        let check = |method: Method, (headers, jar): (HeaderMap, CookieJar)| {
+            guard.check(&method, &headers, &jar, SESSION)
+        };
+
+        assert!(check(Method::GET, request(None, None, None)).is_ok());
+        assert!(
+            check(
+                Method::PUT,
+                request(Some("https://alfred.example.com"), None, Some(&token))
+            )
+            .is_ok()
+        );
+        assert!(
+            check(
+                Method::DELETE,
+                request(None, Some("https://alfred.example.com/users"), Some(&token))
+            )
+            .is_ok()
+        );
+        assert!(
+            check(
+                Method::PUT,
+                request(Some("https://web.app.example.com"), None, Some(&token))
+            )
+            .is_ok()
+        );
+
+        // Чужой источник или его отсутствие
+        assert!(
+            check(
+                Method::PUT,
+                request(Some("https://evil.example.com"), None, Some(&token))
+            )
+            .is_err()
+        );
+        assert!(
+            check(
+                Method::PUT,
+                request(
+                    None,
+                    Some("https://alfred.example.com.evil.io/"),
+                    Some(&token)
+                )
+            )
+            .is_err()
+        );
+        assert!(check(Method::PUT, request(None, None, Some(&token))).is_err());
+
+        // Нет токена, токен не совпадает с cookie или выпущен для другой сессии
+        assert_eq!(
+            check(
+                Method::POST,
+                request(Some("https://alfred.example.com"), None, None)
+            ),
+            Err("Missing CSRF token")
+        );
+        let (mut headers, jar) = request(Some("https://alfred.example.com"), None, Some(&token));
+        headers.insert(
+            CSRF_HEADER,
+            HeaderValue::from_str(&issue_token(SECRET, SESSION)).unwrap(),
+        );
+        assert!(check(Method::POST, (headers, jar)).is_err());
+        let foreign = issue_token(SECRET, "other-session");
+        assert!(
+            check(
+                Method::POST,
+                request(Some("https://alfred.example.com"), None, Some(&foreign))
+            )
+            .is_err()
+        );
+    }
+}
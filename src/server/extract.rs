@@ -0,0 +1,110 @@
+//! Извлекатели с ответами об ошибках в формате API
+//!
+//! Стандартные извлекатели axum отвечают на неверный запрос текстом.
+//! Эти обертки превращают отказ в `AppError`, поэтому клиент получает
+//! то же тело ошибки, что и от обработчиков.
+use axum::{
+    extract::{
+        FromRequest, FromRequestParts,
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+use crate::AppError;
+
+/// JSON-тело запроса или ответа
+#[derive(Debug, Clone, Copy, Default, FromRequest)]
+#[from_request(via(axum::Json), rejection(AppError))]
+pub struct Json<T>(pub T);
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+/// Параметры строки запроса
+#[derive(Debug, Clone, Copy, Default, FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(AppError))]
+pub struct Query<T>(pub T);
+
+/// Параметры пути
+#[derive(Debug, Clone, Copy, Default, FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(AppError))]
+pub struct Path<T>(pub T);
+
+impl From<JsonRejection> for AppError {
+    fn from(rejection: JsonRejection) -> Self {
+        match rejection {
+            JsonRejection::JsonDataError(e) => AppError::InvalidBody(e.body_text()),
+            JsonRejection::JsonSyntaxError(e) => AppError::MalformedRequest(e.body_text()),
+            JsonRejection::MissingJsonContentType(_) => {
+                AppError::Http(StatusCode::UNSUPPORTED_MEDIA_TYPE)
+            }
+            rejection => AppError::Http(rejection.status()),
+        }
+    }
+}
+
+impl From<QueryRejection> for AppError {
+    fn from(rejection: QueryRejection) -> Self {
+        AppError::MalformedRequest(rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for AppError {
+    fn from(rejection: PathRejection) -> Self {
+        match rejection.status() {
+            status if status.is_server_error() => AppError::Custom(rejection.body_text()),
+            _ => AppError::MalformedRequest(rejection.body_text()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{Router, body::Body, http::Request, routing::post};
+    use serde::Deserialize;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Form {
+        #[allow(dead_code)]
+        email: String,
+    }
+
+    async fn send(content_type: &str, body: &'static str) -> (StatusCode, serde_json::Value) {
+        let app = Router::new().route("/", post(|Json(_): Json<Form>| async {}));
+        let request = Request::post("/")
+            .header("content-type", content_type)
+            .body(Body::from(body))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_json_rejections() {
+        let (status, body) = send("application/json", "{").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "malformed_request");
+
+        let (status, body) = send("application/json", "{}").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "invalid_body");
+        assert!(body["message"].as_str().unwrap().contains("email"));
+
+        let (status, body) = send("text/plain", "{}").await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(body["code"], "unsupported_media_type");
+    }
+}
@@ -1,79 +1,384 @@
 use crate::{
-    AppState,
-    server::{ErrorResponse, TOKEN, TokenClaims},
+    AppError, AppState,
+    crypto::API_TOKEN_PREFIX,
+    error::ApiError,
+    i18n::Locale,
+    metrics,
+    models::User,
+    server::{
+        AuthMethod, TOKEN,
+        csrf::{CsrfGuard, SessionCookie},
+        rate_limit::RateLimiter,
+        tls::ClientCertificate,
+    },
+    settings::HttpLimits,
 };
 
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc, time::Instant};
 
 use axum::{
-    Json,
     body::Body,
-    extract::State,
-    http::{Request, StatusCode, header},
+    extract::{ConnectInfo, MatchedPath, State},
+    http::{HeaderMap, HeaderName, HeaderValue, Request, StatusCode, header},
     middleware::Next,
-    response::IntoResponse,
+    response::{IntoResponse, Response},
 };
 
 use axum_extra::extract::cookie::CookieJar;
-use jsonwebtoken::{DecodingKey, Validation, decode};
 
 pub async fn auth(
     cookie_jar: CookieJar,
     State(state): State<Arc<AppState>>,
     mut req: Request<Body>,
     next: Next,
-) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
-    for (key, value) in req.headers() {
-        tracing::debug!("Header '{key}': '{value:?}'");
+) -> Result<impl IntoResponse, AppError> {
+    let bearer = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|auth_header| auth_header.to_str().ok())
+        .and_then(|auth_value| auth_value.strip_prefix("Bearer "));
+    if let Some(api_token) = bearer.filter(|t| t.starts_with(API_TOKEN_PREFIX)) {
+        let (user, details) = state
+            .api_tokens_service
+            .authenticate(api_token)
+            .await
+            .map_err(|_| AppError::Unauthenticated("Invalid API token"))?;
+        let locale = user.info.locale;
+        req.extensions_mut().insert(user);
+        req.extensions_mut().insert(AuthMethod::ApiToken(details));
+        return Ok(with_user_locale(locale, req, next).await);
     }
-    let token = cookie_jar
+    // Заголовок важнее cookie: только запросы с cookie нуждаются в защите от CSRF
+    let bearer = bearer.map(str::to_owned);
+    let cookie = cookie_jar
         .get(TOKEN)
-        .map(|cookie| cookie.value().to_string())
-        .or_else(|| {
-            req.headers()
-                .get(header::AUTHORIZATION)
-                .and_then(|auth_header| auth_header.to_str().ok())
-                .and_then(|auth_value| {
-                    if auth_value.starts_with("Bearer ") {
-                        Some(auth_value.replace("Bearer ", "").to_owned())
-                    } else {
-                        None
-                    }
-                })
-        });
-    let token = token.ok_or((
-        StatusCode::UNAUTHORIZED,
-        Json(ErrorResponse {
-            status: "fail",
-            message: "No token provided".into(),
-        }),
-    ))?;
-
-    let claims = decode::<TokenClaims>(
-        &token,
-        &DecodingKey::from_secret(state.jwt_settings.secret.as_ref()),
-        &Validation::default(),
-    )
-    .map_err(|_| {
-        (
-            StatusCode::UNAUTHORIZED,
-            Json(ErrorResponse {
-                status: "fail",
-                message: "Invalid token".into(),
-            }),
-        )
-    })?
-    .claims;
+        .map(|cookie| cookie.value().to_string());
+    let from_cookie = bearer.is_none() && cookie.is_some();
+    let Some(token) = bearer.or(cookie) else {
+        return auth_client_certificate(&state, req, next).await;
+    };
+
+    let claims = state
+        .jwt_keys
+        .verify(&token)
+        .map_err(|_| AppError::Unauthenticated("Invalid token"))?;
     let user_id = claims.sub;
-    let user = state.users_service.get_by_id(&user_id).await.map_err(|_| {
-        (
-            StatusCode::UNAUTHORIZED,
-            Json(ErrorResponse {
-                status: "fail",
-                message: "Invalid token".into(),
-            }),
-        )
-    })?;
+    let user = state
+        .users_service
+        .get_by_id(&user_id)
+        .await
+        .map_err(|_| AppError::Unauthenticated("Invalid token"))?;
+    let locale = user.info.locale;
+    req.extensions_mut().insert(user);
+    let signed_in_at = chrono::DateTime::from_timestamp(claims.iat as i64, 0).unwrap_or_default();
+    req.extensions_mut()
+        .insert(AuthMethod::Session { signed_in_at });
+    if from_cookie {
+        req.extensions_mut().insert(SessionCookie(token));
+    }
+    Ok(with_user_locale(locale, req, next).await)
+}
+
+/// Аутентифицирует запрос без токена по клиентскому сертификату
+///
+/// Имя из сертификата должно совпадать с именем сервисного аккаунта.
+/// Запросы с заголовком `Origin` так не аутентифицируются: браузер
+/// предъявляет сертификат автоматически, как cookie, а защиты от CSRF
+/// у такого запроса нет.
+async fn auth_client_certificate(
+    state: &AppState,
+    mut req: Request<Body>,
+    next: Next,
+) -> Result<Response, AppError> {
+    let client = req
+        .extensions()
+        .get::<ClientCertificate>()
+        .filter(|_| !req.headers().contains_key(header::ORIGIN))
+        .ok_or(AppError::Unauthenticated("No token provided"))?;
+    let user = state
+        .users_service
+        .find_service_account(&client.name)
+        .await
+        .map_err(|_| AppError::Unauthenticated("Unknown client certificate"))?;
+    let locale = user.info.locale;
     req.extensions_mut().insert(user);
+    req.extensions_mut().insert(AuthMethod::ClientCertificate);
+    Ok(with_user_locale(locale, req, next).await)
+}
+
+/// Обрабатывает запрос на языке из настроек пользователя, если он задан
+async fn with_user_locale(locale: Option<Locale>, req: Request<Body>, next: Next) -> Response {
+    let Some(locale) = locale else {
+        return next.run(req).await;
+    };
+    let mut response = locale.scope(next.run(req)).await;
+    response.headers_mut().insert(
+        header::CONTENT_LANGUAGE,
+        HeaderValue::from_static(locale.tag()),
+    );
+    response
+}
+
+/// Выбирает язык ответа по заголовку `Accept-Language`
+///
+/// Язык действует до конца обработки запроса; `auth` заменяет его
+/// языком из настроек пользователя. Должен выполняться раньше
+/// `error_envelope`, чтобы ошибки фреймворка тоже переводились.
+pub async fn locale(req: Request<Body>, next: Next) -> Response {
+    let locale = req
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(Locale::negotiate)
+        .unwrap_or_default();
+    let mut response = locale.scope(next.run(req)).await;
+    let headers = response.headers_mut();
+    headers
+        .entry(header::CONTENT_LANGUAGE)
+        .or_insert(HeaderValue::from_static(locale.tag()));
+    headers.append(header::VARY, HeaderValue::from_static("accept-language"));
+    response
+}
+
+/// Проверяет CSRF-токен и источник запросов, аутентифицированных cookie
+///
+/// Должен выполняться после `auth`: запросы с заголовком `Authorization`
+/// пропускаются без проверки.
+pub async fn csrf(
+    cookie_jar: CookieJar,
+    State(guard): State<Arc<CsrfGuard>>,
+    req: Request<Body>,
+    next: Next,
+) -> Result<impl IntoResponse, AppError> {
+    if let Some(SessionCookie(token)) = req.extensions().get::<SessionCookie>() {
+        guard
+            .check(req.method(), req.headers(), &cookie_jar, token)
+            .map_err(AppError::CsrfRejected)?;
+    }
     Ok(next.run(req).await)
 }
+
+/// Ограничивает частоту запросов по политике группы маршрутов
+///
+/// Для политик по пользователю должен выполняться после `auth`.
+/// Ответ дополняется заголовками `RateLimit-*`; при исчерпании лимита
+/// возвращается 429 с `Retry-After`.
+pub async fn rate_limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    let Some(decision) = limiter
+        .check(peer, req.headers(), req.extensions().get::<User>())
+        .await
+    else {
+        return next.run(req).await;
+    };
+    let headers = limiter.headers(&decision);
+    let mut response = if decision.allowed {
+        next.run(req).await
+    } else {
+        AppError::TooManyRequests.into_response()
+    };
+    response.headers_mut().extend(headers);
+    response
+}
+
+/// Ограничивает время обработки запроса
+///
+/// Время выбирается по пути запроса из `HttpLimits`; по истечении
+/// обработка прерывается и клиент получает 408.
+pub async fn timeout(
+    State(limits): State<Arc<HttpLimits>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let duration = limits.timeout_for(req.uri().path());
+    match tokio::time::timeout(duration, next.run(req)).await {
+        Ok(response) => response,
+        Err(_) => StatusCode::REQUEST_TIMEOUT.into_response(),
+    }
+}
+
+/// Добавляет заголовки безопасности к ответу
+///
+/// Заголовки, которые уже задал обработчик, не заменяются: так страница
+/// документации сохраняет собственную политику `Content-Security-Policy`.
+pub async fn security_headers(
+    State(headers): State<Arc<HeaderMap>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let mut response = next.run(req).await;
+    for (name, value) in headers.iter() {
+        response
+            .headers_mut()
+            .entry(name)
+            .or_insert_with(|| value.clone());
+    }
+    response
+}
+
+/// Записывает число и длительность HTTP-запросов
+///
+/// Запрос помечается шаблоном маршрута, а не путем: идентификаторы
+/// в адресах не должны порождать новые временные ряды.
+pub async fn track_http(req: Request<Body>, next: Next) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned())
+        .unwrap_or_else(|| metrics::UNMATCHED_ROUTE.to_owned());
+    let method = req.method().clone();
+    let started = Instant::now();
+    let response = next.run(req).await;
+    metrics::record_http_request(
+        method.as_str(),
+        &route,
+        response.status().as_u16(),
+        started.elapsed(),
+    );
+    response
+}
+
+/// Приводит все ответы с ошибкой к телу `ApiError`
+///
+/// `AppError` не знает, в каком запросе возникла, поэтому кладет тело
+/// ответа в расширения; здесь оно сериализуется заново с `request_id`.
+/// Ошибки, которые формирует сам фреймворк или слои tower - 405,
+/// истечение времени, слишком большое тело, - приходят без такого
+/// тела и заменяются ответом с кодом по статусу. Ответы, которые
+/// обработчик сам сформировал в JSON, например отчет о готовности, не меняются.
+/// Должен выполняться после слоя, назначающего идентификатор.
+///
+/// # Аргументы
+///
+/// * `header` - Заголовок с идентификатором запроса
+pub async fn error_envelope(
+    State(header): State<HeaderName>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let request_id = req
+        .headers()
+        .get(&header)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+    let mut response = next.run(req).await;
+    let status = response.status();
+    let body = match response.extensions_mut().remove::<ApiError>() {
+        Some(body) => body,
+        None if is_json(&response) => return response,
+        None if status.is_client_error() || status.is_server_error() => {
+            ApiError::from(AppError::Http(status))
+        }
+        None => return response,
+    };
+    let body = match request_id {
+        Some(request_id) => body.with_request_id(request_id),
+        None => body,
+    };
+    let Ok(body) = serde_json::to_vec(&body) else {
+        return response;
+    };
+    let (mut parts, _) = response.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    Response::from_parts(parts, Body::from(body))
+}
+
+fn is_json(response: &Response) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{Router, middleware, routing::get};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn app(request_id: &HeaderName) -> Router {
+        Router::new()
+            .route(
+                "/denied",
+                get(|| async { Err::<(), _>(AppError::AccessDenied) }),
+            )
+            .layer(middleware::from_fn_with_state(
+                request_id.clone(),
+                error_envelope,
+            ))
+    }
+
+    #[tokio::test]
+    async fn test_error_request_id() {
+        let request_id = HeaderName::from_static("alfred-request-id");
+        let app = app(&request_id);
+        let request = Request::get("/denied")
+            .header(&request_id, "req-1")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "access_denied");
+        assert_eq!(body["request_id"], "req-1");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_route_timeouts() {
+        let limits = HttpLimits {
+            timeout_secs: 1,
+            route_timeouts: [("/slow".to_string(), 5)].into(),
+            ..Default::default()
+        };
+        let handler = || async {
+            tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+        };
+        let app = Router::new()
+            .route("/fast", get(handler))
+            .route("/slow", get(handler))
+            .layer(middleware::from_fn_with_state(Arc::new(limits), timeout));
+        let request = |uri| Request::get(uri).body(Body::empty()).unwrap();
+
+        let response = app.clone().oneshot(request("/fast")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+        let response = app.oneshot(request("/slow")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_framework_errors_are_wrapped() {
+        let request_id = HeaderName::from_static("alfred-request-id");
+        let request = Request::post("/denied")
+            .header(&request_id, "req-2")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app(&request_id).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        // Заголовок Allow от маршрутизатора сохраняется
+        assert!(response.headers().contains_key(header::ALLOW));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "method_not_allowed");
+        assert_eq!(body["request_id"], "req-2");
+    }
+}
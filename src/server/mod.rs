@@ -1,14 +1,30 @@
+mod cors;
+mod csrf;
+mod extract;
+mod jwt;
 pub mod middleware;
+mod origins;
+mod rate_limit;
+mod response;
 mod routes;
-use std::sync::Arc;
+mod tls;
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+pub use jwt::JwtKeys;
+pub use rate_limit::RateLimits;
 
 pub const TOKEN: &str = "alfred-token";
 
+use http::header;
 use serde::{Deserialize, Serialize};
+use tower_http::set_header::SetResponseHeaderLayer;
 
 use crate::{
     AppError, AppResult,
-    services::UsersService,
+    logger::LogFilter,
+    metrics::Metrics,
+    models::{ApiScope, ApiToken},
+    services::{ApiTokensService, BootstrapService, HealthService, OidcService, UsersService},
     settings::{JWTSettings, ServerSettings},
 };
 
@@ -17,49 +33,242 @@ pub struct TokenClaims {
     pub sub: String,
     pub iat: usize,
     pub exp: usize,
+    pub iss: String,
+    pub aud: Vec<String>,
 }
 
 pub struct Server {
     addr: String,
-    origin: String,
+    admin_addr: Option<String>,
+    redirect_addr: Option<String>,
+    drain: Duration,
+    settings: ServerSettings,
     state: Arc<AppState>,
 }
 impl Server {
     pub fn new(settings: ServerSettings, state: Arc<AppState>) -> Self {
         Self {
             addr: settings.server_address(),
-            origin: settings.origin,
+            admin_addr: settings.admin_address(),
+            redirect_addr: settings.redirect_address(),
+            drain: Duration::from_secs(settings.shutdown_drain_secs),
+            settings,
             state,
         }
     }
+    /// Запускает API и, если заданы порты, служебный сервер
+    /// и перенаправление с HTTP на HTTPS
+    ///
+    /// По сигналу завершения проверка готовности начинает отвечать 503,
+    /// и только через `shutdown_drain_secs` серверы перестают принимать
+    /// соединения и дожидаются обработки начатых запросов.
     pub async fn start(&self) -> AppResult<()> {
-        tracing::info!("Starting server on {addr}", addr = self.addr);
-        let listener = tokio::net::TcpListener::bind(&self.addr).await?;
-        tracing::info!("Server listening on {addr}", addr = self.addr);
-        let app = routes::init(self.state.clone(), &self.origin);
-        if let Err(e) = axum::serve(listener, app)
-            .with_graceful_shutdown(shutdown_signal())
-            .await
-        {
-            return Err(AppError::IOError(e));
-        }
+        let mut app = routes::init(self.state.clone(), &self.settings);
+        let tls_config = match &self.settings.tls {
+            Some(settings) => {
+                let (config, resolver) = tls::server_config(settings)?;
+                tls::spawn_reload(
+                    &resolver,
+                    Duration::from_secs(settings.reload_interval_secs),
+                );
+                if let Some(hsts) = tls::hsts(settings) {
+                    app = app.layer(SetResponseHeaderLayer::overriding(
+                        header::STRICT_TRANSPORT_SECURITY,
+                        hsts,
+                    ));
+                }
+                Some(config)
+            }
+            None => None,
+        };
+        let (stop_tx, stop_rx) = tokio::sync::watch::channel(false);
+        let health = self.state.health.clone();
+        let drain = self.drain;
+        tokio::spawn(async move {
+            shutdown_signal().await;
+            health.begin_shutdown();
+            tracing::info!("Draining for {drain:?} before shutdown");
+            tokio::time::sleep(drain).await;
+            let _ = stop_tx.send(true);
+        });
+        let api = async {
+            match tls_config {
+                Some(config) => serve_tls(&self.addr, app, config, stop_rx.clone()).await,
+                None => serve(&self.addr, app, stop_rx.clone()).await,
+            }
+        };
+        let admin = optional(self.admin_addr.as_ref().map(|admin_addr| {
+            serve(
+                admin_addr,
+                routes::admin(self.state.clone()),
+                stop_rx.clone(),
+            )
+        }));
+        let redirect = optional(self.redirect_addr.as_ref().map(|redirect_addr| {
+            serve(
+                redirect_addr,
+                tls::redirect(&self.settings.origin),
+                stop_rx.clone(),
+            )
+        }));
+        tokio::try_join!(api, admin, redirect)?;
         tracing::info!("Server shutting down gracefully");
         Ok(())
     }
 }
 
+/// Запускает сервер, если он включен
+async fn optional(server: Option<impl Future<Output = AppResult<()>>>) -> AppResult<()> {
+    match server {
+        Some(server) => server.await,
+        None => Ok(()),
+    }
+}
+
+/// Принимает соединения на адресе, пока не придет команда остановки
+async fn serve(
+    addr: &str,
+    app: axum::Router,
+    mut stop: tokio::sync::watch::Receiver<bool>,
+) -> AppResult<()> {
+    tracing::info!("Starting server on {addr}");
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    tracing::info!("Server listening on {addr}");
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move {
+        let _ = stop.wait_for(|stop| *stop).await;
+    })
+    .await
+    .map_err(AppError::IOError)
+}
+
+/// Принимает соединения по TLS, пока не придет команда остановки
+async fn serve_tls(
+    addr: &str,
+    app: axum::Router,
+    config: Arc<rustls::ServerConfig>,
+    mut stop: tokio::sync::watch::Receiver<bool>,
+) -> AppResult<()> {
+    tracing::info!("Starting TLS server on {addr}");
+    let listener = tls::TlsListener::bind(addr, config).await?;
+    tracing::info!("Server listening on {addr}");
+    axum::serve(
+        listener,
+        app.layer(axum::middleware::from_fn(tls::connect_info))
+            .into_make_service_with_connect_info::<tls::TlsConnectInfo>(),
+    )
+    .with_graceful_shutdown(async move {
+        let _ = stop.wait_for(|stop| *stop).await;
+    })
+    .await
+    .map_err(AppError::IOError)
+}
+
+/// Способ, которым аутентифицирован текущий запрос
+///
+/// Добавляется в расширения запроса middleware `auth` вместе с `User`.
+#[derive(Clone, Debug)]
+pub enum AuthMethod {
+    /// JWT из cookie или заголовка `Authorization`
+    Session {
+        /// Момент входа, выдавшего токен (`iat`)
+        signed_in_at: chrono::DateTime<chrono::Utc>,
+    },
+    /// API-ключ из заголовка `Authorization: Bearer alf_...`
+    ApiToken(ApiToken),
+    /// Клиентский сертификат сервисного аккаунта, см. `tls.client_ca_path`
+    ClientCertificate,
+}
+impl AuthMethod {
+    /// Проверяет, что запросу разрешена указанная область действия
+    ///
+    /// Сессии пользователя и клиентскому сертификату разрешено все,
+    /// что разрешено роли; API-ключу - только выданные ему области.
+    pub fn require(&self, scope: ApiScope) -> AppResult<()> {
+        match self {
+            AuthMethod::Session { .. } | AuthMethod::ClientCertificate => Ok(()),
+            AuthMethod::ApiToken(token) if token.allows(scope) => Ok(()),
+            AuthMethod::ApiToken(_) => Err(AppError::AccessDenied),
+        }
+    }
+    /// Проверяет, что запрос выполнен в сессии пользователя, а не по API-ключу
+    ///
+    /// Используется для операций, которые нельзя делегировать ключам
+    /// и сертификатам: управление ключами, удаление аккаунта.
+    pub fn require_session(&self) -> AppResult<()> {
+        match self {
+            AuthMethod::Session { .. } => Ok(()),
+            AuthMethod::ApiToken(_) | AuthMethod::ClientCertificate => Err(AppError::AccessDenied),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct AppState {
     pub users_service: Arc<UsersService>,
+    pub api_tokens_service: Arc<ApiTokensService>,
+    pub oidc_service: Option<Arc<OidcService>>,
     pub jwt_settings: Arc<JWTSettings>,
+    pub jwt_keys: Arc<JwtKeys>,
+    pub rate_limits: Option<Arc<RateLimits>>,
+    pub metrics: Option<Arc<Metrics>>,
+    pub health: Arc<HealthService>,
+    pub log_filter: Option<LogFilter>,
+    pub bootstrap: Option<Arc<BootstrapService>>,
 }
 impl AppState {
-    pub fn new(users_service: Arc<UsersService>, jwt_settings: Arc<JWTSettings>) -> Self {
+    pub fn new(
+        users_service: Arc<UsersService>,
+        api_tokens_service: Arc<ApiTokensService>,
+        jwt_settings: Arc<JWTSettings>,
+        jwt_keys: Arc<JwtKeys>,
+    ) -> Self {
         Self {
             users_service,
+            api_tokens_service,
+            oidc_service: None,
             jwt_settings,
+            jwt_keys,
+            rate_limits: None,
+            metrics: None,
+            health: Arc::new(HealthService::default()),
+            log_filter: None,
+            bootstrap: None,
         }
     }
+    /// Включает вход через провайдера OpenID Connect
+    pub fn with_oidc(mut self, oidc_service: Arc<OidcService>) -> Self {
+        self.oidc_service = Some(oidc_service);
+        self
+    }
+    /// Включает ограничение частоты запросов
+    pub fn with_rate_limits(mut self, rate_limits: Arc<RateLimits>) -> Self {
+        self.rate_limits = Some(rate_limits);
+        self
+    }
+    /// Подключает проверки готовности
+    pub fn with_health(mut self, health: Arc<HealthService>) -> Self {
+        self.health = health;
+        self
+    }
+    /// Включает отдачу метрик на служебном порту
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+    /// Разрешает менять фильтр журнала через служебный порт
+    pub fn with_log_filter(mut self, log_filter: LogFilter) -> Self {
+        self.log_filter = Some(log_filter);
+        self
+    }
+    /// Разрешает первой регистрации получить роль владельца по токену настройки
+    pub fn with_bootstrap(mut self, bootstrap: Arc<BootstrapService>) -> Self {
+        self.bootstrap = Some(bootstrap);
+        self
+    }
 }
 async fn shutdown_signal() {
     tracing::info!("Shutdown signal handler installed");
@@ -86,9 +295,3 @@ async fn shutdown_signal() {
         _ = terminate => {},
     }
 }
-
-#[derive(Clone, Debug, Serialize)]
-pub struct ErrorResponse {
-    pub status: &'static str,
-    pub message: String,
-}
@@ -0,0 +1,116 @@
+//! Источники, которым разрешены запросы из браузера
+//!
+//! Один и тот же список используют CORS и проверка CSRF: страница,
+//! прошедшая предварительный запрос, должна и выполнять изменяющие запросы.
+//! Источники задаются точно (`https://app.example.com`) или шаблоном
+//! с поддоменами (`https://*.example.com`).
+
+use crate::settings::ServerSettings;
+
+/// Разрешенный источник запросов
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AllowedOrigin {
+    /// Источник, совпадающий полностью
+    Exact(String),
+    /// Любой поддомен: схема вида `https://` и домен с необязательным портом
+    Subdomains { scheme: String, domain: String },
+}
+impl AllowedOrigin {
+    fn parse(pattern: &str) -> Self {
+        let pattern = pattern.trim_end_matches('/').to_lowercase();
+        match pattern.split_once("://*.") {
+            Some((scheme, domain)) => AllowedOrigin::Subdomains {
+                scheme: format!("{scheme}://"),
+                domain: format!(".{domain}"),
+            },
+            None => AllowedOrigin::Exact(pattern),
+        }
+    }
+
+    fn matches(&self, origin: &str) -> bool {
+        match self {
+            AllowedOrigin::Exact(allowed) => allowed.eq_ignore_ascii_case(origin),
+            AllowedOrigin::Subdomains { scheme, domain } => {
+                let origin = origin.to_lowercase();
+                origin
+                    .strip_prefix(scheme.as_str())
+                    .and_then(|host| host.strip_suffix(domain.as_str()))
+                    .is_some_and(|subdomain| !subdomain.is_empty())
+            }
+        }
+    }
+}
+
+/// Список разрешенных источников
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllowedOrigins(Vec<AllowedOrigin>);
+
+impl AllowedOrigins {
+    /// Собирает список из адреса сервиса и `cors.allowed_origins`
+    ///
+    /// # Аргументы
+    ///
+    /// * `settings` - Настройки сервера
+    pub fn from_settings(settings: &ServerSettings) -> Self {
+        Self::new(
+            std::iter::once(settings.origin.as_str())
+                .chain(settings.cors.allowed_origins.iter().map(String::as_str)),
+        )
+    }
+
+    /// Собирает список из точных источников и шаблонов с поддоменами
+    pub fn new<'a>(patterns: impl IntoIterator<Item = &'a str>) -> Self {
+        Self(patterns.into_iter().map(AllowedOrigin::parse).collect())
+    }
+
+    /// Проверяет значение заголовка `Origin`
+    pub fn matches(&self, origin: &str) -> bool {
+        self.0.iter().any(|allowed| allowed.matches(origin))
+    }
+
+    /// Проверяет источник адреса из заголовка `Referer`
+    pub fn matches_referer(&self, referer: &str) -> bool {
+        let Some((scheme, rest)) = referer.split_once("://") else {
+            return false;
+        };
+        let authority = rest.find(['/', '?', '#']).map_or(rest, |end| &rest[..end]);
+        !authority.is_empty() && self.matches(&format!("{scheme}://{authority}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allowed_origin() {
+        let exact = AllowedOrigin::parse("https://App.example.com/");
+        assert!(exact.matches("https://app.example.com"));
+        assert!(!exact.matches("https://app.example.com:8443"));
+        assert!(!exact.matches("http://app.example.com"));
+
+        let wildcard = AllowedOrigin::parse("https://*.example.com");
+        assert!(wildcard.matches("https://app.example.com"));
+        assert!(wildcard.matches("https://a.b.example.com"));
+        assert!(!wildcard.matches("https://example.com"));
+        assert!(!wildcard.matches("https://evilexample.com"));
+        assert!(!wildcard.matches("https://app.example.com.evil.com"));
+        assert!(!wildcard.matches("https://app.example.com:8443"));
+        assert!(!wildcard.matches("http://app.example.com"));
+
+        let with_port = AllowedOrigin::parse("http://*.localhost:5173");
+        assert!(with_port.matches("http://app.localhost:5173"));
+        assert!(!with_port.matches("http://app.localhost"));
+    }
+
+    #[test]
+    fn test_referer() {
+        let origins = AllowedOrigins::new(["https://alfred.example.com", "https://*.example.org"]);
+        assert!(origins.matches_referer("https://alfred.example.com"));
+        assert!(origins.matches_referer("https://alfred.example.com/users?page=2"));
+        assert!(origins.matches_referer("https://app.example.org#top"));
+        assert!(!origins.matches_referer("https://alfred.example.com.evil.io/"));
+        assert!(!origins.matches_referer("https://alfred.example.com@evil.io/"));
+        assert!(!origins.matches_referer("alfred.example.com/users"));
+    }
+}
@@ -0,0 +1,321 @@
+//! Ограничение частоты запросов по алгоритму корзины токенов
+//!
+//! Для каждой группы маршрутов задается своя политика: сколько запросов
+//! клиент может выполнить за период и чем клиенты различаются - IP-адресом
+//! или пользователем. Корзины хранятся в `RateLimitStore`: в памяти
+//! или в PostgreSQL, если экземпляров сервера несколько.
+
+use std::{net::IpAddr, sync::Arc, time::Duration};
+
+use axum::http::{HeaderMap, HeaderName, HeaderValue, header};
+use ipnet::IpNet;
+
+use crate::{
+    models::User,
+    settings::{RateLimitKey, RateLimitSettings},
+    storage::{RateLimitDecision, RateLimitStore, TokenBucket},
+};
+
+/// Заголовок со списком адресов, через которые прошел запрос
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// Ограничители для всех групп маршрутов
+pub struct RateLimits {
+    settings: RateLimitSettings,
+    store: Arc<dyn RateLimitStore>,
+    trusted_proxies: Arc<Vec<IpNet>>,
+}
+
+impl RateLimits {
+    /// Создает ограничители по настройкам
+    ///
+    /// # Аргументы
+    ///
+    /// * `settings` - Политики групп и доверенные прокси
+    /// * `store` - Хранилище корзин токенов
+    pub fn new(settings: &RateLimitSettings, store: Arc<dyn RateLimitStore>) -> Self {
+        Self {
+            settings: settings.clone(),
+            store,
+            trusted_proxies: Arc::new(settings.trusted_proxies.clone()),
+        }
+    }
+
+    /// Возвращает ограничитель группы маршрутов
+    ///
+    /// # Возвращает
+    ///
+    /// * `Some(RateLimiter)` - Ограничитель с политикой группы
+    /// * `None` - Ограничение выключено или для группы нет политики
+    pub fn limiter(&self, group: &str) -> Option<Arc<RateLimiter>> {
+        if !self.settings.enabled {
+            return None;
+        }
+        let policy = self.settings.policy(group)?;
+        Some(Arc::new(RateLimiter {
+            group: group.to_string(),
+            bucket: TokenBucket::per_period(
+                policy.requests,
+                Duration::from_secs(policy.period_secs),
+            ),
+            key: policy.key,
+            store: self.store.clone(),
+            trusted_proxies: self.trusted_proxies.clone(),
+        }))
+    }
+
+    /// Хранилище корзин токенов
+    pub fn store(&self) -> Arc<dyn RateLimitStore> {
+        self.store.clone()
+    }
+
+    /// Время простоя, после которого корзину можно удалить:
+    /// за это время наполняется корзина любой политики
+    pub fn idle_timeout(&self) -> Duration {
+        [
+            crate::settings::RATE_LIMIT_AUTH_GROUP,
+            crate::settings::RATE_LIMIT_API_GROUP,
+        ]
+        .into_iter()
+        .filter_map(|group| self.settings.policy(group))
+        .chain(self.settings.policies.values().cloned())
+        .map(|policy| Duration::from_secs(policy.period_secs))
+        .max()
+        .unwrap_or_default()
+    }
+}
+
+/// Ограничитель одной группы маршрутов
+pub struct RateLimiter {
+    group: String,
+    bucket: TokenBucket,
+    key: RateLimitKey,
+    store: Arc<dyn RateLimitStore>,
+    trusted_proxies: Arc<Vec<IpNet>>,
+}
+
+impl RateLimiter {
+    /// Забирает токен для запроса
+    ///
+    /// Ошибка хранилища не должна делать сервис недоступным, поэтому
+    /// в этом случае запрос пропускается без ограничения.
+    ///
+    /// # Аргументы
+    ///
+    /// * `peer` - Адрес, с которого установлено соединение
+    /// * `headers` - Заголовки запроса
+    /// * `user` - Аутентифицированный пользователь, если есть
+    ///
+    /// # Возвращает
+    ///
+    /// * `Some(RateLimitDecision)` - Решение по запросу
+    /// * `None` - Хранилище недоступно
+    pub async fn check(
+        &self,
+        peer: Option<IpAddr>,
+        headers: &HeaderMap,
+        user: Option<&User>,
+    ) -> Option<RateLimitDecision> {
+        let client = match (self.key, user) {
+            (RateLimitKey::User, Some(user)) => format!("user:{}", user.user_id),
+            _ => match self.client_ip(peer, headers) {
+                Some(ip) => format!("ip:{ip}"),
+                None => "ip:unknown".to_string(),
+            },
+        };
+        let key = format!("{}:{client}", self.group);
+        match self.store.take_token(&key, self.bucket).await {
+            Ok(decision) => Some(decision),
+            Err(e) => {
+                tracing::error!("rate limit store failed: {e}");
+                None
+            }
+        }
+    }
+
+    /// Определяет адрес клиента
+    ///
+    /// `X-Forwarded-For` учитывается, только если соединение установлено
+    /// доверенным прокси. Адреса в нем просматриваются справа налево,
+    /// и клиентом считается первый адрес, не принадлежащий доверенным сетям:
+    /// левую часть заголовка может подделать сам клиент.
+    pub fn client_ip(&self, peer: Option<IpAddr>, headers: &HeaderMap) -> Option<IpAddr> {
+        let mut client = peer?;
+        if !self.is_trusted(client) {
+            return Some(client);
+        }
+        let forwarded = headers
+            .get_all(X_FORWARDED_FOR)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .collect::<Vec<_>>();
+        for hop in forwarded.into_iter().rev() {
+            let Ok(ip) = hop.trim().parse::<IpAddr>() else {
+                break;
+            };
+            client = ip;
+            if !self.is_trusted(ip) {
+                break;
+            }
+        }
+        Some(client)
+    }
+
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|net| net.contains(&ip))
+    }
+
+    /// Заголовки `RateLimit-*` для ответа
+    ///
+    /// Для отклоненного запроса добавляется `Retry-After`.
+    pub fn headers(&self, decision: &RateLimitDecision) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let mut insert = |name: &'static str, value: String| {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                headers.insert(HeaderName::from_static(name), value);
+            }
+        };
+        insert("ratelimit-limit", decision.limit.to_string());
+        insert("ratelimit-remaining", decision.remaining.to_string());
+        insert(
+            "ratelimit-reset",
+            decision.reset_after.as_secs_f64().ceil().to_string(),
+        );
+        insert(
+            "ratelimit-policy",
+            format!(
+                "{};w={}",
+                self.bucket.capacity,
+                self.bucket.window().as_secs_f64().ceil()
+            ),
+        );
+        if !decision.allowed {
+            let retry_after = decision.retry_after.as_secs_f64().ceil().max(1.0);
+            if let Ok(value) = HeaderValue::from_str(&retry_after.to_string()) {
+                headers.insert(header::RETRY_AFTER, value);
+            }
+        }
+        headers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use axum::{
+        Router,
+        body::Body,
+        extract::ConnectInfo,
+        http::{Request, StatusCode},
+        middleware,
+        routing::get,
+    };
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{
+        settings::{RATE_LIMIT_AUTH_GROUP, RateLimitPolicySettings},
+        storage::MemoryRateLimitStore,
+    };
+
+    fn limits(trusted_proxies: &[&str]) -> RateLimits {
+        let settings = RateLimitSettings {
+            trusted_proxies: trusted_proxies
+                .iter()
+                .map(|net| net.parse().unwrap())
+                .collect(),
+            policies: [(
+                RATE_LIMIT_AUTH_GROUP.to_string(),
+                RateLimitPolicySettings {
+                    requests: 2,
+                    period_secs: 60,
+                    key: RateLimitKey::Ip,
+                },
+            )]
+            .into(),
+            ..Default::default()
+        };
+        RateLimits::new(&settings, Arc::new(MemoryRateLimitStore::default()))
+    }
+
+    fn forwarded(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(X_FORWARDED_FOR, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let limiter = limits(&["10.0.0.0/8"])
+            .limiter(RATE_LIMIT_AUTH_GROUP)
+            .unwrap();
+        let proxy = Some("10.0.0.5".parse().unwrap());
+        let client: IpAddr = "203.0.113.7".parse().unwrap();
+
+        // Заголовок от недоверенного адреса игнорируется
+        assert_eq!(
+            limiter.client_ip(Some(client), &forwarded("198.51.100.1")),
+            Some(client)
+        );
+        // Подделанная левая часть заголовка не учитывается
+        assert_eq!(
+            limiter.client_ip(proxy, &forwarded("198.51.100.1, 203.0.113.7, 10.0.0.9")),
+            Some(client)
+        );
+        // Без заголовка клиентом считается сам прокси
+        assert_eq!(limiter.client_ip(proxy, &HeaderMap::new()), proxy);
+        assert_eq!(limiter.client_ip(None, &HeaderMap::new()), None);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_middleware() {
+        let limiter = limits(&[]).limiter(RATE_LIMIT_AUTH_GROUP).unwrap();
+        let app = Router::new()
+            .route("/signin", get(|| async { "ok" }))
+            .layer(middleware::from_fn_with_state(
+                limiter,
+                crate::server::middleware::rate_limit,
+            ));
+        let request = |ip: &str| {
+            let mut request = Request::get("/signin").body(Body::empty()).unwrap();
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(SocketAddr::new(ip.parse().unwrap(), 4000)));
+            request
+        };
+
+        let response = app.clone().oneshot(request("192.0.2.1")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["ratelimit-limit"], "2");
+        assert_eq!(response.headers()["ratelimit-remaining"], "1");
+        assert_eq!(response.headers()["ratelimit-policy"], "2;w=60");
+        app.clone().oneshot(request("192.0.2.1")).await.unwrap();
+
+        let response = app.clone().oneshot(request("192.0.2.1")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "30");
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["status"], "fail");
+
+        // Другой клиент ограничен отдельно
+        let response = app.oneshot(request("192.0.2.2")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_disabled_rate_limits() {
+        let settings = RateLimitSettings {
+            enabled: false,
+            ..Default::default()
+        };
+        let limits = RateLimits::new(&settings, Arc::new(MemoryRateLimitStore::default()));
+        assert!(limits.limiter(RATE_LIMIT_AUTH_GROUP).is_none());
+        assert!(limits.limiter("unknown").is_none());
+    }
+}
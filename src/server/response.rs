@@ -0,0 +1,182 @@
+//! Конверт успешных ответов API
+//!
+//! Все обработчики отвечают телом `{"status": "success", "data": ..., "meta": ...}`;
+//! ошибки описаны в `crate::error::ApiError`.
+use axum::{
+    http::{HeaderValue, Uri, header},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::services::UsersListResponse;
+
+/// Успешный ответ API
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ApiResponse<T> {
+    /// Всегда `success`
+    status: &'static str,
+    /// Данные ответа
+    data: T,
+    /// Сведения о странице списка
+    #[serde(skip_serializing_if = "Option::is_none")]
+    meta: Option<PageMeta>,
+}
+
+impl<T> ApiResponse<T> {
+    /// Создает ответ с данными
+    pub fn new(data: T) -> Self {
+        Self {
+            status: "success",
+            data,
+            meta: None,
+        }
+    }
+}
+
+impl<T: Serialize> IntoResponse for ApiResponse<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self).into_response()
+    }
+}
+
+/// Сведения о странице списка
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+pub struct PageMeta {
+    /// Номер страницы, начиная с 1
+    pub page: u32,
+    /// Размер страницы
+    pub per_page: u32,
+    /// Всего записей
+    pub total: u32,
+    /// Всего страниц
+    pub total_pages: u32,
+}
+
+impl PageMeta {
+    /// Значение заголовка `Link` по RFC 8288 со ссылками на первую,
+    /// предыдущую, следующую и последнюю страницы
+    ///
+    /// Ссылки сохраняют остальные параметры запроса и заменяют только `page`.
+    ///
+    /// # Аргументы
+    ///
+    /// * `uri` - Исходный адрес запроса
+    pub fn link_header(&self, uri: &Uri) -> Option<HeaderValue> {
+        let last = self.total_pages.max(1);
+        let mut links = vec![(1, "first")];
+        if self.page > 1 {
+            links.push((self.page.min(last + 1) - 1, "prev"));
+        }
+        if self.page < last {
+            links.push((self.page + 1, "next"));
+        }
+        links.push((last, "last"));
+        let value = links
+            .into_iter()
+            .map(|(page, rel)| format!("<{}>; rel=\"{rel}\"", page_uri(uri, page)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        HeaderValue::from_str(&value).ok()
+    }
+}
+
+/// Адрес той же выборки с другим номером страницы
+fn page_uri(uri: &Uri, page: u32) -> String {
+    let mut query = uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| !pair.is_empty() && pair.split('=').next() != Some("page"))
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    query.push(format!("page={page}"));
+    format!("{}?{}", uri.path(), query.join("&"))
+}
+
+impl From<UsersListResponse> for ApiResponse<Vec<crate::models::User>> {
+    fn from(list: UsersListResponse) -> Self {
+        let meta = PageMeta {
+            page: list.current_filter.page(),
+            per_page: list.current_filter.per_page(),
+            total: list.total,
+            total_pages: list.total_pages(),
+        };
+        Self {
+            status: "success",
+            data: list.users,
+            meta: Some(meta),
+        }
+    }
+}
+
+/// Страница списка с заголовком `Link`
+pub struct Paginated<T> {
+    response: ApiResponse<T>,
+    uri: Uri,
+}
+
+impl<T> Paginated<T> {
+    /// Создает ответ со ссылками на соседние страницы
+    ///
+    /// # Аргументы
+    ///
+    /// * `response` - Ответ со сведениями о странице
+    /// * `uri` - Исходный адрес запроса, от которого строятся ссылки
+    pub fn new(response: ApiResponse<T>, uri: Uri) -> Self {
+        Self { response, uri }
+    }
+}
+
+impl<T: Serialize> IntoResponse for Paginated<T> {
+    fn into_response(self) -> Response {
+        let link = self
+            .response
+            .meta
+            .and_then(|meta| meta.link_header(&self.uri));
+        let mut response = self.response.into_response();
+        if let Some(link) = link {
+            response.headers_mut().insert(header::LINK, link);
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(page: u32, total_pages: u32) -> PageMeta {
+        PageMeta {
+            page,
+            per_page: 10,
+            total: total_pages * 10,
+            total_pages,
+        }
+    }
+
+    #[test]
+    fn test_link_header() {
+        let uri: Uri = "/api/v1/users?q=john&page=2&per_page=10".parse().unwrap();
+        assert_eq!(
+            meta(2, 3).link_header(&uri).unwrap(),
+            "</api/v1/users?q=john&per_page=10&page=1>; rel=\"first\", \
+             </api/v1/users?q=john&per_page=10&page=1>; rel=\"prev\", \
+             </api/v1/users?q=john&per_page=10&page=3>; rel=\"next\", \
+             </api/v1/users?q=john&per_page=10&page=3>; rel=\"last\""
+        );
+
+        // На первой и последней странице нет соседей с одной стороны
+        let uri: Uri = "/api/v1/users".parse().unwrap();
+        assert_eq!(
+            meta(1, 1).link_header(&uri).unwrap(),
+            "</api/v1/users?page=1>; rel=\"first\", </api/v1/users?page=1>; rel=\"last\""
+        );
+        // Страница за концом списка ссылается назад на последнюю
+        assert_eq!(
+            meta(5, 2).link_header(&uri).unwrap(),
+            "</api/v1/users?page=1>; rel=\"first\", </api/v1/users?page=2>; rel=\"prev\", \
+             </api/v1/users?page=2>; rel=\"last\""
+        );
+    }
+}
@@ -0,0 +1,61 @@
+//! Маршруты служебного порта
+//!
+//! Служебный сервер слушает отдельный порт на `admin_host`, по умолчанию
+//! только на `127.0.0.1`, поэтому его маршруты не требуют аутентификации
+//! и не входят в описание API.
+use std::sync::Arc;
+
+use axum::{Router, extract::State, http::header, response::IntoResponse, routing::get};
+use serde::{Deserialize, Serialize};
+
+use crate::{AppError, AppResult, AppState, server::extract::Json};
+
+/// Тип содержимого текстового формата Prometheus
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+pub(super) fn routes(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/metrics", get(metrics_handler))
+        .route(
+            "/log-filter",
+            get(log_filter_handler).put(set_log_filter_handler),
+        )
+        .with_state(state)
+}
+
+/// Фильтр журнала в формате `RUST_LOG`
+#[derive(Debug, Serialize, Deserialize)]
+struct LogFilterBody {
+    filter: String,
+}
+
+/// Метрики в текстовом формате Prometheus
+async fn metrics_handler(State(state): State<Arc<AppState>>) -> AppResult<impl IntoResponse> {
+    let metrics = state.metrics.as_ref().ok_or(AppError::EntryNotFound)?;
+    Ok((
+        [(header::CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)],
+        metrics.render(&state.users_service).await,
+    ))
+}
+
+/// Текущий фильтр журнала
+async fn log_filter_handler(State(state): State<Arc<AppState>>) -> AppResult<Json<LogFilterBody>> {
+    let log_filter = state.log_filter.as_ref().ok_or(AppError::EntryNotFound)?;
+    Ok(Json(LogFilterBody {
+        filter: log_filter.current()?,
+    }))
+}
+
+/// Заменяет фильтр журнала без перезапуска
+///
+/// Изменение действует до перезапуска; фильтр из настроек и `RUST_LOG`
+/// не меняется.
+async fn set_log_filter_handler(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<LogFilterBody>,
+) -> AppResult<Json<LogFilterBody>> {
+    let log_filter = state.log_filter.as_ref().ok_or(AppError::EntryNotFound)?;
+    Ok(Json(LogFilterBody {
+        filter: log_filter.set(&body.filter)?,
+    }))
+}
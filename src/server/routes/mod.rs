@@ -1,26 +1,40 @@
+mod admin;
+mod oidc;
+mod openapi;
 mod public;
 mod users;
-use std::sync::Arc;
+mod well_known;
+use std::{any::Any, sync::Arc};
 
-use axum::{Router, middleware};
-use http::{Method, header};
+use axum::{
+    Router,
+    extract::DefaultBodyLimit,
+    middleware,
+    response::{IntoResponse, Response},
+};
 use tower::ServiceBuilder;
 use tower_http::{
     catch_panic::CatchPanicLayer,
     compression::CompressionLayer,
-    cors::CorsLayer,
     request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
-    timeout::TimeoutLayer,
     trace::TraceLayer,
 };
 use tracing::{error, info_span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use utoipa::OpenApi;
+use utoipa_axum::router::OpenApiRouter;
 
-use crate::AppState;
+use crate::{
+    AppError, AppState,
+    server::{cors, csrf::CsrfGuard, origins::AllowedOrigins},
+    settings::{RATE_LIMIT_API_GROUP, RATE_LIMIT_AUTH_GROUP, ServerSettings},
+    telemetry,
+};
 
 const REQUEST_ID_HEADER: &str = "alfred-request-id";
 
-pub(super) fn init(state: Arc<AppState>, origin: &str) -> Router {
-    let catch_panic_layer = CatchPanicLayer::new();
+pub(super) fn init(state: Arc<AppState>, settings: &ServerSettings) -> Router {
+    let catch_panic_layer = CatchPanicLayer::custom(panic_handler);
 
     let x_request_id = axum::http::HeaderName::from_static(REQUEST_ID_HEADER);
 
@@ -33,60 +47,764 @@ pub(super) fn init(state: Arc<AppState>, origin: &str) -> Router {
             TraceLayer::new_for_http().make_span_with(|request: &axum::http::Request<_>| {
                 let request_id = request.headers().get(REQUEST_ID_HEADER);
 
-                match request_id {
+                let span = match request_id {
                     Some(request_id) => info_span!(
                         "http_request",
+                        otel.kind = "server",
                         request_id = ?request_id,
                     ),
                     None => {
                         error!("could not extract request_id");
-                        info_span!("http_request")
+                        info_span!("http_request", otel.kind = "server")
                     }
-                }
+                };
+                // Продолжаем трассировку вызывающего сервиса из `traceparent`;
+                // без экспорта трассировок привязывать контекст не к чему
+                let _ = span.set_parent(telemetry::extract_context(request.headers()));
+                span
             }),
         )
-        .layer(PropagateRequestIdLayer::new(x_request_id));
+        .layer(PropagateRequestIdLayer::new(x_request_id.clone()));
 
-    let timeout_layer = TimeoutLayer::with_status_code(
-        axum::http::StatusCode::REQUEST_TIMEOUT,
-        std::time::Duration::from_secs(10),
+    let timeout_layer = middleware::from_fn_with_state(
+        Arc::new(settings.limits.clone()),
+        super::middleware::timeout,
     );
 
-    let cors_layer = CorsLayer::new()
-        .allow_origin([origin.parse().unwrap()])
-        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
-        .allow_headers([header::ACCEPT, header::AUTHORIZATION])
-        .max_age(std::time::Duration::from_secs(60 * 60))
-        .allow_credentials(true);
+    let security_headers_layer = middleware::from_fn_with_state(
+        Arc::new(settings.security_headers.header_map()),
+        super::middleware::security_headers,
+    );
+
+    let origins = AllowedOrigins::from_settings(settings);
+    let cors_layer = cors::layer(&settings.cors, origins.clone());
 
     let compression_layer = CompressionLayer::new();
 
-    let users_routes = Router::new().nest("/users", users::routes(state.clone()));
+    let users_routes = OpenApiRouter::new().nest("/users", users::routes(state.clone()));
 
-    let protected_routes = Router::new()
-        .merge(users_routes)
-        .layer(middleware::from_fn_with_state(
-            state.clone(),
-            super::middleware::auth,
-        ));
+    let csrf_guard = Arc::new(CsrfGuard::new(origins, &state.jwt_settings.secret));
+    let protected_routes =
+        OpenApiRouter::new()
+            .merge(users_routes)
+            .layer(middleware::from_fn_with_state(
+                csrf_guard,
+                super::middleware::csrf,
+            ));
+    let protected_routes = rate_limited(protected_routes, &state, RATE_LIMIT_API_GROUP).layer(
+        middleware::from_fn_with_state(state.clone(), super::middleware::auth),
+    );
 
-    let app = Router::new()
+    let mut app = OpenApiRouter::new()
         .merge(public::routes(state.clone()))
+        .merge(rate_limited(
+            public::auth_routes(state.clone()),
+            &state,
+            RATE_LIMIT_AUTH_GROUP,
+        ))
         .merge(protected_routes);
-    Router::new()
+    if state.oidc_service.is_some() {
+        app = app.nest(
+            "/auth/oidc",
+            rate_limited(oidc::routes(state.clone()), &state, RATE_LIMIT_AUTH_GROUP),
+        );
+    }
+    let (router, api) = OpenApiRouter::with_openapi(openapi::ApiDoc::openapi())
+        .nest("/.well-known", well_known::routes(state.clone()))
         .nest("/api/v1", app)
+        .split_for_parts();
+    // Fallback добавляется до слоев, иначе они его не оборачивают.
+    // Время и размер тела ограничиваются внутри слоя ошибок, чтобы ответы
+    // 408 и 413 тоже получили тело ошибки и идентификатор запроса.
+    router
+        .merge(openapi::routes(api))
+        .fallback(fallback_handler)
+        .layer(DefaultBodyLimit::max(settings.limits.max_body_bytes))
+        .layer(timeout_layer)
         .layer(catch_panic_layer)
+        .layer(middleware::from_fn_with_state(
+            x_request_id.clone(),
+            super::middleware::error_envelope,
+        ))
+        .layer(middleware::from_fn(super::middleware::locale))
+        .layer(security_headers_layer)
         .layer(request_id_middleware)
-        .layer(timeout_layer)
         .layer(cors_layer)
         .layer(compression_layer)
-        .fallback(fallback_handler)
+        .layer(middleware::from_fn(super::middleware::track_http))
+}
+
+/// Маршруты служебного порта: метрики
+pub(super) fn admin(state: Arc<AppState>) -> Router {
+    admin::routes(state).layer(middleware::from_fn(super::middleware::track_http))
 }
 
-// Fallback handler: Returns 404 status with informative message
-async fn fallback_handler(uri: axum::http::Uri) -> (axum::http::StatusCode, String) {
-    (
-        axum::http::StatusCode::NOT_FOUND,
-        format!("No route for {uri}"),
-    )
+/// Ограничивает частоту запросов к маршрутам по политике группы,
+/// если ограничение включено
+fn rate_limited(router: OpenApiRouter, state: &AppState, group: &str) -> OpenApiRouter {
+    match state
+        .rate_limits
+        .as_ref()
+        .and_then(|rate_limits| rate_limits.limiter(group))
+    {
+        Some(limiter) => router.layer(middleware::from_fn_with_state(
+            limiter,
+            super::middleware::rate_limit,
+        )),
+        None => router,
+    }
+}
+
+/// Отвечает 404 на запросы к несуществующим маршрутам
+async fn fallback_handler(uri: axum::http::Uri) -> AppError {
+    AppError::RouteNotFound(uri.to_string())
+}
+
+/// Превращает панику обработчика во внутреннюю ошибку
+///
+/// Текст паники пишется в журнал и не попадает в ответ.
+fn panic_handler(panic: Box<dyn Any + Send + 'static>) -> Response {
+    let message = panic
+        .downcast_ref::<String>()
+        .map(String::as_str)
+        .or_else(|| panic.downcast_ref::<&str>().copied())
+        .unwrap_or("unknown panic");
+    AppError::Custom(format!("handler panicked: {message}")).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+    use sqlx::PgPool;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{
+        server::{
+            JwtKeys,
+            csrf::{CSRF_COOKIE, CSRF_HEADER},
+        },
+        services::{ApiTokensService, UsersService},
+        settings::JWTSettings,
+        storage::PgStorage,
+    };
+
+    /// Состояние приложения поверх тестовой базы, без OIDC и ограничений частоты
+    pub(super) fn test_state(pool: PgPool) -> AppState {
+        let storage = Arc::new(PgStorage::with_pool(pool));
+        let jwt_settings = JWTSettings {
+            secret: "secret".to_string(),
+            expires_in: 60,
+            maxage: 1,
+            issuer: "alfred".to_string(),
+            audience: vec!["alfred".to_string()],
+            keys: vec![],
+        };
+        let jwt_keys = JwtKeys::from_settings(&jwt_settings).unwrap();
+        AppState::new(
+            Arc::new(UsersService::new(storage.clone())),
+            Arc::new(ApiTokensService::new(storage.clone(), storage)),
+            Arc::new(jwt_settings),
+            Arc::new(jwt_keys),
+        )
+    }
+
+    async fn json(response: Response) -> serde_json::Value {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[sqlx::test]
+    async fn test_unknown_route(pool: PgPool) {
+        let app = init(Arc::new(test_state(pool)), &ServerSettings::default());
+        let request = Request::get("/api/v1/nothing").body(Body::empty()).unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::NOT_FOUND);
+        assert_eq!(
+            response.headers()[http::header::CONTENT_TYPE],
+            "application/json"
+        );
+        let request_id = response.headers()[REQUEST_ID_HEADER].clone();
+        let body = json(response).await;
+        assert_eq!(body["code"], "route_not_found");
+        assert_eq!(body["request_id"], request_id.to_str().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_panic_is_redacted() {
+        let response = panic_handler(Box::new("secret at /etc/alfred"));
+        assert_eq!(
+            response.status(),
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR
+        );
+        let body = json(response).await;
+        assert_eq!(body["code"], "internal_error");
+        assert!(!body.to_string().contains("secret"));
+    }
+
+    #[sqlx::test]
+    async fn test_health_probes(pool: PgPool) {
+        use crate::services::{DatabaseCheck, HealthService, MigrationsCheck};
+
+        let storage = PgStorage::with_pool(pool.clone());
+        let health = Arc::new(
+            HealthService::default()
+                .with_check(DatabaseCheck(storage.clone()))
+                .with_check(MigrationsCheck(storage)),
+        );
+        let app = init(
+            Arc::new(test_state(pool).with_health(health.clone())),
+            &ServerSettings::default(),
+        );
+        let get = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+
+        let response = app
+            .clone()
+            .oneshot(get("/api/v1/health/live"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let response = app
+            .clone()
+            .oneshot(get("/api/v1/health/ready"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let body = json(response).await;
+        assert_eq!(body["status"], "ready");
+        assert_eq!(body["checks"]["database"]["status"], "up");
+        assert_eq!(body["checks"]["migrations"]["status"], "up");
+
+        // Во время завершения отчет не заменяется телом ошибки
+        health.begin_shutdown();
+        let response = app
+            .clone()
+            .oneshot(get("/api/v1/health/ready"))
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status(),
+            axum::http::StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(json(response).await["status"], "shutting_down");
+        let response = app.oneshot(get("/api/v1/health/live")).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+    }
+
+    #[sqlx::test]
+    async fn test_metrics(pool: PgPool) {
+        let metrics = crate::metrics::Metrics::install()
+            .unwrap()
+            .with_pool(pool.clone());
+        let state = Arc::new(test_state(pool).with_metrics(Arc::new(metrics)));
+        let app = init(state.clone(), &ServerSettings::default());
+        for uri in ["/api/v1/health", "/api/v1/nothing/42"] {
+            let request = Request::get(uri).body(Body::empty()).unwrap();
+            app.clone().oneshot(request).await.unwrap();
+        }
+
+        let request = Request::get("/metrics").body(Body::empty()).unwrap();
+        let response = admin(state).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        assert!(text.contains(r#"route="/api/v1/health",status="200""#));
+        assert!(text.contains(r#"route="unmatched",status="404""#));
+        assert!(!text.contains("/api/v1/nothing/42"));
+        assert!(text.contains(r#"alfred_users{role="guest"} 0"#));
+        assert!(text.contains("alfred_db_pool_connections"));
+    }
+
+    #[sqlx::test]
+    async fn test_locale_negotiation(pool: PgPool) {
+        let state = Arc::new(test_state(pool));
+        let app = init(state.clone(), &ServerSettings::default());
+        let request = |language: &str| {
+            Request::get("/api/v1/nothing")
+                .header(http::header::ACCEPT_LANGUAGE, language)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(request("en-US,ru;q=0.5"))
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[http::header::CONTENT_LANGUAGE], "en");
+        assert_eq!(
+            json(response).await["message"],
+            "No route for /api/v1/nothing"
+        );
+        let response = app.clone().oneshot(request("de")).await.unwrap();
+        assert_eq!(response.headers()[http::header::CONTENT_LANGUAGE], "ru");
+        assert_eq!(
+            json(response).await["message"],
+            "Маршрут /api/v1/nothing не найден"
+        );
+
+        // Язык из настроек пользователя важнее заголовка
+        let user = state
+            .users_service
+            .signup("guest@example.com", "Correct-Horse-Battery-9", None)
+            .await
+            .unwrap();
+        let mut update = crate::models::UserToUpdate::from(user.clone());
+        update.info.locale = Some(crate::i18n::Locale::En);
+        state
+            .users_service
+            .update(&user, &user.user_id.to_string(), update)
+            .await
+            .unwrap();
+        let token = state.jwt_keys.sign(user.user_id).unwrap();
+        let response = app
+            .oneshot(
+                Request::get("/api/v1/users/me")
+                    .header(http::header::AUTHORIZATION, format!("Bearer {token}"))
+                    .header(http::header::ACCEPT_LANGUAGE, "ru")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[http::header::CONTENT_LANGUAGE], "en");
+        let body = json(response).await;
+        assert_eq!(body["data"]["role"], "guest");
+        assert_eq!(body["data"]["role_label"], "Guest");
+        assert_eq!(body["data"]["info"]["locale"], "en");
+    }
+
+    fn post_json(uri: &str, body: serde_json::Value) -> Request<Body> {
+        Request::post(uri)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn test_http_settings(pool: PgPool) {
+        let mut settings = ServerSettings::default();
+        settings.cors.allowed_origins = vec!["https://*.example.com".into()];
+        settings.limits.max_body_bytes = 4096;
+        let state = Arc::new(test_state(pool));
+        let app = init(state.clone(), &settings);
+        let preflight = |origin: &str| {
+            Request::options("/api/v1/signin")
+                .header(http::header::ORIGIN, origin)
+                .header(http::header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+                .header(http::header::ACCESS_CONTROL_REQUEST_HEADERS, "content-type")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(preflight("https://app.example.com"))
+            .await
+            .unwrap();
+        let headers = response.headers();
+        assert_eq!(
+            headers[http::header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://app.example.com"
+        );
+        assert!(
+            headers[http::header::ACCESS_CONTROL_ALLOW_HEADERS]
+                .to_str()
+                .unwrap()
+                .contains("content-type")
+        );
+        let response = app
+            .clone()
+            .oneshot(preflight("https://example.com.evil.org"))
+            .await
+            .unwrap();
+        assert!(
+            !response
+                .headers()
+                .contains_key(http::header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        );
+
+        let response = app
+            .clone()
+            .oneshot(post_json(
+                "/api/v1/signin",
+                serde_json::json!({ "email": "a".repeat(4096), "password": "" }),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::PAYLOAD_TOO_LARGE);
+        let headers = response.headers();
+        assert_eq!(headers[http::header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert_eq!(headers[http::header::X_FRAME_OPTIONS], "DENY");
+        assert_eq!(headers[http::header::REFERRER_POLICY], "no-referrer");
+        assert_eq!(
+            headers[http::header::CONTENT_SECURITY_POLICY],
+            "default-src 'none'; frame-ancestors 'none'"
+        );
+        assert_eq!(json(response).await["code"], "payload_too_large");
+
+        // Источник, разрешенный для CORS, проходит и проверку CSRF
+        let user = state
+            .users_service
+            .signup("guest@example.com", "Correct-Horse-Battery-9", None)
+            .await
+            .unwrap();
+        let session = state.jwt_keys.sign(user.user_id).unwrap();
+        let csrf = crate::server::csrf::issue_token(&state.jwt_settings.secret, &session);
+        let update = |origin: &str| {
+            Request::put(format!("/api/v1/users/{}", user.user_id))
+                .header(http::header::ORIGIN, origin)
+                .header(http::header::CONTENT_TYPE, "application/json")
+                .header(
+                    http::header::COOKIE,
+                    format!("{}={session}; {}={csrf}", crate::server::TOKEN, CSRF_COOKIE),
+                )
+                .header(CSRF_HEADER, &csrf)
+                .body(Body::from(
+                    serde_json::to_string(&crate::models::UserToUpdate::from(user.clone()))
+                        .unwrap(),
+                ))
+                .unwrap()
+        };
+        let response = app
+            .clone()
+            .oneshot(update("https://app.example.com"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let response = app
+            .clone()
+            .oneshot(update("https://example.com.evil.org"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::FORBIDDEN);
+
+        // Страница документации получает собственную политику
+        let request = Request::get("/api/v1/docs").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert!(
+            response.headers()[http::header::CONTENT_SECURITY_POLICY]
+                .to_str()
+                .unwrap()
+                .contains("https://cdn.jsdelivr.net")
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_client_certificate_auth(pool: PgPool) {
+        use crate::{models::ServiceAccountData, server::tls::ClientCertificate};
+
+        let state = Arc::new(test_state(pool));
+        let app = init(state.clone(), &ServerSettings::default());
+        let account = state
+            .users_service
+            .storage
+            .create_service_account(ServiceAccountData {
+                name: "billing".to_string(),
+                role: crate::models::UserRole::Employee,
+            })
+            .await
+            .unwrap();
+        let request = |method: http::Method, path: &str, name: Option<&str>| {
+            let mut request = Request::builder()
+                .method(method)
+                .uri(path)
+                .body(Body::empty())
+                .unwrap();
+            if let Some(name) = name {
+                request.extensions_mut().insert(ClientCertificate {
+                    name: name.to_string(),
+                });
+            }
+            request
+        };
+
+        let response = app
+            .clone()
+            .oneshot(request(
+                http::Method::GET,
+                "/api/v1/users/me",
+                Some("billing"),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        assert_eq!(json(response).await["data"]["email"], account.email);
+
+        // Действия, доступные только в сессии, сертификату запрещены
+        let response = app
+            .clone()
+            .oneshot(request(
+                http::Method::POST,
+                "/api/v1/users/me/restore",
+                Some("billing"),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::FORBIDDEN);
+
+        for name in [None, Some("unknown")] {
+            let response = app
+                .clone()
+                .oneshot(request(http::Method::GET, "/api/v1/users/me", name))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), axum::http::StatusCode::UNAUTHORIZED);
+        }
+
+        // Запрос из браузера сертификатом не аутентифицируется
+        let mut from_browser = request(http::Method::GET, "/api/v1/users/me", Some("billing"));
+        from_browser.headers_mut().insert(
+            http::header::ORIGIN,
+            http::HeaderValue::from_static("https://evil.example.com"),
+        );
+        let response = app.clone().oneshot(from_browser).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::UNAUTHORIZED);
+    }
+
+    #[sqlx::test]
+    async fn test_signin_does_not_reveal_accounts(pool: PgPool) {
+        let state = Arc::new(test_state(pool));
+        let app = init(state.clone(), &ServerSettings::default());
+        state
+            .users_service
+            .signup("guest@example.com", "Correct-Horse-Battery-9", None)
+            .await
+            .unwrap();
+
+        // Неизвестный email и неверный пароль получают одинаковый ответ
+        for email in ["guest@example.com", "nobody@example.com"] {
+            let response = app
+                .clone()
+                .oneshot(post_json(
+                    "/api/v1/signin",
+                    serde_json::json!({ "email": email, "password": "Wrong-Horse-Battery-9" }),
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), axum::http::StatusCode::UNAUTHORIZED);
+            assert_eq!(json(response).await["code"], "invalid_credentials");
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_self_update_keeps_role(pool: PgPool) {
+        use crate::models::{ApiScope, NewApiToken, UserRole, UserToUpdate};
+
+        let state = Arc::new(test_state(pool));
+        let app = init(state.clone(), &ServerSettings::default());
+        let user = state
+            .users_service
+            .signup("guest@example.com", "Correct-Horse-Battery-9", None)
+            .await
+            .unwrap();
+        let issued = state
+            .api_tokens_service
+            .issue(
+                &user,
+                &user,
+                NewApiToken {
+                    name: "profile".to_string(),
+                    scopes: vec![ApiScope::ProfileWrite],
+                    expires_at: None,
+                },
+            )
+            .await
+            .unwrap();
+        let update = |data: &UserToUpdate| {
+            Request::put(format!("/api/v1/users/{}", user.user_id))
+                .header(http::header::CONTENT_TYPE, "application/json")
+                .header(
+                    http::header::AUTHORIZATION,
+                    format!("Bearer {}", issued.token),
+                )
+                .body(Body::from(serde_json::to_string(data).unwrap()))
+                .unwrap()
+        };
+
+        let promote = UserToUpdate {
+            role: UserRole::Owner,
+            ..UserToUpdate::from(user.clone())
+        };
+        let response = app.clone().oneshot(update(&promote)).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::FORBIDDEN);
+        let stored = state
+            .users_service
+            .get_by_id(&user.user_id.to_string())
+            .await
+            .unwrap();
+        assert_eq!(stored.role, user.role);
+
+        let mut rename = UserToUpdate::from(user.clone());
+        rename.info.first_name = Some("Guest".to_string());
+        let response = app.oneshot(update(&rename)).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        assert_eq!(json(response).await["data"]["role"], user.role.to_string());
+    }
+
+    #[sqlx::test]
+    async fn test_sso_user_schedules_deletion(pool: PgPool) {
+        use crate::models::{ExternalIdentity, UserRole};
+
+        let state = Arc::new(test_state(pool));
+        let app = init(state.clone(), &ServerSettings::default());
+        let identity = ExternalIdentity {
+            issuer: "https://idp.example.com".to_string(),
+            subject: "sso-user".to_string(),
+            email: "sso@example.com".to_string(),
+            email_verified: true,
+            given_name: None,
+            family_name: None,
+            groups: vec![],
+        };
+        let user = state
+            .users_service
+            .signin_external(&identity, Some(UserRole::Guest))
+            .await
+            .unwrap();
+        let token = state.jwt_keys.sign(user.user_id).unwrap();
+
+        let response = app
+            .oneshot(
+                Request::delete("/api/v1/users/me")
+                    .header(http::header::CONTENT_TYPE, "application/json")
+                    .header(http::header::AUTHORIZATION, format!("Bearer {token}"))
+                    .body(Body::from("{}"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        assert!(!json(response).await["data"]["deletion_scheduled_at"].is_null());
+    }
+
+    #[sqlx::test]
+    async fn test_signup_with_setup_token(pool: PgPool) {
+        use crate::services::{BootstrapOutcome, BootstrapService};
+
+        let state = test_state(pool);
+        let bootstrap = Arc::new(BootstrapService::new(state.users_service.clone()));
+        let BootstrapOutcome::SetupToken(token) = bootstrap.run(&Default::default()).await.unwrap()
+        else {
+            panic!("setup token should be issued");
+        };
+        let app = init(
+            Arc::new(state.with_bootstrap(bootstrap)),
+            &ServerSettings::default(),
+        );
+        let signup = |email: &str, token: &str| {
+            post_json(
+                "/api/v1/signup",
+                serde_json::json!({
+                    "email": email,
+                    "password": "Correct-Horse-Battery-9",
+                    "confirm_password": "Correct-Horse-Battery-9",
+                    "setup_token": token,
+                }),
+            )
+        };
+
+        let response = app
+            .clone()
+            .oneshot(signup("owner@example.com", "wrong"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::FORBIDDEN);
+        let response = app
+            .clone()
+            .oneshot(signup("owner@example.com", &token))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        assert_eq!(json(response).await["data"]["user"]["role"], "owner");
+        let response = app
+            .oneshot(signup("second@example.com", &token))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::FORBIDDEN);
+    }
+
+    #[sqlx::test]
+    async fn test_response_shapes(pool: PgPool) {
+        let state = Arc::new(test_state(pool));
+        let app = init(state.clone(), &ServerSettings::default());
+
+        let response = app
+            .clone()
+            .oneshot(post_json(
+                "/api/v1/signup",
+                serde_json::json!({
+                    "email": "guest@example.com",
+                    "password": "Correct-Horse-Battery-9",
+                    "confirm_password": "Correct-Horse-Battery-9",
+                }),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            response.headers()[http::header::CONTENT_TYPE],
+            "application/json"
+        );
+        assert_eq!(
+            response
+                .headers()
+                .get_all(http::header::SET_COOKIE)
+                .iter()
+                .count(),
+            2
+        );
+        insta::assert_json_snapshot!("signup", json(response).await, {
+            ".data.token" => "[token]",
+            ".data.csrf_token" => "[csrf token]",
+            ".data.user.user_id" => "[uuid]",
+            ".data.user.created" => "[timestamp]",
+            ".data.user.updated" => "[timestamp]",
+        });
+
+        let admin = state
+            .users_service
+            .signup(
+                "admin@example.com",
+                "Correct-Horse-Battery-9",
+                Some("Администратор"),
+            )
+            .await
+            .unwrap();
+        let token = state.jwt_keys.sign(admin.user_id).unwrap();
+        let response = app
+            .clone()
+            .oneshot(
+                Request::get("/api/v1/users?per_page=1&page=2")
+                    .header(http::header::AUTHORIZATION, format!("Bearer {token}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            response.headers()[http::header::LINK],
+            "</api/v1/users?per_page=1&page=1>; rel=\"first\", \
+             </api/v1/users?per_page=1&page=1>; rel=\"prev\", \
+             </api/v1/users?per_page=1&page=2>; rel=\"last\""
+        );
+        insta::assert_json_snapshot!("users_page", json(response).await, {
+            ".data[].user_id" => "[uuid]",
+            ".data[].created" => "[timestamp]",
+            ".data[].updated" => "[timestamp]",
+        });
+
+        let response = app
+            .clone()
+            .oneshot(post_json("/api/v1/signin", serde_json::json!({})))
+            .await
+            .unwrap();
+        insta::assert_json_snapshot!("invalid_body", json(response).await, {
+            ".request_id" => "[uuid]",
+        });
+    }
 }
@@ -0,0 +1,136 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    response::{IntoResponse, Redirect},
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::{
+    AppError, AppResult, AppState,
+    error::ApiError,
+    server::{
+        extract::Query,
+        response::ApiResponse,
+        routes::public::{Session, session_response},
+    },
+};
+
+/// Cookie, в которой между началом входа и обратным вызовом
+/// хранятся `state`, `nonce` и секрет PKCE
+const OIDC_FLOW_COOKIE: &str = "alfred-oidc";
+/// Сколько минут пользователь может провести на странице провайдера
+const OIDC_FLOW_TTL_MINUTES: i64 = 10;
+
+pub(super) fn routes(state: Arc<AppState>) -> OpenApiRouter {
+    OpenApiRouter::new()
+        .routes(routes!(login_handler))
+        .routes(routes!(callback_handler))
+        .with_state(state)
+}
+
+/// Параметры начатого входа, подписанные секретом JWT
+#[derive(Debug, Serialize, Deserialize)]
+struct FlowClaims {
+    state: String,
+    nonce: String,
+    code_verifier: String,
+    exp: usize,
+}
+
+/// Начинает вход через провайдера OpenID Connect
+#[utoipa::path(
+    get,
+    path = "/login",
+    tag = "auth",
+    security(()),
+    responses((status = 303, description = "Перенаправление на страницу провайдера"))
+)]
+async fn login_handler(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+) -> AppResult<impl IntoResponse> {
+    let oidc = state.oidc_service.as_ref().ok_or(AppError::EntryNotFound)?;
+    let request = oidc.authorization_request().await?;
+    let exp = (chrono::Utc::now() + chrono::Duration::minutes(OIDC_FLOW_TTL_MINUTES)).timestamp();
+    let flow = encode(
+        &Header::default(),
+        &FlowClaims {
+            state: request.state,
+            nonce: request.nonce,
+            code_verifier: request.code_verifier,
+            exp: exp as usize,
+        },
+        &EncodingKey::from_secret(state.jwt_settings.secret.as_ref()),
+    )
+    .map_err(|e| AppError::CryptoError(e.to_string()))?;
+    let cookie = Cookie::build((OIDC_FLOW_COOKIE, flow))
+        .path("/")
+        .max_age(time::Duration::minutes(OIDC_FLOW_TTL_MINUTES))
+        .same_site(SameSite::Lax)
+        .http_only(true);
+    Ok((jar.add(cookie), Redirect::to(&request.url)))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct CallbackParams {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+/// Завершает вход через провайдера OpenID Connect
+#[utoipa::path(
+    get,
+    path = "/callback",
+    tag = "auth",
+    security(()),
+    params(CallbackParams),
+    responses(
+        (status = 200, description = "Пользователь вошел", body = ApiResponse<Session>),
+        (status = 400, description = "Провайдер не передал код", body = ApiError),
+        (status = 401, description = "Вход не начат, истек или подделан", body = ApiError),
+        (status = 502, description = "Ошибка провайдера", body = ApiError),
+    )
+)]
+async fn callback_handler(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Query(params): Query<CallbackParams>,
+) -> AppResult<impl IntoResponse> {
+    let oidc = state.oidc_service.as_ref().ok_or(AppError::EntryNotFound)?;
+    if let Some(error) = params.error {
+        return Err(AppError::IdentityProvider(error));
+    }
+    let flow = jar
+        .get(OIDC_FLOW_COOKIE)
+        .and_then(|cookie| {
+            decode::<FlowClaims>(
+                cookie.value(),
+                &DecodingKey::from_secret(state.jwt_settings.secret.as_ref()),
+                &Validation::default(),
+            )
+            .ok()
+        })
+        .ok_or(AppError::InvalidCredentials)?
+        .claims;
+    if params.state.as_deref() != Some(flow.state.as_str()) {
+        return Err(AppError::InvalidCredentials);
+    }
+    let code = params.code.ok_or(AppError::InvalidInput)?;
+    let identity = oidc
+        .exchange(&code, &flow.code_verifier, &flow.nonce)
+        .await?;
+    let user = state
+        .users_service
+        .signin_external(&identity, oidc.provisioning_role(&identity.groups))
+        .await?;
+
+    let jar = jar.remove(Cookie::build(OIDC_FLOW_COOKIE).path("/"));
+    Ok((jar, session_response(&state, user)?))
+}
@@ -0,0 +1,223 @@
+//! Описание API в формате OpenAPI 3
+//!
+//! Пути и схемы собираются из аннотаций обработчиков при построении
+//! маршрутизатора, поэтому документ не может разойтись с маршрутами.
+use axum::{
+    Json, Router,
+    http::{HeaderValue, header},
+    routing::get,
+};
+use tower_http::set_header::SetResponseHeaderLayer;
+use utoipa::{
+    Modify, OpenApi,
+    openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+};
+use utoipa_scalar::{Scalar, Servable};
+
+use crate::{
+    error::{ApiError, ErrorCode},
+    server::{TOKEN, csrf::CSRF_HEADER},
+};
+
+/// Путь документа OpenAPI
+pub(super) const OPENAPI_PATH: &str = "/api/v1/openapi.json";
+/// Путь интерактивной документации
+pub(super) const DOCS_PATH: &str = "/api/v1/docs";
+/// Политика `Content-Security-Policy` страницы документации
+///
+/// Интерфейс Scalar загружается с CDN и встраивает стили в страницу,
+/// поэтому общая политика сервиса для нее слишком строгая.
+const DOCS_CSP: &str = "default-src 'none'; \
+    script-src https://cdn.jsdelivr.net; \
+    style-src 'unsafe-inline' https://cdn.jsdelivr.net; \
+    font-src https://fonts.scalar.com; \
+    img-src data: https:; \
+    connect-src 'self'; \
+    frame-ancestors 'none'";
+
+/// Общая часть документа: описание, теги и схемы аутентификации
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Alfred API",
+        description = "Управление пользователями, сессиями и ключами API"
+    ),
+    modifiers(&SecuritySchemes),
+    security(("bearer" = []), ("cookie" = [])),
+    components(schemas(ApiError, ErrorCode)),
+    tags(
+        (name = "auth", description = "Регистрация и вход"),
+        (name = "users", description = "Пользователи, сервисные аккаунты и ключи API"),
+        (name = "keys", description = "Открытые ключи для проверки JWT"),
+        (name = "service", description = "Состояние сервиса"),
+    )
+)]
+pub(super) struct ApiDoc;
+
+/// Добавляет схемы аутентификации: JWT или ключ API в заголовке
+/// `Authorization` и JWT в cookie
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .description(Some(
+                        "JWT сессии или ключ API с префиксом `alf_`".to_string(),
+                    ))
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "cookie",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::with_description(
+                TOKEN,
+                &format!("JWT сессии; изменяющие запросы также требуют заголовок `{CSRF_HEADER}`"),
+            ))),
+        );
+    }
+}
+
+/// Маршруты документа OpenAPI и интерактивной документации
+///
+/// # Аргументы
+///
+/// * `api` - Документ, собранный из маршрутов приложения
+pub(super) fn routes(api: utoipa::openapi::OpenApi) -> Router {
+    let document = api.clone();
+    Router::new()
+        .route(OPENAPI_PATH, get(move || async move { Json(document) }))
+        .merge(Router::from(Scalar::with_url(DOCS_PATH, api)).layer(
+            SetResponseHeaderLayer::overriding(
+                header::CONTENT_SECURITY_POLICY,
+                HeaderValue::from_static(DOCS_CSP),
+            ),
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeSet, sync::Arc};
+
+    use axum::{
+        body::Body,
+        http::{Method, Request, StatusCode, header},
+    };
+    use sqlx::PgPool;
+    use tower::ServiceExt;
+
+    use crate::AppResult;
+
+    const METHODS: [Method; 5] = [
+        Method::GET,
+        Method::POST,
+        Method::PUT,
+        Method::DELETE,
+        Method::PATCH,
+    ];
+
+    /// Запрос не дошел до обработчика: маршрута нет или метод не зарегистрирован
+    async fn unrouted(response: axum::response::Response) -> bool {
+        match response.status() {
+            StatusCode::METHOD_NOT_ALLOWED => true,
+            StatusCode::NOT_FOUND => {
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                body["code"] == "route_not_found"
+            }
+            _ => false,
+        }
+    }
+
+    /// Каждая операция документа обслуживается маршрутизатором,
+    /// а методы, которых нет в документе, отклоняются
+    #[sqlx::test]
+    async fn spec_matches_routes_test(pool: PgPool) -> AppResult<()> {
+        let state = super::super::tests::test_state(pool);
+        let admin = state
+            .users_service
+            .signup(
+                "admin@example.com",
+                "Correct-Horse-Battery-9",
+                Some("Администратор"),
+            )
+            .await?;
+        let token = state.jwt_keys.sign(admin.user_id)?;
+        let app = super::super::init(Arc::new(state), &Default::default());
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::get(super::OPENAPI_PATH)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let spec: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let paths = spec["paths"].as_object().unwrap();
+        assert!(paths.contains_key("/api/v1/users"));
+        assert!(paths.contains_key("/api/v1/users/{id}/tokens/{token_id}"));
+        assert!(paths.contains_key("/.well-known/jwks.json"));
+
+        for (path, item) in paths {
+            let mut documented = BTreeSet::new();
+            for (method, operation) in item.as_object().unwrap() {
+                assert!(
+                    operation["responses"]
+                        .as_object()
+                        .is_some_and(|responses| !responses.is_empty()),
+                    "{method} {path} has no documented responses"
+                );
+                documented.insert(method.to_uppercase());
+            }
+            let uri = path
+                .replace("{id}", &uuid::Uuid::new_v4().to_string())
+                .replace("{token_id}", &uuid::Uuid::new_v4().to_string());
+            for method in METHODS {
+                let request = Request::builder()
+                    .method(method.clone())
+                    .uri(&uri)
+                    .header(header::AUTHORIZATION, format!("Bearer {token}"))
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from("{}"))
+                    .unwrap();
+                let response = app.clone().oneshot(request).await.unwrap();
+                if documented.contains(method.as_str()) {
+                    assert!(
+                        !unrouted(response).await,
+                        "{method} {path} is documented but not routed"
+                    );
+                } else {
+                    assert_eq!(
+                        response.status(),
+                        StatusCode::METHOD_NOT_ALLOWED,
+                        "{method} {path} is routed but not documented"
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn docs_page_test() {
+        let api = utoipa::openapi::OpenApiBuilder::new().build();
+        let response = super::routes(api)
+            .oneshot(Request::get(super::DOCS_PATH).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -1,43 +1,129 @@
 use std::sync::Arc;
 
 use crate::{
-    AppResult, AppState,
-    server::{ErrorResponse, TOKEN, TokenClaims},
+    AppError, AppResult, AppState,
+    error::ApiError,
+    models::User,
+    server::{TOKEN, csrf, extract::Json, response::ApiResponse},
+    services::HealthReport,
     settings::JWTSettings,
 };
 use axum::{
-    Json, Router,
     extract::State,
-    http::{Response, header},
-    response::IntoResponse,
-    routing::{get, post},
+    http::{StatusCode, header},
+    response::{AppendHeaders, IntoResponse},
 };
 use axum_extra::extract::cookie::{Cookie, SameSite};
-use jsonwebtoken::{EncodingKey, Header, encode};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
+use utoipa::ToSchema;
+use utoipa_axum::{router::OpenApiRouter, routes};
+use validator::{ValidationError, ValidationErrors};
 
-pub(super) fn routes(state: Arc<AppState>) -> Router {
-    Router::new()
-        .route("/health", get(health_check_handler))
-        .route("/signin", post(signin_handler))
-        .route("/signup", post(signup_handler))
+pub(super) fn routes(state: Arc<AppState>) -> OpenApiRouter {
+    OpenApiRouter::new()
+        .routes(routes!(health_check_handler))
+        .routes(routes!(liveness_handler))
+        .routes(routes!(readiness_handler))
         .with_state(state)
 }
 
-async fn health_check_handler() -> impl IntoResponse {
-    Json(json!({
-        "status": "ok",
+pub(super) fn auth_routes(state: Arc<AppState>) -> OpenApiRouter {
+    OpenApiRouter::new()
+        .routes(routes!(signin_handler))
+        .routes(routes!(signup_handler))
+        .with_state(state)
+}
+
+/// Проверка доступности сервиса
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "service",
+    security(()),
+    responses((status = 200, description = "Сервис работает", body = ApiResponse<Object>))
+)]
+async fn health_check_handler() -> ApiResponse<serde_json::Value> {
+    ApiResponse::new(json!({
         "message": "My health is fine, thank you!"
     }))
 }
 
-#[derive(Deserialize, Debug)]
+/// Проверка жизнеспособности процесса
+///
+/// Зависимости не проверяются: перезапуск процесса не поможет,
+/// если недоступна база данных.
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    tag = "service",
+    security(()),
+    responses((status = 200, description = "Процесс отвечает", body = Object))
+)]
+async fn liveness_handler() -> impl IntoResponse {
+    (
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(json!({ "status": "alive" })),
+    )
+}
+
+/// Проверка готовности принимать запросы
+///
+/// Проверяет базу данных, миграции и подключенные внешние сервисы.
+/// Во время завершения работы отвечает 503, чтобы балансировщик
+/// вывел экземпляр из ротации.
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    tag = "service",
+    security(()),
+    responses(
+        (status = 200, description = "Сервис готов", body = HealthReport),
+        (status = 503, description = "Компонент недоступен или сервер завершает работу", body = HealthReport),
+    )
+)]
+async fn readiness_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let report = state.health.readiness().await;
+    let status = if report.is_ready() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, [(header::CACHE_CONTROL, "no-store")], Json(report))
+}
+
+/// Открытая сессия
+///
+/// Токен сессии и CSRF-токен также устанавливаются в cookie.
+#[derive(Serialize, ToSchema)]
+pub(super) struct Session {
+    /// JWT сессии
+    token: String,
+    /// Токен для заголовка `x-csrf-token`
+    csrf_token: String,
+    /// Вошедший пользователь
+    user: User,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
 struct SigninForm {
     email: String,
     password: String,
 }
 
+/// Вход по почте и паролю
+#[utoipa::path(
+    post,
+    path = "/signin",
+    tag = "auth",
+    security(()),
+    request_body = SigninForm,
+    responses(
+        (status = 200, description = "Пользователь вошел", body = ApiResponse<Session>),
+        (status = 401, description = "Неверная почта или пароль", body = ApiError),
+        (status = 429, description = "Слишком много попыток", body = ApiError),
+    )
+)]
 async fn signin_handler(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<SigninForm>,
@@ -47,69 +133,98 @@ async fn signin_handler(
         .signin(&payload.email, &payload.password)
         .await?;
 
-    let token = create_token(existing.user_id, &state.jwt_settings);
-
-    let mut response =
-        Response::new(json!({"status": "success", "token": token, "user": existing}).to_string());
-    response.headers_mut().insert(
-        header::SET_COOKIE,
-        create_cookie(&token, &state.jwt_settings).parse().unwrap(),
-    );
-    Ok(response)
+    session_response(&state, existing)
 }
-#[derive(Deserialize, Debug)]
+
+#[derive(Deserialize, Debug, ToSchema)]
 struct SignupForm {
     email: String,
     password: String,
     confirm_password: String,
+    /// Одноразовый токен настройки, выведенный при первом запуске;
+    /// с ним регистрация создает владельца
+    #[serde(default)]
+    setup_token: Option<String>,
 }
+
+/// Регистрация нового пользователя
+#[utoipa::path(
+    post,
+    path = "/signup",
+    tag = "auth",
+    security(()),
+    request_body = SignupForm,
+    responses(
+        (status = 200, description = "Пользователь зарегистрирован", body = ApiResponse<Session>),
+        (status = 403, description = "Токен настройки неверен или уже использован", body = ApiError),
+        (status = 409, description = "Почта уже занята", body = ApiError),
+        (status = 422, description = "Пароли не совпадают или не соответствуют политике", body = ApiError),
+        (status = 429, description = "Слишком много попыток", body = ApiError),
+    )
+)]
 async fn signup_handler(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<SignupForm>,
 ) -> AppResult<impl IntoResponse> {
     if payload.confirm_password != payload.password {
-        return Ok((
-            axum::http::StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                status: "error",
-                message: "passwords doesn't match".into(),
-            }),
-        )
-            .into_response());
+        let mut errors = ValidationErrors::new();
+        errors.add(
+            "confirm_password",
+            ValidationError::new("password_mismatch").with_message("Passwords do not match".into()),
+        );
+        return Err(errors.into());
     }
-    let new_user = state
-        .users_service
-        .signup(&payload.email, &payload.password, None)
-        .await?;
-    let token = create_token(new_user.user_id, &state.jwt_settings);
-    let mut response =
-        Response::new(json!({"status": "success", "token": token, "user": new_user}).to_string());
-    response.headers_mut().insert(
-        header::SET_COOKIE,
-        create_cookie(&token, &state.jwt_settings).parse().unwrap(),
-    );
-    Ok(response.into_response())
-}
-
-fn create_token(user_id: uuid::Uuid, jwt: &JWTSettings) -> String {
-    let now = chrono::Utc::now();
-    let iat = now.timestamp() as usize;
-    let exp = (now + chrono::Duration::minutes(jwt.expires_in)).timestamp() as usize;
-    let claims: TokenClaims = TokenClaims {
-        sub: user_id.to_string(),
-        exp,
-        iat,
+    let new_user = match (&payload.setup_token, &state.bootstrap) {
+        (Some(token), Some(bootstrap)) => {
+            bootstrap
+                .claim_owner(&payload.email, &payload.password, token)
+                .await?
+        }
+        (Some(_), None) => return Err(AppError::AccessDenied),
+        (None, _) => {
+            state
+                .users_service
+                .signup(&payload.email, &payload.password, None)
+                .await?
+        }
     };
+    session_response(&state, new_user)
+}
 
-    encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(jwt.secret.as_ref()),
-    )
-    .unwrap()
+/// Открывает сессию пользователя
+///
+/// Токен сессии и CSRF-токен возвращаются в теле и устанавливаются в cookie.
+///
+/// # Аргументы
+///
+/// * `state` - Состояние приложения
+/// * `user` - Вошедший пользователь
+pub(super) fn session_response(
+    state: &AppState,
+    user: User,
+) -> AppResult<impl IntoResponse + use<>> {
+    let token = state.jwt_keys.sign(user.user_id)?;
+    let csrf_token = csrf::issue_token(&state.jwt_settings.secret, &token);
+    Ok((
+        AppendHeaders([
+            (
+                header::SET_COOKIE,
+                create_cookie(&token, &state.jwt_settings),
+            ),
+            (
+                header::SET_COOKIE,
+                csrf::create_cookie(&csrf_token, &state.jwt_settings),
+            ),
+        ]),
+        ApiResponse::new(Session {
+            token,
+            csrf_token,
+            user,
+        }),
+    ))
 }
 
-fn create_cookie(token: &String, jwt: &JWTSettings) -> String {
+pub(super) fn create_cookie(token: &String, jwt: &JWTSettings) -> String {
     Cookie::build((TOKEN, token.to_owned()))
         .path("/")
         .max_age(time::Duration::hours(jwt.maxage))
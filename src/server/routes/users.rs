@@ -1,37 +1,59 @@
 use std::sync::Arc;
 
 use axum::{
-    Extension, Json, Router,
-    extract::{Path, Query, State},
-    http::{Response, header},
-    response::IntoResponse,
-    routing::*,
+    Extension,
+    extract::{OriginalUri, State},
+    http::header,
+    response::{AppendHeaders, IntoResponse},
 };
 use axum_extra::extract::cookie::{Cookie, SameSite};
 use serde::Deserialize;
 use serde_json::json;
+use utoipa::{IntoParams, ToSchema};
+use utoipa_axum::{router::OpenApiRouter, routes};
 
 use crate::{
     AppError, AppResult, AppState,
-    models::{User, UserToUpdate},
-    server::TOKEN,
-    services::UsersListResponse,
+    error::ApiError,
+    models::{
+        ApiScope, ApiToken, ImportedUser, IssuedApiToken, LocalizedUser, NewApiToken,
+        ServiceAccountData, User, UserToUpdate,
+    },
+    server::{
+        AuthMethod, TOKEN,
+        csrf::CSRF_COOKIE,
+        extract::{Json, Path, Query},
+        response::{ApiResponse, Paginated},
+    },
+    services::UserDataExport,
 };
 
-pub(super) fn routes(state: Arc<AppState>) -> Router {
-    Router::new()
-        .route(
-            "/{id}",
-            get(get_by_id_handler)
-                .put(update_handler)
-                .delete(delete_handler),
-        )
-        .route("/me", get(getme_handler))
-        .route("/", get(list_handler))
-        .route("/logout", get(logout_handler))
+/// Маршруты пользователей
+///
+/// Обработчики одного пути регистрируются одним вызовом `routes!`:
+/// макрос назначает общий набор методов всем перечисленным путям.
+pub(super) fn routes(state: Arc<AppState>) -> OpenApiRouter {
+    OpenApiRouter::new()
+        .routes(routes!(get_by_id_handler, update_handler, delete_handler))
+        .routes(routes!(getme_handler, delete_me_handler))
+        .routes(routes!(restore_handler))
+        .routes(routes!(export_handler))
+        .routes(routes!(list_tokens_handler, issue_token_handler))
+        .routes(routes!(revoke_token_handler))
+        .routes(routes!(create_service_account_handler))
+        .routes(routes!(import_handler))
+        .routes(routes!(list_handler))
+        .routes(routes!(logout_handler))
         .with_state(state)
 }
 
+/// Выход: удаляет cookie сессии и CSRF-токена
+#[utoipa::path(
+    get,
+    path = "/logout",
+    tag = "users",
+    responses((status = 200, description = "Cookie удалены", body = ApiResponse<Object>))
+)]
 async fn logout_handler() -> impl IntoResponse {
     let cookie = Cookie::build((TOKEN, ""))
         .path("/")
@@ -39,87 +61,456 @@ async fn logout_handler() -> impl IntoResponse {
         .same_site(SameSite::Lax)
         .http_only(true);
 
-    let mut response = Response::new(json!({"status": "success"}).to_string());
-    response
-        .headers_mut()
-        .insert(header::SET_COOKIE, cookie.to_string().parse().unwrap());
-    response
+    let csrf_cookie = Cookie::build((CSRF_COOKIE, ""))
+        .path("/")
+        .max_age(time::Duration::hours(-1))
+        .same_site(SameSite::Lax);
+
+    (
+        AppendHeaders([
+            (header::SET_COOKIE, cookie.to_string()),
+            (header::SET_COOKIE, csrf_cookie.to_string()),
+        ]),
+        ApiResponse::new(json!({})),
+    )
+}
+
+/// Профиль текущего пользователя
+#[utoipa::path(
+    get,
+    path = "/me",
+    tag = "users",
+    responses(
+        (status = 200, description = "Профиль", body = ApiResponse<LocalizedUser>),
+        (status = 401, description = "Не выполнен вход", body = ApiError),
+        (status = 403, description = "Доступ запрещен", body = ApiError),
+    )
+)]
+async fn getme_handler(
+    Extension(user): Extension<User>,
+    Extension(auth): Extension<AuthMethod>,
+) -> AppResult<ApiResponse<LocalizedUser>> {
+    auth.require(ApiScope::ProfileRead)?;
+    Ok(ApiResponse::new(user.localized(user.info.tz())))
+}
+
+/// Запрос на удаление своей учетной записи
+#[utoipa::path(
+    delete,
+    path = "/me",
+    tag = "users",
+    request_body = DeletionRequest,
+    responses(
+        (status = 200, description = "Удаление запланировано", body = ApiResponse<LocalizedUser>),
+        (status = 401, description = "Не выполнен вход, неверный пароль или вход без пароля был слишком давно", body = ApiError),
+        (status = 403, description = "Доступ запрещен", body = ApiError),
+    )
+)]
+async fn delete_me_handler(
+    Extension(user): Extension<User>,
+    Extension(auth): Extension<AuthMethod>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<DeletionRequest>,
+) -> AppResult<ApiResponse<LocalizedUser>> {
+    let AuthMethod::Session { signed_in_at } = auth else {
+        return Err(AppError::AccessDenied);
+    };
+    let scheduled = state
+        .users_service
+        .request_deletion(&user, payload.password.as_deref(), signed_in_at)
+        .await?;
+    Ok(ApiResponse::new(scheduled.localized(user.info.tz())))
+}
+
+/// Отмена запланированного удаления
+#[utoipa::path(
+    post,
+    path = "/me/restore",
+    tag = "users",
+    responses(
+        (status = 200, description = "Удаление отменено", body = ApiResponse<LocalizedUser>),
+        (status = 401, description = "Не выполнен вход", body = ApiError),
+        (status = 403, description = "Доступ запрещен", body = ApiError),
+    )
+)]
+async fn restore_handler(
+    Extension(user): Extension<User>,
+    Extension(auth): Extension<AuthMethod>,
+    State(state): State<Arc<AppState>>,
+) -> AppResult<ApiResponse<LocalizedUser>> {
+    auth.require_session()?;
+    let restored = state.users_service.cancel_deletion(&user).await?;
+    Ok(ApiResponse::new(restored.localized(user.info.tz())))
+}
+
+/// Выгрузка персональных данных
+#[utoipa::path(
+    get,
+    path = "/me/export",
+    tag = "users",
+    responses(
+        (status = 200, description = "Файл выгрузки", body = ApiResponse<UserDataExport>),
+        (status = 401, description = "Не выполнен вход", body = ApiError),
+        (status = 403, description = "Доступ запрещен", body = ApiError),
+    )
+)]
+async fn export_handler(
+    Extension(user): Extension<User>,
+    Extension(auth): Extension<AuthMethod>,
+    State(state): State<Arc<AppState>>,
+) -> AppResult<impl IntoResponse> {
+    auth.require(ApiScope::ProfileRead)?;
+    let export = state.users_service.export(&user).await?;
+    let disposition = format!(
+        "attachment; filename=\"alfred-export-{id}.json\"",
+        id = user.user_id
+    );
+    Ok((
+        [(header::CONTENT_DISPOSITION, disposition)],
+        ApiResponse::new(export),
+    ))
 }
 
-async fn getme_handler(Extension(user): Extension<User>) -> AppResult<Json<User>> {
-    Ok(Json(user))
+#[derive(Clone, Debug, Deserialize, ToSchema)]
+struct DeletionRequest {
+    /// Текущий пароль; аккаунту без пароля достаточно недавнего входа
+    #[serde(default)]
+    password: Option<String>,
 }
+
+/// Пользователь по идентификатору
+#[utoipa::path(
+    get,
+    path = "/{id}",
+    tag = "users",
+    params(("id" = Uuid, Path, description = "Идентификатор пользователя")),
+    responses(
+        (status = 200, description = "Пользователь", body = ApiResponse<LocalizedUser>),
+        (status = 401, description = "Не выполнен вход", body = ApiError),
+        (status = 403, description = "Доступ запрещен", body = ApiError),
+        (status = 400, description = "Неверный идентификатор", body = ApiError),
+        (status = 404, description = "Пользователь не найден", body = ApiError),
+    )
+)]
 async fn get_by_id_handler(
     Extension(user): Extension<User>,
+    Extension(auth): Extension<AuthMethod>,
     Path(id): Path<String>,
     State(state): State<Arc<AppState>>,
-) -> AppResult<Json<User>> {
+) -> AppResult<ApiResponse<LocalizedUser>> {
     match uuid::Uuid::parse_str(&id) {
         Ok(parsed_id) => {
-            if !user.role.is_admin() && user.user_id != parsed_id {
+            if user.user_id == parsed_id {
+                auth.require(ApiScope::ProfileRead)?;
+            } else if user.role.is_admin() {
+                auth.require(ApiScope::UsersRead)?;
+            } else {
                 return Err(AppError::AccessDenied);
             }
             let founded = state.users_service.get_by_id(&id).await?;
-            Ok(Json(founded))
+            Ok(ApiResponse::new(founded.localized(user.info.tz())))
         }
         Err(_) => Err(AppError::InvalidInput),
     }
 }
+
+/// Удаление пользователя
+#[utoipa::path(
+    delete,
+    path = "/{id}",
+    tag = "users",
+    params(("id" = Uuid, Path, description = "Идентификатор пользователя")),
+    responses(
+        (status = 200, description = "Удаленный пользователь", body = ApiResponse<User>),
+        (status = 401, description = "Не выполнен вход", body = ApiError),
+        (status = 403, description = "Доступ запрещен", body = ApiError),
+        (status = 400, description = "Неверный идентификатор", body = ApiError),
+        (status = 404, description = "Пользователь не найден", body = ApiError),
+    )
+)]
 async fn delete_handler(
     Extension(user): Extension<User>,
+    Extension(auth): Extension<AuthMethod>,
     Path(id): Path<String>,
     State(state): State<Arc<AppState>>,
-) -> AppResult<Json<User>> {
+) -> AppResult<ApiResponse<User>> {
     match uuid::Uuid::parse_str(&id) {
         Ok(_) => {
             if !user.role.is_admin() {
                 return Err(AppError::AccessDenied);
             }
+            auth.require(ApiScope::UsersWrite)?;
             let deleted = state.users_service.delete(&id).await?;
-            Ok(Json(deleted))
+            Ok(ApiResponse::new(deleted))
         }
         Err(_) => Err(AppError::InvalidInput),
     }
 }
 
+/// Список пользователей с фильтрацией и постраничным выводом
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "users",
+    params(Filter),
+    responses(
+        (status = 200, description = "Страница списка", body = ApiResponse<Vec<User>>,
+            headers(("Link" = String, description = "Ссылки на соседние страницы по RFC 8288"))),
+        (status = 401, description = "Не выполнен вход", body = ApiError),
+        (status = 403, description = "Доступ запрещен", body = ApiError),
+    )
+)]
 async fn list_handler(
     Extension(user): Extension<User>,
+    Extension(auth): Extension<AuthMethod>,
     State(state): State<Arc<AppState>>,
+    OriginalUri(uri): OriginalUri,
     Query(filter): Query<Filter>,
-) -> AppResult<Json<UsersListResponse>> {
+) -> AppResult<Paginated<Vec<User>>> {
     if !user.role.is_admin() {
         return Err(AppError::AccessDenied);
     }
+    auth.require(ApiScope::UsersRead)?;
     let result = state
         .users_service
         .list(filter.page, filter.per_page, filter.role, filter.q)
         .await?;
-    Ok(Json(result))
+    Ok(Paginated::new(result.into(), uri))
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct Filter {
+    /// Номер страницы, начиная с 1
     page: Option<String>,
+    /// Размер страницы
     per_page: Option<String>,
+    /// Роль пользователей
     role: Option<String>,
+    /// Строка поиска по почте, имени пользователя, имени и фамилии
     q: Option<String>,
 }
 
+/// Изменение данных пользователя
+#[utoipa::path(
+    put,
+    path = "/{id}",
+    tag = "users",
+    params(("id" = Uuid, Path, description = "Идентификатор пользователя")),
+    request_body = UserToUpdate,
+    responses(
+        (status = 200, description = "Обновленный пользователь", body = ApiResponse<LocalizedUser>),
+        (status = 401, description = "Не выполнен вход", body = ApiError),
+        (status = 403, description = "Доступ запрещен", body = ApiError),
+        (status = 409, description = "Почта уже занята", body = ApiError),
+        (status = 422, description = "Данные не прошли проверку", body = ApiError),
+        (status = 400, description = "Неверный идентификатор", body = ApiError),
+        (status = 404, description = "Пользователь не найден", body = ApiError),
+    )
+)]
 #[axum::debug_handler]
 async fn update_handler(
     Extension(user): Extension<User>,
+    Extension(auth): Extension<AuthMethod>,
     Path(id): Path<String>,
     State(state): State<Arc<AppState>>,
     Json(payload): Json<UserToUpdate>,
-) -> AppResult<Json<User>> {
+) -> AppResult<ApiResponse<LocalizedUser>> {
     match uuid::Uuid::parse_str(&id) {
         Ok(parsed_id) => {
-            if !user.role.is_admin() && user.user_id != parsed_id {
+            if user.user_id == parsed_id {
+                auth.require(ApiScope::ProfileWrite)?;
+                // Свою роль поменять нельзя: иначе ключ с `profile:write`
+                // или сервисный аккаунт повысили бы себя до владельца
+                if payload.role != user.role {
+                    return Err(AppError::AccessDenied);
+                }
+            } else if user.role.is_admin() {
+                auth.require(ApiScope::UsersWrite)?;
+            } else {
                 return Err(AppError::AccessDenied);
             }
-            let updated = state.users_service.update(&id, payload).await?;
-            Ok(Json(updated))
+            let updated = state.users_service.update(&user, &id, payload).await?;
+            let tz = if updated.user_id == user.user_id {
+                updated.info.tz()
+            } else {
+                user.info.tz()
+            };
+            Ok(ApiResponse::new(updated.localized(tz)))
         }
         Err(_) => Err(AppError::InvalidInput),
     }
 }
+
+/// Возвращает владельца ключей, если текущий пользователь может ими управлять
+///
+/// Пользователь управляет своими ключами; администратор - ключами
+/// сервисных аккаунтов и, для отзыва, ключами любых пользователей.
+async fn tokens_owner(
+    state: &AppState,
+    user: &User,
+    id: &str,
+    any_for_admin: bool,
+) -> AppResult<User> {
+    let owner_id = uuid::Uuid::parse_str(id).map_err(|_| AppError::InvalidInput)?;
+    if owner_id == user.user_id {
+        return Ok(user.clone());
+    }
+    if !user.role.is_admin() {
+        return Err(AppError::AccessDenied);
+    }
+    let owner = state.users_service.get_by_id(id).await?;
+    if owner.service_account || any_for_admin {
+        Ok(owner)
+    } else {
+        Err(AppError::AccessDenied)
+    }
+}
+
+/// Ключи API пользователя
+#[utoipa::path(
+    get,
+    path = "/{id}/tokens",
+    tag = "users",
+    params(("id" = Uuid, Path, description = "Идентификатор пользователя")),
+    responses(
+        (status = 200, description = "Ключи без секретов", body = ApiResponse<Vec<ApiToken>>),
+        (status = 401, description = "Не выполнен вход", body = ApiError),
+        (status = 403, description = "Доступ запрещен", body = ApiError),
+        (status = 400, description = "Неверный идентификатор", body = ApiError),
+        (status = 404, description = "Пользователь не найден", body = ApiError),
+    )
+)]
+async fn list_tokens_handler(
+    Extension(user): Extension<User>,
+    Extension(auth): Extension<AuthMethod>,
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> AppResult<ApiResponse<Vec<ApiToken>>> {
+    auth.require_session()?;
+    let owner = tokens_owner(&state, &user, &id, true).await?;
+    let tokens = state.api_tokens_service.list(owner.user_id).await?;
+    Ok(ApiResponse::new(tokens))
+}
+
+/// Выпуск ключа API
+#[utoipa::path(
+    post,
+    path = "/{id}/tokens",
+    tag = "users",
+    params(("id" = Uuid, Path, description = "Идентификатор пользователя")),
+    request_body = NewApiToken,
+    responses(
+        (status = 201, description = "Ключ выпущен; секрет показывается один раз", body = ApiResponse<IssuedApiToken>),
+        (status = 401, description = "Не выполнен вход", body = ApiError),
+        (status = 403, description = "Доступ запрещен", body = ApiError),
+        (status = 422, description = "Данные не прошли проверку", body = ApiError),
+        (status = 400, description = "Неверный идентификатор", body = ApiError),
+        (status = 404, description = "Пользователь не найден", body = ApiError),
+    )
+)]
+async fn issue_token_handler(
+    Extension(user): Extension<User>,
+    Extension(auth): Extension<AuthMethod>,
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<NewApiToken>,
+) -> AppResult<impl IntoResponse> {
+    auth.require_session()?;
+    let owner = tokens_owner(&state, &user, &id, false).await?;
+    let issued = state
+        .api_tokens_service
+        .issue(&user, &owner, payload)
+        .await?;
+    Ok((axum::http::StatusCode::CREATED, ApiResponse::new(issued)))
+}
+
+/// Отзыв ключа API
+#[utoipa::path(
+    delete,
+    path = "/{id}/tokens/{token_id}",
+    tag = "users",
+    params(("id" = Uuid, Path, description = "Идентификатор пользователя"),
+        ("token_id" = Uuid, Path, description = "Идентификатор ключа")),
+    responses(
+        (status = 200, description = "Отозванный ключ", body = ApiResponse<ApiToken>),
+        (status = 401, description = "Не выполнен вход", body = ApiError),
+        (status = 403, description = "Доступ запрещен", body = ApiError),
+        (status = 400, description = "Неверный идентификатор", body = ApiError),
+        (status = 404, description = "Ключ не найден", body = ApiError),
+    )
+)]
+async fn revoke_token_handler(
+    Extension(user): Extension<User>,
+    Extension(auth): Extension<AuthMethod>,
+    Path((id, token_id)): Path<(String, String)>,
+    State(state): State<Arc<AppState>>,
+) -> AppResult<ApiResponse<ApiToken>> {
+    auth.require_session()?;
+    let owner = tokens_owner(&state, &user, &id, true).await?;
+    let token_id = uuid::Uuid::parse_str(&token_id).map_err(|_| AppError::InvalidInput)?;
+    let revoked = state
+        .api_tokens_service
+        .revoke(&user, owner.user_id, token_id)
+        .await?;
+    Ok(ApiResponse::new(revoked))
+}
+
+/// Создание сервисного аккаунта
+#[utoipa::path(
+    post,
+    path = "/service-accounts",
+    tag = "users",
+    request_body = ServiceAccountData,
+    responses(
+        (status = 201, description = "Сервисный аккаунт", body = ApiResponse<LocalizedUser>),
+        (status = 401, description = "Не выполнен вход", body = ApiError),
+        (status = 403, description = "Доступ запрещен", body = ApiError),
+        (status = 409, description = "Почта уже занята", body = ApiError),
+        (status = 422, description = "Данные не прошли проверку", body = ApiError),
+    )
+)]
+async fn create_service_account_handler(
+    Extension(user): Extension<User>,
+    Extension(auth): Extension<AuthMethod>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<ServiceAccountData>,
+) -> AppResult<impl IntoResponse> {
+    auth.require_session()?;
+    let account = state
+        .users_service
+        .create_service_account(&user, payload)
+        .await?;
+    Ok((
+        axum::http::StatusCode::CREATED,
+        ApiResponse::new(account.localized(user.info.tz())),
+    ))
+}
+
+/// Импорт пользователя с готовым хэшем пароля
+#[utoipa::path(
+    post,
+    path = "/import",
+    tag = "users",
+    request_body = ImportedUser,
+    responses(
+        (status = 201, description = "Импортированный пользователь", body = ApiResponse<LocalizedUser>),
+        (status = 401, description = "Не выполнен вход", body = ApiError),
+        (status = 403, description = "Доступ запрещен", body = ApiError),
+        (status = 409, description = "Почта уже занята", body = ApiError),
+        (status = 422, description = "Данные не прошли проверку", body = ApiError),
+    )
+)]
+async fn import_handler(
+    Extension(user): Extension<User>,
+    Extension(auth): Extension<AuthMethod>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<ImportedUser>,
+) -> AppResult<impl IntoResponse> {
+    auth.require(ApiScope::UsersWrite)?;
+    let imported = state.users_service.import_user(&user, payload).await?;
+    Ok((
+        axum::http::StatusCode::CREATED,
+        ApiResponse::new(imported.localized(user.info.tz())),
+    ))
+}
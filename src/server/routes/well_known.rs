@@ -0,0 +1,33 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::header, response::IntoResponse};
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::{AppState, server::extract::Json};
+
+/// Сколько секунд проверяющие сервисы могут кэшировать набор ключей
+const JWKS_MAX_AGE_SECS: u64 = 300;
+
+pub(super) fn routes(state: Arc<AppState>) -> OpenApiRouter {
+    OpenApiRouter::new()
+        .routes(routes!(jwks_handler))
+        .with_state(state)
+}
+
+/// Набор открытых ключей для проверки выданных JWT
+#[utoipa::path(
+    get,
+    path = "/jwks.json",
+    tag = "keys",
+    security(()),
+    responses((status = 200, description = "JSON Web Key Set", body = Object))
+)]
+async fn jwks_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    (
+        [(
+            header::CACHE_CONTROL,
+            format!("public, max-age={JWKS_MAX_AGE_SECS}"),
+        )],
+        Json(state.jwt_keys.jwks()),
+    )
+}
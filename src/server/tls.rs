@@ -0,0 +1,643 @@
+//! Обслуживание HTTPS средствами rustls
+//!
+//! Сертификат сервера выдается через [`CertResolver`], который периодически
+//! сверяет время изменения файлов и перечитывает их после продления.
+//! Рукопожатия выполняются в отдельных задачах, чтобы медленный клиент
+//! не задерживал прием остальных соединений. Имя из проверенного
+//! клиентского сертификата передается запросам как [`ClientCertificate`].
+
+use std::{
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex, RwLock, Weak},
+    time::{Duration, SystemTime},
+};
+
+use axum::{
+    Router,
+    body::Body,
+    extract::{ConnectInfo, connect_info::Connected},
+    http::{Request, Uri},
+    middleware::Next,
+    response::{Redirect, Response},
+    serve::{IncomingStream, Listener},
+};
+use http::HeaderValue;
+use rustls::{
+    RootCertStore, ServerConfig, SupportedProtocolVersion,
+    crypto::{CryptoProvider, ring},
+    pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+    server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier},
+    sign::CertifiedKey,
+    version::{TLS12, TLS13},
+};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_rustls::{TlsAcceptor, server::TlsStream};
+use x509_parser::{certificate::X509Certificate, extensions::GeneralName, prelude::FromDer};
+
+use crate::{
+    AppError, AppResult,
+    settings::{ClientAuth, TlsSettings, TlsVersion},
+};
+
+/// Сколько ждать завершения рукопожатия, прежде чем закрыть соединение
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Сколько установленных соединений может ждать, пока их заберет сервер
+const ACCEPT_BACKLOG: usize = 128;
+
+/// Выдает текущий сертификат сервера и перечитывает его при изменении файлов
+#[derive(Debug)]
+pub(crate) struct CertResolver {
+    cert_path: std::path::PathBuf,
+    key_path: std::path::PathBuf,
+    provider: Arc<CryptoProvider>,
+    current: RwLock<Arc<CertifiedKey>>,
+    modified: Mutex<(SystemTime, SystemTime)>,
+}
+impl CertResolver {
+    /// Загружает сертификат и ключ по путям из настроек
+    fn load(settings: &TlsSettings, provider: Arc<CryptoProvider>) -> AppResult<Self> {
+        let modified = modified(settings)?;
+        let key = load_certified_key(&settings.cert_path, &settings.key_path, &provider)?;
+        Ok(Self {
+            cert_path: settings.cert_path.clone(),
+            key_path: settings.key_path.clone(),
+            provider,
+            current: RwLock::new(Arc::new(key)),
+            modified: Mutex::new(modified),
+        })
+    }
+    /// Перечитывает сертификат, если файлы изменились с прошлой загрузки
+    ///
+    /// Если новые файлы не читаются или ключ не подходит к сертификату
+    /// (например, файлы заменены не одновременно), остается прежний
+    /// сертификат, а попытка повторяется при следующей проверке.
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(true)` - Сертификат заменен
+    /// * `Ok(false)` - Файлы не менялись
+    /// * `Err(AppError)` - Новые файлы не удалось загрузить
+    pub(crate) fn reload_if_changed(&self) -> AppResult<bool> {
+        let modified = (modified_at(&self.cert_path)?, modified_at(&self.key_path)?);
+        if *self.modified.lock().unwrap() == modified {
+            return Ok(false);
+        }
+        let key = load_certified_key(&self.cert_path, &self.key_path, &self.provider)?;
+        *self.current.write().unwrap() = Arc::new(key);
+        *self.modified.lock().unwrap() = modified;
+        Ok(true)
+    }
+}
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap().clone())
+    }
+}
+
+/// Собирает конфигурацию rustls по настройкам
+///
+/// Сертификаты УЦ для проверки клиентов читаются один раз при запуске.
+///
+/// # Аргументы
+///
+/// * `settings` - Настройки TLS
+///
+/// # Возвращает
+///
+/// * `Ok((ServerConfig, CertResolver))` - Конфигурация и источник сертификата сервера
+/// * `Err(AppError::Custom)` - Файлы не читаются или содержат неподходящие данные
+pub(crate) fn server_config(
+    settings: &TlsSettings,
+) -> AppResult<(Arc<ServerConfig>, Arc<CertResolver>)> {
+    let provider = Arc::new(ring::default_provider());
+    let resolver = Arc::new(CertResolver::load(settings, provider.clone())?);
+    let verifier = match &settings.client_ca_path {
+        Some(path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(path)? {
+                roots
+                    .add(cert)
+                    .map_err(|e| tls_error(path, e.to_string()))?;
+            }
+            let builder =
+                WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider.clone());
+            let builder = match settings.client_auth {
+                ClientAuth::Required => builder,
+                ClientAuth::Optional => builder.allow_unauthenticated(),
+            };
+            builder
+                .build()
+                .map_err(|e| tls_error(path, e.to_string()))?
+        }
+        None => WebPkiClientVerifier::no_client_auth(),
+    };
+    let versions: &[&SupportedProtocolVersion] = match settings.min_version {
+        TlsVersion::Tls12 => &[&TLS13, &TLS12],
+        TlsVersion::Tls13 => &[&TLS13],
+    };
+    let mut config = ServerConfig::builder_with_provider(provider)
+        .with_protocol_versions(versions)
+        .map_err(|e| AppError::Custom(format!("tls: {e}")))?
+        .with_client_cert_verifier(verifier)
+        .with_cert_resolver(resolver.clone());
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok((Arc::new(config), resolver))
+}
+
+/// Запускает периодическую проверку файлов сертификата
+///
+/// Задача завершается вместе с последней ссылкой на `resolver`.
+pub(crate) fn spawn_reload(resolver: &Arc<CertResolver>, interval: Duration) {
+    let resolver: Weak<CertResolver> = Arc::downgrade(resolver);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval.max(Duration::from_secs(1)));
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let Some(resolver) = resolver.upgrade() else {
+                return;
+            };
+            match resolver.reload_if_changed() {
+                Ok(true) => tracing::info!("TLS certificate reloaded"),
+                Ok(false) => {}
+                Err(e) => tracing::error!("Failed to reload TLS certificate: {e}"),
+            }
+        }
+    });
+}
+
+/// Значение заголовка `Strict-Transport-Security`, если HSTS включен
+pub(crate) fn hsts(settings: &TlsSettings) -> Option<HeaderValue> {
+    if settings.hsts_max_age_secs == 0 {
+        return None;
+    }
+    let mut value = format!("max-age={}", settings.hsts_max_age_secs);
+    if settings.hsts_include_subdomains {
+        value.push_str("; includeSubDomains");
+    }
+    HeaderValue::from_str(&value).ok()
+}
+
+/// Маршруты, перенаправляющие любой запрос на тот же путь по адресу `origin`
+///
+/// Адрес берется из настроек, а не из заголовка `Host`, чтобы
+/// перенаправление нельзя было увести на чужой домен.
+pub(crate) fn redirect(origin: &str) -> Router {
+    let origin = origin.trim_end_matches('/').to_string();
+    Router::new().fallback(move |uri: Uri| {
+        let path = uri.path_and_query().map_or("/", |path| path.as_str());
+        let target = format!("{origin}{path}");
+        async move { Redirect::permanent(&target) }
+    })
+}
+
+/// Слушатель, отдающий серверу соединения после рукопожатия TLS
+pub(crate) struct TlsListener {
+    connections: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+    local_addr: SocketAddr,
+}
+impl TlsListener {
+    /// Открывает порт и начинает принимать соединения
+    ///
+    /// # Аргументы
+    ///
+    /// * `addr` - Адрес вида `host:port`
+    /// * `config` - Конфигурация rustls
+    pub(crate) async fn bind(addr: &str, config: Arc<ServerConfig>) -> AppResult<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        let (tx, rx) = mpsc::channel(ACCEPT_BACKLOG);
+        tokio::spawn(accept_loop(listener, TlsAcceptor::from(config), tx));
+        Ok(Self {
+            connections: rx,
+            local_addr,
+        })
+    }
+}
+impl Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.connections.recv().await {
+            Some(connection) => connection,
+            // Цикл приема завершается только после закрытия слушателя
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+/// Клиент, предъявивший сертификат, подписанный УЦ из `client_ca_path`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ClientCertificate {
+    /// Первое DNS-имя из SAN или, если их нет, CN субъекта
+    pub(crate) name: String,
+}
+impl ClientCertificate {
+    /// Извлекает имя клиента из сертификата в формате DER
+    ///
+    /// # Возвращает
+    ///
+    /// * `Some(ClientCertificate)` - Сертификат содержит имя
+    /// * `None` - Сертификат не разбирается или не содержит ни SAN, ни CN
+    fn from_der(der: &[u8]) -> Option<Self> {
+        let (_, cert) = X509Certificate::from_der(der).ok()?;
+        let san = cert
+            .subject_alternative_name()
+            .ok()
+            .flatten()
+            .and_then(|san| {
+                san.value.general_names.iter().find_map(|name| match name {
+                    GeneralName::DNSName(name) => Some(name.to_string()),
+                    _ => None,
+                })
+            });
+        let name = match san {
+            Some(name) => name,
+            None => cert
+                .subject()
+                .iter_common_name()
+                .next()
+                .and_then(|cn| cn.as_str().ok())?
+                .to_string(),
+        };
+        Some(Self {
+            name: name.to_lowercase(),
+        })
+    }
+}
+
+/// Сведения о соединении TLS, которые сервер передает запросам
+#[derive(Debug, Clone)]
+pub(crate) struct TlsConnectInfo {
+    remote_addr: SocketAddr,
+    client: Option<ClientCertificate>,
+}
+impl Connected<IncomingStream<'_, TlsListener>> for TlsConnectInfo {
+    fn connect_info(stream: IncomingStream<'_, TlsListener>) -> Self {
+        // rustls отдает только сертификаты, прошедшие проверку при рукопожатии
+        let client = stream
+            .io()
+            .get_ref()
+            .1
+            .peer_certificates()
+            .and_then(|certs| certs.first())
+            .and_then(|cert| ClientCertificate::from_der(cert));
+        Self {
+            remote_addr: *stream.remote_addr(),
+            client,
+        }
+    }
+}
+
+/// Переносит сведения о соединении TLS в расширения запроса
+///
+/// Адрес клиента добавляется как `ConnectInfo<SocketAddr>`, как и без TLS,
+/// а клиентский сертификат - как [`ClientCertificate`] для middleware `auth`.
+pub(crate) async fn connect_info(mut req: Request<Body>, next: Next) -> Response {
+    if let Some(ConnectInfo(info)) = req.extensions_mut().remove::<ConnectInfo<TlsConnectInfo>>() {
+        req.extensions_mut().insert(ConnectInfo(info.remote_addr));
+        if let Some(client) = info.client {
+            req.extensions_mut().insert(client);
+        }
+    }
+    next.run(req).await
+}
+
+/// Принимает TCP-соединения, пока слушатель не закрыт
+async fn accept_loop(
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+    tx: mpsc::Sender<(TlsStream<TcpStream>, SocketAddr)>,
+) {
+    loop {
+        let (stream, addr) = tokio::select! {
+            _ = tx.closed() => return,
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    tracing::warn!("Failed to accept connection: {e}");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            },
+        };
+        let acceptor = acceptor.clone();
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                Ok(Ok(stream)) => {
+                    let _ = tx.send((stream, addr)).await;
+                }
+                Ok(Err(e)) => tracing::debug!(%addr, "TLS handshake failed: {e}"),
+                Err(_) => tracing::debug!(%addr, "TLS handshake timed out"),
+            }
+        });
+    }
+}
+
+fn load_certified_key(
+    cert_path: &std::path::Path,
+    key_path: &std::path::Path,
+    provider: &CryptoProvider,
+) -> AppResult<CertifiedKey> {
+    let certs = load_certs(cert_path)?;
+    let key =
+        PrivateKeyDer::from_pem_file(key_path).map_err(|e| tls_error(key_path, e.to_string()))?;
+    let key = provider
+        .key_provider
+        .load_private_key(key)
+        .map_err(|e| tls_error(key_path, e.to_string()))?;
+    let certified = CertifiedKey::new(certs, key);
+    certified
+        .keys_match()
+        .map_err(|e| tls_error(key_path, e.to_string()))?;
+    Ok(certified)
+}
+
+fn load_certs(path: &std::path::Path) -> AppResult<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| tls_error(path, e.to_string()))?;
+    if certs.is_empty() {
+        return Err(tls_error(path, "no certificates found".to_string()));
+    }
+    Ok(certs)
+}
+
+fn modified(settings: &TlsSettings) -> AppResult<(SystemTime, SystemTime)> {
+    Ok((
+        modified_at(&settings.cert_path)?,
+        modified_at(&settings.key_path)?,
+    ))
+}
+
+fn modified_at(path: &std::path::Path) -> AppResult<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .map_err(|e| tls_error(path, e.to_string()))
+}
+
+fn tls_error(path: &std::path::Path, message: String) -> AppError {
+    AppError::Custom(format!("tls: {}: {message}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use rustls::ClientConfig;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::TlsConnector;
+    use tower::ServiceExt;
+
+    /// Самоподписанный сертификат для `localhost` в формате PEM
+    fn self_signed() -> (String, String) {
+        let generated = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        (generated.cert.pem(), generated.signing_key.serialize_pem())
+    }
+
+    fn write_pair(dir: &std::path::Path, (cert, key): &(String, String)) {
+        std::fs::write(dir.join("cert.pem"), cert).unwrap();
+        std::fs::write(dir.join("key.pem"), key).unwrap();
+    }
+
+    fn settings(dir: &std::path::Path) -> TlsSettings {
+        TlsSettings {
+            cert_path: dir.join("cert.pem"),
+            key_path: dir.join("key.pem"),
+            min_version: TlsVersion::Tls12,
+            client_ca_path: None,
+            client_auth: ClientAuth::Required,
+            reload_interval_secs: 60,
+            redirect_port: None,
+            hsts_max_age_secs: 31_536_000,
+            hsts_include_subdomains: false,
+        }
+    }
+
+    async fn start(config: Arc<ServerConfig>) -> SocketAddr {
+        let listener = TlsListener::bind("127.0.0.1:0", config).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new().route("/", get(|| async { "hello" }));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        addr
+    }
+
+    /// Выполняет запрос и возвращает ответ и сертификат, предъявленный сервером
+    async fn request(
+        addr: SocketAddr,
+        trusted: &[&str],
+        versions: &[&'static SupportedProtocolVersion],
+    ) -> io::Result<(String, CertificateDer<'static>)> {
+        request_as(addr, trusted, versions, None).await
+    }
+
+    /// То же, что `request`, но с клиентским сертификатом, если он передан
+    async fn request_as(
+        addr: SocketAddr,
+        trusted: &[&str],
+        versions: &[&'static SupportedProtocolVersion],
+        client: Option<&(String, String)>,
+    ) -> io::Result<(String, CertificateDer<'static>)> {
+        let mut roots = RootCertStore::empty();
+        for cert in trusted {
+            roots
+                .add(CertificateDer::from_pem_slice(cert.as_bytes()).unwrap())
+                .unwrap();
+        }
+        let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_protocol_versions(versions)
+            .unwrap()
+            .with_root_certificates(roots);
+        let config = match client {
+            Some((cert, key)) => config
+                .with_client_auth_cert(
+                    vec![CertificateDer::from_pem_slice(cert.as_bytes()).unwrap()],
+                    PrivateKeyDer::from_pem_slice(key.as_bytes()).unwrap(),
+                )
+                .unwrap(),
+            None => config.with_no_client_auth(),
+        };
+        let stream = TcpStream::connect(addr).await?;
+        let mut stream = TlsConnector::from(Arc::new(config))
+            .connect("localhost".try_into().unwrap(), stream)
+            .await?;
+        let peer = stream.get_ref().1.peer_certificates().unwrap()[0].clone();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        Ok((response, peer))
+    }
+
+    #[tokio::test]
+    async fn test_serve_and_reload() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let first = self_signed();
+        write_pair(dir, &first);
+        let (config, resolver) = server_config(&settings(dir)).unwrap();
+        let addr = start(config).await;
+        let second = self_signed();
+        let trusted = [first.0.as_str(), second.0.as_str()];
+
+        let (response, peer) = request(addr, &trusted, &[&TLS13]).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("hello"));
+        let first_der = CertificateDer::from_pem_slice(first.0.as_bytes()).unwrap();
+        assert_eq!(peer, first_der);
+        assert!(!resolver.reload_if_changed().unwrap());
+
+        // Ключ от другого сертификата не принимается, прежний остается в работе
+        std::fs::write(dir.join("cert.pem"), &second.0).unwrap();
+        assert!(resolver.reload_if_changed().is_err());
+        let (_, peer) = request(addr, &trusted, &[&TLS12]).await.unwrap();
+        assert_eq!(peer, first_der);
+
+        write_pair(dir, &second);
+        assert!(resolver.reload_if_changed().unwrap());
+        let (_, peer) = request(addr, &trusted, &[&TLS13]).await.unwrap();
+        assert_eq!(
+            peer,
+            CertificateDer::from_pem_slice(second.0.as_bytes()).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handshake_policy() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let pair = self_signed();
+        write_pair(dir, &pair);
+        let trusted = [pair.0.as_str()];
+
+        let tls13 = TlsSettings {
+            min_version: TlsVersion::Tls13,
+            ..settings(dir)
+        };
+        let addr = start(server_config(&tls13).unwrap().0).await;
+        assert!(request(addr, &trusted, &[&TLS12]).await.is_err());
+        assert!(request(addr, &trusted, &[&TLS13]).await.is_ok());
+
+        // Клиент без сертификата не получает ответа, если сертификат обязателен
+        std::fs::write(dir.join("ca.pem"), &pair.0).unwrap();
+        let mtls = TlsSettings {
+            client_ca_path: Some(dir.join("ca.pem")),
+            ..settings(dir)
+        };
+        let addr = start(server_config(&mtls).unwrap().0).await;
+        let result = request(addr, &trusted, &[&TLS13]).await;
+        assert!(result.is_err_and(|e| e.to_string().contains("CertificateRequired")));
+        let optional = TlsSettings {
+            client_auth: ClientAuth::Optional,
+            ..mtls
+        };
+        let addr = start(server_config(&optional).unwrap().0).await;
+        assert!(request(addr, &trusted, &[&TLS13]).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_client_certificate() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let pair = self_signed();
+        write_pair(dir, &pair);
+        let generated = rcgen::generate_simple_self_signed(vec!["billing".to_string()]).unwrap();
+        let client = (generated.cert.pem(), generated.signing_key.serialize_pem());
+        std::fs::write(dir.join("ca.pem"), &client.0).unwrap();
+        let optional = TlsSettings {
+            client_ca_path: Some(dir.join("ca.pem")),
+            client_auth: ClientAuth::Optional,
+            ..settings(dir)
+        };
+        let listener = TlsListener::bind("127.0.0.1:0", server_config(&optional).unwrap().0)
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new()
+            .route(
+                "/",
+                get(
+                    |ConnectInfo(remote): ConnectInfo<SocketAddr>,
+                     client: Option<axum::Extension<ClientCertificate>>| async move {
+                        let name = client.map_or("-".to_string(), |client| client.0.name);
+                        format!("{} {name}", remote.ip())
+                    },
+                ),
+            )
+            .layer(axum::middleware::from_fn(connect_info));
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<TlsConnectInfo>(),
+            )
+            .await
+        });
+        let trusted = [pair.0.as_str()];
+
+        let (response, _) = request_as(addr, &trusted, &[&TLS13], Some(&client))
+            .await
+            .unwrap();
+        assert!(response.ends_with("127.0.0.1 billing"));
+        let (response, _) = request(addr, &trusted, &[&TLS13]).await.unwrap();
+        assert!(response.ends_with("127.0.0.1 -"));
+
+        // Без DNS-имен в SAN используется CN субъекта
+        let mut params = rcgen::CertificateParams::new(Vec::<String>::new()).unwrap();
+        params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, "Reporting");
+        let cert = params
+            .self_signed(&rcgen::KeyPair::generate().unwrap())
+            .unwrap();
+        assert_eq!(
+            ClientCertificate::from_der(cert.der()).unwrap().name,
+            "reporting"
+        );
+        assert!(ClientCertificate::from_der(b"not a certificate").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_redirect_and_hsts() {
+        let app = redirect("https://alfred.example.com/");
+        let request = axum::http::Request::get("/api/v1/health?full=1")
+            .header(http::header::HOST, "evil.example.com")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(
+            response.status(),
+            axum::http::StatusCode::PERMANENT_REDIRECT
+        );
+        assert_eq!(
+            response.headers()[http::header::LOCATION],
+            "https://alfred.example.com/api/v1/health?full=1"
+        );
+
+        let dir = std::path::Path::new("unused");
+        assert_eq!(hsts(&settings(dir)).unwrap(), "max-age=31536000");
+        let subdomains = TlsSettings {
+            hsts_include_subdomains: true,
+            ..settings(dir)
+        };
+        assert_eq!(
+            hsts(&subdomains).unwrap(),
+            "max-age=31536000; includeSubDomains"
+        );
+        let disabled = TlsSettings {
+            hsts_max_age_secs: 0,
+            ..settings(dir)
+        };
+        assert!(hsts(&disabled).is_none());
+    }
+}
@@ -0,0 +1,276 @@
+use std::sync::Arc;
+
+use validator::Validate;
+
+use crate::{
+    AppError, AppResult,
+    crypto::{API_TOKEN_PREFIX, generate_api_token, hash_api_token},
+    models::{ApiToken, AuditAction, IssuedApiToken, NewApiToken, NewAuditEvent, User},
+    storage::{ApiTokensRepository, AuditRepository, UsersRepository},
+};
+
+/// Количество символов ключа, сохраняемых для отображения в списке
+const DISPLAY_PREFIX_LEN: usize = API_TOKEN_PREFIX.len() + 8;
+
+/// Сервис для работы с API-ключами
+///
+/// Выпускает, отзывает и проверяет долгоживущие API-ключи,
+/// которыми интеграции аутентифицируются вместо пароля.
+#[derive(Clone)]
+pub struct ApiTokensService {
+    pub storage: Arc<dyn ApiTokensRepository>,
+    pub users: Arc<dyn UsersRepository>,
+    pub audit: Option<Arc<dyn AuditRepository>>,
+}
+impl ApiTokensService {
+    /// Создает новый экземпляр сервиса API-ключей
+    ///
+    /// # Аргументы
+    ///
+    /// * `storage` - Реализация трейта `ApiTokensRepository` в `Arc`
+    /// * `users` - Реализация трейта `UsersRepository` в `Arc`
+    ///
+    /// # Возвращает
+    ///
+    /// Новый экземпляр `ApiTokensService`
+    pub fn new(storage: Arc<dyn ApiTokensRepository>, users: Arc<dyn UsersRepository>) -> Self {
+        Self {
+            storage,
+            users,
+            audit: None,
+        }
+    }
+    /// Подключает журнал аудита
+    ///
+    /// # Аргументы
+    ///
+    /// * `audit` - Реализация трейта `AuditRepository` в `Arc`
+    pub fn with_audit(mut self, audit: Arc<dyn AuditRepository>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+    /// Выпускает новый API-ключ
+    ///
+    /// # Аргументы
+    ///
+    /// * `actor` - Пользователь, выпускающий ключ
+    /// * `owner` - Владелец ключа (сам `actor` или сервисный аккаунт)
+    /// * `data` - Название, области действия и срок действия ключа
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(IssuedApiToken)` - Ключ в открытом виде; повторно он не выдается
+    /// * `Err(AppError::AccessDenied)` - Запрошены административные области
+    ///   для владельца без административной роли
+    /// * `Err(AppError)` - Невалидные данные или ошибка сохранения
+    pub async fn issue(
+        &self,
+        actor: &User,
+        owner: &User,
+        data: NewApiToken,
+    ) -> AppResult<IssuedApiToken> {
+        data.validate()?;
+        if data.expires_at.is_some_and(|at| at <= chrono::Utc::now()) {
+            return Err(AppError::InvalidInput);
+        }
+        if data.scopes.iter().any(|s| s.is_admin()) && !owner.role.is_admin() {
+            return Err(AppError::AccessDenied);
+        }
+        let token = generate_api_token();
+        let details = self
+            .storage
+            .create_token(
+                owner.user_id,
+                &token[..DISPLAY_PREFIX_LEN],
+                &hash_api_token(&token),
+                data,
+            )
+            .await?;
+        self.record(NewAuditEvent {
+            user_id: Some(owner.user_id),
+            actor_id: Some(actor.user_id),
+            action: AuditAction::ApiTokenIssued,
+            details: Some(format!("token_id={}", details.token_id)),
+        })
+        .await;
+        Ok(IssuedApiToken { token, details })
+    }
+    /// Получает список ключей пользователя
+    ///
+    /// # Аргументы
+    ///
+    /// * `owner_id` - UUID владельца ключей
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(Vec<ApiToken>)` - Ключи без открытых значений
+    /// * `Err(AppError)` - Ошибка чтения
+    pub async fn list(&self, owner_id: uuid::Uuid) -> AppResult<Vec<ApiToken>> {
+        self.storage.list_tokens(owner_id).await
+    }
+    /// Отзывает API-ключ
+    ///
+    /// # Аргументы
+    ///
+    /// * `actor` - Пользователь, отзывающий ключ
+    /// * `owner_id` - UUID владельца ключа
+    /// * `token_id` - UUID ключа
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(ApiToken)` - Отозванный ключ
+    /// * `Err(AppError::EntryNotFound)` - Ключ не найден у этого владельца
+    pub async fn revoke(
+        &self,
+        actor: &User,
+        owner_id: uuid::Uuid,
+        token_id: uuid::Uuid,
+    ) -> AppResult<ApiToken> {
+        let revoked = self.storage.revoke_token(owner_id, token_id).await?;
+        self.record(NewAuditEvent {
+            user_id: Some(owner_id),
+            actor_id: Some(actor.user_id),
+            action: AuditAction::ApiTokenRevoked,
+            details: Some(format!("token_id={token_id}")),
+        })
+        .await;
+        Ok(revoked)
+    }
+    /// Аутентифицирует запрос по API-ключу
+    ///
+    /// При успехе отмечает время последнего использования ключа.
+    ///
+    /// # Аргументы
+    ///
+    /// * `token` - Ключ в открытом виде
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok((User, ApiToken))` - Владелец ключа и сведения о ключе
+    /// * `Err(AppError::InvalidCredentials)` - Ключ неизвестен, отозван,
+    ///   просрочен или его владелец удален
+    pub async fn authenticate(&self, token: &str) -> AppResult<(User, ApiToken)> {
+        if !token.starts_with(API_TOKEN_PREFIX) {
+            return Err(AppError::InvalidCredentials);
+        }
+        let details = match self
+            .storage
+            .find_token_by_hash(&hash_api_token(token))
+            .await
+        {
+            Ok(details) => details,
+            Err(AppError::EntryNotFound) => return Err(AppError::InvalidCredentials),
+            Err(e) => return Err(e),
+        };
+        if !details.is_active(chrono::Utc::now()) {
+            return Err(AppError::InvalidCredentials);
+        }
+        let user = match self.users.get(details.user_id).await {
+            Ok(user) => user,
+            Err(AppError::EntryNotFound) => return Err(AppError::InvalidCredentials),
+            Err(e) => return Err(e),
+        };
+        if let Err(e) = self.storage.touch_token(details.token_id).await {
+            tracing::error!("failed to update api token usage: {e}");
+        }
+        Ok((user, details))
+    }
+    /// Записывает событие в журнал аудита, если он подключен
+    async fn record(&self, event: NewAuditEvent) {
+        if let Some(audit) = &self.audit
+            && let Err(e) = audit.record(event).await
+        {
+            tracing::error!("failed to record audit event: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::{
+        models::{ApiScope, SignupData, UserRole},
+        storage::PgStorage,
+    };
+
+    async fn setup(pool: PgPool) -> AppResult<(ApiTokensService, User)> {
+        let storage = Arc::new(PgStorage::with_pool(pool));
+        let user = storage
+            .create(SignupData {
+                email: "keys@example.com".to_string(),
+                password: "str0nGp@ssw0rD".to_string(),
+                role: UserRole::Employee,
+            })
+            .await?;
+        let service = ApiTokensService::new(storage.clone(), storage.clone()).with_audit(storage);
+        Ok((service, user))
+    }
+
+    fn new_token(scopes: Vec<ApiScope>) -> NewApiToken {
+        NewApiToken {
+            name: "script".to_string(),
+            scopes,
+            expires_at: None,
+        }
+    }
+
+    #[sqlx::test]
+    async fn issue_and_authenticate_test(pool: PgPool) -> AppResult<()> {
+        let (service, user) = setup(pool).await?;
+        let issued = service
+            .issue(&user, &user, new_token(vec![ApiScope::ProfileRead]))
+            .await?;
+        assert!(issued.token.starts_with(API_TOKEN_PREFIX));
+        assert!(issued.token.starts_with(&issued.details.prefix));
+
+        let (owner, details) = service.authenticate(&issued.token).await?;
+        assert_eq!(owner.user_id, user.user_id);
+        assert!(details.allows(ApiScope::ProfileRead));
+        let listed = service.list(user.user_id).await?;
+        assert!(listed[0].last_used_at.is_some());
+
+        assert!(matches!(
+            service.authenticate("alf_unknown").await,
+            Err(AppError::InvalidCredentials)
+        ));
+
+        service
+            .revoke(&user, user.user_id, issued.details.token_id)
+            .await?;
+        assert!(matches!(
+            service.authenticate(&issued.token).await,
+            Err(AppError::InvalidCredentials)
+        ));
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn issue_rejects_admin_scopes_and_past_expiry_test(pool: PgPool) -> AppResult<()> {
+        let (service, user) = setup(pool).await?;
+        assert!(matches!(
+            service
+                .issue(&user, &user, new_token(vec![ApiScope::UsersRead]))
+                .await,
+            Err(AppError::AccessDenied)
+        ));
+
+        let mut expired = new_token(vec![ApiScope::ProfileRead]);
+        expired.expires_at = Some(chrono::Utc::now() - chrono::Duration::minutes(1));
+        assert!(matches!(
+            service.issue(&user, &user, expired).await,
+            Err(AppError::InvalidInput)
+        ));
+
+        assert!(
+            service
+                .issue(&user, &user, new_token(vec![]))
+                .await
+                .is_err()
+        );
+        Ok(())
+    }
+}
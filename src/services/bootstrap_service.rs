@@ -0,0 +1,225 @@
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+
+use crate::{
+    AppError, AppResult,
+    crypto::{constant_time_eq, random_urlsafe},
+    models::{User, UserRole},
+    services::UsersService,
+    settings::BootstrapSettings,
+};
+
+/// Количество случайных байт в токене настройки
+const SETUP_TOKEN_BYTES: usize = 32;
+
+/// Итог первичной настройки при запуске
+#[derive(Debug, Clone, PartialEq)]
+pub enum BootstrapOutcome {
+    /// Владелец уже существует, первичная настройка отключена
+    OwnerExists,
+    /// Владелец создан по настройкам
+    OwnerCreated(Box<User>),
+    /// Выпущен одноразовый токен, с которым первая регистрация получает роль владельца
+    SetupToken(String),
+    /// Владельца нет, но выдача токена настройки отключена
+    Disabled,
+}
+
+/// Сервис первичного создания владельца
+///
+/// У нового развертывания нет ни одного администратора, а регистрация
+/// всегда выдает роль по умолчанию. Сервис создает владельца из настроек
+/// либо выпускает одноразовый токен настройки. Запуск идемпотентен:
+/// как только владелец появляется, сервис больше ничего не делает.
+pub struct BootstrapService {
+    users: Arc<UsersService>,
+    setup_token: Mutex<Option<String>>,
+}
+impl BootstrapService {
+    /// Создает новый экземпляр сервиса первичной настройки
+    ///
+    /// # Аргументы
+    ///
+    /// * `users` - Сервис пользователей
+    ///
+    /// # Возвращает
+    ///
+    /// Новый экземпляр `BootstrapService`
+    pub fn new(users: Arc<UsersService>) -> Self {
+        Self {
+            users,
+            setup_token: Mutex::new(None),
+        }
+    }
+    /// Выполняет первичную настройку при запуске
+    ///
+    /// # Аргументы
+    ///
+    /// * `settings` - Настройки первичного создания владельца
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(BootstrapOutcome)` - Что было сделано
+    /// * `Err(AppError::WeakPassword)` - Пароль владельца не соответствует парольной политике
+    /// * `Err(AppError::Custom)` - Email владельца занят аккаунтом с другой ролью
+    pub async fn run(&self, settings: &BootstrapSettings) -> AppResult<BootstrapOutcome> {
+        if self.users.has_owner().await? {
+            self.setup_token.lock().await.take();
+            return Ok(BootstrapOutcome::OwnerExists);
+        }
+        if let (Some(email), Some(password)) = (&settings.owner_email, &settings.owner_password) {
+            return match self
+                .users
+                .signup(email, password, Some(UserRole::Owner.as_ref()))
+                .await
+            {
+                Ok(owner) => Ok(BootstrapOutcome::OwnerCreated(Box::new(owner))),
+                // Владельца мог одновременно создать другой экземпляр сервера
+                Err(AppError::EntryAlreadyExists) if self.users.has_owner().await? => {
+                    Ok(BootstrapOutcome::OwnerExists)
+                }
+                Err(AppError::EntryAlreadyExists) => Err(AppError::Custom(format!(
+                    "bootstrap owner {email} already exists without the owner role"
+                ))),
+                Err(e) => Err(e),
+            };
+        }
+        if !settings.setup_token {
+            return Ok(BootstrapOutcome::Disabled);
+        }
+        let token = random_urlsafe(SETUP_TOKEN_BYTES);
+        *self.setup_token.lock().await = Some(token.clone());
+        Ok(BootstrapOutcome::SetupToken(token))
+    }
+    /// Регистрирует владельца по одноразовому токену настройки
+    ///
+    /// Токен действует, пока в системе нет владельца, и сгорает
+    /// после успешной регистрации.
+    ///
+    /// # Аргументы
+    ///
+    /// * `email` - Email владельца
+    /// * `password` - Пароль владельца
+    /// * `token` - Токен настройки, выведенный при запуске
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(User)` - Созданный владелец
+    /// * `Err(AppError::AccessDenied)` - Токен неверен, уже использован или владелец уже есть
+    /// * `Err(AppError)` - Ошибка регистрации
+    pub async fn claim_owner(&self, email: &str, password: &str, token: &str) -> AppResult<User> {
+        let mut setup_token = self.setup_token.lock().await;
+        let valid = setup_token
+            .as_deref()
+            .is_some_and(|expected| constant_time_eq(expected.as_bytes(), token.as_bytes()));
+        if !valid {
+            return Err(AppError::AccessDenied);
+        }
+        if self.users.has_owner().await? {
+            *setup_token = None;
+            return Err(AppError::AccessDenied);
+        }
+        let owner = self
+            .users
+            .signup(email, password, Some(UserRole::Owner.as_ref()))
+            .await?;
+        *setup_token = None;
+        tracing::info!(user_id = %owner.user_id, "Owner account claimed with setup token");
+        Ok(owner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::PgPool;
+
+    use crate::storage::PgStorage;
+
+    fn service(pool: PgPool) -> BootstrapService {
+        let storage = Arc::new(PgStorage::with_pool(pool));
+        BootstrapService::new(Arc::new(UsersService::new(storage)))
+    }
+
+    fn owner_settings(email: &str) -> BootstrapSettings {
+        BootstrapSettings {
+            owner_email: Some(email.to_string()),
+            owner_password: Some("Correct-Horse-Battery-9".to_string()),
+            setup_token: true,
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_owner_from_settings(pool: PgPool) -> AppResult<()> {
+        let bootstrap = service(pool);
+        let settings = owner_settings("owner@example.com");
+
+        let BootstrapOutcome::OwnerCreated(owner) = bootstrap.run(&settings).await? else {
+            panic!("owner should be created");
+        };
+        assert_eq!(owner.role, UserRole::Owner);
+        assert_eq!(owner.email, "owner@example.com");
+
+        // Повторный запуск ничего не меняет, даже с другими настройками
+        assert_eq!(
+            bootstrap.run(&settings).await?,
+            BootstrapOutcome::OwnerExists
+        );
+        let other = owner_settings("other@example.com");
+        assert_eq!(bootstrap.run(&other).await?, BootstrapOutcome::OwnerExists);
+        assert_eq!(bootstrap.users.list(None, None, None, None).await?.total, 1);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_owner_email_taken(pool: PgPool) -> AppResult<()> {
+        let bootstrap = service(pool);
+        bootstrap
+            .users
+            .signup("owner@example.com", "Correct-Horse-Battery-9", None)
+            .await?;
+
+        let result = bootstrap.run(&owner_settings("owner@example.com")).await;
+        assert!(matches!(result, Err(AppError::Custom(_))));
+        assert!(!bootstrap.users.has_owner().await?);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_setup_token(pool: PgPool) -> AppResult<()> {
+        let bootstrap = service(pool);
+        let disabled = BootstrapSettings {
+            setup_token: false,
+            ..Default::default()
+        };
+        assert_eq!(bootstrap.run(&disabled).await?, BootstrapOutcome::Disabled);
+
+        let BootstrapOutcome::SetupToken(token) =
+            bootstrap.run(&BootstrapSettings::default()).await?
+        else {
+            panic!("setup token should be issued");
+        };
+        let password = "Correct-Horse-Battery-9";
+        let result = bootstrap
+            .claim_owner("owner@example.com", password, "wrong")
+            .await;
+        assert!(matches!(result, Err(AppError::AccessDenied)));
+
+        let owner = bootstrap
+            .claim_owner("owner@example.com", password, &token)
+            .await?;
+        assert_eq!(owner.role, UserRole::Owner);
+
+        // Токен одноразовый
+        let result = bootstrap
+            .claim_owner("second@example.com", password, &token)
+            .await;
+        assert!(matches!(result, Err(AppError::AccessDenied)));
+        assert_eq!(
+            bootstrap.run(&BootstrapSettings::default()).await?,
+            BootstrapOutcome::OwnerExists
+        );
+        Ok(())
+    }
+}
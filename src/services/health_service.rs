@@ -0,0 +1,292 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use serde::Serialize;
+use tokio::task::JoinSet;
+use utoipa::ToSchema;
+
+use crate::{AppError, AppResult, storage::PgStorage};
+
+/// Время на одну проверку по умолчанию
+pub const DEFAULT_HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Проверка зависимости, без которой сервис не может обслуживать запросы
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+    /// Название компонента в ответе проверки готовности
+    fn name(&self) -> &'static str;
+    /// Проверяет доступность компонента
+    async fn check(&self) -> AppResult<()>;
+}
+
+/// Доступность базы данных
+pub struct DatabaseCheck(pub PgStorage);
+
+#[async_trait]
+impl HealthCheck for DatabaseCheck {
+    fn name(&self) -> &'static str {
+        "database"
+    }
+
+    async fn check(&self) -> AppResult<()> {
+        self.0.ping().await
+    }
+}
+
+/// Все миграции приложения применены к базе данных
+pub struct MigrationsCheck(pub PgStorage);
+
+#[async_trait]
+impl HealthCheck for MigrationsCheck {
+    fn name(&self) -> &'static str {
+        "migrations"
+    }
+
+    async fn check(&self) -> AppResult<()> {
+        let pending = self.0.pending_migrations().await?;
+        if pending.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Custom(format!("pending migrations: {pending:?}")))
+        }
+    }
+}
+
+/// Доступность внешнего сервера по TCP, например почтового
+pub struct TcpCheck {
+    name: &'static str,
+    addr: String,
+}
+
+impl TcpCheck {
+    /// Создает проверку
+    ///
+    /// # Аргументы
+    ///
+    /// * `name` - Название компонента
+    /// * `addr` - Адрес в формате `host:port`
+    pub fn new(name: &'static str, addr: impl Into<String>) -> Self {
+        Self {
+            name,
+            addr: addr.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl HealthCheck for TcpCheck {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    async fn check(&self) -> AppResult<()> {
+        tokio::net::TcpStream::connect(&self.addr).await?;
+        Ok(())
+    }
+}
+
+/// Итог проверки готовности
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Readiness {
+    /// Все компоненты доступны
+    Ready,
+    /// Хотя бы один компонент недоступен
+    NotReady,
+    /// Сервер завершает работу и не принимает новую нагрузку
+    ShuttingDown,
+}
+
+/// Состояние компонента
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ComponentStatus {
+    Up,
+    Down,
+}
+
+/// Результат проверки одного компонента
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ComponentHealth {
+    pub status: ComponentStatus,
+    /// Длительность проверки, миллисекунды
+    pub duration_ms: u64,
+    /// Причина недоступности; подробности пишутся в журнал
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Отчет о готовности сервиса
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct HealthReport {
+    pub status: Readiness,
+    /// Результаты по компонентам
+    pub checks: BTreeMap<String, ComponentHealth>,
+}
+
+impl HealthReport {
+    /// Сервис может принимать запросы
+    pub fn is_ready(&self) -> bool {
+        self.status == Readiness::Ready
+    }
+}
+
+/// Сервис проверки готовности
+///
+/// Выполняет зарегистрированные проверки параллельно, каждую
+/// с ограничением по времени, и помнит, что сервер начал завершение работы.
+pub struct HealthService {
+    checks: Vec<Arc<dyn HealthCheck>>,
+    timeout: Duration,
+    shutting_down: AtomicBool,
+}
+
+impl Default for HealthService {
+    fn default() -> Self {
+        Self::new(DEFAULT_HEALTH_CHECK_TIMEOUT)
+    }
+}
+
+impl HealthService {
+    /// Создает сервис без проверок
+    ///
+    /// # Аргументы
+    ///
+    /// * `timeout` - Время на одну проверку; не уложившийся компонент считается недоступным
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            checks: Vec::new(),
+            timeout,
+            shutting_down: AtomicBool::new(false),
+        }
+    }
+    /// Добавляет проверку компонента
+    pub fn with_check(mut self, check: impl HealthCheck + 'static) -> Self {
+        self.checks.push(Arc::new(check));
+        self
+    }
+    /// Отмечает начало завершения работы: дальше сервис не готов
+    pub fn begin_shutdown(&self) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+    }
+    /// Сервер завершает работу
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+    /// Проверяет готовность сервиса
+    ///
+    /// Во время завершения работы компоненты не проверяются.
+    ///
+    /// # Возвращает
+    ///
+    /// `HealthReport` с итогом и результатами по компонентам
+    pub async fn readiness(&self) -> HealthReport {
+        if self.is_shutting_down() {
+            return HealthReport {
+                status: Readiness::ShuttingDown,
+                checks: BTreeMap::new(),
+            };
+        }
+        let mut tasks = JoinSet::new();
+        for check in &self.checks {
+            let check = check.clone();
+            let timeout = self.timeout;
+            tasks.spawn(async move {
+                let started = Instant::now();
+                let error = match tokio::time::timeout(timeout, check.check()).await {
+                    Ok(Ok(())) => None,
+                    Ok(Err(e)) => {
+                        tracing::warn!("health check '{}' failed: {e}", check.name());
+                        Some("unavailable")
+                    }
+                    Err(_) => {
+                        tracing::warn!("health check '{}' timed out", check.name());
+                        Some("timed out")
+                    }
+                };
+                let health = ComponentHealth {
+                    status: if error.is_none() {
+                        ComponentStatus::Up
+                    } else {
+                        ComponentStatus::Down
+                    },
+                    duration_ms: started.elapsed().as_millis() as u64,
+                    error: error.map(str::to_string),
+                };
+                (check.name().to_string(), health)
+            });
+        }
+        let checks: BTreeMap<_, _> = tasks.join_all().await.into_iter().collect();
+        let ready = checks
+            .values()
+            .all(|health| health.status == ComponentStatus::Up);
+        HealthReport {
+            status: if ready {
+                Readiness::Ready
+            } else {
+                Readiness::NotReady
+            },
+            checks,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fixed(&'static str, Option<Duration>, bool);
+
+    #[async_trait]
+    impl HealthCheck for Fixed {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        async fn check(&self) -> AppResult<()> {
+            if let Some(delay) = self.1 {
+                tokio::time::sleep(delay).await;
+            }
+            if self.2 {
+                Ok(())
+            } else {
+                Err(AppError::Custom("connection refused".into()))
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_readiness() {
+        let service = HealthService::new(Duration::from_millis(50))
+            .with_check(Fixed("database", None, true))
+            .with_check(Fixed("mail", None, true));
+        let report = service.readiness().await;
+        assert!(report.is_ready());
+        assert_eq!(report.checks["mail"].status, ComponentStatus::Up);
+
+        let service = service
+            .with_check(Fixed("broken", None, false))
+            .with_check(Fixed("slow", Some(Duration::from_secs(5)), true));
+        let report = service.readiness().await;
+        assert_eq!(report.status, Readiness::NotReady);
+        assert_eq!(report.checks["database"].status, ComponentStatus::Up);
+        assert_eq!(
+            report.checks["broken"].error.as_deref(),
+            Some("unavailable")
+        );
+        assert_eq!(report.checks["slow"].error.as_deref(), Some("timed out"));
+        assert!(report.checks["slow"].duration_ms < 1000);
+
+        service.begin_shutdown();
+        let report = service.readiness().await;
+        assert_eq!(report.status, Readiness::ShuttingDown);
+        assert!(report.checks.is_empty());
+    }
+}
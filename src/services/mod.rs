@@ -1,2 +1,15 @@
+mod api_tokens_service;
+pub use api_tokens_service::ApiTokensService;
+mod bootstrap_service;
+pub use bootstrap_service::{BootstrapOutcome, BootstrapService};
+mod health_service;
+pub use health_service::{
+    ComponentHealth, ComponentStatus, DEFAULT_HEALTH_CHECK_TIMEOUT, DatabaseCheck, HealthCheck,
+    HealthReport, HealthService, MigrationsCheck, Readiness, TcpCheck,
+};
+mod oidc_service;
+pub use oidc_service::{AuthorizationRequest, OidcService, ProviderMetadata};
 mod users_service;
-pub use users_service::{UsersListResponse, UsersService};
+pub use users_service::{
+    DEFAULT_DELETION_GRACE_DAYS, UserDataExport, UserPreferences, UsersListResponse, UsersService,
+};
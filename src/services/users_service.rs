@@ -1,14 +1,27 @@
 use std::{str::FromStr, sync::Arc};
 
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use validator::Validate;
 
 use crate::{
     AppError, AppResult,
-    models::{SigninData, User, UserRole, UserToUpdate},
-    storage::{DEFAULT_PAGE_NUM, DEFAULT_PER_PAGE, UsersFilter, UsersRepository},
+    crypto::{UNUSABLE_PASSWORD_HASH, hash_password_blocking},
+    i18n::Locale,
+    metrics,
+    models::{
+        AuditAction, AuditEvent, ExternalIdentity, ImportedUser, NewAuditEvent, PasswordPolicy,
+        ServiceAccountData, SigninData, User, UserRole, UserToUpdate,
+    },
+    storage::{AuditRepository, DEFAULT_PAGE_NUM, DEFAULT_PER_PAGE, UsersFilter, UsersRepository},
 };
 
+/// Срок по умолчанию, в течение которого удаление аккаунта можно отменить
+pub const DEFAULT_DELETION_GRACE_DAYS: i64 = 30;
+
+/// Сколько минут после входа аккаунт без пароля может подтвердить удаление
+pub const RECENT_SIGNIN_MINUTES: i64 = 10;
+
 /// Сервис для работы с пользователями
 ///
 /// Предоставляет высокоуровневые операции над пользователями,
@@ -17,6 +30,8 @@ use crate::{
 #[derive(Clone)]
 pub struct UsersService {
     pub storage: Arc<dyn UsersRepository>,
+    pub audit: Option<Arc<dyn AuditRepository>>,
+    deletion_grace: chrono::Duration,
 }
 impl UsersService {
     /// Создает новый экземпляр сервиса пользователей
@@ -29,7 +44,29 @@ impl UsersService {
     ///
     /// Новый экземпляр `UsersService`
     pub fn new(storage: Arc<dyn UsersRepository>) -> Self {
-        Self { storage }
+        Self {
+            storage,
+            audit: None,
+            deletion_grace: chrono::Duration::days(DEFAULT_DELETION_GRACE_DAYS),
+        }
+    }
+    /// Подключает журнал аудита
+    ///
+    /// # Аргументы
+    ///
+    /// * `audit` - Реализация трейта `AuditRepository` в `Arc`
+    pub fn with_audit(mut self, audit: Arc<dyn AuditRepository>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+    /// Устанавливает срок, в течение которого удаление аккаунта можно отменить
+    ///
+    /// # Аргументы
+    ///
+    /// * `grace` - Длительность периода ожидания
+    pub fn with_deletion_grace(mut self, grace: chrono::Duration) -> Self {
+        self.deletion_grace = grace;
+        self
     }
     /// Создает нового пользователя
     ///
@@ -42,11 +79,19 @@ impl UsersService {
     /// # Возвращает
     ///
     /// * `Ok(User)` - Созданный пользователь
+    /// * `Err(AppError::WeakPassword)` - Пароль не соответствует парольной политике;
+    ///   содержит оценку стойкости и список невыполненных требований
     /// * `Err(AppError)` - Ошибка валидации, парсинга роли или сохранения
     pub async fn signup(&self, email: &str, password: &str, role: Option<&str>) -> AppResult<User> {
         let role = role
             .and_then(|r| UserRole::from_str(r).ok())
             .unwrap_or_default();
+        let report = PasswordPolicy::current()
+            .check_blocking(password, &[email])
+            .await?;
+        if !report.is_ok() {
+            return Err(AppError::WeakPassword(report));
+        }
         let data = (email, password, role.as_ref()).try_into()?;
         let new_user = self.storage.create(data).await.map_err(|e| {
             if e.to_string().contains("duplicate key") {
@@ -55,6 +100,8 @@ impl UsersService {
                 e
             }
         })?;
+        self.record(NewAuditEvent::own(new_user.user_id, AuditAction::Signup))
+            .await;
         Ok(new_user)
     }
     /// Получает пользователя по идентификатору
@@ -94,6 +141,29 @@ impl UsersService {
         let user = self.storage.find_by_email(&data.email).await?;
         Ok(user)
     }
+    /// Находит сервисный аккаунт по имени
+    ///
+    /// # Аргументы
+    ///
+    /// * `name` - Имя сервисного аккаунта
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(User)` - Найденный сервисный аккаунт
+    /// * `Err(AppError::ValidationError)` - Имя не может принадлежать сервисному аккаунту
+    /// * `Err(AppError::EntryNotFound)` - Аккаунт не найден или принадлежит человеку
+    pub async fn find_service_account(&self, name: &str) -> AppResult<User> {
+        let data = ServiceAccountData {
+            name: name.to_string(),
+            role: UserRole::default(),
+        };
+        data.validate()?;
+        let user = self.storage.find_by_email(&data.email()).await?;
+        if !user.service_account {
+            return Err(AppError::EntryNotFound);
+        }
+        Ok(user)
+    }
     /// Получает список пользователей с пагинацией и фильтрацией
     ///
     /// # Аргументы
@@ -143,6 +213,28 @@ impl UsersService {
         };
         Ok(res)
     }
+    /// Подсчитывает активных пользователей каждой роли
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<Vec<(UserRole, u32)>>` - Число пользователей по ролям или ошибку
+    pub async fn count_by_role(&self) -> AppResult<Vec<(UserRole, u32)>> {
+        let mut res = Vec::with_capacity(UserRole::all().len());
+        for role in UserRole::values() {
+            let filter = UsersFilter::builder().role(Some(role.clone())).build()?;
+            res.push((role, self.storage.total(filter).await?));
+        }
+        Ok(res)
+    }
+    /// Проверяет, есть ли в системе хотя бы один владелец
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<bool>` - `true`, если владелец существует, или ошибку
+    pub async fn has_owner(&self) -> AppResult<bool> {
+        let filter = UsersFilter::builder().role(Some(UserRole::Owner)).build()?;
+        Ok(self.storage.total(filter).await? > 0)
+    }
     /// Выполняет аутентификацию пользователя
     ///
     /// # Аргументы
@@ -153,18 +245,197 @@ impl UsersService {
     /// # Возвращает
     ///
     /// * `Ok(User)` - Аутентифицированный пользователь
-    /// * `Err(AppError::InvalidCredentials)` - Неверные учетные данные
-    /// * `Err(AppError)` - Другие ошибки (валидация, поиск пользователя и т.д.)
+    /// * `Err(AppError::InvalidCredentials)` - Неверный пароль или пользователь не найден
+    /// * `Err(AppError)` - Другие ошибки (валидация, чтение из базы и т.д.)
     pub async fn signin(&self, email: &str, password: &str) -> AppResult<User> {
+        let res = self.verify_signin(email, password).await;
+        metrics::record_signin(res.is_ok());
+        res
+    }
+    /// Проверяет учетные данные и записывает попытку входа в журнал аудита
+    ///
+    /// Неизвестный email неотличим от неверного пароля.
+    async fn verify_signin(&self, email: &str, password: &str) -> AppResult<User> {
+        let not_found = |e| match e {
+            AppError::EntryNotFound => AppError::InvalidCredentials,
+            e => e,
+        };
         let signin_data = SigninData::try_from((email, password))?;
-        let is_verified = self.storage.verify_user(signin_data.clone()).await?;
+        let is_verified = self
+            .storage
+            .verify_user(signin_data.clone())
+            .await
+            .map_err(not_found)?;
+        let user = self
+            .storage
+            .find_by_email(&signin_data.email)
+            .await
+            .map_err(not_found)?;
         if is_verified {
-            let user = self.storage.find_by_email(&signin_data.email).await?;
+            self.record(NewAuditEvent::own(user.user_id, AuditAction::Signin))
+                .await;
             Ok(user)
         } else {
+            self.record(NewAuditEvent::own(user.user_id, AuditAction::SigninFailed))
+                .await;
             Err(crate::AppError::InvalidCredentials)
         }
     }
+    /// Выполняет вход по учетной записи внешнего провайдера
+    ///
+    /// Пользователь ищется сначала по связанной учетной записи провайдера,
+    /// затем по подтвержденному email. Найденный по email аккаунт связывается
+    /// с учетной записью провайдера. Если аккаунта нет и задана `jit_role`,
+    /// он создается с этой ролью.
+    ///
+    /// # Аргументы
+    ///
+    /// * `identity` - Проверенные сведения от провайдера
+    /// * `jit_role` - Роль для автоматически создаваемого аккаунта;
+    ///   `None` запрещает создание
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(User)` - Пользователь, выполнивший вход
+    /// * `Err(AppError::InvalidCredentials)` - Email не подтвержден провайдером
+    /// * `Err(AppError::AccessDenied)` - Аккаунт не найден и создание запрещено,
+    ///   либо email принадлежит сервисному аккаунту
+    pub async fn signin_external(
+        &self,
+        identity: &ExternalIdentity,
+        jit_role: Option<UserRole>,
+    ) -> AppResult<User> {
+        match self
+            .storage
+            .find_by_identity(&identity.issuer, &identity.subject)
+            .await
+        {
+            Ok(user) => {
+                self.record(
+                    NewAuditEvent::own(user.user_id, AuditAction::Signin)
+                        .with_details(format!("oidc issuer={}", identity.issuer)),
+                )
+                .await;
+                return Ok(user);
+            }
+            Err(AppError::EntryNotFound) => {}
+            Err(e) => return Err(e),
+        }
+        if !identity.email_verified {
+            return Err(AppError::InvalidCredentials);
+        }
+        let email = identity.email.trim().to_lowercase();
+        match self.storage.find_by_email(&email).await {
+            Ok(user) if user.service_account => Err(AppError::AccessDenied),
+            Ok(user) => {
+                self.storage
+                    .link_identity(user.user_id, &identity.issuer, &identity.subject)
+                    .await?;
+                self.record(
+                    NewAuditEvent::own(user.user_id, AuditAction::Signin)
+                        .with_details(format!("oidc issuer={} linked", identity.issuer)),
+                )
+                .await;
+                Ok(user)
+            }
+            Err(AppError::EntryNotFound) => {
+                let Some(role) = jit_role else {
+                    return Err(AppError::AccessDenied);
+                };
+                let identity = ExternalIdentity {
+                    email,
+                    ..identity.clone()
+                };
+                let user = self.storage.create_external(&identity, role).await?;
+                self.record(
+                    NewAuditEvent::own(user.user_id, AuditAction::Signup)
+                        .with_details(format!("oidc issuer={}", identity.issuer)),
+                )
+                .await;
+                Ok(user)
+            }
+            Err(e) => Err(e),
+        }
+    }
+    /// Создает сервисный аккаунт
+    ///
+    /// # Аргументы
+    ///
+    /// * `actor` - Администратор, создающий аккаунт
+    /// * `data` - Имя и роль сервисного аккаунта
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(User)` - Созданный сервисный аккаунт
+    /// * `Err(AppError::AccessDenied)` - `actor` не администратор или запрошена роль владельца
+    /// * `Err(AppError::EntryAlreadyExists)` - Аккаунт с таким именем уже существует
+    pub async fn create_service_account(
+        &self,
+        actor: &User,
+        data: ServiceAccountData,
+    ) -> AppResult<User> {
+        if !actor.role.is_admin() || data.role == UserRole::Owner {
+            return Err(AppError::AccessDenied);
+        }
+        data.validate()?;
+        let account = self
+            .storage
+            .create_service_account(data)
+            .await
+            .map_err(|e| {
+                if e.to_string().contains("duplicate key") {
+                    AppError::EntryAlreadyExists
+                } else {
+                    e
+                }
+            })?;
+        self.record(NewAuditEvent {
+            user_id: Some(account.user_id),
+            actor_id: Some(actor.user_id),
+            action: AuditAction::ServiceAccountCreated,
+            details: None,
+        })
+        .await;
+        Ok(account)
+    }
+    /// Переносит пользователя из другой системы с готовым хэшем пароля
+    ///
+    /// Хэш bcrypt или PBKDF2 сохраняется как есть и пересчитывается
+    /// в Argon2 при первом успешном входе пользователя.
+    ///
+    /// # Аргументы
+    ///
+    /// * `actor` - Администратор, выполняющий импорт
+    /// * `data` - Email, хэш пароля и роль пользователя
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(User)` - Созданный пользователь
+    /// * `Err(AppError::AccessDenied)` - `actor` не администратор или запрошена роль владельца
+    /// * `Err(AppError::ValidationError)` - Неверный email или неподдерживаемый формат хэша
+    /// * `Err(AppError::EntryAlreadyExists)` - Пользователь с таким email уже существует
+    pub async fn import_user(&self, actor: &User, mut data: ImportedUser) -> AppResult<User> {
+        if !actor.role.is_admin() || data.role == UserRole::Owner {
+            return Err(AppError::AccessDenied);
+        }
+        data.email = data.email.trim().to_lowercase();
+        data.validate()?;
+        let user = self.storage.create_imported(data).await.map_err(|e| {
+            if e.to_string().contains("duplicate key") {
+                AppError::EntryAlreadyExists
+            } else {
+                e
+            }
+        })?;
+        self.record(NewAuditEvent {
+            user_id: Some(user.user_id),
+            actor_id: Some(actor.user_id),
+            action: AuditAction::UserImported,
+            details: None,
+        })
+        .await;
+        Ok(user)
+    }
     /// Удаляет пользователя по идентификатору
     ///
     /// # Аргументы
@@ -184,18 +455,230 @@ impl UsersService {
     ///
     /// # Аргументы
     ///
+    /// * `actor` - Пользователь, выполняющий изменение
     /// * `id` - UUID пользователя в строковом формате
     /// * `user` - Новые данные пользователя
     ///
     /// # Возвращает
     ///
     /// * `Ok(User)` - Обновленный пользователь
-    /// * `Err(AppError)` - Ошибка парсинга UUID или если пользователь не найден
-    pub async fn update(&self, id: &str, user: UserToUpdate) -> AppResult<User> {
+    /// * `Err(AppError)` - Ошибка парсинга UUID, валидации или если пользователь
+    ///   не найден либо анонимизирован
+    pub async fn update(&self, actor: &User, id: &str, user: UserToUpdate) -> AppResult<User> {
         let user_id = uuid::Uuid::parse_str(id)?;
+        user.validate()?;
         let updated_user = self.storage.update(user_id, user).await?;
+        self.record(NewAuditEvent {
+            user_id: Some(user_id),
+            actor_id: Some(actor.user_id),
+            action: AuditAction::ProfileUpdated,
+            details: None,
+        })
+        .await;
         Ok(updated_user)
     }
+    /// Меняет роль пользователя, сохраняя остальные данные профиля
+    ///
+    /// # Аргументы
+    ///
+    /// * `actor` - Пользователь, меняющий роль; `None` для команд CLI
+    /// * `id` - UUID пользователя
+    /// * `role` - Новая роль
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(User)` - Пользователь с новой ролью
+    /// * `Err(AppError::EntryNotFound)` - Пользователь не найден
+    pub async fn set_role(
+        &self,
+        actor: Option<&User>,
+        id: uuid::Uuid,
+        role: UserRole,
+    ) -> AppResult<User> {
+        let user = self.storage.get(id).await?;
+        let previous = user.role.clone();
+        let data = UserToUpdate {
+            email: user.email,
+            role,
+            info: user.info,
+        };
+        let updated = self.storage.update(id, data).await?;
+        self.record(NewAuditEvent {
+            user_id: Some(id),
+            actor_id: actor.map(|actor| actor.user_id),
+            action: AuditAction::RoleChanged,
+            details: Some(format!("{previous} -> {}", updated.role)),
+        })
+        .await;
+        Ok(updated)
+    }
+    /// Устанавливает пользователю новый пароль без проверки текущего
+    ///
+    /// # Аргументы
+    ///
+    /// * `id` - UUID пользователя
+    /// * `password` - Новый пароль
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(User)` - Обновленный пользователь
+    /// * `Err(AppError::WeakPassword)` - Пароль не соответствует парольной политике
+    /// * `Err(AppError::EntryNotFound)` - Пользователь не найден
+    pub async fn reset_password(&self, id: uuid::Uuid, password: &str) -> AppResult<User> {
+        let user = self.storage.get(id).await?;
+        let report = PasswordPolicy::current()
+            .check_blocking(password, &[&user.email])
+            .await?;
+        if !report.is_ok() {
+            return Err(AppError::WeakPassword(report));
+        }
+        let password_hash = hash_password_blocking(password).await?;
+        let updated = self.storage.set_password_hash(id, &password_hash).await?;
+        self.record(NewAuditEvent {
+            user_id: Some(id),
+            actor_id: None,
+            action: AuditAction::PasswordReset,
+            details: None,
+        })
+        .await;
+        Ok(updated)
+    }
+    /// Запрашивает удаление собственного аккаунта
+    ///
+    /// Аккаунт не удаляется сразу: назначается момент анонимизации,
+    /// до наступления которого удаление можно отменить. Аккаунт с паролем
+    /// подтверждает удаление паролем; аккаунт без пароля (вход через
+    /// провайдера) - входом не раньше чем `RECENT_SIGNIN_MINUTES` назад.
+    ///
+    /// # Аргументы
+    ///
+    /// * `user` - Аутентифицированный пользователь
+    /// * `password` - Текущий пароль для подтверждения
+    /// * `signed_in_at` - Момент входа в текущей сессии
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(User)` - Пользователь с заполненным `deletion_scheduled_at`
+    /// * `Err(AppError::InvalidCredentials)` - Пароль не подтвержден
+    /// * `Err(AppError::Unauthenticated)` - Аккаунт без пароля вошел слишком давно
+    pub async fn request_deletion(
+        &self,
+        user: &User,
+        password: Option<&str>,
+        signed_in_at: chrono::DateTime<chrono::Utc>,
+    ) -> AppResult<User> {
+        if user.password_hash == UNUSABLE_PASSWORD_HASH {
+            let recent = chrono::Duration::minutes(RECENT_SIGNIN_MINUTES);
+            if chrono::Utc::now() - signed_in_at > recent {
+                return Err(AppError::Unauthenticated("Sign in again to confirm"));
+            }
+        } else {
+            let password = password.ok_or(AppError::InvalidCredentials)?;
+            let signin_data = SigninData::try_from((user.email.as_str(), password))
+                .map_err(|_| AppError::InvalidCredentials)?;
+            if !self.storage.verify_user(signin_data).await? {
+                return Err(AppError::InvalidCredentials);
+            }
+        }
+        let at = chrono::Utc::now() + self.deletion_grace;
+        let scheduled = self
+            .storage
+            .schedule_deletion(user.user_id, Some(at))
+            .await?;
+        self.record(
+            NewAuditEvent::own(user.user_id, AuditAction::DeletionRequested)
+                .with_details(format!("scheduled_at={}", at.to_rfc3339())),
+        )
+        .await;
+        Ok(scheduled)
+    }
+    /// Отменяет запрошенное удаление аккаунта
+    ///
+    /// # Аргументы
+    ///
+    /// * `user` - Аутентифицированный пользователь
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(User)` - Пользователь без запланированного удаления
+    /// * `Err(AppError)` - Ошибка сохранения
+    pub async fn cancel_deletion(&self, user: &User) -> AppResult<User> {
+        if user.deletion_scheduled_at.is_none() {
+            return Ok(user.clone());
+        }
+        let restored = self.storage.schedule_deletion(user.user_id, None).await?;
+        self.record(NewAuditEvent::own(
+            user.user_id,
+            AuditAction::DeletionCancelled,
+        ))
+        .await;
+        Ok(restored)
+    }
+    /// Анонимизирует аккаунты, период ожидания удаления которых истек
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(usize)` - Количество анонимизированных аккаунтов
+    /// * `Err(AppError)` - Ошибка получения списка аккаунтов
+    pub async fn purge_due_deletions(&self) -> AppResult<usize> {
+        let due = self.storage.due_for_deletion(chrono::Utc::now()).await?;
+        let mut purged = 0;
+        for user_id in due {
+            match self.storage.anonymize(user_id).await {
+                Ok(_) => {
+                    purged += 1;
+                    self.record(NewAuditEvent {
+                        user_id: Some(user_id),
+                        actor_id: None,
+                        action: AuditAction::Anonymized,
+                        details: None,
+                    })
+                    .await;
+                }
+                Err(e) => tracing::error!("failed to anonymize user {user_id}: {e}"),
+            }
+        }
+        Ok(purged)
+    }
+    /// Собирает выгрузку персональных данных пользователя
+    ///
+    /// # Аргументы
+    ///
+    /// * `user` - Аутентифицированный пользователь
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(UserDataExport)` - Профиль, настройки, история входов и журнал аудита
+    /// * `Err(AppError)` - Ошибка чтения журнала аудита
+    pub async fn export(&self, user: &User) -> AppResult<UserDataExport> {
+        let events = match &self.audit {
+            Some(audit) => audit.list_for_user(user.user_id).await?,
+            None => Vec::new(),
+        };
+        let (login_history, audit_log) = events.into_iter().partition(|e| e.action.is_login());
+        self.record(NewAuditEvent::own(user.user_id, AuditAction::DataExported))
+            .await;
+        Ok(UserDataExport {
+            exported_at: chrono::Utc::now(),
+            profile: user.clone(),
+            preferences: UserPreferences {
+                timezone: user.info.timezone.clone(),
+                locale: user.info.locale,
+            },
+            login_history,
+            audit_log,
+        })
+    }
+    /// Записывает событие в журнал аудита, если он подключен
+    ///
+    /// Ошибка записи не прерывает основную операцию и только логируется.
+    async fn record(&self, event: NewAuditEvent) {
+        if let Some(audit) = &self.audit
+            && let Err(e) = audit.record(event).await
+        {
+            tracing::error!("failed to record audit event: {e}");
+        }
+    }
 }
 
 /// Структура для валидации email
@@ -215,13 +698,42 @@ struct Email {
 /// # Сериализация
 ///
 /// Структура реализует `Serialize` и `Deserialize` для использования в API.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 pub struct UsersListResponse {
     pub current_filter: UsersFilter,
     pub total: u32,
     pub users: Vec<User>,
 }
 
+impl UsersListResponse {
+    /// Количество страниц при текущем размере страницы
+    pub fn total_pages(&self) -> u32 {
+        self.total.div_ceil(self.current_filter.per_page().max(1))
+    }
+}
+
+/// Выгрузка персональных данных пользователя
+///
+/// Содержит все сведения, которые система хранит о пользователе.
+/// Хэш пароля в выгрузку не попадает.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserDataExport {
+    pub exported_at: chrono::DateTime<chrono::Utc>,
+    pub profile: User,
+    pub preferences: UserPreferences,
+    pub login_history: Vec<AuditEvent>,
+    pub audit_log: Vec<AuditEvent>,
+}
+
+/// Пользовательские настройки, включаемые в выгрузку
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserPreferences {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locale: Option<Locale>,
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::{Arc, Mutex};
@@ -240,18 +752,18 @@ mod tests {
     /// Позволяет контролировать возвращаемые данные и ошибки.
     struct TestUsersRepo {
         users: Arc<Mutex<Vec<User>>>,
+        identities: Mutex<Vec<(Uuid, String, String)>>,
     }
 
     impl TestUsersRepo {
         fn new() -> Self {
-            Self {
-                users: Arc::new(Mutex::new(Vec::new())),
-            }
+            Self::with_users(Vec::new())
         }
 
         fn with_users(users: Vec<User>) -> Self {
             Self {
                 users: Arc::new(Mutex::new(users)),
+                identities: Mutex::new(Vec::new()),
             }
         }
     }
@@ -266,13 +778,97 @@ mod tests {
                 password_hash,
                 role: signup_data.role,
                 info: crate::models::UserInfo::default(),
-                created: chrono::Utc::now().naive_utc(),
-                updated: chrono::Utc::now().naive_utc(),
+                created: chrono::Utc::now(),
+                updated: chrono::Utc::now(),
+                deletion_scheduled_at: None,
+                service_account: false,
             };
             self.users.lock().unwrap().push(user.clone());
             Ok(user)
         }
 
+        async fn create_imported(&self, data: ImportedUser) -> AppResult<User> {
+            let user = User {
+                user_id: Uuid::new_v4(),
+                email: data.email,
+                password_hash: data.password_hash,
+                role: data.role,
+                info: UserInfo::default(),
+                created: chrono::Utc::now(),
+                updated: chrono::Utc::now(),
+                deletion_scheduled_at: None,
+                service_account: false,
+            };
+            self.users.lock().unwrap().push(user.clone());
+            Ok(user)
+        }
+
+        async fn create_service_account(&self, data: ServiceAccountData) -> AppResult<User> {
+            let user = User {
+                user_id: Uuid::new_v4(),
+                email: data.email(),
+                password_hash: crate::crypto::UNUSABLE_PASSWORD_HASH.to_string(),
+                role: data.role,
+                info: UserInfo {
+                    username: Some(data.name),
+                    ..Default::default()
+                },
+                created: chrono::Utc::now(),
+                updated: chrono::Utc::now(),
+                deletion_scheduled_at: None,
+                service_account: true,
+            };
+            self.users.lock().unwrap().push(user.clone());
+            Ok(user)
+        }
+
+        async fn create_external(
+            &self,
+            identity: &ExternalIdentity,
+            role: UserRole,
+        ) -> AppResult<User> {
+            let user = User {
+                user_id: Uuid::new_v4(),
+                email: identity.email.clone(),
+                password_hash: crate::crypto::UNUSABLE_PASSWORD_HASH.to_string(),
+                role,
+                info: UserInfo {
+                    first_name: identity.given_name.clone(),
+                    last_name: identity.family_name.clone(),
+                    ..Default::default()
+                },
+                created: chrono::Utc::now(),
+                updated: chrono::Utc::now(),
+                deletion_scheduled_at: None,
+                service_account: false,
+            };
+            self.users.lock().unwrap().push(user.clone());
+            self.link_identity(user.user_id, &identity.issuer, &identity.subject)
+                .await?;
+            Ok(user)
+        }
+
+        async fn find_by_identity(&self, issuer: &str, subject: &str) -> AppResult<User> {
+            let user_id = self
+                .identities
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|(_, i, s)| i == issuer && s == subject)
+                .map(|(id, _, _)| *id)
+                .ok_or(AppError::EntryNotFound)?;
+            self.get(user_id).await
+        }
+
+        async fn link_identity(&self, user_id: Uuid, issuer: &str, subject: &str) -> AppResult<()> {
+            self.identities.lock().unwrap().push((
+                user_id,
+                issuer.to_string(),
+                subject.to_string(),
+            ));
+            Ok(())
+        }
+
         async fn get(&self, id: Uuid) -> AppResult<User> {
             self.users
                 .lock()
@@ -414,6 +1010,86 @@ mod tests {
                 Err(e) => Err(e),
             }
         }
+
+        async fn set_password_hash(&self, id: Uuid, password_hash: &str) -> AppResult<User> {
+            let mut users = self.users.lock().unwrap();
+            let user = users
+                .iter_mut()
+                .find(|u| u.user_id == id)
+                .ok_or(AppError::EntryNotFound)?;
+            user.password_hash = password_hash.to_string();
+            Ok(user.clone())
+        }
+
+        async fn schedule_deletion(
+            &self,
+            id: Uuid,
+            at: Option<chrono::DateTime<chrono::Utc>>,
+        ) -> AppResult<User> {
+            let mut users = self.users.lock().unwrap();
+            let user = users
+                .iter_mut()
+                .find(|u| u.user_id == id)
+                .ok_or(AppError::EntryNotFound)?;
+            user.deletion_scheduled_at = at;
+            Ok(user.clone())
+        }
+
+        async fn anonymize(&self, id: Uuid) -> AppResult<User> {
+            let mut users = self.users.lock().unwrap();
+            let pos = users
+                .iter()
+                .position(|u| u.user_id == id)
+                .ok_or(AppError::EntryNotFound)?;
+            Ok(users.remove(pos))
+        }
+
+        async fn due_for_deletion(
+            &self,
+            now: chrono::DateTime<chrono::Utc>,
+        ) -> AppResult<Vec<Uuid>> {
+            Ok(self
+                .users
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|u| u.deletion_scheduled_at.is_some_and(|at| at <= now))
+                .map(|u| u.user_id)
+                .collect())
+        }
+    }
+
+    /// Тестовый журнал аудита, хранящий события в памяти
+    #[derive(Default)]
+    struct TestAuditRepo {
+        events: Mutex<Vec<AuditEvent>>,
+    }
+
+    #[async_trait]
+    impl AuditRepository for TestAuditRepo {
+        async fn record(&self, event: NewAuditEvent) -> AppResult<AuditEvent> {
+            let event = AuditEvent {
+                event_id: Uuid::new_v4(),
+                user_id: event.user_id,
+                actor_id: event.actor_id,
+                action: event.action,
+                details: event.details,
+                created: chrono::Utc::now(),
+            };
+            self.events.lock().unwrap().push(event.clone());
+            Ok(event)
+        }
+
+        async fn list_for_user(&self, user_id: Uuid) -> AppResult<Vec<AuditEvent>> {
+            Ok(self
+                .events
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|e| e.user_id == Some(user_id))
+                .cloned()
+                .collect())
+        }
     }
 
     /// Создает тестового пользователя
@@ -429,8 +1105,10 @@ mod tests {
                 last_name: Some("User".to_string()),
                 ..Default::default()
             },
-            created: chrono::Utc::now().naive_utc(),
-            updated: chrono::Utc::now().naive_utc(),
+            created: chrono::Utc::now(),
+            updated: chrono::Utc::now(),
+            deletion_scheduled_at: None,
+            service_account: false,
         }
     }
 
@@ -474,6 +1152,30 @@ mod tests {
         assert_eq!(user.role, UserRole::Guest); // Guest - роль по умолчанию
     }
 
+    /// Тест отклонения слабого пароля со списком невыполненных требований
+    #[tokio::test]
+    async fn test_create_user_with_weak_password() {
+        let test_repo = TestUsersRepo::new();
+        let service = UsersService::new(Arc::new(test_repo));
+
+        let result = service.signup("test@example.com", "пароль", None).await;
+
+        let Err(AppError::WeakPassword(report)) = result else {
+            panic!("expected weak password error");
+        };
+        assert_eq!(report.score, 0);
+        assert!(
+            report
+                .failed_rules
+                .contains(&crate::models::PasswordRule::TooShort { min: 8 })
+        );
+        assert!(
+            report
+                .failed_rules
+                .contains(&crate::models::PasswordRule::MissingDigit)
+        );
+    }
+
     /// Тест создания пользователя с невалидной ролью
     #[tokio::test]
     async fn test_create_user_with_invalid_role() {
@@ -613,6 +1315,7 @@ mod tests {
         assert_eq!(response.total, 5);
         assert_eq!(response.current_filter.page(), 1);
         assert_eq!(response.current_filter.per_page(), 2);
+        assert_eq!(response.total_pages(), 3);
 
         // Вторая страница
         let result = service
@@ -810,7 +1513,7 @@ mod tests {
         let result = service
             .signin("nonexistent@example.com", "p@sSword123")
             .await;
-        assert!(result.is_err());
+        assert!(matches!(result, Err(AppError::InvalidCredentials)));
     }
 
     /// Тест удаления пользователя
@@ -859,8 +1562,10 @@ mod tests {
         let test_user =
             create_test_user(user_id, "old@example.com", UserRole::Guest, Some("olduser"));
 
+        let admin = create_test_user(Uuid::new_v4(), "admin@example.com", UserRole::Admin, None);
         let test_repo = TestUsersRepo::with_users(vec![test_user.clone()]);
-        let service = UsersService::new(Arc::new(test_repo));
+        let audit = Arc::new(TestAuditRepo::default());
+        let service = UsersService::new(Arc::new(test_repo)).with_audit(audit.clone());
 
         // Создаем обновленного пользователя
         let mut updated_user = test_user.clone();
@@ -869,7 +1574,7 @@ mod tests {
         updated_user.role = UserRole::Admin;
 
         let result = service
-            .update(&user_id.to_string(), updated_user.clone().into())
+            .update(&admin, &user_id.to_string(), updated_user.clone().into())
             .await;
         assert!(result.is_ok());
         let updated = result.unwrap();
@@ -880,6 +1585,29 @@ mod tests {
         // Проверяем, что данные обновились
         let get_result = service.get_by_id(&user_id.to_string()).await.unwrap();
         assert_eq!(get_result.email, "new@example.com");
+
+        // В журнале аудита указан администратор, выполнивший изменение
+        let events = audit.list_for_user(user_id).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].action, AuditAction::ProfileUpdated);
+        assert_eq!(events[0].actor_id, Some(admin.user_id));
+    }
+
+    /// Тест обновления пользователя с невалидным часовым поясом
+    #[tokio::test]
+    async fn test_update_user_invalid_timezone() {
+        let user_id = Uuid::new_v4();
+        let test_user = create_test_user(user_id, "tz@example.com", UserRole::Guest, None);
+
+        let test_repo = TestUsersRepo::with_users(vec![test_user.clone()]);
+        let service = UsersService::new(Arc::new(test_repo));
+
+        let mut updated_user = test_user.clone();
+        updated_user.info.timezone = Some("Not/AZone".to_string());
+        let result = service
+            .update(&test_user, &user_id.to_string(), updated_user.into())
+            .await;
+        assert!(matches!(result.unwrap_err(), AppError::ValidationErrors(_)));
     }
 
     /// Тест обновления несуществующего пользователя
@@ -890,7 +1618,7 @@ mod tests {
 
         let user = create_test_user(Uuid::new_v4(), "test@example.com", UserRole::Guest, None);
         let result = service
-            .update(&Uuid::new_v4().to_string(), user.into())
+            .update(&user, &Uuid::new_v4().to_string(), user.clone().into())
             .await;
         assert!(result.is_err());
     }
@@ -1023,7 +1751,7 @@ mod tests {
         let mut updated_user = retrieved.clone();
         updated_user.info.username = Some("integration_user".to_string());
         let updated = service
-            .update(&user_id.to_string(), updated_user.into())
+            .update(&retrieved, &user_id.to_string(), updated_user.into())
             .await
             .unwrap();
         assert_eq!(updated.info.username, Some("integration_user".to_string()));
@@ -1076,4 +1804,314 @@ mod tests {
         let result = service.get_user_info("").await;
         assert!(result.is_err());
     }
+
+    /// Тест запроса и отмены удаления аккаунта
+    #[tokio::test]
+    async fn test_request_and_cancel_deletion() {
+        let user_id = Uuid::new_v4();
+        let user = create_test_user(user_id, "bye@example.com", UserRole::Guest, None);
+        let audit = Arc::new(TestAuditRepo::default());
+        let service = UsersService::new(Arc::new(TestUsersRepo::with_users(vec![user.clone()])))
+            .with_audit(audit.clone());
+
+        let now = chrono::Utc::now();
+        let result = service
+            .request_deletion(&user, Some("wrong_p@sSword1"), now)
+            .await;
+        assert!(matches!(result, Err(AppError::InvalidCredentials)));
+        let result = service.request_deletion(&user, None, now).await;
+        assert!(matches!(result, Err(AppError::InvalidCredentials)));
+
+        let scheduled = service
+            .request_deletion(&user, Some("test_p@sSword1123"), now)
+            .await
+            .unwrap();
+        let at = scheduled.deletion_scheduled_at.unwrap();
+        assert!(at > chrono::Utc::now() + chrono::Duration::days(DEFAULT_DELETION_GRACE_DAYS - 1));
+
+        let restored = service.cancel_deletion(&scheduled).await.unwrap();
+        assert!(restored.deletion_scheduled_at.is_none());
+
+        let actions: Vec<_> = audit
+            .list_for_user(user_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|e| e.action)
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                AuditAction::DeletionRequested,
+                AuditAction::DeletionCancelled
+            ]
+        );
+    }
+
+    /// Тест удаления аккаунта, созданного при входе через провайдера
+    #[tokio::test]
+    async fn test_request_deletion_without_password() {
+        let service = UsersService::new(Arc::new(TestUsersRepo::new()));
+        let user = service
+            .signin_external(
+                &external_identity("sso@example.com", true),
+                Some(UserRole::Guest),
+            )
+            .await
+            .unwrap();
+
+        // Давний вход не подтверждает удаление
+        let stale = chrono::Utc::now() - chrono::Duration::minutes(RECENT_SIGNIN_MINUTES + 1);
+        let result = service.request_deletion(&user, None, stale).await;
+        assert!(matches!(result, Err(AppError::Unauthenticated(_))));
+
+        let scheduled = service
+            .request_deletion(&user, None, chrono::Utc::now())
+            .await
+            .unwrap();
+        assert!(scheduled.deletion_scheduled_at.is_some());
+    }
+
+    /// Тест анонимизации аккаунтов с истекшим периодом ожидания
+    #[tokio::test]
+    async fn test_purge_due_deletions() {
+        let user_id = Uuid::new_v4();
+        let user = create_test_user(user_id, "gone@example.com", UserRole::Guest, None);
+        let service = UsersService::new(Arc::new(TestUsersRepo::with_users(vec![user.clone()])))
+            .with_deletion_grace(chrono::Duration::zero());
+
+        assert_eq!(service.purge_due_deletions().await.unwrap(), 0);
+        service
+            .request_deletion(&user, Some("test_p@sSword1123"), chrono::Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(service.purge_due_deletions().await.unwrap(), 1);
+        assert!(service.get_by_id(&user_id.to_string()).await.is_err());
+    }
+
+    /// Тест выгрузки персональных данных
+    #[tokio::test]
+    async fn test_export_user_data() {
+        let audit = Arc::new(TestAuditRepo::default());
+        let service = UsersService::new(Arc::new(TestUsersRepo::new())).with_audit(audit.clone());
+        let user = service
+            .signup("export@example.com", "test_p@sSword1123", None)
+            .await
+            .unwrap();
+        service
+            .signin("export@example.com", "test_p@sSword1123")
+            .await
+            .unwrap();
+        let _ = service
+            .signin("export@example.com", "wrong_p@sSword1")
+            .await;
+
+        let export = service.export(&user).await.unwrap();
+        assert_eq!(export.profile.user_id, user.user_id);
+        assert_eq!(export.login_history.len(), 2);
+        assert_eq!(export.audit_log.len(), 1);
+        assert_eq!(export.audit_log[0].action, AuditAction::Signup);
+
+        let json = serde_json::to_value(&export).unwrap();
+        assert!(json["profile"].get("password_hash").is_none());
+    }
+
+    /// Тест создания сервисного аккаунта
+    #[tokio::test]
+    async fn test_create_service_account() {
+        let service = UsersService::new(Arc::new(TestUsersRepo::new()));
+        let admin = create_test_user(Uuid::new_v4(), "admin@example.com", UserRole::Admin, None);
+        let guest = create_test_user(Uuid::new_v4(), "guest@example.com", UserRole::Guest, None);
+        let data = ServiceAccountData {
+            name: "ci-bot".to_string(),
+            role: UserRole::Employee,
+        };
+
+        let result = service.create_service_account(&guest, data.clone()).await;
+        assert!(matches!(result, Err(AppError::AccessDenied)));
+
+        let owner_data = ServiceAccountData {
+            role: UserRole::Owner,
+            ..data.clone()
+        };
+        let result = service.create_service_account(&admin, owner_data).await;
+        assert!(matches!(result, Err(AppError::AccessDenied)));
+
+        let account = service.create_service_account(&admin, data).await.unwrap();
+        assert!(account.service_account);
+        assert_eq!(account.role, UserRole::Employee);
+        assert!(
+            service
+                .signin(&account.email, "any_p@sSword1")
+                .await
+                .is_err()
+        );
+    }
+
+    /// Тест поиска сервисного аккаунта по имени
+    #[tokio::test]
+    async fn test_find_service_account() {
+        let service = UsersService::new(Arc::new(TestUsersRepo::new()));
+        let admin = create_test_user(Uuid::new_v4(), "admin@example.com", UserRole::Admin, None);
+        let data = ServiceAccountData {
+            name: "billing".to_string(),
+            role: UserRole::Employee,
+        };
+        let account = service.create_service_account(&admin, data).await.unwrap();
+
+        let found = service.find_service_account("billing").await.unwrap();
+        assert_eq!(found.user_id, account.user_id);
+        let result = service.find_service_account("unknown").await;
+        assert!(matches!(result, Err(AppError::EntryNotFound)));
+        let result = service.find_service_account("person@example.com").await;
+        assert!(matches!(result, Err(AppError::ValidationErrors(_))));
+    }
+
+    /// Тест импорта пользователя с хэшем из другой системы
+    #[tokio::test]
+    async fn test_import_user() {
+        let service = UsersService::new(Arc::new(TestUsersRepo::new()));
+        let admin = create_test_user(Uuid::new_v4(), "admin@example.com", UserRole::Admin, None);
+        let guest = create_test_user(Uuid::new_v4(), "guest@example.com", UserRole::Guest, None);
+        let data = ImportedUser {
+            email: " Legacy@Example.com ".to_string(),
+            password_hash: "$pbkdf2-sha256$29000$N2YMIWQsBWBMae09x1jrPQ$lEjTD4tvq5SOB5ctjdSxvnbzU5PQEfMComvCIxNEqq8".to_string(),
+            role: UserRole::Employee,
+        };
+
+        let result = service.import_user(&guest, data.clone()).await;
+        assert!(matches!(result, Err(AppError::AccessDenied)));
+        let owner = ImportedUser {
+            role: UserRole::Owner,
+            ..data.clone()
+        };
+        let result = service.import_user(&admin, owner).await;
+        assert!(matches!(result, Err(AppError::AccessDenied)));
+        let plain = ImportedUser {
+            password_hash: "password".to_string(),
+            ..data.clone()
+        };
+        let result = service.import_user(&admin, plain).await;
+        assert!(matches!(result, Err(AppError::ValidationErrors(_))));
+
+        let user = service.import_user(&admin, data).await.unwrap();
+        assert_eq!(user.email, "legacy@example.com");
+        assert!(service.signin(&user.email, "password").await.is_ok());
+    }
+
+    /// Тест смены роли с сохранением профиля
+    #[tokio::test]
+    async fn test_set_role() {
+        let user_id = Uuid::new_v4();
+        let user = create_test_user(user_id, "role@example.com", UserRole::Guest, Some("role"));
+        let admin = create_test_user(Uuid::new_v4(), "admin@example.com", UserRole::Admin, None);
+        let audit = Arc::new(TestAuditRepo::default());
+        let service = UsersService::new(Arc::new(TestUsersRepo::with_users(vec![user])))
+            .with_audit(audit.clone());
+
+        let updated = service
+            .set_role(Some(&admin), user_id, UserRole::Owner)
+            .await
+            .unwrap();
+        assert_eq!(updated.role, UserRole::Owner);
+        assert_eq!(updated.email, "role@example.com");
+        assert_eq!(updated.info.username, Some("role".to_string()));
+
+        let events = audit.list_for_user(user_id).await.unwrap();
+        assert_eq!(events[0].action, AuditAction::RoleChanged);
+        assert_eq!(events[0].actor_id, Some(admin.user_id));
+
+        let result = service
+            .set_role(None, Uuid::new_v4(), UserRole::Admin)
+            .await;
+        assert!(matches!(result, Err(AppError::EntryNotFound)));
+    }
+
+    /// Тест сброса пароля без знания текущего
+    #[tokio::test]
+    async fn test_reset_password() {
+        let user_id = Uuid::new_v4();
+        let user = create_test_user(user_id, "reset@example.com", UserRole::Employee, None);
+        let service = UsersService::new(Arc::new(TestUsersRepo::with_users(vec![user])));
+
+        let result = service.reset_password(user_id, "weak").await;
+        assert!(matches!(result, Err(AppError::WeakPassword(_))));
+
+        service
+            .reset_password(user_id, "n3w_p@sSword_reset")
+            .await
+            .unwrap();
+        assert!(
+            service
+                .signin("reset@example.com", "test_p@sSword1123")
+                .await
+                .is_err()
+        );
+        assert!(
+            service
+                .signin("reset@example.com", "n3w_p@sSword_reset")
+                .await
+                .is_ok()
+        );
+    }
+
+    fn external_identity(email: &str, verified: bool) -> ExternalIdentity {
+        ExternalIdentity {
+            issuer: "https://idp.example.com".to_string(),
+            subject: format!("sub-{email}"),
+            email: email.to_string(),
+            email_verified: verified,
+            given_name: Some("Ivan".to_string()),
+            family_name: None,
+            groups: vec![],
+        }
+    }
+
+    /// Тест связывания существующего аккаунта по подтвержденному email
+    #[tokio::test]
+    async fn test_signin_external_links_by_verified_email() {
+        let user_id = Uuid::new_v4();
+        let user = create_test_user(user_id, "sso@example.com", UserRole::Employee, None);
+        let service = UsersService::new(Arc::new(TestUsersRepo::with_users(vec![user])));
+
+        let unverified = external_identity("sso@example.com", false);
+        let result = service.signin_external(&unverified, None).await;
+        assert!(matches!(result, Err(AppError::InvalidCredentials)));
+
+        let identity = external_identity("SSO@example.com", true);
+        let linked = service.signin_external(&identity, None).await.unwrap();
+        assert_eq!(linked.user_id, user_id);
+
+        // После связывания вход работает по `sub`, даже если email изменился у провайдера
+        let renamed = ExternalIdentity {
+            email: "renamed@example.com".to_string(),
+            email_verified: false,
+            ..identity
+        };
+        let again = service.signin_external(&renamed, None).await.unwrap();
+        assert_eq!(again.user_id, user_id);
+    }
+
+    /// Тест автоматического создания аккаунта при первом входе
+    #[tokio::test]
+    async fn test_signin_external_jit_provisioning() {
+        let service = UsersService::new(Arc::new(TestUsersRepo::new()));
+        let identity = external_identity("new@example.com", true);
+
+        let result = service.signin_external(&identity, None).await;
+        assert!(matches!(result, Err(AppError::AccessDenied)));
+
+        let created = service
+            .signin_external(&identity, Some(UserRole::Employee))
+            .await
+            .unwrap();
+        assert_eq!(created.role, UserRole::Employee);
+        assert_eq!(created.info.first_name, Some("Ivan".to_string()));
+        assert!(
+            service
+                .signin("new@example.com", "any_p@sSword1")
+                .await
+                .is_err()
+        );
+    }
 }
@@ -1,36 +1,313 @@
 //! Модуль для работы с настройками приложения
 //!
 //! Этот модуль содержит структуры и функции для загрузки и работы
-//! с настройками приложения из конфигурационных файлов.
+//! с настройками приложения из конфигурационных файлов, переменных
+//! окружения и файлов секретов.
 
-use serde::Deserialize;
-use std::{borrow::Cow, sync::Arc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{borrow::Cow, collections::HashMap, path::Path, sync::Arc};
 use tracing::instrument;
 
+use crate::{
+    AppError, AppResult,
+    logger::{REDACTED, is_sensitive},
+};
+
+/// Основной файл настроек
+pub const SETTINGS_FILE: &str = "settings.toml";
+/// Префикс переменных окружения с настройками
+pub const ENV_PREFIX: &str = "ALF";
+/// Разделитель префикса и уровней вложенности в именах переменных
+pub const ENV_SEPARATOR: &str = "__";
+/// Переменная окружения с названием окружения, например `production`
+pub const ENVIRONMENT_VAR: &str = "ALF_ENV";
+/// Окончание переменных, в которых передается путь к файлу с секретом
+pub const SECRET_FILE_SUFFIX: &str = "_FILE";
+/// Минимальная длина секрета JWT в байтах
+pub const MIN_JWT_SECRET_LEN: usize = 32;
+
+/// Параметры, которые в переменных окружения задаются списком через запятую
+const LIST_KEYS: [&str; 5] = [
+    "jwt_settings.audience",
+    "oidc_settings.scopes",
+    "rate_limits.trusted_proxies",
+    "server_settings.cors.allowed_origins",
+    "server_settings.cors.allowed_headers",
+];
+
+/// Секреты, имена которых не распознаются `is_sensitive`
+const SECRET_KEYS: [&str; 2] = ["pepper", "retired_peppers"];
+
+/// Загружает и проверяет настройки
+///
+/// Подробнее об источниках - в описании [`load`].
+///
+/// # Аргументы
+///
+/// * `file` - Основной файл настроек
+///
+/// # Возвращает
+///
+/// * `Ok(Settings)` - Настройки пригодны для запуска
+/// * `Err(AppError::Custom)` - Настройки не прочитаны или не прошли проверку
 #[instrument(name = "initializing settings")]
-pub fn init(file: &str) -> Settings {
-    config::Config::builder()
-        .add_source(config::File::with_name(file))
+pub fn init(file: &str) -> AppResult<Settings> {
+    let settings = load(file)?;
+    settings.validate()?;
+    Ok(settings)
+}
+
+/// Загружает настройки без проверки
+///
+/// Источники применяются по порядку, каждый следующий переопределяет предыдущие:
+/// 1. основной файл, например `settings.toml`;
+/// 2. файл окружения, названного в `ALF_ENV`, например `settings.production.toml`;
+/// 3. переменные `ALF__<РАЗДЕЛ>__<ПАРАМЕТР>`, например `ALF__JWT_SETTINGS__SECRET`;
+/// 4. файлы секретов из переменных `ALF__<РАЗДЕЛ>__<ПАРАМЕТР>_FILE`, например
+///    `ALF__DATABASE_SETTINGS__PASSWORD_FILE=/run/secrets/db_password`.
+///
+/// Файлы необязательны: все настройки можно передать через окружение.
+pub fn load(file: &str) -> AppResult<Settings> {
+    load_from(file, std::env::vars().collect())
+}
+
+fn load_from(file: &str, env: HashMap<String, String>) -> AppResult<Settings> {
+    let mut builder =
+        config::Config::builder().add_source(config::File::with_name(file).required(false));
+    if let Some(environment) = env.get(ENVIRONMENT_VAR).filter(|name| !name.is_empty()) {
+        let base = Path::new(file).with_extension("");
+        let name = format!("{}.{environment}", base.display());
+        builder = builder.add_source(config::File::with_name(&name).required(false));
+    }
+    let variables = LIST_KEYS.iter().fold(
+        config::Environment::with_prefix(ENV_PREFIX)
+            .prefix_separator(ENV_SEPARATOR)
+            .separator(ENV_SEPARATOR)
+            .try_parsing(true)
+            .list_separator(","),
+        |variables, key| variables.with_list_parse_key(key),
+    );
+    builder = builder.add_source(variables.source(Some(env.clone())));
+    let prefix = format!("{ENV_PREFIX}{ENV_SEPARATOR}");
+    for (name, path) in &env {
+        let Some(key) = name
+            .strip_prefix(&prefix)
+            .and_then(|name| name.strip_suffix(SECRET_FILE_SUFFIX))
+        else {
+            continue;
+        };
+        let secret = std::fs::read_to_string(path)
+            .map_err(|e| AppError::Custom(format!("secret file from {name}: {e}")))?;
+        let key = key.to_lowercase().replace(ENV_SEPARATOR, ".");
+        builder = builder
+            .set_override(key, secret.trim_end_matches(['\r', '\n']))
+            .map_err(|e| AppError::Custom(format!("settings: {e}")))?;
+    }
+    builder
         .build()
-        .expect("Failed to read config file 'settings.toml'")
-        .try_deserialize()
-        .expect("Failed to deserialize settings")
+        .and_then(config::Config::try_deserialize)
+        .map_err(|e| AppError::Custom(format!("settings: {e}")))
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Settings {
+    #[serde(default)]
     pub database_settings: DatabaseSettings,
-    pub email_settings: EmailSettings,
+    pub email_settings: Option<EmailSettings>,
+    #[serde(default)]
     pub server_settings: ServerSettings,
     pub jwt_settings: JWTSettings,
+    pub cache_settings: Option<CacheSettings>,
+    #[serde(default)]
+    pub account_settings: AccountSettings,
+    #[serde(default)]
+    pub bootstrap: BootstrapSettings,
+    pub oidc_settings: Option<OidcSettings>,
+    #[serde(default)]
+    pub password_policy: PasswordPolicySettings,
+    #[serde(default)]
+    pub password_hashing: PasswordHashSettings,
+    #[serde(default)]
+    pub rate_limits: RateLimitSettings,
+    #[serde(default)]
+    pub health: HealthSettings,
+    #[serde(default)]
+    pub telemetry: TelemetrySettings,
+    #[serde(default)]
+    pub logging: LoggingSettings,
 }
 impl Settings {
     pub fn jwt(&self) -> Arc<JWTSettings> {
         Arc::new(self.jwt_settings.clone())
     }
+
+    /// Проверяет смысл настроек, которые нельзя проверить при разборе
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(())` - Настройки пригодны для запуска
+    /// * `Err(AppError::Custom)` - Перечень всех найденных проблем
+    pub fn validate(&self) -> AppResult<()> {
+        let mut problems = Vec::new();
+        let secret = &self.jwt_settings.secret;
+        if secret.len() < MIN_JWT_SECRET_LEN {
+            problems.push(format!(
+                "jwt_settings.secret must be at least {MIN_JWT_SECRET_LEN} bytes long"
+            ));
+        } else if secret
+            .bytes()
+            .collect::<std::collections::HashSet<_>>()
+            .len()
+            < 8
+        {
+            problems.push("jwt_settings.secret is too repetitive".to_string());
+        }
+        if self.jwt_settings.expires_in <= 0 {
+            problems.push("jwt_settings.expires_in must be positive".to_string());
+        }
+        if self.jwt_settings.maxage <= 0 {
+            problems.push("jwt_settings.maxage must be positive".to_string());
+        }
+        if let Err(e) = validate_origin(&self.server_settings.origin) {
+            problems.push(format!("server_settings.origin: {e}"));
+        }
+        if let Some(redirect_port) = self
+            .server_settings
+            .tls
+            .as_ref()
+            .and_then(|tls| tls.redirect_port)
+        {
+            if redirect_port == self.server_settings.port
+                || Some(redirect_port) == self.server_settings.admin_port
+            {
+                problems.push("server_settings.tls.redirect_port is already in use".to_string());
+            }
+            if !self.server_settings.origin.starts_with("https://") {
+                problems.push(
+                    "server_settings.origin must use https when redirecting to it".to_string(),
+                );
+            }
+        }
+        let cors = &self.server_settings.cors;
+        for origin in &cors.allowed_origins {
+            // Шаблон с поддоменами проверяется как обычный адрес без `*.`
+            if let Err(e) = validate_origin(&origin.replacen("://*.", "://", 1)) {
+                problems.push(format!("server_settings.cors.allowed_origins: {e}"));
+            }
+        }
+        for name in &cors.allowed_headers {
+            if http::HeaderName::from_bytes(name.as_bytes()).is_err() {
+                problems.push(format!(
+                    "server_settings.cors.allowed_headers: '{name}' is not a header name"
+                ));
+            }
+        }
+        if self.server_settings.limits.timeout_secs == 0
+            || self
+                .server_settings
+                .limits
+                .route_timeouts
+                .values()
+                .any(|secs| *secs == 0)
+        {
+            problems.push("server_settings.limits: timeouts must be positive".to_string());
+        }
+        let headers = &self.server_settings.security_headers;
+        for (key, value) in [
+            ("content_security_policy", &headers.content_security_policy),
+            ("referrer_policy", &headers.referrer_policy),
+            ("frame_options", &headers.frame_options),
+        ] {
+            if http::HeaderValue::from_str(value).is_err() {
+                problems.push(format!(
+                    "server_settings.security_headers.{key} is not a valid header value"
+                ));
+            }
+        }
+        if let Some(oidc) = &self.oidc_settings {
+            for (key, url) in [
+                ("issuer_url", &oidc.issuer_url),
+                ("redirect_url", &oidc.redirect_url),
+            ] {
+                if let Err(e) = validate_url(url) {
+                    problems.push(format!("oidc_settings.{key}: {e}"));
+                }
+            }
+        }
+        if self.bootstrap.owner_email.is_some() != self.bootstrap.owner_password.is_some() {
+            problems.push(
+                "bootstrap.owner_email and bootstrap.owner_password must be set together"
+                    .to_string(),
+            );
+        }
+        if !(0.0..=1.0).contains(&self.telemetry.sample_ratio) {
+            problems.push("telemetry.sample_ratio must be between 0 and 1".to_string());
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Custom(format!(
+                "invalid settings: {}",
+                problems.join("; ")
+            )))
+        }
+    }
+
+    /// Действующие настройки со скрытыми секретами
+    pub fn redacted(&self) -> Value {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        redact(&mut value, false);
+        value
+    }
+}
+
+/// Проверяет адрес HTTP(S)
+fn validate_url(url: &str) -> Result<reqwest::Url, String> {
+    let parsed =
+        reqwest::Url::parse(url).map_err(|e| format!("'{url}' is not a valid URL: {e}"))?;
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host().is_none() {
+        return Err(format!("'{url}' must be an http or https URL with a host"));
+    }
+    Ok(parsed)
+}
+
+/// Проверяет, что адрес - источник (origin): схема, хост и порт без пути
+fn validate_origin(origin: &str) -> Result<(), String> {
+    let parsed = validate_url(origin)?;
+    if parsed.path() != "/"
+        || origin.ends_with('/')
+        || parsed.query().is_some()
+        || parsed.fragment().is_some()
+        || !parsed.username().is_empty()
+    {
+        return Err(format!(
+            "'{origin}' must contain only a scheme, host and port, e.g. https://alfred.example.com"
+        ));
+    }
+    Ok(())
+}
+
+/// Заменяет значения секретов на `[REDACTED]`; пустые значения остаются,
+/// чтобы было видно, что секрет не задан
+fn redact(value: &mut Value, secret: bool) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                redact(
+                    value,
+                    secret || is_sensitive(key) || SECRET_KEYS.contains(&key.as_str()),
+                );
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| redact(item, secret)),
+        Value::String(text) if secret && !text.is_empty() => *text = REDACTED.to_string(),
+        _ => {}
+    }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct DatabaseSettings {
     pub host: String,
     pub port: u16,
@@ -41,6 +318,20 @@ pub struct DatabaseSettings {
     pub idle_timeout: Option<u64>,
 }
 
+impl Default for DatabaseSettings {
+    fn default() -> Self {
+        Self {
+            host: "localhost".into(),
+            port: 5432,
+            username: "postgres".into(),
+            password: String::new(),
+            database: "alfred".into(),
+            max_connections: None,
+            idle_timeout: None,
+        }
+    }
+}
+
 impl DatabaseSettings {
     #[instrument(name = "creating database url", skip(self))]
     pub fn db_url(&self) -> Cow<'static, str> {
@@ -54,29 +345,947 @@ impl DatabaseSettings {
         ))
     }
 }
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmailSettings {
     pub host: String,
+    /// Порт IMAP-сервера; проверяется при проверке готовности
+    #[serde(default = "default_imap_port")]
+    pub port: u16,
     pub username: String,
     pub password: String,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+impl EmailSettings {
+    /// Адрес почтового сервера, если он задан
+    pub fn server_address(&self) -> Option<String> {
+        (!self.host.is_empty())
+            .then(|| format!("{host}:{port}", host = self.host, port = self.port))
+    }
+}
+
+fn default_imap_port() -> u16 {
+    993
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ServerSettings {
     pub host: String,
     pub port: u16,
     pub origin: String,
+    /// Служебный порт с `/metrics`; если не задан, служебный сервер не запускается
+    ///
+    /// Служебный порт не должен быть доступен извне: метрики и смена
+    /// фильтра журнала не требуют аутентификации.
+    pub admin_port: Option<u16>,
+    /// Адрес, на котором слушает служебный сервер; по умолчанию только
+    /// локальный, независимо от `host`
+    pub admin_host: String,
+    /// Сколько секунд после сигнала завершения сервер продолжает
+    /// принимать соединения, отвечая 503 на проверку готовности,
+    /// чтобы балансировщик успел вывести его из ротации
+    pub shutdown_drain_secs: u64,
+    /// Обслуживание HTTPS на основном порту; если не задано, сервер принимает HTTP
+    pub tls: Option<TlsSettings>,
+    /// Правила CORS
+    pub cors: CorsSettings,
+    /// Ограничения времени обработки и размера запросов
+    pub limits: HttpLimits,
+    /// Заголовки безопасности, добавляемые к ответам
+    pub security_headers: SecurityHeadersSettings,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".into(),
+            port: 3000,
+            origin: "http://localhost:3000".into(),
+            admin_port: None,
+            admin_host: "127.0.0.1".into(),
+            shutdown_drain_secs: 0,
+            tls: None,
+            cors: CorsSettings::default(),
+            limits: HttpLimits::default(),
+            security_headers: SecurityHeadersSettings::default(),
+        }
+    }
 }
 
 impl ServerSettings {
     pub fn server_address(&self) -> String {
         format!("{host}:{port}", host = self.host, port = self.port)
     }
+    /// Адрес служебного сервера, если он включен
+    pub fn admin_address(&self) -> Option<String> {
+        self.admin_port
+            .map(|port| format!("{host}:{port}", host = self.admin_host))
+    }
+    /// Адрес, на котором HTTP перенаправляется на HTTPS, если он включен
+    pub fn redirect_address(&self) -> Option<String> {
+        self.tls
+            .as_ref()
+            .and_then(|tls| tls.redirect_port)
+            .map(|port| format!("{host}:{port}", host = self.host))
+    }
+}
+
+/// Настройки CORS
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CorsSettings {
+    /// Источники, которым помимо `origin` разрешены запросы из браузера,
+    /// включая изменяющие запросы с сессионной cookie; `https://*.example.com`
+    /// разрешает любой поддомен
+    pub allowed_origins: Vec<String>,
+    /// Заголовки, которые браузер может отправлять; заголовок CSRF разрешен всегда
+    pub allowed_headers: Vec<String>,
+    /// Сколько секунд браузер может не повторять предварительный запрос
+    pub max_age_secs: u64,
+}
+
+impl Default for CorsSettings {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec![],
+            allowed_headers: vec![
+                "accept".into(),
+                "authorization".into(),
+                "content-type".into(),
+            ],
+            max_age_secs: 3600,
+        }
+    }
+}
+
+/// Ограничения времени обработки и размера запросов
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpLimits {
+    /// Время обработки запроса в секундах, после которого клиент получает 408
+    pub timeout_secs: u64,
+    /// Время обработки для отдельных путей в секундах, например
+    /// `"/api/v1/users/import" = 60`; путь действует и на вложенные пути,
+    /// а из нескольких подходящих выбирается самый длинный
+    pub route_timeouts: HashMap<String, u64>,
+    /// Максимальный размер тела запроса в байтах
+    pub max_body_bytes: usize,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+impl Default for HttpLimits {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 10,
+            route_timeouts: HashMap::new(),
+            max_body_bytes: 1024 * 1024,
+        }
+    }
+}
+
+impl HttpLimits {
+    /// Возвращает время обработки для пути запроса
+    ///
+    /// # Аргументы
+    ///
+    /// * `path` - Путь запроса без строки запроса
+    pub fn timeout_for(&self, path: &str) -> std::time::Duration {
+        let secs = self
+            .route_timeouts
+            .iter()
+            .map(|(prefix, secs)| (prefix.trim_end_matches('/'), secs))
+            .filter(|(prefix, _)| {
+                path.strip_prefix(prefix)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.timeout_secs, |(_, secs)| *secs);
+        std::time::Duration::from_secs(secs)
+    }
+}
+
+/// Заголовки безопасности; пустое значение отключает заголовок
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SecurityHeadersSettings {
+    /// Значение `Content-Security-Policy`
+    ///
+    /// Страница документации API получает собственную политику,
+    /// разрешающую загрузку интерфейса.
+    pub content_security_policy: String,
+    /// Значение `Referrer-Policy`
+    pub referrer_policy: String,
+    /// Значение `X-Frame-Options`
+    pub frame_options: String,
+    /// Добавляет `X-Content-Type-Options: nosniff`
+    pub content_type_options: bool,
+}
+
+impl Default for SecurityHeadersSettings {
+    fn default() -> Self {
+        Self {
+            content_security_policy: "default-src 'none'; frame-ancestors 'none'".into(),
+            referrer_policy: "no-referrer".into(),
+            frame_options: "DENY".into(),
+            content_type_options: true,
+        }
+    }
+}
+
+impl SecurityHeadersSettings {
+    /// Собирает включенные заголовки
+    ///
+    /// Значения проверяются в `Settings::validate`; некорректные пропускаются.
+    pub fn header_map(&self) -> http::HeaderMap {
+        let nosniff = if self.content_type_options {
+            "nosniff"
+        } else {
+            ""
+        };
+        [
+            (
+                http::header::CONTENT_SECURITY_POLICY,
+                self.content_security_policy.as_str(),
+            ),
+            (http::header::REFERRER_POLICY, self.referrer_policy.as_str()),
+            (http::header::X_FRAME_OPTIONS, self.frame_options.as_str()),
+            (http::header::X_CONTENT_TYPE_OPTIONS, nosniff),
+        ]
+        .into_iter()
+        .filter(|(_, value)| !value.is_empty())
+        .filter_map(|(name, value)| Some((name, http::HeaderValue::from_str(value).ok()?)))
+        .collect()
+    }
+}
+
+/// Настройки TLS
+///
+/// Сертификат и ключ перечитываются с диска, когда файлы меняются,
+/// поэтому продление сертификата не требует перезапуска.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TlsSettings {
+    /// Путь к цепочке сертификатов в формате PEM, начиная с сертификата сервера
+    pub cert_path: std::path::PathBuf,
+    /// Путь к закрытому ключу в формате PEM
+    pub key_path: std::path::PathBuf,
+    /// Минимальная версия протокола
+    #[serde(default)]
+    pub min_version: TlsVersion,
+    /// Путь к сертификатам УЦ в формате PEM, которыми подписаны
+    /// клиентские сертификаты; если не задан, клиентские сертификаты не запрашиваются
+    ///
+    /// Запрос без токена с таким сертификатом аутентифицируется как сервисный
+    /// аккаунт, имя которого совпадает с первым DNS-именем из SAN
+    /// сертификата или, если их нет, с CN субъекта.
+    pub client_ca_path: Option<std::path::PathBuf>,
+    /// Обязателен ли клиентский сертификат, если задан `client_ca_path`
+    #[serde(default)]
+    pub client_auth: ClientAuth,
+    /// Интервал в секундах между проверками изменения файлов сертификата
+    #[serde(default = "default_tls_reload_interval_secs")]
+    pub reload_interval_secs: u64,
+    /// Порт, на котором запросы по HTTP перенаправляются на `origin`
+    pub redirect_port: Option<u16>,
+    /// Значение `max-age` заголовка `Strict-Transport-Security` в секундах;
+    /// `0` отключает заголовок
+    #[serde(default = "default_hsts_max_age_secs")]
+    pub hsts_max_age_secs: u64,
+    /// Распространяет HSTS на поддомены
+    #[serde(default)]
+    pub hsts_include_subdomains: bool,
+}
+
+fn default_tls_reload_interval_secs() -> u64 {
+    60
+}
+
+fn default_hsts_max_age_secs() -> u64 {
+    // Один год
+    31_536_000
+}
+
+/// Минимальная версия TLS
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TlsVersion {
+    #[default]
+    #[serde(rename = "1.2")]
+    Tls12,
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+/// Режим проверки клиентских сертификатов
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientAuth {
+    /// Соединения без сертификата отклоняются
+    #[default]
+    Required,
+    /// Сертификат проверяется, только если клиент его предъявил;
+    /// остальные клиенты аутентифицируются токеном
+    Optional,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JWTSettings {
+    /// Секрет HS256; используется для подписи сессий, только если `keys` пуст
     pub secret: String,
     pub expires_in: i64,
     pub maxage: i64,
+    /// Значение claim `iss` выпускаемых токенов
+    #[serde(default = "default_jwt_issuer")]
+    pub issuer: String,
+    /// Значения claim `aud` выпускаемых токенов
+    #[serde(default = "default_jwt_audience")]
+    pub audience: Vec<String>,
+    /// Асимметричные ключи подписи, публикуемые в `/.well-known/jwks.json`
+    #[serde(default)]
+    pub keys: Vec<JwtKeySettings>,
+}
+
+/// Настройки ключа подписи JWT
+///
+/// Ротация выполняется по расписанию: новый ключ добавляется заранее
+/// с `active_from` в будущем и сразу публикуется в JWKS, а старому
+/// задается `retire_at` не раньше, чем истекут подписанные им токены.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JwtKeySettings {
+    /// Идентификатор ключа (`kid` в заголовке токена)
+    pub kid: String,
+    /// Алгоритм подписи: `RS256` или `EdDSA`
+    pub algorithm: jsonwebtoken::Algorithm,
+    /// Путь к закрытому ключу в PEM; без него ключ используется только для проверки
+    pub private_key_file: Option<String>,
+    /// Путь к открытому ключу в PEM
+    pub public_key_file: String,
+    /// Момент, с которого ключ используется для подписи
+    pub active_from: Option<chrono::DateTime<chrono::Utc>>,
+    /// Момент, после которого подписанные ключом токены не принимаются
+    pub retire_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+fn default_jwt_issuer() -> String {
+    "alfred".into()
+}
+
+fn default_jwt_audience() -> Vec<String> {
+    vec!["alfred".into()]
+}
+
+/// Настройки кэша пользователей в пути аутентификации
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheSettings {
+    /// Время жизни записи в секундах
+    pub ttl: u64,
+    /// Максимальное количество записей
+    pub max_entries: usize,
+    /// Включает межсерверную инвалидацию через `LISTEN/NOTIFY`
+    #[serde(default)]
+    pub notify: bool,
+    /// Имя канала PostgreSQL для уведомлений
+    pub notify_channel: Option<String>,
+}
+
+/// Настройки проверки готовности
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthSettings {
+    /// Время на проверку одного компонента, миллисекунды
+    #[serde(default = "default_health_check_timeout_ms")]
+    pub check_timeout_ms: u64,
+}
+
+impl Default for HealthSettings {
+    fn default() -> Self {
+        Self {
+            check_timeout_ms: default_health_check_timeout_ms(),
+        }
+    }
+}
+
+fn default_health_check_timeout_ms() -> u64 {
+    crate::services::DEFAULT_HEALTH_CHECK_TIMEOUT.as_millis() as u64
+}
+
+/// Настройки журнала
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingSettings {
+    /// Фильтр в формате `RUST_LOG`, например `info,alfred=debug,sqlx=warn`;
+    /// переменная окружения `RUST_LOG` имеет приоритет
+    pub filter: String,
+    /// Формат вывода в консоль
+    pub format: LogFormat,
+    /// Запись журнала в файлы с ротацией
+    pub file: Option<LogFileSettings>,
+}
+
+impl Default for LoggingSettings {
+    fn default() -> Self {
+        Self {
+            filter: "info".to_string(),
+            format: LogFormat::default(),
+            file: None,
+        }
+    }
+}
+
+/// Формат записей журнала
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// Текст для чтения человеком
+    #[default]
+    Text,
+    /// JSON, по объекту на строку, для сборщиков журналов
+    Json,
+}
+
+/// Настройки записи журнала в файлы
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogFileSettings {
+    /// Каталог файлов журнала
+    pub directory: std::path::PathBuf,
+    /// Начало имени файла; к нему добавляется дата периода
+    #[serde(default = "default_log_file_prefix")]
+    pub prefix: String,
+    /// Как часто начинать новый файл
+    #[serde(default)]
+    pub rotation: LogRotation,
+    /// Сколько последних файлов хранить; без ограничения, если не задано
+    pub max_files: Option<usize>,
+    /// Формат записей в файле
+    #[serde(default = "default_log_file_format")]
+    pub format: LogFormat,
+}
+
+fn default_log_file_prefix() -> String {
+    "alfred.log".to_string()
+}
+
+fn default_log_file_format() -> LogFormat {
+    LogFormat::Json
+}
+
+/// Период ротации файлов журнала
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogRotation {
+    Hourly,
+    #[default]
+    Daily,
+    /// Один файл без ротации
+    Never,
+}
+
+/// Настройки экспорта трассировок в OpenTelemetry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TelemetrySettings {
+    /// Включает экспорт трассировок
+    pub enabled: bool,
+    /// Адрес приема трассировок коллектора по OTLP/HTTP
+    pub endpoint: String,
+    /// Дополнительные заголовки запросов к коллектору, например для авторизации
+    pub headers: HashMap<String, String>,
+    /// Имя сервиса в трассировках
+    pub service_name: String,
+    /// Какие спаны экспортировать, в формате `RUST_LOG` без фильтров по полям
+    pub filter: String,
+    /// Доля трассировок, начатых этим сервисом, которые экспортируются (от 0 до 1)
+    pub sample_ratio: f64,
+    /// Следовать решению о сэмплировании из входящего `traceparent`
+    pub parent_based: bool,
+    /// Время на отправку пакета спанов, миллисекунды
+    pub export_timeout_ms: u64,
+}
+
+impl Default for TelemetrySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: "http://localhost:4318/v1/traces".to_string(),
+            headers: HashMap::new(),
+            service_name: "alfred".to_string(),
+            filter: "info".to_string(),
+            sample_ratio: 1.0,
+            parent_based: true,
+            export_timeout_ms: 10_000,
+        }
+    }
+}
+
+/// Настройки жизненного цикла аккаунтов
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountSettings {
+    /// Срок в днях, в течение которого удаление аккаунта можно отменить
+    #[serde(default = "default_deletion_cooling_off_days")]
+    pub deletion_cooling_off_days: i64,
+    /// Интервал в секундах между проверками аккаунтов, подлежащих анонимизации
+    #[serde(default = "default_purge_interval_secs")]
+    pub purge_interval_secs: u64,
+}
+
+impl Default for AccountSettings {
+    fn default() -> Self {
+        Self {
+            deletion_cooling_off_days: default_deletion_cooling_off_days(),
+            purge_interval_secs: default_purge_interval_secs(),
+        }
+    }
+}
+
+fn default_deletion_cooling_off_days() -> i64 {
+    crate::services::DEFAULT_DELETION_GRACE_DAYS
+}
+
+fn default_purge_interval_secs() -> u64 {
+    3600
+}
+
+/// Настройки первичного создания владельца
+///
+/// Пока в системе нет ни одного владельца, при запуске создается владелец
+/// с указанными email и паролем, а если они не заданы - выдается одноразовый
+/// токен настройки. После появления владельца настройки ни на что не влияют.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BootstrapSettings {
+    /// Email владельца, создаваемого при первом запуске
+    pub owner_email: Option<String>,
+    /// Пароль владельца; удобно задавать через `ALF__BOOTSTRAP__OWNER_PASSWORD_FILE`
+    pub owner_password: Option<String>,
+    /// Выдает одноразовый токен настройки, если владелец не задан
+    pub setup_token: bool,
+}
+
+impl Default for BootstrapSettings {
+    fn default() -> Self {
+        Self {
+            owner_email: None,
+            owner_password: None,
+            setup_token: true,
+        }
+    }
+}
+
+/// Настройки входа через корпоративного провайдера OpenID Connect
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OidcSettings {
+    /// Адрес провайдера (`iss`); по нему запрашивается
+    /// `/.well-known/openid-configuration`
+    pub issuer_url: String,
+    /// Идентификатор клиента, зарегистрированного у провайдера
+    pub client_id: String,
+    /// Секрет клиента; не нужен публичным клиентам, использующим только PKCE
+    pub client_secret: Option<String>,
+    /// Адрес обратного вызова, зарегистрированный у провайдера
+    pub redirect_url: String,
+    /// Запрашиваемые области действия
+    #[serde(default = "default_oidc_scopes")]
+    pub scopes: Vec<String>,
+    /// Имя claim со списком групп пользователя
+    #[serde(default = "default_oidc_groups_claim")]
+    pub groups_claim: String,
+    /// Соответствие групп провайдера ролям Alfred
+    #[serde(default)]
+    pub role_mapping: HashMap<String, String>,
+    /// Роль новых пользователей, не попавших ни в одну группу из `role_mapping`
+    pub default_role: Option<String>,
+    /// Создавать ли пользователя при первом входе, если аккаунта с таким email нет
+    #[serde(default = "default_true")]
+    pub jit_provisioning: bool,
+}
+
+fn default_oidc_scopes() -> Vec<String> {
+    vec!["openid".into(), "email".into(), "profile".into()]
+}
+
+fn default_oidc_groups_claim() -> String {
+    "groups".into()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Настройки парольной политики
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PasswordPolicySettings {
+    /// Минимальная длина пароля в символах (не меньше 8)
+    pub min_length: usize,
+    /// Максимальная длина пароля в символах (не больше 256)
+    pub max_length: usize,
+    /// Требовать хотя бы одну цифру
+    pub require_digit: bool,
+    /// Требовать хотя бы одну заглавную букву
+    pub require_uppercase: bool,
+    /// Требовать хотя бы одну строчную букву
+    pub require_lowercase: bool,
+    /// Требовать хотя бы один специальный символ
+    pub require_special: bool,
+    /// Минимальная оценка стойкости от 0 до 4
+    pub min_score: u8,
+    /// Файл или каталог со списком утекших паролей в формате SHA-1
+    pub breached_passwords_path: Option<String>,
+    /// Минимальное число утечек, при котором пароль отклоняется
+    pub breached_min_count: u64,
+}
+
+impl Default for PasswordPolicySettings {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            max_length: 64,
+            require_digit: true,
+            require_uppercase: true,
+            require_lowercase: true,
+            require_special: true,
+            min_score: 0,
+            breached_passwords_path: None,
+            breached_min_count: 1,
+        }
+    }
+}
+
+/// Настройки хэширования паролей
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PasswordHashSettings {
+    /// Объем памяти Argon2 в КиБ
+    pub memory_kib: u32,
+    /// Число проходов Argon2
+    pub iterations: u32,
+    /// Степень параллелизма Argon2
+    pub parallelism: u32,
+    /// Серверный перец; хранится только в настройках
+    pub pepper: Option<String>,
+    /// Идентификатор текущего перца (до 8 байт), записываемый в хэш
+    pub pepper_id: String,
+    /// Прежние перцы по идентификаторам; нужны, пока не все хэши пересчитаны
+    pub retired_peppers: HashMap<String, String>,
+    /// Максимум одновременных вычислений хэша; по умолчанию - число ядер
+    pub max_concurrent: Option<usize>,
+}
+
+impl Default for PasswordHashSettings {
+    fn default() -> Self {
+        Self {
+            memory_kib: argon2::Params::DEFAULT_M_COST,
+            iterations: argon2::Params::DEFAULT_T_COST,
+            parallelism: argon2::Params::DEFAULT_P_COST,
+            pepper: None,
+            pepper_id: "1".into(),
+            retired_peppers: HashMap::new(),
+            max_concurrent: None,
+        }
+    }
+}
+
+/// Группа маршрутов для входа и регистрации
+pub const RATE_LIMIT_AUTH_GROUP: &str = "auth";
+/// Группа маршрутов, требующих аутентификации
+pub const RATE_LIMIT_API_GROUP: &str = "api";
+
+/// Настройки ограничения частоты запросов
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitSettings {
+    /// Включает ограничение частоты запросов
+    pub enabled: bool,
+    /// Где хранить корзины токенов
+    pub store: RateLimitStoreKind,
+    /// Сети обратных прокси, которым доверяется заголовок `X-Forwarded-For`
+    pub trusted_proxies: Vec<ipnet::IpNet>,
+    /// Политики по группам маршрутов (`auth`, `api`); для группы
+    /// без записи действует политика по умолчанию
+    pub policies: HashMap<String, RateLimitPolicySettings>,
+    /// Интервал в секундах между удалениями неиспользуемых корзин
+    pub purge_interval_secs: u64,
+}
+
+impl Default for RateLimitSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            store: RateLimitStoreKind::default(),
+            trusted_proxies: Vec::new(),
+            policies: HashMap::new(),
+            purge_interval_secs: 600,
+        }
+    }
+}
+
+impl RateLimitSettings {
+    /// Возвращает политику группы маршрутов
+    ///
+    /// # Возвращает
+    ///
+    /// * `Some(RateLimitPolicySettings)` - Политика из настроек или по умолчанию
+    /// * `None` - Для группы нет политики
+    pub fn policy(&self, group: &str) -> Option<RateLimitPolicySettings> {
+        if let Some(policy) = self.policies.get(group) {
+            return Some(policy.clone());
+        }
+        match group {
+            RATE_LIMIT_AUTH_GROUP => Some(RateLimitPolicySettings {
+                requests: 10,
+                period_secs: 60,
+                key: RateLimitKey::Ip,
+            }),
+            RATE_LIMIT_API_GROUP => Some(RateLimitPolicySettings {
+                requests: 300,
+                period_secs: 60,
+                key: RateLimitKey::User,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Хранилище корзин токенов
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitStoreKind {
+    /// Память процесса; счетчики не разделяются между экземплярами
+    #[default]
+    Memory,
+    /// Таблица PostgreSQL, общая для всех экземпляров
+    Postgres,
+}
+
+/// Чем различаются клиенты при подсчете запросов
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitKey {
+    /// IP-адрес клиента
+    #[default]
+    Ip,
+    /// Аутентифицированный пользователь, а без аутентификации - IP-адрес
+    User,
+}
+
+/// Политика ограничения для группы маршрутов
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RateLimitPolicySettings {
+    /// Сколько запросов можно выполнить за период, в том числе подряд
+    pub requests: u32,
+    /// Период в секундах, за который корзина наполняется полностью
+    pub period_secs: u64,
+    /// Чем различаются клиенты
+    #[serde(default)]
+    pub key: RateLimitKey,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "kR7#vQ2!mZ9@xL4$wP6^tN8&yB3*hD5%";
+
+    /// Каталог с файлами настроек для теста
+    fn config_dir(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, contents) in files {
+            std::fs::write(dir.path().join(name), contents).unwrap();
+        }
+        dir
+    }
+
+    fn env(vars: &[(&str, &str)]) -> HashMap<String, String> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_layered_sources() {
+        let dir = config_dir(&[
+            (
+                "settings.toml",
+                &format!(
+                    "[server_settings]\nport = 3000\norigin = \"http://localhost:3000\"\n\
+                     [jwt_settings]\nsecret = \"{SECRET}\"\nexpires_in = 60\nmaxage = 1\n\
+                     [database_settings]\npassword = \"from-file\"\n"
+                ),
+            ),
+            (
+                "settings.production.toml",
+                "[server_settings]\nport = 8080\norigin = \"https://alfred.example.com\"\n",
+            ),
+            ("db_password", "from-secret-file\n"),
+        ]);
+        let file = dir.path().join("settings.toml");
+        let file = file.to_str().unwrap();
+
+        let settings = load_from(file, HashMap::new()).unwrap();
+        assert_eq!(settings.server_settings.port, 3000);
+        assert_eq!(settings.database_settings.password, "from-file");
+        assert_eq!(settings.database_settings.host, "localhost");
+        assert!(settings.email_settings.is_none());
+        settings.validate().unwrap();
+
+        let secret_file = dir.path().join("db_password");
+        let settings = load_from(
+            file,
+            env(&[
+                ("ALF_ENV", "production"),
+                ("ALF__SERVER_SETTINGS__HOST", "0.0.0.0"),
+                ("ALF__JWT_SETTINGS__AUDIENCE", "alfred,partners"),
+                (
+                    "ALF__DATABASE_SETTINGS__PASSWORD_FILE",
+                    secret_file.to_str().unwrap(),
+                ),
+                ("OTHER__SERVER_SETTINGS__PORT", "1"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(settings.server_settings.port, 8080);
+        assert_eq!(settings.server_settings.host, "0.0.0.0");
+        assert_eq!(
+            settings.server_settings.origin,
+            "https://alfred.example.com"
+        );
+        assert_eq!(settings.jwt_settings.audience, ["alfred", "partners"]);
+        assert_eq!(settings.database_settings.password, "from-secret-file");
+
+        let missing = load_from(
+            file,
+            env(&[("ALF__JWT_SETTINGS__SECRET_FILE", "/nonexistent/secret")]),
+        );
+        assert!(
+            matches!(missing, Err(AppError::Custom(e)) if e.contains("ALF__JWT_SETTINGS__SECRET_FILE"))
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let vars = env(&[
+            ("ALF__JWT_SETTINGS__SECRET", "secret"),
+            ("ALF__JWT_SETTINGS__EXPIRES_IN", "60"),
+            ("ALF__JWT_SETTINGS__MAXAGE", "0"),
+            ("ALF__SERVER_SETTINGS__ORIGIN", "localhost:3000/app"),
+            (
+                "ALF__SERVER_SETTINGS__CORS__ALLOWED_ORIGINS",
+                "https://*.example.com,app.example.com",
+            ),
+            (
+                "ALF__SERVER_SETTINGS__CORS__ALLOWED_HEADERS",
+                "content type",
+            ),
+        ]);
+        let mut settings = load_from("missing.toml", vars).unwrap();
+        assert_eq!(
+            settings.server_settings.cors.allowed_origins,
+            ["https://*.example.com", "app.example.com"]
+        );
+        let Err(AppError::Custom(problems)) = settings.validate() else {
+            panic!("settings should be invalid");
+        };
+        assert!(problems.contains("jwt_settings.secret must be at least 32 bytes long"));
+        assert!(problems.contains("jwt_settings.maxage must be positive"));
+        assert!(problems.contains("server_settings.origin"));
+        assert!(problems.contains("server_settings.cors.allowed_origins"));
+        assert!(!problems.contains("https://*.example.com"));
+        assert!(problems.contains("'content type' is not a header name"));
+        settings.server_settings.cors.allowed_origins.truncate(1);
+        settings.server_settings.cors.allowed_headers = vec!["content-type".into()];
+
+        settings.jwt_settings.secret = "a".repeat(MIN_JWT_SECRET_LEN);
+        settings.jwt_settings.maxage = 1;
+        settings.server_settings.origin = "https://alfred.example.com/".into();
+        let Err(AppError::Custom(problems)) = settings.validate() else {
+            panic!("settings should be invalid");
+        };
+        assert!(problems.contains("too repetitive"));
+        assert!(problems.contains("must contain only a scheme, host and port"));
+
+        settings.jwt_settings.secret = SECRET.into();
+        settings.server_settings.origin = "https://alfred.example.com:8443".into();
+        settings.server_settings.tls = Some(TlsSettings {
+            redirect_port: Some(settings.server_settings.port),
+            ..serde_json::from_value(serde_json::json!({
+                "cert_path": "cert.pem",
+                "key_path": "key.pem",
+            }))
+            .unwrap()
+        });
+        settings.server_settings.origin = "http://alfred.example.com".into();
+        let Err(AppError::Custom(problems)) = settings.validate() else {
+            panic!("settings should be invalid");
+        };
+        assert!(problems.contains("redirect_port is already in use"));
+        assert!(problems.contains("origin must use https"));
+
+        settings.server_settings.origin = "https://alfred.example.com:8443".into();
+        settings.server_settings.tls = None;
+        settings.bootstrap.owner_email = Some("owner@example.com".into());
+        let Err(AppError::Custom(problems)) = settings.validate() else {
+            panic!("settings should be invalid");
+        };
+        assert!(problems.contains("must be set together"));
+
+        settings.bootstrap.owner_password = Some("owner-password".into());
+        settings.validate().unwrap();
+    }
+
+    #[test]
+    fn test_admin_address() {
+        let mut settings = ServerSettings {
+            host: "0.0.0.0".into(),
+            admin_port: Some(9090),
+            ..Default::default()
+        };
+        // Служебный сервер не слушает внешние интерфейсы вслед за API
+        assert_eq!(settings.server_address(), "0.0.0.0:3000");
+        assert_eq!(settings.admin_address().as_deref(), Some("127.0.0.1:9090"));
+        settings.admin_host = "10.0.0.5".into();
+        assert_eq!(settings.admin_address().as_deref(), Some("10.0.0.5:9090"));
+    }
+
+    #[test]
+    fn test_route_timeouts() {
+        let limits = HttpLimits {
+            timeout_secs: 10,
+            route_timeouts: HashMap::from([
+                ("/api/v1/users".to_string(), 30),
+                ("/api/v1/users/import/".to_string(), 120),
+            ]),
+            ..Default::default()
+        };
+        let secs = |path| limits.timeout_for(path).as_secs();
+        assert_eq!(secs("/api/v1/health"), 10);
+        assert_eq!(secs("/api/v1/users"), 30);
+        assert_eq!(secs("/api/v1/users/42"), 30);
+        assert_eq!(secs("/api/v1/users/import"), 120);
+        assert_eq!(secs("/api/v1/users/import/csv"), 120);
+        assert_eq!(secs("/api/v1/usersfoo"), 10);
+    }
+
+    #[test]
+    fn test_redacted() {
+        let vars = env(&[
+            ("ALF__JWT_SETTINGS__SECRET", SECRET),
+            ("ALF__JWT_SETTINGS__EXPIRES_IN", "60"),
+            ("ALF__JWT_SETTINGS__MAXAGE", "1"),
+            ("ALF__DATABASE_SETTINGS__PASSWORD", "db-password"),
+            ("ALF__PASSWORD_HASHING__PEPPER", "pepper"),
+            ("ALF__PASSWORD_HASHING__RETIRED_PEPPERS__0", "old-pepper"),
+            ("ALF__BOOTSTRAP__OWNER_EMAIL", "owner@example.com"),
+            ("ALF__BOOTSTRAP__OWNER_PASSWORD", "owner-password"),
+        ]);
+        let settings = load_from("missing.toml", vars).unwrap();
+        let value = settings.redacted();
+        assert_eq!(value["jwt_settings"]["secret"], REDACTED);
+        assert_eq!(value["database_settings"]["password"], REDACTED);
+        assert_eq!(value["database_settings"]["username"], "postgres");
+        assert_eq!(value["password_hashing"]["pepper"], REDACTED);
+        assert_eq!(value["password_hashing"]["retired_peppers"]["0"], REDACTED);
+        assert_eq!(value["password_hashing"]["pepper_id"], "1");
+        assert_eq!(value["bootstrap"]["owner_email"], "owner@example.com");
+        assert_eq!(value["bootstrap"]["owner_password"], REDACTED);
+        let text = value.to_string();
+        for secret in [SECRET, "db-password", "old-pepper", "owner-password"] {
+            assert!(!text.contains(secret));
+        }
+    }
 }
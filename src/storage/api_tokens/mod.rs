@@ -0,0 +1,30 @@
+mod pg_api_tokens_repository;
+use crate::{
+    AppResult,
+    models::{ApiToken, NewApiToken},
+};
+use async_trait::async_trait;
+
+/// Трейт репозитория API-ключей
+///
+/// Определяет контракт для хранения API-ключей. Репозиторий работает
+/// только с хэшами ключей и никогда не видит их в открытом виде.
+#[async_trait]
+pub trait ApiTokensRepository: Send + Sync {
+    /// Сохраняет новый ключ пользователя
+    async fn create_token(
+        &self,
+        user_id: uuid::Uuid,
+        prefix: &str,
+        token_hash: &str,
+        data: NewApiToken,
+    ) -> AppResult<ApiToken>;
+    /// Получает все ключи пользователя, включая отозванные
+    async fn list_tokens(&self, user_id: uuid::Uuid) -> AppResult<Vec<ApiToken>>;
+    /// Находит ключ по хэшу
+    async fn find_token_by_hash(&self, token_hash: &str) -> AppResult<ApiToken>;
+    /// Отмечает время последнего использования ключа
+    async fn touch_token(&self, token_id: uuid::Uuid) -> AppResult<()>;
+    /// Отзывает ключ пользователя
+    async fn revoke_token(&self, user_id: uuid::Uuid, token_id: uuid::Uuid) -> AppResult<ApiToken>;
+}
@@ -0,0 +1,261 @@
+//! Репозиторий API-ключей для PostgreSQL
+//!
+//! Этот модуль содержит реализацию репозитория API-ключей
+//! для работы с базой данных PostgreSQL.
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use tracing::instrument;
+
+use crate::{
+    AppError, AppResult,
+    models::{ApiScope, ApiToken, NewApiToken},
+    storage::{ApiTokensRepository, PgStorage},
+};
+
+#[async_trait]
+impl ApiTokensRepository for PgStorage {
+    /// Сохраняет новый ключ пользователя
+    ///
+    /// # Аргументы
+    ///
+    /// * `user_id` - UUID владельца ключа
+    /// * `prefix` - Начало ключа для отображения
+    /// * `token_hash` - Хэш ключа
+    /// * `data` - Название, области действия и срок действия ключа
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<ApiToken>` - Сохраненный ключ или ошибку
+    #[instrument(name = "create api token", skip(self, token_hash))]
+    async fn create_token(
+        &self,
+        user_id: uuid::Uuid,
+        prefix: &str,
+        token_hash: &str,
+        data: NewApiToken,
+    ) -> AppResult<ApiToken> {
+        let scopes: Vec<String> = data.scopes.iter().map(|s| s.to_string()).collect();
+        let res = sqlx::query_as!(
+            ApiTokenDTO,
+            r#"
+			INSERT INTO api_tokens (user_id, name, prefix, token_hash, scopes, expires_at)
+			VALUES ($1, $2, $3, $4, $5, $6)
+			RETURNING *;
+			"#,
+            user_id,
+            data.name,
+            prefix,
+            token_hash,
+            &scopes,
+            data.expires_at,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        res.try_into()
+    }
+
+    /// Получает все ключи пользователя
+    ///
+    /// # Аргументы
+    ///
+    /// * `user_id` - UUID владельца ключей
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<Vec<ApiToken>>` - Ключи в порядке убывания даты создания
+    #[instrument(name = "list api tokens", skip(self))]
+    async fn list_tokens(&self, user_id: uuid::Uuid) -> AppResult<Vec<ApiToken>> {
+        let rows = sqlx::query_as!(
+            ApiTokenDTO,
+            r#"
+			SELECT * FROM api_tokens WHERE user_id = $1 ORDER BY created DESC;
+			"#,
+            user_id,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        rows.into_iter().map(ApiToken::try_from).collect()
+    }
+
+    /// Находит ключ по хэшу
+    ///
+    /// # Аргументы
+    ///
+    /// * `token_hash` - Хэш ключа
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<ApiToken>` - Найденный ключ или `AppError::EntryNotFound`
+    #[instrument(name = "find api token", skip_all)]
+    async fn find_token_by_hash(&self, token_hash: &str) -> AppResult<ApiToken> {
+        let res = sqlx::query_as!(
+            ApiTokenDTO,
+            r#"
+			SELECT * FROM api_tokens WHERE token_hash = $1;
+			"#,
+            token_hash,
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(AppError::EntryNotFound)?;
+        res.try_into()
+    }
+
+    /// Отмечает время последнего использования ключа
+    ///
+    /// # Аргументы
+    ///
+    /// * `token_id` - UUID ключа
+    #[instrument(name = "touch api token", skip(self))]
+    async fn touch_token(&self, token_id: uuid::Uuid) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+			UPDATE api_tokens SET last_used_at = NOW() WHERE token_id = $1;
+			"#,
+            token_id,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Отзывает ключ пользователя
+    ///
+    /// Повторный отзыв не меняет время первого отзыва.
+    ///
+    /// # Аргументы
+    ///
+    /// * `user_id` - UUID владельца ключа
+    /// * `token_id` - UUID ключа
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<ApiToken>` - Отозванный ключ или `AppError::EntryNotFound`
+    #[instrument(name = "revoke api token", skip(self))]
+    async fn revoke_token(&self, user_id: uuid::Uuid, token_id: uuid::Uuid) -> AppResult<ApiToken> {
+        let res = sqlx::query_as!(
+            ApiTokenDTO,
+            r#"
+			UPDATE api_tokens SET revoked_at = COALESCE(revoked_at, NOW())
+			WHERE token_id = $1 AND user_id = $2
+			RETURNING *;
+			"#,
+            token_id,
+            user_id,
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(AppError::EntryNotFound)?;
+        res.try_into()
+    }
+}
+
+/// DTO (Data Transfer Object) для API-ключа
+struct ApiTokenDTO {
+    token_id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    name: String,
+    prefix: String,
+    #[allow(unused)]
+    token_hash: String,
+    scopes: Vec<String>,
+    created: chrono::DateTime<chrono::Utc>,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    last_used_at: Option<chrono::DateTime<chrono::Utc>>,
+    revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl TryFrom<ApiTokenDTO> for ApiToken {
+    type Error = AppError;
+    fn try_from(value: ApiTokenDTO) -> AppResult<Self> {
+        let scopes = value
+            .scopes
+            .iter()
+            .map(|s| ApiScope::from_str(s))
+            .collect::<AppResult<Vec<_>>>()?;
+        Ok(Self {
+            token_id: value.token_id,
+            user_id: value.user_id,
+            name: value.name,
+            prefix: value.prefix,
+            scopes,
+            created: value.created,
+            expires_at: value.expires_at,
+            last_used_at: value.last_used_at,
+            revoked_at: value.revoked_at,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use crate::{
+        AppError, AppResult,
+        models::{ApiScope, NewApiToken, SignupData, UserRole},
+        storage::{ApiTokensRepository, PgStorage, UsersRepository},
+    };
+
+    #[sqlx::test]
+    async fn api_tokens_lifecycle_test(pool: PgPool) -> AppResult<()> {
+        let storage = PgStorage::with_pool(pool);
+        let user = storage
+            .create(SignupData {
+                email: "tokens@example.com".to_string(),
+                password: "str0nGp@ssw0rD".to_string(),
+                role: UserRole::Guest,
+            })
+            .await?;
+
+        let created = storage
+            .create_token(
+                user.user_id,
+                "alf_abcd",
+                "hash",
+                NewApiToken {
+                    name: "ci".to_string(),
+                    scopes: vec![ApiScope::ProfileRead, ApiScope::ProfileWrite],
+                    expires_at: None,
+                },
+            )
+            .await?;
+        assert_eq!(
+            created.scopes,
+            vec![ApiScope::ProfileRead, ApiScope::ProfileWrite]
+        );
+        assert!(created.last_used_at.is_none());
+
+        let found = storage.find_token_by_hash("hash").await?;
+        assert_eq!(found.token_id, created.token_id);
+        assert!(matches!(
+            storage.find_token_by_hash("other").await,
+            Err(AppError::EntryNotFound)
+        ));
+
+        storage.touch_token(created.token_id).await?;
+        assert!(
+            storage
+                .find_token_by_hash("hash")
+                .await?
+                .last_used_at
+                .is_some()
+        );
+
+        // Чужой ключ отозвать нельзя
+        assert!(matches!(
+            storage
+                .revoke_token(uuid::Uuid::new_v4(), created.token_id)
+                .await,
+            Err(AppError::EntryNotFound)
+        ));
+        let revoked = storage.revoke_token(user.user_id, created.token_id).await?;
+        let revoked_again = storage.revoke_token(user.user_id, created.token_id).await?;
+        assert!(revoked.revoked_at.is_some());
+        assert_eq!(revoked.revoked_at, revoked_again.revoked_at);
+
+        assert_eq!(storage.list_tokens(user.user_id).await?.len(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,17 @@
+mod pg_audit_repository;
+use crate::{
+    AppResult,
+    models::{AuditEvent, NewAuditEvent},
+};
+use async_trait::async_trait;
+
+/// Трейт репозитория журнала аудита
+///
+/// Определяет контракт для записи и чтения событий аудита.
+#[async_trait]
+pub trait AuditRepository: Send + Sync {
+    /// Записывает новое событие в журнал
+    async fn record(&self, event: NewAuditEvent) -> AppResult<AuditEvent>;
+    /// Получает все события, касающиеся пользователя, в порядке возрастания времени
+    async fn list_for_user(&self, user_id: uuid::Uuid) -> AppResult<Vec<AuditEvent>>;
+}
@@ -0,0 +1,136 @@
+//! Репозиторий журнала аудита для PostgreSQL
+//!
+//! Этот модуль содержит реализацию репозитория журнала аудита
+//! для работы с базой данных PostgreSQL.
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use tracing::instrument;
+
+use crate::{
+    AppResult,
+    models::{AuditAction, AuditEvent, NewAuditEvent},
+    storage::{AuditRepository, PgStorage},
+};
+
+#[async_trait]
+impl AuditRepository for PgStorage {
+    /// Записывает новое событие в журнал
+    ///
+    /// # Аргументы
+    ///
+    /// * `event` - Данные события
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<AuditEvent>` - Сохраненное событие или ошибку
+    #[instrument(name = "record audit event", skip_all, fields(action = %event.action))]
+    async fn record(&self, event: NewAuditEvent) -> AppResult<AuditEvent> {
+        let res = sqlx::query_as!(
+            AuditEventDTO,
+            r#"
+			INSERT INTO audit_log (user_id, actor_id, action, details)
+			VALUES ($1, $2, $3, $4)
+			RETURNING *;
+			"#,
+            event.user_id,
+            event.actor_id,
+            event.action.as_ref(),
+            event.details,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        res.try_into()
+    }
+
+    /// Получает все события, касающиеся пользователя
+    ///
+    /// # Аргументы
+    ///
+    /// * `user_id` - UUID пользователя
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<Vec<AuditEvent>>` - События в порядке возрастания времени
+    #[instrument(name = "list audit events", skip(self))]
+    async fn list_for_user(&self, user_id: uuid::Uuid) -> AppResult<Vec<AuditEvent>> {
+        let rows = sqlx::query_as!(
+            AuditEventDTO,
+            r#"
+			SELECT * FROM audit_log WHERE user_id = $1 ORDER BY created ASC;
+			"#,
+            user_id,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        rows.into_iter().map(AuditEvent::try_from).collect()
+    }
+}
+
+/// DTO (Data Transfer Object) для события аудита
+struct AuditEventDTO {
+    event_id: uuid::Uuid,
+    user_id: Option<uuid::Uuid>,
+    actor_id: Option<uuid::Uuid>,
+    action: String,
+    details: Option<String>,
+    created: chrono::DateTime<chrono::Utc>,
+}
+
+impl TryFrom<AuditEventDTO> for AuditEvent {
+    type Error = crate::AppError;
+    fn try_from(value: AuditEventDTO) -> AppResult<Self> {
+        Ok(Self {
+            event_id: value.event_id,
+            user_id: value.user_id,
+            actor_id: value.actor_id,
+            action: AuditAction::from_str(&value.action)?,
+            details: value.details,
+            created: value.created,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use crate::{
+        AppResult,
+        models::{AuditAction, NewAuditEvent, SignupData, UserRole},
+        storage::{AuditRepository, PgStorage, UsersRepository},
+    };
+
+    #[sqlx::test]
+    async fn record_and_list_events_test(pool: PgPool) -> AppResult<()> {
+        let storage = PgStorage::with_pool(pool);
+        let user = storage
+            .create(SignupData {
+                email: "audit@example.com".to_string(),
+                password: "str0nGp@ssw0rD".to_string(),
+                role: UserRole::Guest,
+            })
+            .await?;
+
+        let recorded = storage
+            .record(NewAuditEvent::own(user.user_id, AuditAction::Signup))
+            .await?;
+        assert_eq!(recorded.user_id, Some(user.user_id));
+        storage
+            .record(
+                NewAuditEvent::own(user.user_id, AuditAction::SigninFailed).with_details("test"),
+            )
+            .await?;
+
+        let events = storage.list_for_user(user.user_id).await?;
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].action, AuditAction::Signup);
+        assert_eq!(events[1].action, AuditAction::SigninFailed);
+        assert_eq!(events[1].details, Some("test".to_string()));
+
+        // События переживают удаление пользователя, но теряют связь с ним
+        storage.delete(user.user_id).await?;
+        assert!(storage.list_for_user(user.user_id).await?.is_empty());
+        Ok(())
+    }
+}
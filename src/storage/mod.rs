@@ -1,10 +1,16 @@
 //! Модуль для работы с базами данных
 //!
 //! Этот модуль содержит структуры и методы для работы с базами данных
+mod api_tokens;
+pub use api_tokens::ApiTokensRepository;
+mod audit;
+pub use audit::AuditRepository;
+mod rate_limits;
+pub use rate_limits::{MemoryRateLimitStore, RateLimitDecision, RateLimitStore, TokenBucket};
 mod users;
 pub use users::{
-    DEFAULT_PAGE_NUM, DEFAULT_PER_PAGE, MAX_PER_PAGE, UsersFilter, UsersFilterBuilderError,
-    UsersRepository,
+    CacheStats, CachedUsersRepository, DEFAULT_NOTIFY_CHANNEL, DEFAULT_PAGE_NUM, DEFAULT_PER_PAGE,
+    MAX_PER_PAGE, UsersFilter, UsersFilterBuilderError, UsersRepository,
 };
 mod pg_storage;
-pub use pg_storage::PgStorage;
+pub use pg_storage::{MigrationStatus, PgStorage};
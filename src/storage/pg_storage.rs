@@ -1,7 +1,21 @@
 use crate::AppResult;
-use sqlx::{Connection, Pool, Postgres};
+use sqlx::{Connection, Pool, Postgres, migrate::Migrator};
 use tracing::instrument;
 
+/// Миграции схемы, встроенные в приложение
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Состояние миграции схемы базы данных
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationStatus {
+    /// Версия миграции
+    pub version: i64,
+    /// Описание миграции
+    pub description: String,
+    /// Применена ли миграция к базе данных
+    pub applied: bool,
+}
+
 /// Хранилище данных на основе PostgreSQL
 ///
 /// Обеспечивает подключение и работу с базой данных PostgreSQL через пул соединений.
@@ -41,13 +55,90 @@ impl PgStorage {
     /// * `INFO` - успешная инициализация хранилища с деталями подключения
     #[instrument(name = "initializing pg repository", skip(pool))]
     pub async fn init(pool: Pool<Postgres>) -> AppResult<Self> {
+        let storage = Self::connect(pool).await?;
+        storage.migrate().await?;
+        Ok(storage)
+    }
+    /// Подключается к базе данных без применения миграций
+    ///
+    /// Используется командами управления схемой, которым нужно
+    /// работать с базой данных в ее текущем состоянии.
+    ///
+    /// # Аргументы
+    ///
+    /// * `pool` - Пул соединений с базой данных
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(PgStorage)` - если ping к базе данных прошел
+    /// * `Err(AppError)` - если произошла ошибка при подключении
+    #[instrument(name = "connecting pg repository", skip(pool))]
+    pub async fn connect(pool: Pool<Postgres>) -> AppResult<Self> {
         let mut conn = pool.acquire().await?;
         conn.ping().await?;
         tracing::debug!("Ping to db successfully");
         conn.close().await?;
-        sqlx::migrate!().run(&pool).await?;
         Ok(Self { pool })
     }
+    /// Применяет все непримененные миграции
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<Vec<i64>>` - Версии примененных миграций или ошибку
+    #[instrument(name = "running migrations", skip(self))]
+    pub async fn migrate(&self) -> AppResult<Vec<i64>> {
+        let pending = self.pending_migrations().await?;
+        MIGRATOR.run(&self.pool).await?;
+        Ok(pending)
+    }
+    /// Откатывает примененные миграции
+    ///
+    /// # Аргументы
+    ///
+    /// * `target` - Версия, до которой откатывается схема (сама она остается примененной).
+    ///   Если не указана, откатывается только последняя примененная миграция
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<Vec<i64>>` - Версии откаченных миграций, начиная с последней, или ошибку
+    #[instrument(name = "reverting migrations", skip(self))]
+    pub async fn revert_migrations(&self, target: Option<i64>) -> AppResult<Vec<i64>> {
+        let mut applied = self.applied_migrations().await?;
+        applied.sort_unstable();
+        let target = match target {
+            Some(target) => target,
+            None => match applied.as_slice() {
+                [.., previous, _] => *previous,
+                _ => 0,
+            },
+        };
+        let reverted = applied
+            .into_iter()
+            .rev()
+            .filter(|version| *version > target)
+            .collect();
+        MIGRATOR.undo(&self.pool, target).await?;
+        Ok(reverted)
+    }
+    /// Возвращает состояние всех миграций приложения
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<Vec<MigrationStatus>>` - Миграции в порядке версий или ошибку
+    #[instrument(name = "listing migrations", skip(self))]
+    pub async fn migration_status(&self) -> AppResult<Vec<MigrationStatus>> {
+        let applied = self.applied_migrations().await?;
+        let status = MIGRATOR
+            .iter()
+            .filter(|migration| migration.migration_type.is_up_migration())
+            .map(|migration| MigrationStatus {
+                version: migration.version,
+                description: migration.description.to_string(),
+                applied: applied.contains(&migration.version),
+            })
+            .collect();
+        Ok(status)
+    }
     /// Закрывает пул соединений с базой данных
     ///
     /// Ожидает завершения всех активных операций и освобождает ресурсы.
@@ -61,6 +152,60 @@ impl PgStorage {
     pub async fn close(&self) {
         self.pool.close().await;
     }
+    /// Проверяет, что база данных принимает запросы
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(())` - Соединение получено и ответило на ping
+    /// * `Err(AppError::DatabaseInternalError)` - База данных недоступна
+    #[instrument(name = "pinging pg", skip(self))]
+    pub async fn ping(&self) -> AppResult<()> {
+        let mut conn = self.pool.acquire().await?;
+        conn.ping().await?;
+        Ok(())
+    }
+    /// Возвращает версии миграций, которые есть в приложении,
+    /// но не применены к базе данных
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<Vec<i64>>` - Версии непримененных миграций или ошибку
+    #[instrument(name = "listing pending migrations", skip(self))]
+    pub async fn pending_migrations(&self) -> AppResult<Vec<i64>> {
+        let applied = self.applied_migrations().await?;
+        let pending = MIGRATOR
+            .iter()
+            .filter(|migration| migration.migration_type.is_up_migration())
+            .map(|migration| migration.version)
+            .filter(|version| !applied.contains(version))
+            .collect();
+        Ok(pending)
+    }
+    /// Возвращает версии успешно примененных миграций
+    ///
+    /// До первого запуска миграций таблицы `_sqlx_migrations` нет,
+    /// и в этом случае возвращается пустой список.
+    async fn applied_migrations(&self) -> AppResult<Vec<i64>> {
+        // Таблица принадлежит sqlx и создается при первом запуске миграций,
+        // поэтому запросы не проверяются на этапе сборки
+        let exists: bool = sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+            .fetch_one(&self.pool)
+            .await?;
+        if !exists {
+            return Ok(Vec::new());
+        }
+        let applied = sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(applied)
+    }
+    /// Возвращает пул соединений хранилища
+    ///
+    /// Используется компонентами, которым требуется собственный доступ
+    /// к базе данных, например для подписки на `LISTEN/NOTIFY`.
+    pub fn pool(&self) -> &sqlx::PgPool {
+        &self.pool
+    }
     #[cfg(test)]
     pub(crate) fn with_pool(pool: sqlx::PgPool) -> Self {
         Self { pool }
@@ -80,4 +225,46 @@ mod tests {
         pg_storage.unwrap().close().await;
         Ok(())
     }
+
+    #[sqlx::test]
+    async fn test_readiness_checks(pool: PgPool) -> AppResult<()> {
+        let pg_storage = PgStorage::init(pool).await?;
+        pg_storage.ping().await?;
+        assert!(pg_storage.pending_migrations().await?.is_empty());
+
+        sqlx::query("DELETE FROM _sqlx_migrations WHERE version = 20251220100000")
+            .execute(pg_storage.pool())
+            .await?;
+        assert_eq!(pg_storage.pending_migrations().await?, vec![20251220100000]);
+
+        pg_storage.close().await;
+        assert!(pg_storage.ping().await.is_err());
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn test_migrate_and_revert(pool: PgPool) -> AppResult<()> {
+        let pg_storage = PgStorage::connect(pool).await?;
+        let status = pg_storage.migration_status().await?;
+        assert!(!status.is_empty());
+        assert!(status.iter().all(|migration| !migration.applied));
+        assert_eq!(status[0].description, "create users table");
+
+        let versions: Vec<i64> = status.iter().map(|migration| migration.version).collect();
+        assert_eq!(pg_storage.migrate().await?, versions);
+        assert!(pg_storage.migrate().await?.is_empty());
+        assert!(pg_storage.pending_migrations().await?.is_empty());
+
+        let last = *versions.last().unwrap();
+        assert_eq!(pg_storage.revert_migrations(None).await?, vec![last]);
+        assert_eq!(pg_storage.pending_migrations().await?, vec![last]);
+
+        assert_eq!(
+            pg_storage.revert_migrations(Some(0)).await?.len(),
+            versions.len() - 1
+        );
+        let status = pg_storage.migration_status().await?;
+        assert!(status.iter().all(|migration| !migration.applied));
+        Ok(())
+    }
 }
@@ -0,0 +1,120 @@
+//! Хранилище корзин токенов в памяти процесса
+//!
+//! Подходит для одного экземпляра сервера: счетчики не разделяются
+//! между процессами и теряются при перезапуске.
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+
+use crate::{
+    AppResult,
+    storage::rate_limits::{RateLimitDecision, RateLimitStore, TokenBucket},
+};
+
+/// Количество корзин по умолчанию, после которого удаляются полные
+const DEFAULT_MAX_BUCKETS: usize = 100_000;
+
+/// Состояние корзины
+struct BucketState {
+    tokens: f64,
+    updated: Instant,
+    /// Момент, когда корзина снова наполнится и запись можно удалить
+    full_at: Instant,
+}
+
+/// Хранилище корзин токенов в памяти
+pub struct MemoryRateLimitStore {
+    buckets: Mutex<HashMap<String, BucketState>>,
+    max_buckets: usize,
+}
+
+impl Default for MemoryRateLimitStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_BUCKETS)
+    }
+}
+
+impl MemoryRateLimitStore {
+    /// Создает хранилище
+    ///
+    /// # Аргументы
+    ///
+    /// * `max_buckets` - Число корзин, при превышении которого
+    ///   из памяти удаляются уже наполнившиеся корзины
+    pub fn new(max_buckets: usize) -> Self {
+        Self {
+            buckets: Mutex::new(HashMap::new()),
+            max_buckets: max_buckets.max(1),
+        }
+    }
+
+    fn take_at(&self, key: &str, bucket: TokenBucket, now: Instant) -> RateLimitDecision {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= self.max_buckets && !buckets.contains_key(key) {
+            buckets.retain(|_, state| state.full_at > now);
+        }
+        let state = buckets
+            .entry(key.to_string())
+            .or_insert_with(|| BucketState {
+                tokens: f64::from(bucket.capacity),
+                updated: now,
+                full_at: now,
+            });
+        let (tokens, decision) = bucket.take(state.tokens, now - state.updated);
+        state.tokens = tokens;
+        state.updated = now;
+        state.full_at = now + decision.reset_after;
+        decision
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryRateLimitStore {
+    async fn take_token(&self, key: &str, bucket: TokenBucket) -> AppResult<RateLimitDecision> {
+        Ok(self.take_at(key, bucket, Instant::now()))
+    }
+
+    async fn purge_idle_buckets(&self, idle: Duration) -> AppResult<u64> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let before = buckets.len();
+        buckets.retain(|_, state| now.duration_since(state.updated) < idle);
+        Ok((before - buckets.len()) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_store() {
+        let store = MemoryRateLimitStore::new(2);
+        let bucket = TokenBucket::per_period(2, Duration::from_secs(10));
+        let start = Instant::now();
+
+        assert!(store.take_at("a", bucket, start).allowed);
+        assert!(store.take_at("a", bucket, start).allowed);
+        let denied = store.take_at("a", bucket, start);
+        assert!(!denied.allowed);
+        assert_eq!(denied.retry_after, Duration::from_secs(5));
+        // Корзины разных ключей независимы
+        assert!(store.take_at("b", bucket, start).allowed);
+        assert!(
+            store
+                .take_at("a", bucket, start + Duration::from_secs(5))
+                .allowed
+        );
+
+        // При переполнении удаляются только наполнившиеся корзины
+        let later = start + Duration::from_secs(6);
+        assert!(store.take_at("c", bucket, later).allowed);
+        let buckets = store.buckets.lock().unwrap();
+        assert!(buckets.contains_key("a"));
+        assert!(!buckets.contains_key("b"));
+    }
+}
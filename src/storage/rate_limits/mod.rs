@@ -0,0 +1,127 @@
+mod memory_rate_limit_store;
+mod pg_rate_limits_repository;
+use std::time::Duration;
+
+use crate::AppResult;
+use async_trait::async_trait;
+pub use memory_rate_limit_store::MemoryRateLimitStore;
+
+/// Параметры корзины токенов
+///
+/// Корзина вмещает `capacity` токенов и пополняется со скоростью
+/// `refill_per_sec`; каждый запрос забирает один токен.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TokenBucket {
+    /// Максимальное число токенов - допустимый всплеск запросов
+    pub capacity: u32,
+    /// Скорость пополнения, токенов в секунду
+    pub refill_per_sec: f64,
+}
+
+impl TokenBucket {
+    /// Создает корзину, которая пропускает `capacity` запросов за `period`
+    pub fn per_period(capacity: u32, period: Duration) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            refill_per_sec: f64::from(capacity.max(1)) / period.as_secs_f64().max(1.0),
+        }
+    }
+
+    /// Время, за которое пустая корзина наполняется полностью
+    pub fn window(&self) -> Duration {
+        Duration::from_secs_f64(f64::from(self.capacity) / self.refill_per_sec)
+    }
+
+    /// Пополняет корзину за прошедшее время и пытается забрать токен
+    ///
+    /// # Аргументы
+    ///
+    /// * `tokens` - Токены в корзине после прошлого запроса
+    /// * `elapsed` - Время, прошедшее с прошлого запроса
+    ///
+    /// # Возвращает
+    ///
+    /// Новое число токенов и решение по запросу
+    pub fn take(&self, tokens: f64, elapsed: Duration) -> (f64, RateLimitDecision) {
+        let refilled =
+            (tokens + elapsed.as_secs_f64() * self.refill_per_sec).min(f64::from(self.capacity));
+        let allowed = refilled >= 1.0;
+        let tokens = if allowed { refilled - 1.0 } else { refilled };
+        (tokens, self.decision(tokens, allowed))
+    }
+
+    /// Формирует решение по числу оставшихся токенов
+    pub fn decision(&self, tokens: f64, allowed: bool) -> RateLimitDecision {
+        let tokens = tokens.clamp(0.0, f64::from(self.capacity));
+        RateLimitDecision {
+            allowed,
+            limit: self.capacity,
+            remaining: tokens.floor() as u32,
+            reset_after: Duration::from_secs_f64(
+                (f64::from(self.capacity) - tokens) / self.refill_per_sec,
+            ),
+            retry_after: if allowed {
+                Duration::ZERO
+            } else {
+                Duration::from_secs_f64((1.0 - tokens) / self.refill_per_sec)
+            },
+        }
+    }
+}
+
+/// Решение ограничителя по запросу
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitDecision {
+    /// Запрос разрешен
+    pub allowed: bool,
+    /// Размер корзины
+    pub limit: u32,
+    /// Сколько запросов еще можно выполнить сразу
+    pub remaining: u32,
+    /// Через сколько корзина наполнится полностью
+    pub reset_after: Duration,
+    /// Через сколько появится следующий токен; ноль, если запрос разрешен
+    pub retry_after: Duration,
+}
+
+/// Трейт хранилища корзин токенов
+///
+/// Позволяет держать счетчики в памяти процесса или в общей базе,
+/// если приложение запущено в нескольких экземплярах.
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Забирает токен из корзины с указанным ключом
+    async fn take_token(&self, key: &str, bucket: TokenBucket) -> AppResult<RateLimitDecision>;
+    /// Удаляет корзины, к которым не обращались дольше `idle`
+    async fn purge_idle_buckets(&self, idle: Duration) -> AppResult<u64>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let bucket = TokenBucket::per_period(3, Duration::from_secs(60));
+        assert_eq!(bucket.window(), Duration::from_secs(60));
+
+        let (tokens, decision) = bucket.take(3.0, Duration::ZERO);
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 2);
+        assert_eq!(decision.reset_after, Duration::from_secs(20));
+
+        let (tokens, _) = bucket.take(tokens, Duration::ZERO);
+        let (tokens, decision) = bucket.take(tokens, Duration::ZERO);
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 0);
+        let (tokens, decision) = bucket.take(tokens, Duration::ZERO);
+        assert!(!decision.allowed);
+        assert_eq!(decision.retry_after, Duration::from_secs(20));
+
+        // Через 20 секунд появляется один токен, но не больше емкости
+        let (_, decision) = bucket.take(tokens, Duration::from_secs(20));
+        assert!(decision.allowed);
+        let (tokens, _) = bucket.take(tokens, Duration::from_secs(3600));
+        assert_eq!(tokens, 2.0);
+    }
+}
@@ -0,0 +1,109 @@
+//! Хранилище корзин токенов в PostgreSQL
+//!
+//! Корзины разделяются всеми экземплярами сервера. Пополнение
+//! и списание выполняются одним запросом по часам базы данных,
+//! поэтому одновременные запросы не могут взять лишние токены.
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tracing::instrument;
+
+use crate::{
+    AppResult,
+    storage::{
+        PgStorage,
+        rate_limits::{RateLimitDecision, RateLimitStore, TokenBucket},
+    },
+};
+
+#[async_trait]
+impl RateLimitStore for PgStorage {
+    /// Забирает токен из корзины
+    ///
+    /// # Аргументы
+    ///
+    /// * `key` - Ключ корзины
+    /// * `bucket` - Емкость и скорость пополнения
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<RateLimitDecision>` - Решение по запросу или ошибку
+    #[instrument(name = "take rate limit token", skip(self, bucket))]
+    async fn take_token(&self, key: &str, bucket: TokenBucket) -> AppResult<RateLimitDecision> {
+        let res = sqlx::query!(
+            r#"
+			INSERT INTO rate_limit_buckets AS b (bucket_key, tokens, allowed)
+			VALUES ($1, $2::FLOAT8 - 1, TRUE)
+			ON CONFLICT (bucket_key) DO UPDATE SET
+				allowed = LEAST($2, b.tokens + GREATEST(EXTRACT(EPOCH FROM NOW() - b.updated)::FLOAT8, 0) * $3::FLOAT8) >= 1,
+				tokens = LEAST($2, b.tokens + GREATEST(EXTRACT(EPOCH FROM NOW() - b.updated)::FLOAT8, 0) * $3::FLOAT8)
+					- CASE WHEN LEAST($2, b.tokens + GREATEST(EXTRACT(EPOCH FROM NOW() - b.updated)::FLOAT8, 0) * $3::FLOAT8) >= 1 THEN 1 ELSE 0 END,
+				updated = NOW()
+			RETURNING tokens, allowed;
+			"#,
+            key,
+            f64::from(bucket.capacity),
+            bucket.refill_per_sec,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(bucket.decision(res.tokens, res.allowed))
+    }
+
+    /// Удаляет корзины, к которым не обращались дольше `idle`
+    ///
+    /// # Аргументы
+    ///
+    /// * `idle` - Время простоя; должно быть не меньше окна самой
+    ///   медленной политики, иначе удаленная корзина окажется полной раньше срока
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<u64>` - Количество удаленных корзин или ошибку
+    #[instrument(name = "purge idle rate limit buckets", skip(self))]
+    async fn purge_idle_buckets(&self, idle: Duration) -> AppResult<u64> {
+        let res = sqlx::query!(
+            r#"
+			DELETE FROM rate_limit_buckets
+			WHERE updated < NOW() - make_interval(secs => $1);
+			"#,
+            idle.as_secs_f64(),
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::AppResult;
+
+    #[sqlx::test]
+    async fn take_token_test(pool: PgPool) -> AppResult<()> {
+        let storage = PgStorage::with_pool(pool);
+        let bucket = TokenBucket::per_period(2, Duration::from_secs(3600));
+
+        let first = storage.take_token("ip:192.0.2.1", bucket).await?;
+        assert!(first.allowed);
+        assert_eq!(first.remaining, 1);
+        assert!(storage.take_token("ip:192.0.2.1", bucket).await?.allowed);
+        let denied = storage.take_token("ip:192.0.2.1", bucket).await?;
+        assert!(!denied.allowed);
+        assert_eq!(denied.remaining, 0);
+        assert!(denied.retry_after > Duration::from_secs(1700));
+        // Другой ключ - другая корзина
+        assert!(storage.take_token("ip:192.0.2.2", bucket).await?.allowed);
+
+        assert_eq!(
+            storage.purge_idle_buckets(Duration::from_secs(60)).await?,
+            0
+        );
+        assert_eq!(storage.purge_idle_buckets(Duration::ZERO).await?, 2);
+        assert!(storage.take_token("ip:192.0.2.1", bucket).await?.allowed);
+        Ok(())
+    }
+}
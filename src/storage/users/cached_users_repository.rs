@@ -0,0 +1,597 @@
+//! Кэширующий декоратор репозитория пользователей
+//!
+//! Этот модуль содержит обертку над любой реализацией `UsersRepository`,
+//! которая кэширует результаты `get` в памяти. Используется в пути
+//! аутентификации, где пользователь запрашивается на каждый запрос.
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use serde::Serialize;
+use sqlx::postgres::PgListener;
+use tracing::instrument;
+
+use crate::{
+    AppResult, metrics,
+    models::{
+        ExternalIdentity, ImportedUser, ServiceAccountData, SigninData, SignupData, User, UserRole,
+        UserToUpdate,
+    },
+    storage::{UsersRepository, users::UsersFilter},
+};
+
+/// Канал PostgreSQL по умолчанию для уведомлений об инвалидации
+pub const DEFAULT_NOTIFY_CHANNEL: &str = "alfred_users_cache";
+
+/// Запись кэша
+struct CacheEntry {
+    user: User,
+    inserted: Instant,
+}
+
+/// Статистика работы кэша
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    /// Количество запросов, обслуженных из кэша
+    pub hits: u64,
+    /// Количество запросов, переданных во внутренний репозиторий
+    pub misses: u64,
+    /// Текущее количество записей в кэше
+    pub entries: usize,
+}
+
+/// Репозиторий пользователей с кэшированием
+///
+/// Кэширует пользователей, полученных по идентификатору, с ограничением
+/// по времени жизни и количеству записей. Запись инвалидируется при
+/// обновлении и удалении пользователя. При включенном `LISTEN/NOTIFY`
+/// инвалидация рассылается остальным экземплярам сервера через PostgreSQL.
+pub struct CachedUsersRepository<R: UsersRepository> {
+    inner: R,
+    entries: Mutex<HashMap<uuid::Uuid, CacheEntry>>,
+    /// Счетчик инвалидаций; меняется под блокировкой `entries`
+    generation: AtomicU64,
+    ttl: Duration,
+    max_entries: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    notifier: Option<(sqlx::PgPool, String)>,
+}
+
+impl<R: UsersRepository> CachedUsersRepository<R> {
+    /// Создает кэширующую обертку над репозиторием
+    ///
+    /// # Аргументы
+    ///
+    /// * `inner` - Репозиторий, к которому направляются промахи кэша
+    /// * `ttl` - Время жизни записи
+    /// * `max_entries` - Максимальное количество записей в кэше
+    ///
+    /// # Возвращает
+    ///
+    /// Новый экземпляр `CachedUsersRepository` без межсерверной инвалидации
+    pub fn new(inner: R, ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            inner,
+            entries: Mutex::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+            ttl,
+            max_entries,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            notifier: None,
+        }
+    }
+    /// Включает рассылку инвалидаций через `NOTIFY`
+    ///
+    /// # Аргументы
+    ///
+    /// * `pool` - Пул соединений, через который отправляются уведомления
+    /// * `channel` - Имя канала PostgreSQL
+    pub fn with_pg_notify(mut self, pool: sqlx::PgPool, channel: &str) -> Self {
+        self.notifier = Some((pool, channel.to_string()));
+        self
+    }
+    /// Возвращает текущую статистику кэша
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.lock().len(),
+        }
+    }
+    /// Удаляет пользователя из локального кэша
+    pub fn invalidate(&self, id: uuid::Uuid) {
+        let mut entries = self.lock();
+        entries.remove(&id);
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+    /// Полностью очищает локальный кэш
+    pub fn clear(&self) {
+        let mut entries = self.lock();
+        entries.clear();
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+    /// Запускает фоновую задачу, которая слушает канал `NOTIFY`
+    /// и инвалидирует записи, измененные другими экземплярами
+    ///
+    /// Пока соединение с PostgreSQL потеряно, уведомления не доходят,
+    /// поэтому после разрыва весь кэш сбрасывается.
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(Some(JoinHandle))` - если задача запущена
+    /// * `Ok(None)` - если `NOTIFY` не настроен
+    /// * `Err(AppError)` - если не удалось подписаться на канал
+    #[instrument(name = "spawn users cache listener", skip(self))]
+    pub async fn spawn_listener(self: &Arc<Self>) -> AppResult<Option<tokio::task::JoinHandle<()>>>
+    where
+        R: 'static,
+    {
+        let Some((pool, channel)) = &self.notifier else {
+            return Ok(None);
+        };
+        let mut listener = PgListener::connect_with(pool).await?;
+        listener.listen(channel).await?;
+        let cache = Arc::downgrade(self);
+        let handle = tokio::spawn(async move {
+            loop {
+                // `try_recv` сообщает о разрыве соединения и переподключается
+                // при следующем вызове; уведомления за время разрыва потеряны
+                let received = listener.try_recv().await;
+                let Some(cache) = cache.upgrade() else {
+                    break;
+                };
+                match received {
+                    Ok(Some(notification)) => {
+                        // Нераспознанное сообщение тоже сбрасывает весь кэш
+                        match uuid::Uuid::parse_str(notification.payload()) {
+                            Ok(id) => cache.invalidate(id),
+                            Err(_) => cache.clear(),
+                        }
+                    }
+                    Ok(None) => {
+                        tracing::warn!("users cache listener connection lost, clearing cache");
+                        cache.clear();
+                    }
+                    Err(e) => {
+                        tracing::warn!("users cache listener error: {e}");
+                        cache.clear();
+                        drop(cache);
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                    }
+                }
+            }
+        });
+        Ok(Some(handle))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<uuid::Uuid, CacheEntry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+    fn lookup(&self, id: uuid::Uuid) -> Option<User> {
+        let mut entries = self.lock();
+        match entries.get(&id) {
+            Some(entry) if entry.inserted.elapsed() < self.ttl => Some(entry.user.clone()),
+            Some(_) => {
+                entries.remove(&id);
+                None
+            }
+            None => None,
+        }
+    }
+    /// Сохраняет пользователя, прочитанного при поколении `generation`
+    ///
+    /// Если с начала чтения была инвалидация, прочитанная строка могла
+    /// устареть, и она не сохраняется.
+    fn store(&self, user: &User, generation: u64) {
+        if self.max_entries == 0 {
+            return;
+        }
+        let mut entries = self.lock();
+        if self.generation.load(Ordering::Relaxed) != generation {
+            return;
+        }
+        if entries.len() >= self.max_entries && !entries.contains_key(&user.user_id) {
+            entries.retain(|_, entry| entry.inserted.elapsed() < self.ttl);
+        }
+        if entries.len() >= self.max_entries && !entries.contains_key(&user.user_id) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.inserted)
+                .map(|(id, _)| *id);
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(
+            user.user_id,
+            CacheEntry {
+                user: user.clone(),
+                inserted: Instant::now(),
+            },
+        );
+    }
+    /// Инвалидирует запись после изменения пользователя
+    ///
+    /// Изменение к этому моменту уже сохранено, поэтому ошибка рассылки
+    /// только пишется в журнал: остальные экземпляры увидят изменение
+    /// не позже чем через `ttl`.
+    async fn evict(&self, id: uuid::Uuid) {
+        self.invalidate(id);
+        if let Some((pool, channel)) = &self.notifier
+            && let Err(e) = sqlx::query("SELECT pg_notify($1, $2)")
+                .bind(channel)
+                .bind(id.to_string())
+                .execute(pool)
+                .await
+        {
+            tracing::error!(user_id = %id, "failed to notify users cache invalidation: {e}");
+        }
+    }
+}
+
+#[async_trait]
+impl<R: UsersRepository> UsersRepository for CachedUsersRepository<R> {
+    async fn create(&self, signup_data: SignupData) -> AppResult<User> {
+        self.inner.create(signup_data).await
+    }
+    async fn create_imported(&self, data: ImportedUser) -> AppResult<User> {
+        self.inner.create_imported(data).await
+    }
+    async fn create_service_account(&self, data: ServiceAccountData) -> AppResult<User> {
+        self.inner.create_service_account(data).await
+    }
+    async fn create_external(
+        &self,
+        identity: &ExternalIdentity,
+        role: UserRole,
+    ) -> AppResult<User> {
+        self.inner.create_external(identity, role).await
+    }
+    async fn find_by_identity(&self, issuer: &str, subject: &str) -> AppResult<User> {
+        self.inner.find_by_identity(issuer, subject).await
+    }
+    async fn link_identity(
+        &self,
+        user_id: uuid::Uuid,
+        issuer: &str,
+        subject: &str,
+    ) -> AppResult<()> {
+        self.inner.link_identity(user_id, issuer, subject).await
+    }
+    #[instrument(name = "get cached user by id", skip(self))]
+    async fn get(&self, id: uuid::Uuid) -> AppResult<User> {
+        if let Some(user) = self.lookup(id) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            metrics::record_users_cache(true);
+            tracing::debug!("users cache hit");
+            return Ok(user);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        metrics::record_users_cache(false);
+        tracing::debug!("users cache miss");
+        let generation = self.generation.load(Ordering::Relaxed);
+        let user = self.inner.get(id).await?;
+        self.store(&user, generation);
+        Ok(user)
+    }
+    async fn list(&self, filter: UsersFilter) -> AppResult<Vec<User>> {
+        self.inner.list(filter).await
+    }
+    async fn total(&self, filter: UsersFilter) -> AppResult<u32> {
+        self.inner.total(filter).await
+    }
+    async fn find_by_email(&self, email: &str) -> AppResult<User> {
+        self.inner.find_by_email(email).await
+    }
+    async fn update(&self, id: uuid::Uuid, user: UserToUpdate) -> AppResult<User> {
+        let updated = self.inner.update(id, user).await?;
+        self.evict(id).await;
+        Ok(updated)
+    }
+    async fn delete(&self, id: uuid::Uuid) -> AppResult<User> {
+        let deleted = self.inner.delete(id).await?;
+        self.evict(id).await;
+        Ok(deleted)
+    }
+    async fn verify_user(&self, signin_data: SigninData) -> AppResult<bool> {
+        self.inner.verify_user(signin_data).await
+    }
+    async fn set_password_hash(&self, id: uuid::Uuid, password_hash: &str) -> AppResult<User> {
+        let updated = self.inner.set_password_hash(id, password_hash).await?;
+        self.evict(id).await;
+        Ok(updated)
+    }
+    async fn schedule_deletion(
+        &self,
+        id: uuid::Uuid,
+        at: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> AppResult<User> {
+        let scheduled = self.inner.schedule_deletion(id, at).await?;
+        self.evict(id).await;
+        Ok(scheduled)
+    }
+    async fn anonymize(&self, id: uuid::Uuid) -> AppResult<User> {
+        let anonymized = self.inner.anonymize(id).await?;
+        self.evict(id).await;
+        Ok(anonymized)
+    }
+    async fn due_for_deletion(
+        &self,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> AppResult<Vec<uuid::Uuid>> {
+        self.inner.due_for_deletion(now).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::PgPool;
+
+    use crate::{AppError, models::UserRole, storage::PgStorage};
+
+    fn signup_data(email: &str) -> SignupData {
+        SignupData {
+            email: email.to_string(),
+            password: "str0nGp@ssw0rD".to_string(),
+            role: UserRole::Guest,
+        }
+    }
+
+    #[sqlx::test]
+    async fn cache_hit_and_miss_test(pool: PgPool) -> AppResult<()> {
+        let repo =
+            CachedUsersRepository::new(PgStorage::with_pool(pool), Duration::from_secs(60), 10);
+        let created = repo.create(signup_data("cache@example.com")).await?;
+
+        let first = repo.get(created.user_id).await?;
+        let second = repo.get(created.user_id).await?;
+        assert_eq!(first, second);
+        assert_eq!(
+            repo.stats(),
+            CacheStats {
+                hits: 1,
+                misses: 1,
+                entries: 1
+            }
+        );
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn cache_invalidation_on_update_and_delete_test(pool: PgPool) -> AppResult<()> {
+        let repo =
+            CachedUsersRepository::new(PgStorage::with_pool(pool), Duration::from_secs(60), 10);
+        let created = repo.create(signup_data("cache@example.com")).await?;
+        repo.get(created.user_id).await?;
+
+        let mut to_update = UserToUpdate::from(created.clone());
+        to_update.email = "updated@example.com".to_string();
+        repo.update(created.user_id, to_update).await?;
+        assert_eq!(repo.stats().entries, 0);
+        let retrieved = repo.get(created.user_id).await?;
+        assert_eq!(retrieved.email, "updated@example.com");
+
+        repo.delete(created.user_id).await?;
+        let result = repo.get(created.user_id).await;
+        assert!(matches!(result.unwrap_err(), AppError::EntryNotFound));
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn cache_capacity_test(pool: PgPool) -> AppResult<()> {
+        let repo =
+            CachedUsersRepository::new(PgStorage::with_pool(pool), Duration::from_secs(60), 2);
+        let mut ids = Vec::new();
+        for i in 0..3 {
+            let created = repo
+                .create(signup_data(&format!("user{i}@example.com")))
+                .await?;
+            repo.get(created.user_id).await?;
+            ids.push(created.user_id);
+        }
+        // Самая старая запись вытесняется при превышении лимита
+        assert_eq!(repo.stats().entries, 2);
+        assert!(repo.lookup(ids[0]).is_none());
+        assert!(repo.lookup(ids[2]).is_some());
+        Ok(())
+    }
+
+    /// Репозиторий, который приостанавливает `get` после чтения строки,
+    /// пока тест удерживает `resume`
+    struct PausedGet {
+        inner: PgStorage,
+        fetched: tokio::sync::Notify,
+        resume: tokio::sync::Mutex<()>,
+    }
+
+    #[async_trait]
+    impl UsersRepository for PausedGet {
+        async fn create(&self, signup_data: SignupData) -> AppResult<User> {
+            self.inner.create(signup_data).await
+        }
+        async fn create_imported(&self, data: ImportedUser) -> AppResult<User> {
+            self.inner.create_imported(data).await
+        }
+        async fn create_service_account(&self, data: ServiceAccountData) -> AppResult<User> {
+            self.inner.create_service_account(data).await
+        }
+        async fn create_external(
+            &self,
+            identity: &ExternalIdentity,
+            role: UserRole,
+        ) -> AppResult<User> {
+            self.inner.create_external(identity, role).await
+        }
+        async fn find_by_identity(&self, issuer: &str, subject: &str) -> AppResult<User> {
+            self.inner.find_by_identity(issuer, subject).await
+        }
+        async fn link_identity(
+            &self,
+            user_id: uuid::Uuid,
+            issuer: &str,
+            subject: &str,
+        ) -> AppResult<()> {
+            self.inner.link_identity(user_id, issuer, subject).await
+        }
+        async fn get(&self, id: uuid::Uuid) -> AppResult<User> {
+            let user = self.inner.get(id).await;
+            self.fetched.notify_one();
+            drop(self.resume.lock().await);
+            user
+        }
+        async fn list(&self, filter: UsersFilter) -> AppResult<Vec<User>> {
+            self.inner.list(filter).await
+        }
+        async fn total(&self, filter: UsersFilter) -> AppResult<u32> {
+            self.inner.total(filter).await
+        }
+        async fn find_by_email(&self, email: &str) -> AppResult<User> {
+            self.inner.find_by_email(email).await
+        }
+        async fn update(&self, id: uuid::Uuid, user: UserToUpdate) -> AppResult<User> {
+            self.inner.update(id, user).await
+        }
+        async fn delete(&self, id: uuid::Uuid) -> AppResult<User> {
+            self.inner.delete(id).await
+        }
+        async fn verify_user(&self, signin_data: SigninData) -> AppResult<bool> {
+            self.inner.verify_user(signin_data).await
+        }
+        async fn set_password_hash(&self, id: uuid::Uuid, password_hash: &str) -> AppResult<User> {
+            self.inner.set_password_hash(id, password_hash).await
+        }
+        async fn schedule_deletion(
+            &self,
+            id: uuid::Uuid,
+            at: Option<chrono::DateTime<chrono::Utc>>,
+        ) -> AppResult<User> {
+            self.inner.schedule_deletion(id, at).await
+        }
+        async fn anonymize(&self, id: uuid::Uuid) -> AppResult<User> {
+            self.inner.anonymize(id).await
+        }
+        async fn due_for_deletion(
+            &self,
+            now: chrono::DateTime<chrono::Utc>,
+        ) -> AppResult<Vec<uuid::Uuid>> {
+            self.inner.due_for_deletion(now).await
+        }
+    }
+
+    #[sqlx::test]
+    async fn cache_update_during_miss_test(pool: PgPool) -> AppResult<()> {
+        let paused = PausedGet {
+            inner: PgStorage::with_pool(pool),
+            fetched: tokio::sync::Notify::new(),
+            resume: tokio::sync::Mutex::new(()),
+        };
+        let repo = Arc::new(CachedUsersRepository::new(
+            paused,
+            Duration::from_secs(60),
+            10,
+        ));
+        let created = repo.create(signup_data("cache@example.com")).await?;
+
+        // Строка прочитана до обновления, а сохраняется в кэш после него
+        let resume = repo.inner.resume.lock().await;
+        let pending = tokio::spawn({
+            let repo = repo.clone();
+            async move { repo.get(created.user_id).await }
+        });
+        repo.inner.fetched.notified().await;
+        let mut to_update = UserToUpdate::from(created.clone());
+        to_update.email = "updated@example.com".to_string();
+        repo.update(created.user_id, to_update).await?;
+        drop(resume);
+        let stale = pending.await.unwrap()?;
+        assert_eq!(stale.email, "cache@example.com");
+
+        assert_eq!(repo.stats().entries, 0);
+        let retrieved = repo.get(created.user_id).await?;
+        assert_eq!(retrieved.email, "updated@example.com");
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn cache_ttl_test(pool: PgPool) -> AppResult<()> {
+        let repo =
+            CachedUsersRepository::new(PgStorage::with_pool(pool), Duration::from_millis(100), 10);
+        let created = repo.create(signup_data("cache@example.com")).await?;
+        repo.get(created.user_id).await?;
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        repo.get(created.user_id).await?;
+        assert_eq!(repo.stats().hits, 0);
+        assert_eq!(repo.stats().misses, 2);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn cache_pg_notify_invalidation_test(pool: PgPool) -> AppResult<()> {
+        let first = Arc::new(
+            CachedUsersRepository::new(
+                PgStorage::with_pool(pool.clone()),
+                Duration::from_secs(60),
+                10,
+            )
+            .with_pg_notify(pool.clone(), DEFAULT_NOTIFY_CHANNEL),
+        );
+        let second = CachedUsersRepository::new(
+            PgStorage::with_pool(pool.clone()),
+            Duration::from_secs(60),
+            10,
+        )
+        .with_pg_notify(pool, DEFAULT_NOTIFY_CHANNEL);
+        let handle = first.spawn_listener().await?;
+        assert!(handle.is_some());
+
+        let created = first.create(signup_data("cache@example.com")).await?;
+        first.get(created.user_id).await?;
+        assert_eq!(first.stats().entries, 1);
+
+        // Второй экземпляр обновляет пользователя, первый получает уведомление
+        let mut to_update = UserToUpdate::from(created.clone());
+        to_update.email = "updated@example.com".to_string();
+        second.update(created.user_id, to_update).await?;
+
+        for _ in 0..50 {
+            if first.stats().entries == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(first.stats().entries, 0);
+        let retrieved = first.get(created.user_id).await?;
+        assert_eq!(retrieved.email, "updated@example.com");
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn cache_notify_failure_test(pool: PgPool) -> AppResult<()> {
+        let unreachable = sqlx::postgres::PgPoolOptions::new()
+            .acquire_timeout(Duration::from_millis(200))
+            .connect_lazy("postgres://alfred@127.0.0.1:1/alfred")?;
+        let repo =
+            CachedUsersRepository::new(PgStorage::with_pool(pool), Duration::from_secs(60), 10)
+                .with_pg_notify(unreachable, DEFAULT_NOTIFY_CHANNEL);
+        let created = repo.create(signup_data("cache@example.com")).await?;
+        repo.get(created.user_id).await?;
+
+        // Изменение сохранено, даже если разослать инвалидацию не удалось
+        let mut to_update = UserToUpdate::from(created.clone());
+        to_update.email = "updated@example.com".to_string();
+        let updated = repo.update(created.user_id, to_update).await?;
+        assert_eq!(updated.email, "updated@example.com");
+        assert_eq!(repo.stats().entries, 0);
+        Ok(())
+    }
+}
@@ -1,11 +1,17 @@
+mod cached_users_repository;
 mod pg_users_repository;
 use crate::{
     AppResult,
-    models::{SigninData, SignupData, User, UserRole, UserToUpdate},
+    models::{
+        ExternalIdentity, ImportedUser, ServiceAccountData, SigninData, SignupData, User, UserRole,
+        UserToUpdate,
+    },
 };
 use async_trait::async_trait;
+pub use cached_users_repository::{CacheStats, CachedUsersRepository, DEFAULT_NOTIFY_CHANNEL};
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 /// Номер страницы по умолчанию (первая страница)
 pub const DEFAULT_PAGE_NUM: u32 = 1;
@@ -22,6 +28,23 @@ pub const MAX_PER_PAGE: u32 = 100;
 pub trait UsersRepository: Send + Sync {
     /// Создает нового пользователя в базе данных
     async fn create(&self, signup_data: SignupData) -> AppResult<User>;
+    /// Создает пользователя с готовым хэшем пароля из другой системы
+    async fn create_imported(&self, data: ImportedUser) -> AppResult<User>;
+    /// Создает сервисный аккаунт без пароля
+    async fn create_service_account(&self, data: ServiceAccountData) -> AppResult<User>;
+    /// Создает пользователя без пароля по данным внешнего провайдера
+    /// и связывает его с учетной записью провайдера
+    async fn create_external(&self, identity: &ExternalIdentity, role: UserRole)
+    -> AppResult<User>;
+    /// Находит пользователя по связанной учетной записи внешнего провайдера
+    async fn find_by_identity(&self, issuer: &str, subject: &str) -> AppResult<User>;
+    /// Связывает пользователя с учетной записью внешнего провайдера
+    async fn link_identity(
+        &self,
+        user_id: uuid::Uuid,
+        issuer: &str,
+        subject: &str,
+    ) -> AppResult<()>;
     /// Получает пользователя по идентификатору
     async fn get(&self, id: uuid::Uuid) -> AppResult<User>;
     /// Получает список пользователей с применением фильтров и пагинации
@@ -36,12 +59,29 @@ pub trait UsersRepository: Send + Sync {
     async fn delete(&self, id: uuid::Uuid) -> AppResult<User>;
     /// Проверяет правильность пароля пользователя
     async fn verify_user(&self, signin_data: SigninData) -> AppResult<bool>;
+    /// Заменяет хэш пароля пользователя
+    async fn set_password_hash(&self, id: uuid::Uuid, password_hash: &str) -> AppResult<User>;
+    /// Планирует анонимизацию аккаунта на указанный момент
+    ///
+    /// Значение `None` отменяет ранее запланированное удаление.
+    async fn schedule_deletion(
+        &self,
+        id: uuid::Uuid,
+        at: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> AppResult<User>;
+    /// Анонимизирует аккаунт: удаляет персональные данные, сохраняя строку
+    async fn anonymize(&self, id: uuid::Uuid) -> AppResult<User>;
+    /// Возвращает идентификаторы аккаунтов, срок удаления которых наступил
+    async fn due_for_deletion(
+        &self,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> AppResult<Vec<uuid::Uuid>>;
 }
 /// Фильтр для поиска пользователей с поддержкой пагинации
 ///
 /// Используется для фильтрации, поиска и пагинации пользователей в методах
 /// `list` и `total`. Поддерживает поиск по нескольким полям и фильтрацию по роли.
-#[derive(Debug, Clone, Builder, Serialize, Deserialize)]
+#[derive(Debug, Clone, Builder, Serialize, Deserialize, ToSchema)]
 pub struct UsersFilter {
     /// Номер страницы (начиная с 1)
     #[builder(setter(custom), default = DEFAULT_PAGE_NUM)]
@@ -177,7 +217,7 @@ mod tests {
 
         assert_eq!(filter.page(), 2);
         assert_eq!(filter.per_page(), 20);
-        assert_eq!(filter.role(), Some("Администратор"));
+        assert_eq!(filter.role(), Some("admin"));
         assert_eq!(filter.search_string(), Some(&"john".to_string()));
     }
 
@@ -381,7 +421,7 @@ mod tests {
 
         assert!(json.contains("\"page\":1"));
         assert!(json.contains("\"per_page\":20"));
-        assert!(json.contains("\"role\":\"Администратор\""));
+        assert!(json.contains("\"role\":\"admin\""));
         assert!(json.contains("\"search_string\":\"serialize\""));
 
         // Тест десериализации
@@ -389,7 +429,7 @@ mod tests {
 
         assert_eq!(deserialized.page(), 1);
         assert_eq!(deserialized.per_page(), 20);
-        assert_eq!(deserialized.role(), Some("Администратор"));
+        assert_eq!(deserialized.role(), Some("admin"));
         assert_eq!(deserialized.search_string(), Some(&"serialize".to_string()));
     }
 
@@ -415,7 +455,7 @@ mod tests {
 
         assert_eq!(filter.page(), DEFAULT_PAGE_NUM);
         assert_eq!(filter.per_page(), DEFAULT_PER_PAGE);
-        assert_eq!(filter.role(), Some("Сотрудник"));
+        assert_eq!(filter.role(), Some("employee"));
         assert!(filter.search_string().is_none());
     }
 
@@ -11,8 +11,13 @@ use tracing::instrument;
 
 use crate::{
     AppError, AppResult,
-    crypto::{hash_password, verify_password},
-    models::{SigninData, SignupData, User, UserInfo, UserRole, UserToUpdate},
+    crypto::{
+        UNUSABLE_PASSWORD_HASH, hash_password_blocking, needs_rehash, verify_password_blocking,
+    },
+    models::{
+        ExternalIdentity, ImportedUser, ServiceAccountData, SigninData, SignupData, User, UserInfo,
+        UserRole, UserToUpdate,
+    },
     storage::{PgStorage, UsersRepository, users::UsersFilter},
 };
 
@@ -37,6 +42,160 @@ impl UsersRepository for PgStorage {
         Ok(result)
     }
 
+    /// Создает пользователя с готовым хэшем пароля
+    ///
+    /// # Аргументы
+    ///
+    /// * `data` - Email, хэш пароля из прежней системы и роль
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<User>` - Созданный пользователь или ошибку
+    #[instrument(name = "import user", skip_all, fields(email = %data.email))]
+    async fn create_imported(&self, data: ImportedUser) -> AppResult<User> {
+        let mut tx = self.pool.begin().await?;
+        let created_user =
+            UserDTO::insert(&mut tx, &data.email, &data.password_hash, &data.role).await?;
+        let created_info = UserInfoDTO::create(&mut tx, created_user.user_id).await?;
+        let result = User::from((created_user, created_info.into()));
+        tx.commit().await?;
+        Ok(result)
+    }
+
+    /// Создает сервисный аккаунт
+    ///
+    /// Пароль аккаунта непригоден для входа, а имя сохраняется
+    /// в профиле как `username`.
+    ///
+    /// # Аргументы
+    ///
+    /// * `data` - Имя и роль сервисного аккаунта
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<User>` - Созданный аккаунт или ошибку
+    #[instrument(name = "create service account", skip_all, fields(name = %data.name))]
+    async fn create_service_account(&self, data: ServiceAccountData) -> AppResult<User> {
+        let mut tx = self.pool.begin().await?;
+        let created_user = UserDTO::create_service_account(&mut tx, &data).await?;
+        let mut created_info = UserInfoDTO::create(&mut tx, created_user.user_id).await?;
+        created_info.username = sqlx::query_scalar!(
+            r#"
+			UPDATE user_infos SET username = $2 WHERE user_id = $1 RETURNING username;
+			"#,
+            created_user.user_id,
+            data.name,
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        let result = User::from((created_user, created_info.into()));
+        tx.commit().await?;
+        Ok(result)
+    }
+
+    /// Создает пользователя по данным внешнего провайдера
+    ///
+    /// Пароль пользователя непригоден для входа; имя и фамилия берутся
+    /// из ID-токена, а учетная запись провайдера сразу связывается с аккаунтом.
+    ///
+    /// # Аргументы
+    ///
+    /// * `identity` - Проверенные сведения от провайдера
+    /// * `role` - Роль нового пользователя
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<User>` - Созданный пользователь или ошибку
+    #[instrument(name = "create external user", skip_all, fields(email = %identity.email))]
+    async fn create_external(
+        &self,
+        identity: &ExternalIdentity,
+        role: UserRole,
+    ) -> AppResult<User> {
+        let mut tx = self.pool.begin().await?;
+        let created_user = sqlx::query_as!(
+            UserDTO,
+            r#"
+			INSERT INTO users (email, password_hash, role)
+			VALUES ($1, $2, $3)
+			RETURNING *;
+			"#,
+            identity.email,
+            UNUSABLE_PASSWORD_HASH,
+            role.to_string(),
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        let created_info = sqlx::query_as!(
+            UserInfoDTO,
+            r#"
+			INSERT INTO user_infos (user_id, first_name, last_name)
+			VALUES ($1, $2, $3)
+			RETURNING *;
+			"#,
+            created_user.user_id,
+            identity.given_name,
+            identity.family_name,
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        insert_identity(
+            &mut tx,
+            created_user.user_id,
+            &identity.issuer,
+            &identity.subject,
+        )
+        .await?;
+        let result = User::from((created_user, created_info.into()));
+        tx.commit().await?;
+        Ok(result)
+    }
+
+    /// Находит пользователя по учетной записи внешнего провайдера
+    ///
+    /// # Аргументы
+    ///
+    /// * `issuer` - Идентификатор провайдера (`iss`)
+    /// * `subject` - Идентификатор пользователя у провайдера (`sub`)
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<User>` - Найденный пользователь или `AppError::EntryNotFound`
+    #[instrument(name = "find user by identity", skip(self))]
+    async fn find_by_identity(&self, issuer: &str, subject: &str) -> AppResult<User> {
+        let user_id = sqlx::query_scalar!(
+            r#"
+			SELECT user_id FROM user_identities WHERE issuer = $1 AND subject = $2;
+			"#,
+            issuer,
+            subject,
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(AppError::EntryNotFound)?;
+        self.get(user_id).await
+    }
+
+    /// Связывает пользователя с учетной записью внешнего провайдера
+    ///
+    /// # Аргументы
+    ///
+    /// * `user_id` - UUID пользователя
+    /// * `issuer` - Идентификатор провайдера (`iss`)
+    /// * `subject` - Идентификатор пользователя у провайдера (`sub`)
+    #[instrument(name = "link user identity", skip(self))]
+    async fn link_identity(
+        &self,
+        user_id: uuid::Uuid,
+        issuer: &str,
+        subject: &str,
+    ) -> AppResult<()> {
+        let mut tx = self.pool.begin().await?;
+        insert_identity(&mut tx, user_id, issuer, subject).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
     /// Получает пользователя по идентификатору
     ///
     /// # Аргументы
@@ -81,6 +240,9 @@ impl UsersRepository for PgStorage {
 				u.role,
 				u.created,
 				u.updated,
+				u.deletion_scheduled_at,
+				u.anonymized_at,
+				u.service_account,
 				ui.info_id,
 				ui.first_name,
 				ui.middle_name,
@@ -88,33 +250,24 @@ impl UsersRepository for PgStorage {
 				ui.username,
 				ui.avatar_url,
 				ui.bio,
+				ui.timezone,
+				ui.locale,
 				ui.created as info_created,
 				ui.updated as info_updated
 			FROM users u
 			LEFT JOIN user_infos ui ON u.user_id = ui.user_id "#,
         );
 
-        let mut has_conditions = false;
+        qb.push(" WHERE u.anonymized_at IS NULL");
 
         if let Some(role) = filter.role() {
-            if !has_conditions {
-                qb.push(" WHERE ");
-                has_conditions = true;
-            } else {
-                qb.push(" AND ");
-            }
-            qb.push("u.role = ");
+            qb.push(" AND u.role = ");
             qb.push_bind(role.to_string());
         }
 
         if let Some(q) = filter.search_string() {
             let pattern = format!("%{q}%");
-            if !has_conditions {
-                qb.push(" WHERE ");
-            } else {
-                qb.push(" AND ");
-            }
-            qb.push("(");
+            qb.push(" AND (");
             qb.push("u.email ILIKE ");
             qb.push_bind(pattern.clone());
             qb.push(" OR ui.username ILIKE ");
@@ -146,6 +299,9 @@ impl UsersRepository for PgStorage {
                 role: row.get("role"),
                 created: row.get("created"),
                 updated: row.get("updated"),
+                deletion_scheduled_at: row.get("deletion_scheduled_at"),
+                anonymized_at: row.get("anonymized_at"),
+                service_account: row.get("service_account"),
             };
 
             let info_dto = UserInfoDTO {
@@ -157,6 +313,8 @@ impl UsersRepository for PgStorage {
                 username: row.get("username"),
                 avatar_url: row.get("avatar_url"),
                 bio: row.get("bio"),
+                timezone: row.get("timezone"),
+                locale: row.get("locale"),
                 created: row.get("info_created"),
                 updated: row.get("info_updated"),
             };
@@ -182,25 +340,17 @@ impl UsersRepository for PgStorage {
             "SELECT COUNT(*) as total FROM users u LEFT JOIN user_infos ui ON u.user_id = ui.user_id",
         );
 
-        let mut has_conditions = false;
+        query_builder.push(" WHERE u.anonymized_at IS NULL");
 
         if let Some(role) = filter.role {
-            query_builder.push(" WHERE ");
-            has_conditions = true;
-            query_builder.push("u.role = ");
+            query_builder.push(" AND u.role = ");
             query_builder.push_bind(role.to_string());
         }
 
         if let Some(search) = &filter.search_string {
             let search_pattern = format!("%{}%", search);
 
-            if !has_conditions {
-                query_builder.push(" WHERE ");
-            } else {
-                query_builder.push(" AND ");
-            }
-
-            query_builder.push("(");
+            query_builder.push(" AND (");
             query_builder.push("u.email ILIKE ");
             query_builder.push_bind(search_pattern.clone());
             query_builder.push(" OR ui.username ILIKE ");
@@ -251,8 +401,8 @@ impl UsersRepository for PgStorage {
     #[instrument(name = "update user", skip(self, user))]
     async fn update(&self, id: uuid::Uuid, user: UserToUpdate) -> AppResult<User> {
         let mut tx = self.pool.begin().await?;
-        let updated_info = UserInfoDTO::update(&mut tx, id, &user.info).await?;
         let updated_user = UserDTO::update(&mut tx, id, &user.email, user.role.as_ref()).await?;
+        let updated_info = UserInfoDTO::update(&mut tx, id, &user.info).await?;
         tx.commit().await?;
         let res = User::from((updated_user, updated_info.into()));
         Ok(res)
@@ -279,6 +429,11 @@ impl UsersRepository for PgStorage {
 
     /// Проверяет пароль пользователя
     ///
+    /// Если пароль верный, а хэш получен с устаревшими параметрами Argon2
+    /// или перцем, хэш пересчитывается и сохраняется. Ошибка пересчета
+    /// не мешает входу. Для неизвестного email пароль все равно проверяется
+    /// по хэшу-заглушке, чтобы время ответа не выдавало, есть ли аккаунт.
+    ///
     /// # Аргументы
     ///
     /// * `signup_data` - Данные для проверки (email и пароль)
@@ -288,10 +443,147 @@ impl UsersRepository for PgStorage {
     /// * `AppResult<bool>` - Результат проверки пароля (true если пароль верный)
     #[instrument(name = "verify user's password", skip_all, fields(email = %signin_data.email))]
     async fn verify_user(&self, signin_data: SigninData) -> AppResult<bool> {
-        let user = UserDTO::get_by_email(&self.pool, &signin_data.email).await?;
-        let res = verify_password(&user.password_hash, &signin_data.password)?;
+        let user = match UserDTO::get_by_email(&self.pool, &signin_data.email).await {
+            Ok(user) => user,
+            Err(AppError::EntryNotFound) => {
+                verify_password_blocking(UNUSABLE_PASSWORD_HASH, &signin_data.password).await?;
+                return Err(AppError::EntryNotFound);
+            }
+            Err(e) => return Err(e),
+        };
+        let res = verify_password_blocking(&user.password_hash, &signin_data.password).await?;
+        if res && needs_rehash(&user.password_hash) {
+            let rehashed = match hash_password_blocking(&signin_data.password).await {
+                Ok(new_hash) => {
+                    UserDTO::replace_password_hash(
+                        &self.pool,
+                        user.user_id,
+                        &user.password_hash,
+                        &new_hash,
+                    )
+                    .await
+                }
+                Err(e) => Err(e),
+            };
+            if let Err(e) = rehashed {
+                tracing::error!("failed to upgrade password hash: {e}");
+            }
+        }
         Ok(res)
     }
+
+    /// Планирует или отменяет анонимизацию аккаунта
+    ///
+    /// # Аргументы
+    ///
+    /// * `id` - UUID пользователя
+    /// * `at` - Момент анонимизации или `None` для отмены
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<User>` - Обновленного пользователя или ошибку
+    #[instrument(name = "schedule user deletion", skip(self))]
+    async fn schedule_deletion(
+        &self,
+        id: uuid::Uuid,
+        at: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> AppResult<User> {
+        let user = UserDTO::schedule_deletion(&self.pool, id, at).await?;
+        let info = UserInfoDTO::get_by_user_id(&self.pool, user.user_id).await?;
+        Ok(User::from((user, info.into())))
+    }
+
+    /// Заменяет хэш пароля пользователя
+    ///
+    /// # Аргументы
+    ///
+    /// * `id` - UUID пользователя
+    /// * `password_hash` - Новый хэш пароля
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<User>` - Обновленного пользователя или ошибку
+    #[instrument(name = "set user's password hash", skip(self, password_hash))]
+    async fn set_password_hash(&self, id: uuid::Uuid, password_hash: &str) -> AppResult<User> {
+        let user = UserDTO::set_password_hash(&self.pool, id, password_hash).await?;
+        let info = UserInfoDTO::get_by_user_id(&self.pool, user.user_id).await?;
+        Ok(User::from((user, info.into())))
+    }
+
+    /// Анонимизирует аккаунт пользователя
+    ///
+    /// Email заменяется на служебный адрес, пароль становится непригодным
+    /// для входа, а личная информация профиля очищается. Строка пользователя
+    /// сохраняется, чтобы не нарушать ссылки из журнала аудита.
+    ///
+    /// # Аргументы
+    ///
+    /// * `id` - UUID пользователя
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<User>` - Анонимизированного пользователя или ошибку
+    #[instrument(name = "anonymize user", skip(self))]
+    async fn anonymize(&self, id: uuid::Uuid) -> AppResult<User> {
+        let mut tx = self.pool.begin().await?;
+        let user = UserDTO::anonymize(&mut tx, id).await?;
+        let info = UserInfoDTO::anonymize(&mut tx, id).await?;
+        sqlx::query!("DELETE FROM user_identities WHERE user_id = $1;", id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(User::from((user, info.into())))
+    }
+
+    /// Возвращает аккаунты, срок удаления которых наступил
+    ///
+    /// # Аргументы
+    ///
+    /// * `now` - Текущий момент времени
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<Vec<uuid::Uuid>>` - Идентификаторы аккаунтов
+    #[instrument(name = "users due for deletion", skip(self))]
+    async fn due_for_deletion(
+        &self,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> AppResult<Vec<uuid::Uuid>> {
+        let ids = sqlx::query_scalar!(
+            r#"
+			SELECT user_id FROM users
+			WHERE deletion_scheduled_at <= $1 AND anonymized_at IS NULL;
+			"#,
+            now,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(ids)
+    }
+}
+
+/// Сохраняет связь пользователя с учетной записью внешнего провайдера
+///
+/// Повторная связь с тем же пользователем не считается ошибкой.
+async fn insert_identity(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    user_id: uuid::Uuid,
+    issuer: &str,
+    subject: &str,
+) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+		INSERT INTO user_identities (user_id, issuer, subject)
+		VALUES ($1, $2, $3)
+		ON CONFLICT (issuer, subject) DO NOTHING;
+		"#,
+        user_id,
+        issuer,
+        subject,
+    )
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
 }
 
 /// DTO (Data Transfer Object) для пользователя
@@ -302,8 +594,12 @@ struct UserDTO {
     email: String,
     password_hash: String,
     role: String,
-    created: chrono::NaiveDateTime,
-    updated: chrono::NaiveDateTime,
+    created: chrono::DateTime<chrono::Utc>,
+    updated: chrono::DateTime<chrono::Utc>,
+    deletion_scheduled_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[allow(unused)]
+    anonymized_at: Option<chrono::DateTime<chrono::Utc>>,
+    service_account: bool,
 }
 
 impl UserDTO {
@@ -321,7 +617,28 @@ impl UserDTO {
         tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
         signup_data: SignupData,
     ) -> AppResult<Self> {
-        let password_hash = hash_password(&signup_data.password)?;
+        let password_hash = hash_password_blocking(&signup_data.password).await?;
+        Self::insert(tx, &signup_data.email, &password_hash, &signup_data.role).await
+    }
+
+    /// Добавляет пользователя с уже вычисленным хэшем пароля
+    ///
+    /// # Аргументы
+    ///
+    /// * `tx` - Транзакция базы данных
+    /// * `email` - Email пользователя
+    /// * `password_hash` - Хэш пароля
+    /// * `role` - Роль пользователя
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<Self>` - Созданный DTO пользователя
+    async fn insert(
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        email: &str,
+        password_hash: &str,
+        role: &UserRole,
+    ) -> AppResult<Self> {
         let created_user = sqlx::query_as!(
             UserDTO,
             r#"
@@ -329,9 +646,40 @@ impl UserDTO {
 			VALUES ($1, $2, $3)
 			RETURNING *;
 			"#,
-            signup_data.email,
+            email,
             password_hash,
-            signup_data.role.to_string(),
+            role.to_string(),
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+
+        Ok(created_user)
+    }
+
+    /// Создает сервисный аккаунт без пароля
+    ///
+    /// # Аргументы
+    ///
+    /// * `tx` - Транзакция базы данных
+    /// * `data` - Имя и роль сервисного аккаунта
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<Self>` - Созданный DTO пользователя
+    async fn create_service_account(
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        data: &ServiceAccountData,
+    ) -> AppResult<Self> {
+        let created_user = sqlx::query_as!(
+            UserDTO,
+            r#"
+			INSERT INTO users (email, password_hash, role, service_account)
+			VALUES ($1, $2, $3, TRUE)
+			RETURNING *;
+			"#,
+            data.email(),
+            UNUSABLE_PASSWORD_HASH,
+            data.role.to_string(),
         )
         .fetch_one(&mut **tx)
         .await?;
@@ -353,7 +701,7 @@ impl UserDTO {
         let res = sqlx::query_as!(
             UserDTO,
             r#"
-			SELECT * FROM users WHERE user_id = $1;
+			SELECT * FROM users WHERE user_id = $1 AND anonymized_at IS NULL;
 			"#,
             id,
         )
@@ -363,6 +711,33 @@ impl UserDTO {
         Ok(res)
     }
 
+    /// Заменяет хэш пароля, если он не изменился с момента чтения
+    ///
+    /// # Аргументы
+    ///
+    /// * `pool` - Пул соединений с базой данных
+    /// * `id` - UUID пользователя
+    /// * `old_hash` - Прочитанный ранее хэш
+    /// * `new_hash` - Новый хэш
+    async fn replace_password_hash(
+        pool: &sqlx::PgPool,
+        id: uuid::Uuid,
+        old_hash: &str,
+        new_hash: &str,
+    ) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+			UPDATE users SET password_hash = $3 WHERE user_id = $1 AND password_hash = $2;
+			"#,
+            id,
+            old_hash,
+            new_hash,
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
     /// Получает пользователя по email адресу
     ///
     /// # Аргументы
@@ -377,7 +752,7 @@ impl UserDTO {
         let res = sqlx::query_as!(
             UserDTO,
             r#"
-			SELECT * FROM users WHERE email = $1;
+			SELECT * FROM users WHERE email = $1 AND anonymized_at IS NULL;
 			"#,
             email,
         )
@@ -437,9 +812,8 @@ impl UserDTO {
 			UPDATE users
 			SET
 				email = $2,
-				role = $3,
-				updated = NOW()
-			WHERE user_id = $1
+				role = $3
+			WHERE user_id = $1 AND anonymized_at IS NULL
 			RETURNING *;
 			"#,
             id,
@@ -451,6 +825,109 @@ impl UserDTO {
         .ok_or(AppError::EntryNotFound)?;
         Ok(res)
     }
+
+    /// Устанавливает новый хэш пароля
+    ///
+    /// # Аргументы
+    ///
+    /// * `pool` - Пул соединений с базой данных
+    /// * `id` - UUID пользователя
+    /// * `password_hash` - Новый хэш пароля
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<Self>` - Обновленный DTO пользователя или ошибку
+    async fn set_password_hash(
+        pool: &sqlx::PgPool,
+        id: uuid::Uuid,
+        password_hash: &str,
+    ) -> AppResult<Self> {
+        let res = sqlx::query_as!(
+            UserDTO,
+            r#"
+			UPDATE users
+			SET password_hash = $2
+			WHERE user_id = $1 AND anonymized_at IS NULL
+			RETURNING *;
+			"#,
+            id,
+            password_hash,
+        )
+        .fetch_optional(pool)
+        .await?
+        .ok_or(AppError::EntryNotFound)?;
+        Ok(res)
+    }
+
+    /// Устанавливает или сбрасывает момент анонимизации
+    ///
+    /// # Аргументы
+    ///
+    /// * `pool` - Пул соединений с базой данных
+    /// * `id` - UUID пользователя
+    /// * `at` - Момент анонимизации или `None`
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<Self>` - Обновленный DTO пользователя или ошибку
+    async fn schedule_deletion(
+        pool: &sqlx::PgPool,
+        id: uuid::Uuid,
+        at: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> AppResult<Self> {
+        let res = sqlx::query_as!(
+            UserDTO,
+            r#"
+			UPDATE users
+			SET deletion_scheduled_at = $2
+			WHERE user_id = $1 AND anonymized_at IS NULL
+			RETURNING *;
+			"#,
+            id,
+            at,
+        )
+        .fetch_optional(pool)
+        .await?
+        .ok_or(AppError::EntryNotFound)?;
+        Ok(res)
+    }
+
+    /// Удаляет персональные данные пользователя
+    ///
+    /// # Аргументы
+    ///
+    /// * `tx` - Транзакция базы данных
+    /// * `id` - UUID пользователя
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<Self>` - Анонимизированный DTO пользователя или ошибку
+    async fn anonymize(
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        id: uuid::Uuid,
+    ) -> AppResult<Self> {
+        let res = sqlx::query_as!(
+            UserDTO,
+            r#"
+			UPDATE users
+			SET
+				email = 'deleted-' || user_id::text || '@anonymized.invalid',
+				password_hash = $2,
+				role = $3,
+				deletion_scheduled_at = NULL,
+				anonymized_at = NOW()
+			WHERE user_id = $1 AND anonymized_at IS NULL
+			RETURNING *;
+			"#,
+            id,
+            UNUSABLE_PASSWORD_HASH,
+            UserRole::Guest.as_ref(),
+        )
+        .fetch_optional(&mut **tx)
+        .await?
+        .ok_or(AppError::EntryNotFound)?;
+        Ok(res)
+    }
 }
 
 /// DTO (Data Transfer Object) для дополнительной информации о пользователе
@@ -467,10 +944,12 @@ struct UserInfoDTO {
     username: Option<String>,
     avatar_url: Option<String>,
     bio: Option<String>,
+    timezone: Option<String>,
+    locale: Option<String>,
     #[allow(unused)]
-    created: chrono::NaiveDateTime,
+    created: chrono::DateTime<chrono::Utc>,
     #[allow(unused)]
-    updated: chrono::NaiveDateTime,
+    updated: chrono::DateTime<chrono::Utc>,
 }
 
 impl UserInfoDTO {
@@ -579,7 +1058,8 @@ impl UserInfoDTO {
 				username = $5,
 				avatar_url = $6,
 				bio = $7,
-				updated = NOW()
+				timezone = $8,
+				locale = $9
 			WHERE user_id = $1
 			RETURNING *;
 			"#,
@@ -589,13 +1069,53 @@ impl UserInfoDTO {
             info.last_name,
             info.username,
             info.avatar_url,
-            info.bio
+            info.bio,
+            info.timezone,
+            info.locale.map(|locale| locale.to_string())
         )
         .fetch_optional(&mut **tx)
         .await?
         .ok_or(AppError::EntryNotFound)?;
         Ok(updated_info)
     }
+
+    /// Очищает дополнительную информацию о пользователе
+    ///
+    /// # Аргументы
+    ///
+    /// * `tx` - Транзакция базы данных
+    /// * `user_id` - UUID пользователя
+    ///
+    /// # Возвращает
+    ///
+    /// * `AppResult<Self>` - Очищенный DTO информации о пользователе или ошибку
+    async fn anonymize(
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        user_id: uuid::Uuid,
+    ) -> AppResult<Self> {
+        let res = sqlx::query_as!(
+            UserInfoDTO,
+            r#"
+			UPDATE user_infos
+			SET
+				first_name = NULL,
+				middle_name = NULL,
+				last_name = NULL,
+				username = NULL,
+				avatar_url = NULL,
+				bio = NULL,
+				timezone = NULL,
+				locale = NULL
+			WHERE user_id = $1
+			RETURNING *;
+			"#,
+            user_id,
+        )
+        .fetch_optional(&mut **tx)
+        .await?
+        .ok_or(AppError::EntryNotFound)?;
+        Ok(res)
+    }
 }
 
 impl From<UserInfoDTO> for UserInfo {
@@ -607,6 +1127,8 @@ impl From<UserInfoDTO> for UserInfo {
             username: value.username,
             avatar_url: value.avatar_url,
             bio: value.bio,
+            timezone: value.timezone,
+            locale: value.locale.and_then(|locale| locale.parse().ok()),
         }
     }
 }
@@ -622,6 +1144,8 @@ impl From<(UserDTO, UserInfo)> for User {
             info,
             created: user.created,
             updated: user.updated,
+            deletion_scheduled_at: user.deletion_scheduled_at,
+            service_account: user.service_account,
         }
     }
 }
@@ -632,7 +1156,11 @@ mod tests {
 
     use crate::{
         AppError, AppResult,
-        models::{SigninData, SignupData, UserInfo, UserToUpdate},
+        crypto::UNUSABLE_PASSWORD_HASH,
+        models::{
+            ExternalIdentity, ImportedUser, ServiceAccountData, SigninData, SignupData, UserInfo,
+            UserRole, UserToUpdate,
+        },
         storage::{PgStorage, UsersRepository, users::UsersFilter},
     };
     #[sqlx::test]
@@ -654,6 +1182,72 @@ mod tests {
         Ok(())
     }
     #[sqlx::test]
+    async fn create_service_account_test(pool: PgPool) -> AppResult<()> {
+        let storage = PgStorage::with_pool(pool);
+        let account = storage
+            .create_service_account(ServiceAccountData {
+                name: "ci-bot".to_string(),
+                role: UserRole::Employee,
+            })
+            .await?;
+        assert!(account.service_account);
+        assert_eq!(account.email, "ci-bot@service.alfred.invalid");
+        assert_eq!(account.password_hash, UNUSABLE_PASSWORD_HASH);
+        assert_eq!(account.info.username, Some("ci-bot".to_string()));
+        assert!(storage.get(account.user_id).await?.service_account);
+
+        // Вход по паролю для сервисного аккаунта невозможен
+        let verified = storage
+            .verify_user(SigninData {
+                email: account.email.clone(),
+                password: String::new(),
+            })
+            .await?;
+        assert!(!verified);
+        Ok(())
+    }
+    #[sqlx::test]
+    async fn external_identity_test(pool: PgPool) -> AppResult<()> {
+        let storage = PgStorage::with_pool(pool);
+        let identity = ExternalIdentity {
+            issuer: "https://idp.example.com".to_string(),
+            subject: "sub-1".to_string(),
+            email: "sso@example.com".to_string(),
+            email_verified: true,
+            given_name: Some("Ivan".to_string()),
+            family_name: Some("Petrov".to_string()),
+            groups: vec![],
+        };
+        let created = storage
+            .create_external(&identity, UserRole::Employee)
+            .await?;
+        assert_eq!(created.password_hash, UNUSABLE_PASSWORD_HASH);
+        assert_eq!(created.info.first_name, Some("Ivan".to_string()));
+        let found = storage
+            .find_by_identity(&identity.issuer, &identity.subject)
+            .await?;
+        assert_eq!(found.user_id, created.user_id);
+
+        // Повторная связь не дублируется и не считается ошибкой
+        storage
+            .link_identity(created.user_id, &identity.issuer, &identity.subject)
+            .await?;
+        assert!(matches!(
+            storage.find_by_identity(&identity.issuer, "sub-2").await,
+            Err(AppError::EntryNotFound)
+        ));
+
+        // Анонимизация удаляет связь с провайдером
+        storage.anonymize(created.user_id).await?;
+        assert!(
+            storage
+                .find_by_identity(&identity.issuer, &identity.subject)
+                .await
+                .is_err()
+        );
+        Ok(())
+    }
+    #[sqlx::test]
     async fn create_user_failed_test(pool: PgPool) -> AppResult<()> {
         let pg_users_repo = PgStorage::with_pool(pool);
         let signup_data = SignupData {
@@ -773,6 +1367,128 @@ mod tests {
         Ok(())
     }
 
+    #[sqlx::test]
+    async fn updated_timestamp_trigger_test(pool: PgPool) -> AppResult<()> {
+        let pg_users_repo = PgStorage::with_pool(pool);
+
+        let signup_data = SignupData {
+            email: "trigger@example.com".to_string(),
+            password: "str0nGp@ssw0rD".to_string(),
+            role: crate::models::UserRole::Guest,
+        };
+        let created = pg_users_repo.create(signup_data).await?;
+        assert_eq!(created.created, created.updated);
+
+        // Метка обновляется триггером даже при прямом изменении строки
+        sqlx::query("UPDATE users SET role = $2 WHERE user_id = $1")
+            .bind(created.user_id)
+            .bind(crate::models::UserRole::Employee.as_ref())
+            .execute(&pg_users_repo.pool)
+            .await?;
+        sqlx::query("UPDATE user_infos SET timezone = 'Europe/Moscow' WHERE user_id = $1")
+            .bind(created.user_id)
+            .execute(&pg_users_repo.pool)
+            .await?;
+
+        let retrieved = pg_users_repo.get(created.user_id).await?;
+        assert!(retrieved.updated > created.updated);
+        assert_eq!(retrieved.info.timezone, Some("Europe/Moscow".to_string()));
+        assert_eq!(retrieved.created.timezone(), chrono::Utc);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn schedule_and_anonymize_user_test(pool: PgPool) -> AppResult<()> {
+        let pg_users_repo = PgStorage::with_pool(pool);
+
+        let signup_data = SignupData {
+            email: "forget@example.com".to_string(),
+            password: "str0nGp@ssw0rD".to_string(),
+            role: crate::models::UserRole::Admin,
+        };
+        let created = pg_users_repo.create(signup_data.clone()).await?;
+        let mut with_info = UserToUpdate::from(created.clone());
+        with_info.info.first_name = Some("Forget".to_string());
+        pg_users_repo.update(created.user_id, with_info).await?;
+
+        // Планирование и отмена удаления
+        let at = chrono::Utc::now() - chrono::Duration::minutes(1);
+        let scheduled = pg_users_repo
+            .schedule_deletion(created.user_id, Some(at))
+            .await?;
+        assert!(scheduled.deletion_scheduled_at.is_some());
+        let due = pg_users_repo.due_for_deletion(chrono::Utc::now()).await?;
+        assert_eq!(due, vec![created.user_id]);
+
+        let cancelled = pg_users_repo
+            .schedule_deletion(created.user_id, None)
+            .await?;
+        assert!(cancelled.deletion_scheduled_at.is_none());
+        assert!(
+            pg_users_repo
+                .due_for_deletion(chrono::Utc::now())
+                .await?
+                .is_empty()
+        );
+
+        // Анонимизация
+        let anonymized = pg_users_repo.anonymize(created.user_id).await?;
+        assert_ne!(anonymized.email, signup_data.email);
+        assert!(anonymized.email.ends_with("@anonymized.invalid"));
+        assert_eq!(anonymized.role, crate::models::UserRole::Guest);
+        assert_eq!(anonymized.info, UserInfo::default());
+
+        // Анонимизированный аккаунт больше не виден
+        let result = pg_users_repo.get(created.user_id).await;
+        assert!(matches!(result.unwrap_err(), AppError::EntryNotFound));
+        let result = pg_users_repo.find_by_email(&anonymized.email).await;
+        assert!(matches!(result.unwrap_err(), AppError::EntryNotFound));
+        assert_eq!(pg_users_repo.total(UsersFilter::default()).await?, 0);
+        let result = pg_users_repo.anonymize(created.user_id).await;
+        assert!(matches!(result.unwrap_err(), AppError::EntryNotFound));
+        let mut to_update = UserToUpdate::from(anonymized.clone());
+        to_update.email = signup_data.email.clone();
+        to_update.info.first_name = Some("Restored".to_string());
+        let result = pg_users_repo.update(created.user_id, to_update).await;
+        assert!(matches!(result.unwrap_err(), AppError::EntryNotFound));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn set_password_hash_test(pool: PgPool) -> AppResult<()> {
+        let pg_users_repo = PgStorage::with_pool(pool);
+
+        let signup_data = SignupData {
+            email: "reset@example.com".to_string(),
+            password: "str0nGp@ssw0rD".to_string(),
+            role: crate::models::UserRole::Employee,
+        };
+        let created = pg_users_repo.create(signup_data.clone()).await?;
+        let password_hash = crate::crypto::hash_password("an0ther_p@ssw0rD")?;
+        let updated = pg_users_repo
+            .set_password_hash(created.user_id, &password_hash)
+            .await?;
+        assert_eq!(updated.password_hash, password_hash);
+        assert_eq!(updated.email, signup_data.email);
+
+        let verified = pg_users_repo
+            .verify_user(SigninData {
+                email: signup_data.email.clone(),
+                password: "an0ther_p@ssw0rD".to_string(),
+            })
+            .await?;
+        assert!(verified);
+
+        let result = pg_users_repo
+            .set_password_hash(uuid::Uuid::new_v4(), &password_hash)
+            .await;
+        assert!(matches!(result.unwrap_err(), AppError::EntryNotFound));
+
+        Ok(())
+    }
+
     #[sqlx::test]
     async fn update_user_not_found_test(pool: PgPool) -> AppResult<()> {
         let pg_users_repo = PgStorage::with_pool(pool);
@@ -949,6 +1665,97 @@ mod tests {
         Ok(())
     }
 
+    #[sqlx::test]
+    async fn verify_user_upgrades_outdated_hash_test(pool: PgPool) -> AppResult<()> {
+        let pg_users_repo = PgStorage::with_pool(pool.clone());
+
+        let password = "str0nGp@ssw0rD";
+        let user = pg_users_repo
+            .create(SignupData {
+                email: "rehash@example.com".to_string(),
+                password: password.to_string(),
+                role: crate::models::UserRole::Guest,
+            })
+            .await?;
+        let weak = crate::crypto::PasswordHashing::from_settings(
+            &crate::settings::PasswordHashSettings {
+                memory_kib: 1024,
+                iterations: 1,
+                ..Default::default()
+            },
+        )?;
+        let outdated = weak.hash(password)?;
+        sqlx::query!(
+            "UPDATE users SET password_hash = $2 WHERE user_id = $1",
+            user.user_id,
+            outdated
+        )
+        .execute(&pool)
+        .await?;
+        assert!(crate::crypto::needs_rehash(&outdated));
+
+        // Неверный пароль не приводит к пересчету
+        assert!(
+            !pg_users_repo
+                .verify_user(SigninData {
+                    email: "rehash@example.com".to_string(),
+                    password: "WrongPass123!".to_string(),
+                })
+                .await?
+        );
+        assert_eq!(
+            pg_users_repo.get(user.user_id).await?.password_hash,
+            outdated
+        );
+
+        assert!(
+            pg_users_repo
+                .verify_user(SigninData {
+                    email: "rehash@example.com".to_string(),
+                    password: password.to_string(),
+                })
+                .await?
+        );
+        let upgraded = pg_users_repo.get(user.user_id).await?.password_hash;
+        assert_ne!(upgraded, outdated);
+        assert!(!crate::crypto::needs_rehash(&upgraded));
+        assert!(crate::crypto::verify_password(&upgraded, password)?);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn imported_legacy_hash_is_upgraded_test(pool: PgPool) -> AppResult<()> {
+        let pg_users_repo = PgStorage::with_pool(pool);
+
+        // Тестовый вектор bcrypt для пароля "U*U"
+        let legacy = "$2a$05$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOeW";
+        let user = pg_users_repo
+            .create_imported(ImportedUser {
+                email: "legacy@example.com".to_string(),
+                password_hash: legacy.to_string(),
+                role: UserRole::Employee,
+            })
+            .await?;
+        assert_eq!(user.password_hash, legacy);
+        assert_eq!(user.role, UserRole::Employee);
+
+        let signin = |password: &str| SigninData {
+            email: "legacy@example.com".to_string(),
+            password: password.to_string(),
+        };
+        assert!(!pg_users_repo.verify_user(signin("U*V")).await?);
+        assert_eq!(pg_users_repo.get(user.user_id).await?.password_hash, legacy);
+
+        // После входа хэш bcrypt заменяется на Argon2id
+        assert!(pg_users_repo.verify_user(signin("U*U")).await?);
+        let upgraded = pg_users_repo.get(user.user_id).await?.password_hash;
+        assert!(upgraded.starts_with("$argon2id$"));
+        assert!(pg_users_repo.verify_user(signin("U*U")).await?);
+
+        Ok(())
+    }
+
     #[sqlx::test]
     async fn verify_user_wrong_password_test(pool: PgPool) -> AppResult<()> {
         let pg_users_repo = PgStorage::with_pool(pool);
@@ -0,0 +1,386 @@
+//! Модуль экспорта трассировок в OpenTelemetry
+//!
+//! Спаны `tracing` - обработчики, методы репозиториев, `http_request`
+//! из `TraceLayer` - передаются по протоколу OTLP/HTTP в коллектор,
+//! например Jaeger из `compose.yaml`. Контекст трассировки принимается
+//! и передается дальше в заголовке W3C `traceparent`, а каждый запрос
+//! sqlx оформляется отдельным клиентским спаном.
+
+use std::{
+    sync::OnceLock,
+    time::{Duration, SystemTime},
+};
+
+use http::{HeaderMap, HeaderName, HeaderValue};
+use opentelemetry::{
+    Context, KeyValue,
+    propagation::{Extractor, Injector, TextMapPropagator},
+    trace::{Span as _, SpanKind, TraceContextExt, Tracer, TracerProvider},
+};
+use opentelemetry_otlp::{WithExportConfig, WithHttpConfig};
+use opentelemetry_sdk::{
+    Resource,
+    propagation::TraceContextPropagator,
+    trace::{Sampler, SdkTracer, SdkTracerProvider},
+};
+use tracing::{
+    Event, Subscriber,
+    dispatcher::WeakDispatch,
+    field::{Field, Visit},
+    level_filters::LevelFilter,
+};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{
+    Layer,
+    filter::{Targets, filter_fn},
+    layer::Context as LayerContext,
+    registry::LookupSpan,
+};
+
+use crate::{AppError, AppResult, settings::TelemetrySettings};
+
+/// Цель событий, которыми sqlx сообщает о выполненных запросах
+const SQLX_QUERY_TARGET: &str = "sqlx::query";
+
+/// Экспорт трассировок
+///
+/// При удалении отправляет накопленные спаны и останавливает экспорт,
+/// поэтому значение должно жить до завершения приложения.
+pub struct Telemetry {
+    provider: SdkTracerProvider,
+    filter: Targets,
+}
+
+impl Telemetry {
+    /// Настраивает экспорт трассировок по OTLP/HTTP
+    ///
+    /// # Аргументы
+    ///
+    /// * `settings` - Настройки экспорта
+    ///
+    /// # Возвращает
+    ///
+    /// * `Ok(Telemetry)` - Экспорт настроен
+    /// * `Err(AppError::Custom)` - Не удалось создать экспортер или разобрать фильтр
+    pub fn init(settings: &TelemetrySettings) -> AppResult<Self> {
+        let filter = settings
+            .filter
+            .parse::<Targets>()
+            .map_err(|e| AppError::Custom(format!("telemetry filter: {e}")))?;
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .with_endpoint(&settings.endpoint)
+            .with_timeout(Duration::from_millis(settings.export_timeout_ms))
+            .with_headers(settings.headers.clone())
+            .build()
+            .map_err(|e| AppError::Custom(format!("otlp exporter: {e}")))?;
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_sampler(sampler(settings))
+            .with_resource(
+                Resource::builder()
+                    .with_service_name(settings.service_name.clone())
+                    .build(),
+            )
+            .build();
+        Ok(Self { provider, filter })
+    }
+
+    /// Слой `tracing`, передающий спаны в экспорт
+    ///
+    /// Спаны отбираются фильтром из настроек; запросы sqlx
+    /// экспортируются независимо от него.
+    pub fn layer<S>(&self) -> Box<dyn Layer<S> + Send + Sync>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a> + Send + Sync,
+    {
+        let tracer = self.provider.tracer("alfred");
+        let spans = tracing_opentelemetry::layer()
+            .with_tracer(tracer.clone())
+            .with_filter(
+                self.filter
+                    .clone()
+                    .with_target(SQLX_QUERY_TARGET, LevelFilter::OFF),
+            );
+        // Слою нужны и спаны приложения: фильтр слоя скрывает от него
+        // спаны, которые сам не пропустил, и родителя запроса было бы не найти
+        let filter = self.filter.clone();
+        let queries = QuerySpans::new(tracer).with_filter(filter_fn(move |metadata| {
+            metadata.target() == SQLX_QUERY_TARGET
+                || (metadata.is_span() && filter.would_enable(metadata.target(), metadata.level()))
+        }));
+        Box::new(spans.and_then(queries))
+    }
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            eprintln!("failed to flush traces: {e}");
+        }
+    }
+}
+
+/// Выбирает стратегию сэмплирования
+///
+/// Трассировки без родителя отбираются по доле `sample_ratio`;
+/// при `parent_based` решение вызывающего сервиса из `traceparent`
+/// имеет приоритет.
+pub fn sampler(settings: &TelemetrySettings) -> Sampler {
+    let ratio = match settings.sample_ratio {
+        r if r >= 1.0 => Sampler::AlwaysOn,
+        r if r <= 0.0 => Sampler::AlwaysOff,
+        r => Sampler::TraceIdRatioBased(r),
+    };
+    if settings.parent_based {
+        Sampler::ParentBased(Box::new(ratio))
+    } else {
+        ratio
+    }
+}
+
+/// Извлекает контекст трассировки из заголовков входящего запроса
+///
+/// Без корректного `traceparent` возвращается пустой контекст,
+/// и спан запроса начинает новую трассировку.
+pub fn extract_context(headers: &HeaderMap) -> Context {
+    TraceContextPropagator::new().extract(&HeaderExtractor(headers))
+}
+
+/// Заголовки `traceparent` и `tracestate` текущего спана для исходящего запроса
+///
+/// Если экспорт выключен, заголовков нет.
+pub fn trace_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    let context = tracing::Span::current().context();
+    TraceContextPropagator::new().inject_context(&context, &mut HeaderInjector(&mut headers));
+    headers
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(HeaderName::as_str).collect()
+    }
+}
+
+struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(key.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+/// Слой, превращающий события sqlx о выполненных запросах в спаны
+///
+/// sqlx не создает спанов, а пишет событие после выполнения запроса
+/// с его текстом и длительностью. Спан восстанавливается по этому событию
+/// и становится дочерним для спана, в котором выполнялся запрос.
+struct QuerySpans {
+    tracer: SdkTracer,
+    dispatch: OnceLock<WeakDispatch>,
+}
+
+impl QuerySpans {
+    fn new(tracer: SdkTracer) -> Self {
+        Self {
+            tracer,
+            dispatch: OnceLock::new(),
+        }
+    }
+}
+
+impl<S> Layer<S> for QuerySpans
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_register_dispatch(&self, subscriber: &tracing::Dispatch) {
+        let _ = self.dispatch.set(subscriber.downgrade());
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: LayerContext<'_, S>) {
+        // Запросы вне спанов (миграции, фоновые задачи без
+        // инструментирования) не относятся ни к одной трассировке
+        let Some(span) = ctx.event_span(event) else {
+            return;
+        };
+        let Some(dispatch) = self.dispatch.get().and_then(WeakDispatch::upgrade) else {
+            return;
+        };
+        let Some(parent) =
+            tracing_opentelemetry::get_otel_context(&mut span.extensions_mut(), &dispatch)
+        else {
+            return;
+        };
+        if !parent.span().span_context().is_valid() {
+            return;
+        }
+        let mut query = QueryFields::default();
+        event.record(&mut query);
+        let end = SystemTime::now();
+        let start = end - Duration::from_secs_f64(query.elapsed_secs.max(0.0));
+        let text = if query.statement.trim().is_empty() {
+            query.summary.clone()
+        } else {
+            query.statement.trim().to_string()
+        };
+        let name = query.summary.trim_end_matches(" …").to_string();
+        let mut span = self
+            .tracer
+            .span_builder(name)
+            .with_kind(SpanKind::Client)
+            .with_start_time(start)
+            .with_attributes([
+                KeyValue::new("db.system.name", "postgresql"),
+                KeyValue::new("db.query.text", text),
+                KeyValue::new("db.response.returned_rows", query.rows_returned),
+                KeyValue::new("db.response.affected_rows", query.rows_affected),
+            ])
+            .start_with_context(&self.tracer, &parent);
+        span.end_with_timestamp(end);
+    }
+}
+
+/// Поля события sqlx о выполненном запросе
+#[derive(Default)]
+struct QueryFields {
+    summary: String,
+    statement: String,
+    rows_returned: i64,
+    rows_affected: i64,
+    elapsed_secs: f64,
+}
+
+impl Visit for QueryFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "summary" => self.summary = value.to_string(),
+            "db.statement" => self.statement = value.to_string(),
+            _ => {}
+        }
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        let value = i64::try_from(value).unwrap_or(i64::MAX);
+        match field.name() {
+            "rows_returned" => self.rows_returned = value,
+            "rows_affected" => self.rows_affected = value,
+            _ => {}
+        }
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        if field.name() == "elapsed_secs" {
+            self.elapsed_secs = value;
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        match field.name() {
+            "summary" => self.summary = format!("{value:?}"),
+            "db.statement" => self.statement = format!("{value:?}"),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::trace::{SpanId, TraceId};
+    use opentelemetry_sdk::trace::InMemorySpanExporter;
+    use tracing::Level;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    fn settings() -> TelemetrySettings {
+        TelemetrySettings::default()
+    }
+
+    #[test]
+    fn test_sampler() {
+        let mut settings = settings();
+        assert!(matches!(sampler(&settings), Sampler::ParentBased(_)));
+        settings.parent_based = false;
+        assert!(matches!(sampler(&settings), Sampler::AlwaysOn));
+        settings.sample_ratio = 0.25;
+        assert!(matches!(sampler(&settings), Sampler::TraceIdRatioBased(r) if r == 0.25));
+        settings.sample_ratio = 0.0;
+        assert!(matches!(sampler(&settings), Sampler::AlwaysOff));
+    }
+
+    #[test]
+    fn test_propagation() {
+        let exporter = InMemorySpanExporter::default();
+        let telemetry = Telemetry {
+            provider: SdkTracerProvider::builder()
+                .with_simple_exporter(exporter.clone())
+                .with_sampler(sampler(&settings()))
+                .build(),
+            filter: Targets::new().with_default(Level::INFO),
+        };
+        let subscriber = tracing_subscriber::registry().with(telemetry.layer());
+        let mut headers = HeaderMap::new();
+        headers.insert("traceparent", HeaderValue::from_static(TRACEPARENT));
+
+        let outgoing = tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("http_request");
+            span.set_parent(extract_context(&headers)).unwrap();
+            let _guard = span.enter();
+            tracing::debug!(
+                target: SQLX_QUERY_TARGET,
+                summary = "SELECT id FROM users …",
+                db.statement = "\n\nSELECT id FROM users WHERE id = $1\n",
+                rows_affected = 0u64,
+                rows_returned = 1u64,
+                elapsed_secs = 0.002,
+            );
+            // Отладочные события приложения ниже уровня экспорта не попадают
+            tracing::debug!("not exported");
+            trace_headers()
+        });
+        telemetry.provider.force_flush().unwrap();
+
+        let trace_id = TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap();
+        let spans = exporter.get_finished_spans().unwrap();
+        assert_eq!(spans.len(), 2);
+        let request = spans.iter().find(|s| s.name == "http_request").unwrap();
+        assert_eq!(request.span_context.trace_id(), trace_id);
+        assert_eq!(
+            request.parent_span_id,
+            SpanId::from_hex("00f067aa0ba902b7").unwrap()
+        );
+        assert!(request.events.is_empty());
+
+        let query = spans
+            .iter()
+            .find(|s| s.name == "SELECT id FROM users")
+            .unwrap();
+        assert_eq!(query.span_kind, SpanKind::Client);
+        assert_eq!(query.parent_span_id, request.span_context.span_id());
+        assert!(query.attributes.contains(&KeyValue::new(
+            "db.query.text",
+            "SELECT id FROM users WHERE id = $1"
+        )));
+
+        // Исходящий запрос продолжает ту же трассировку от спана запроса
+        let traceparent = outgoing["traceparent"].to_str().unwrap();
+        assert_eq!(
+            traceparent,
+            format!("00-{trace_id}-{}-01", request.span_context.span_id())
+        );
+    }
+}